@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+
+use crate::{dom, markdown::default_handler, options::AdmonitionStyle};
+
+// 可能是提示框的元素
+const CONTAINER_TAGS: &[&str] = &["aside", "div", "section"];
+// epub:type、role、data-type 或 class 中的取值与提示框种类，种类名与 GitHub 的写法一致
+const KINDS: &[(&str, &str)] = &[
+    ("note", "NOTE"),
+    ("notice", "NOTE"),
+    ("doc-notice", "NOTE"),
+    ("info", "NOTE"),
+    ("tip", "TIP"),
+    ("hint", "TIP"),
+    ("doc-tip", "TIP"),
+    ("important", "IMPORTANT"),
+    ("warning", "WARNING"),
+    ("caution", "CAUTION"),
+    ("danger", "CAUTION"),
+];
+// 出版社常把种类写成 callout-note、tip-box 之类的 class
+const CLASS_WORDS: &[&str] = &["callout", "box", "admonition", "alert", "sidebar"];
+
+// 元素表示的提示框种类
+fn kind(tag: &Handle) -> Option<&'static str> {
+    let find = |value: &str| KINDS.iter().find(|(name, _)| name.eq_ignore_ascii_case(value)).map(|(_, kind)| *kind);
+    let semantic = ["type", "role", "data-type"]
+        .iter()
+        .filter_map(|name| dom::attr(tag, name))
+        .find_map(|value| value.split_whitespace().find_map(find));
+    semantic.or_else(|| {
+        dom::attr(tag, "class")?.split_whitespace().find_map(|class| {
+            let words: Vec<&str> = class.split(['-', '_']).collect();
+            match words.as_slice() {
+                [word] => find(word),
+                [a, b] if CLASS_WORDS.contains(&a.to_ascii_lowercase().as_str()) => find(b),
+                [a, b] if CLASS_WORDS.contains(&b.to_ascii_lowercase().as_str()) => find(a),
+                _ => None,
+            }
+        })
+    })
+}
+
+// 提示框开头的标题元素（h1-h6 或 class 为 title 的元素）；取出后从文档中移除
+fn take_title(tag: &Handle) -> Option<String> {
+    let first = tag.children.borrow().iter().find(|child| dom::tag_name(child).is_some()).cloned()?;
+    let heading = matches!(dom::tag_name(&first)?.as_str(), "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "header")
+        || dom::attr(&first, "class").is_some_and(|class| class.split_whitespace().any(|c| c == "title"));
+    if !heading {
+        return None;
+    }
+    let title = dom::text_content(&first);
+    dom::detach(&first);
+    Some(title.trim().trim_end_matches([':', '：']).trim().to_string()).filter(|title| !title.is_empty())
+}
+
+struct AdmonitionHandler {
+    style: AdmonitionStyle,
+    kind: Option<&'static str>,
+    title: Option<String>,
+    start_pos: usize,
+    // 不是提示框时交给原来的处理器
+    fallback: Box<dyn TagHandler>,
+}
+
+impl TagHandler for AdmonitionHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        self.kind = kind(tag);
+        let Some(kind) = self.kind else {
+            self.fallback.handle(tag, printer);
+            return;
+        };
+        // 与种类同名的标题（例如“Note”）不必保留
+        self.title = take_title(tag).filter(|title| !title.eq_ignore_ascii_case(kind));
+        printer.insert_newline();
+        printer.insert_newline();
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        let Some(kind) = self.kind else {
+            self.fallback.after_handle(printer);
+            return;
+        };
+        let body = printer.data.split_off(self.start_pos);
+        let body = body.trim_matches('\n');
+        let admonition = match self.style {
+            AdmonitionStyle::Github => {
+                let title = self.title.as_ref().map(|title| format!("\n**{}**\n", title)).unwrap_or_default();
+                let lines: Vec<String> = format!("{}\n{}", title, body)
+                    .lines()
+                    .map(|line| match line.trim_end() {
+                        "" => ">".to_string(),
+                        line => format!("> {}", line),
+                    })
+                    .collect();
+                format!("> [!{}]\n{}", kind, lines.join("\n").trim_start_matches(">\n"))
+            }
+            AdmonitionStyle::Colon => {
+                let title = self.title.as_ref().map(|title| format!(" {}", title)).unwrap_or_default();
+                format!("::: {}{}\n{}\n:::", kind.to_ascii_lowercase(), title, body)
+            }
+            AdmonitionStyle::Admonish => {
+                let title = self.title.as_ref().map(|title| format!(" title=\"{}\"", title.replace('"', "'")));
+                // 围栏比正文中最长的反引号串更长
+                let longest = body.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+                let fence = "`".repeat(longest.max(2) + 1);
+                format!(
+                    "{}admonish {}{}\n{}\n{}",
+                    fence,
+                    kind.to_ascii_lowercase(),
+                    title.unwrap_or_default(),
+                    body,
+                    fence
+                )
+            }
+            AdmonitionStyle::None => body.to_string(),
+        };
+        printer.append_str(&admonition);
+        printer.insert_newline();
+        printer.insert_newline();
+    }
+
+    fn skip_descendants(&self) -> bool {
+        self.kind.is_none() && self.fallback.skip_descendants()
+    }
+}
+
+struct AdmonitionHandlerFactory {
+    style: AdmonitionStyle,
+    tag: &'static str,
+    fallback: Option<Box<dyn TagHandlerFactory>>,
+}
+
+impl TagHandlerFactory for AdmonitionHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        let fallback = match &self.fallback {
+            Some(factory) => factory.instantiate(),
+            None => default_handler(self.tag),
+        };
+        Box::new(AdmonitionHandler {
+            style: self.style,
+            kind: None,
+            title: None,
+            start_pos: 0,
+            fallback,
+        })
+    }
+}
+
+// 按 --admonitions 把 epub:type="note" 等提示框写成 > [!NOTE]、::: note 或 mdbook-admonish 的代码块；
+// 需在其他处理器之后注册，不是提示框的元素仍交给已注册的处理器
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, style: AdmonitionStyle) {
+    if style == AdmonitionStyle::None {
+        return;
+    }
+    for tag in CONTAINER_TAGS {
+        let fallback = handlers.remove(*tag);
+        handlers.insert(tag.to_string(), Box::new(AdmonitionHandlerFactory { style, tag, fallback }));
+    }
+}
@@ -0,0 +1,414 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Cursor, Read, Seek},
+    path::{Component, Path, PathBuf},
+    sync::OnceLock,
+};
+
+use tempfile::TempDir;
+use zip::{read::read_zipfile_from_stream, ZipArchive};
+
+use crate::{error::EpubToMdError, options::Options};
+
+const LOCAL_HEADER_SIGNATURE: &[u8] = b"PK\x03\x04";
+// 未通过 --max-entry-size / --max-total-size 指定时的默认上限
+pub const DEFAULT_MAX_ENTRY_SIZE: u64 = 256 * 1024 * 1024;
+pub const DEFAULT_MAX_TOTAL_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+
+// 作为输入路径时表示从标准输入读取 EPUB
+pub const STDIN: &str = "-";
+static STDIN_BYTES: OnceLock<Vec<u8>> = OnceLock::new();
+// 交给 pandoc 的标准输入副本的文件名
+const STDIN_FILE_NAME: &str = "book.epub";
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+// EPUB 内容的来源：正常的 zip 压缩包（文件或标准输入）、解压后的目录，或从损坏的压缩包中抢救出的条目
+enum Source {
+    Zip(ZipArchive<Box<dyn ReadSeek>>),
+    Directory(PathBuf),
+    Recovered(BTreeMap<String, Vec<u8>>),
+}
+
+pub struct Archive {
+    source: Source,
+    max_entry_size: u64,
+    max_total_size: u64,
+    // 已解压的总字节数
+    total_read: u64,
+}
+
+impl Archive {
+    // --recover 模式下，中央目录损坏的压缩包会退回逐个扫描本地文件头
+    pub fn open(path: &Path, options: &Options, lost: &mut Vec<String>) -> Result<Self, EpubToMdError> {
+        let max_entry_size = options.max_entry_size.unwrap_or(DEFAULT_MAX_ENTRY_SIZE);
+        let max_total_size = options.max_total_size.unwrap_or(DEFAULT_MAX_TOTAL_SIZE);
+
+        if path.is_dir() {
+            return Ok(Archive {
+                // 条目的真实路径要与根目录比较，根目录本身先解析符号链接
+                source: Source::Directory(path.canonicalize().unwrap_or_else(|_| path.to_path_buf())),
+                max_entry_size,
+                max_total_size,
+                total_read: 0,
+            });
+        }
+
+        let stdin = path == Path::new(STDIN);
+        let reader: Box<dyn ReadSeek> = if stdin {
+            Box::new(Cursor::new(stdin_bytes()?))
+        } else {
+            Box::new(
+                File::open(path)
+                    .map_err(|e| EpubToMdError::InputError(tr!("Failed to open EPUB file: {}", e)))?,
+            )
+        };
+        let source = match ZipArchive::new(reader) {
+            Ok(archive) => Source::Zip(archive),
+            Err(e) if options.recover => {
+                log::warn!("{}", tr!("Failed to read EPUB archive ({}), scanning for readable entries.", e));
+                let bytes = if stdin {
+                    stdin_bytes()?.to_vec()
+                } else {
+                    fs::read(path).map_err(|e| EpubToMdError::InputError(tr!("Failed to read EPUB file: {}", e)))?
+                };
+                Source::Recovered(scan_local_headers(&bytes, max_entry_size, max_total_size, lost)?)
+            }
+            Err(e) => return Err(EpubToMdError::EpubError(tr!("Failed to read EPUB archive: {}", e))),
+        };
+
+        Ok(Archive {
+            source,
+            max_entry_size,
+            max_total_size,
+            total_read: 0,
+        })
+    }
+
+    pub fn read(&mut self, name: &str) -> Result<Vec<u8>, EpubToMdError> {
+        let relative_path = safe_relative_path(name)?;
+        log::trace!("{}", tr!("Reading {}", name));
+
+        let buf = match &mut self.source {
+            Source::Zip(archive) => {
+                let entry = archive
+                    .by_name(name)
+                    .map_err(|e| EpubToMdError::EpubError(tr!("Failed to find '{}' in EPUB: {}", name, e)))?;
+                if entry.size() > self.max_entry_size {
+                    return Err(entry_too_large(name, self.max_entry_size));
+                }
+                // 声明的大小可能是伪造的，解压时仍然限制读取量
+                let mut buf = Vec::new();
+                entry
+                    .take(self.max_entry_size + 1)
+                    .read_to_end(&mut buf)
+                    .map_err(|e| EpubToMdError::EpubError(tr!("Failed to read '{}' from EPUB: {}", name, e)))?;
+                if buf.len() as u64 > self.max_entry_size {
+                    return Err(entry_too_large(name, self.max_entry_size));
+                }
+                buf
+            }
+            Source::Directory(root) => {
+                let path = directory_entry(root, &relative_path)
+                    .ok_or_else(|| EpubToMdError::EpubError(tr!("Unsafe path in EPUB archive: {}", name)))?;
+                let metadata = fs::metadata(&path)
+                    .map_err(|e| EpubToMdError::EpubError(tr!("Failed to find '{}' in EPUB: {}", name, e)))?;
+                if metadata.len() > self.max_entry_size {
+                    return Err(entry_too_large(name, self.max_entry_size));
+                }
+                fs::read(&path).map_err(|e| EpubToMdError::EpubError(tr!("Failed to read '{}' from EPUB: {}", name, e)))?
+            }
+            Source::Recovered(entries) => entries
+                .get(name)
+                .cloned()
+                .ok_or_else(|| EpubToMdError::EpubError(tr!("Failed to find '{}' in EPUB: entry is missing or damaged", name)))?,
+        };
+
+        self.total_read += buf.len() as u64;
+        if self.total_read > self.max_total_size {
+            return Err(EpubToMdError::EpubError(tr!(
+                "EPUB content exceeds the maximum total size of {} bytes.",
+                self.max_total_size
+            )));
+        }
+        Ok(buf)
+    }
+
+    // 只返回路径安全的条目
+    pub fn contains(&self, name: &str) -> bool {
+        match &self.source {
+            Source::Zip(archive) => archive.index_for_name(name).is_some(),
+            Source::Directory(root) => safe_relative_path(name)
+                .ok()
+                .and_then(|path| directory_entry(root, &path))
+                .is_some_and(|path| path.is_file()),
+            Source::Recovered(entries) => entries.contains_key(name),
+        }
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let names: Vec<String> = match &self.source {
+            Source::Zip(archive) => archive.file_names().filter_map(Result::ok).map(|name| name.into_owned()).collect(),
+            Source::Directory(root) => {
+                let mut names = Vec::new();
+                collect_files(root, "", &mut names);
+                // read_dir 的顺序因文件系统而异，排序后结果才稳定
+                names.sort();
+                names
+            }
+            Source::Recovered(entries) => entries.keys().cloned().collect(),
+        };
+        names.into_iter().filter(|name| safe_relative_path(name).is_ok()).collect()
+    }
+}
+
+// 递归列出目录中的文件，路径使用 `/` 分隔，与压缩包条目名一致
+fn collect_files(dir: &Path, prefix: &str, names: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => collect_files(&entry.path(), &format!("{}/", name), names),
+            Ok(file_type) if file_type.is_file() => names.push(name),
+            _ => {}
+        }
+    }
+}
+
+// 解压后的目录中条目的真实路径；经符号链接指到目录之外的条目视为不存在，不会把书之外的文件读进输出。
+// 条目不存在时返回原路径，由读取时报告
+fn directory_entry(root: &Path, relative_path: &Path) -> Option<PathBuf> {
+    let path = root.join(relative_path);
+    match path.canonicalize() {
+        Ok(real) => real.starts_with(root).then_some(real),
+        Err(_) => Some(path),
+    }
+}
+
+fn entry_too_large(name: &str, limit: u64) -> EpubToMdError {
+    EpubToMdError::EpubError(tr!("'{}' exceeds the maximum entry size of {} bytes.", name, limit))
+}
+
+// 标准输入只能读一次，读入内存后供 DRM 检查与转换多次打开
+fn stdin_bytes() -> Result<&'static [u8], EpubToMdError> {
+    if let Some(bytes) = STDIN_BYTES.get() {
+        return Ok(bytes);
+    }
+    let mut bytes = Vec::new();
+    io::stdin()
+        .read_to_end(&mut bytes)
+        .map_err(|e| EpubToMdError::InputError(tr!("Failed to read EPUB from stdin: {}", e)))?;
+    if bytes.is_empty() {
+        return Err(EpubToMdError::InputError(tr!("No EPUB data on stdin.")));
+    }
+    Ok(STDIN_BYTES.get_or_init(|| bytes))
+}
+
+// pandoc 只能读文件，把标准输入的内容写到临时目录下固定名称的 .epub 文件中，
+// 文件名不随机，pandoc 的输出就不会因此变化；目录随返回值一起删除
+pub fn stdin_temp_file() -> Result<(TempDir, PathBuf), EpubToMdError> {
+    let dir = tempfile::tempdir()
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to create temporary directory: {}", e)))?;
+    let path = dir.path().join(STDIN_FILE_NAME);
+    fs::write(&path, stdin_bytes()?)
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to write temporary file: {}", e)))?;
+    Ok((dir, path))
+}
+
+// 校验压缩包条目名并转换为相对路径：拒绝绝对路径、盘符和 `..`，防止解压时写出目标目录（zip slip）
+pub fn safe_relative_path(name: &str) -> Result<PathBuf, EpubToMdError> {
+    let unsafe_path = || EpubToMdError::EpubError(tr!("Unsafe path in EPUB archive: {}", name));
+    if name.is_empty() || name.contains('\\') || name.contains('\0') {
+        return Err(unsafe_path());
+    }
+
+    let mut path = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return Err(unsafe_path()),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(unsafe_path());
+    }
+    Ok(path)
+}
+
+// 在原始字节中查找每个本地文件头并尝试解压，跳过无法读取的条目；
+// 与正常读取一样限制单个条目与解压的总大小，损坏或伪造的压缩包也不能绕过
+fn scan_local_headers(
+    bytes: &[u8],
+    max_entry_size: u64,
+    max_total_size: u64,
+    lost: &mut Vec<String>,
+) -> Result<BTreeMap<String, Vec<u8>>, EpubToMdError> {
+    let mut entries = BTreeMap::new();
+    let mut damaged = 0;
+    let mut total = 0;
+
+    let offsets = bytes
+        .windows(LOCAL_HEADER_SIGNATURE.len())
+        .enumerate()
+        .filter(|(_, window)| *window == LOCAL_HEADER_SIGNATURE)
+        .map(|(offset, _)| offset);
+    for offset in offsets {
+        let mut reader = &bytes[offset..];
+        let Ok(Some(mut entry)) = read_zipfile_from_stream(&mut reader) else {
+            damaged += 1;
+            continue;
+        };
+        let Ok(name) = entry.name().map(|name| name.into_owned()) else {
+            damaged += 1;
+            continue;
+        };
+        // 同名的条目只保留第一个，后面的不再解压
+        if entries.contains_key(&name) {
+            continue;
+        }
+        let mut buf = Vec::new();
+        match (&mut entry).take(max_entry_size + 1).read_to_end(&mut buf) {
+            Ok(_) if buf.len() as u64 > max_entry_size => {
+                lost.push(tr!("{}: exceeds the maximum entry size of {} bytes", name, max_entry_size))
+            }
+            Ok(_) => {
+                total += buf.len() as u64;
+                if total > max_total_size {
+                    return Err(EpubToMdError::EpubError(tr!(
+                        "EPUB content exceeds the maximum total size of {} bytes.",
+                        max_total_size
+                    )));
+                }
+                entries.insert(name, buf);
+            }
+            Err(e) => lost.push(tr!("{}: {}", name, e)),
+        }
+    }
+
+    if damaged > 0 {
+        lost.push(tr!("{} damaged archive entries could not be identified", damaged));
+    }
+    Ok(entries)
+}
+
+// 测试用：按给定的条目构造 zip，名称以 / 结尾的为目录条目
+#[cfg(test)]
+pub fn zip_bytes(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    use std::io::Write;
+
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, bytes) in entries {
+        if name.ends_with('/') {
+            zip.add_directory(*name, SimpleFileOptions::default()).unwrap();
+        } else {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(bytes: &[u8], options: &Options) -> Result<(Archive, Vec<String>), EpubToMdError> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.epub");
+        fs::write(&path, bytes).unwrap();
+        let mut lost = Vec::new();
+        Archive::open(&path, options, &mut lost).map(|archive| (archive, lost))
+    }
+
+    // 截掉中央目录，只留下各个本地文件头
+    fn without_central_directory(bytes: &[u8]) -> Vec<u8> {
+        let end = bytes.windows(4).position(|window| window == b"PK\x01\x02").unwrap();
+        bytes[..end].to_vec()
+    }
+
+    fn recover(max_entry_size: Option<u64>, max_total_size: Option<u64>) -> Options {
+        Options {
+            recover: true,
+            max_entry_size,
+            max_total_size,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn unsafe_paths_are_rejected() {
+        assert_eq!(safe_relative_path("OEBPS/./ch1.xhtml").unwrap(), Path::new("OEBPS/ch1.xhtml"));
+        for name in ["", ".", "../evil", "OEBPS/../../evil", "/etc/passwd", "OEBPS\\..\\evil", "a\0b"] {
+            assert!(safe_relative_path(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_symlinks_stay_inside_the_book() {
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret"), "secret").unwrap();
+        let book = tempfile::tempdir().unwrap();
+        fs::create_dir_all(book.path().join("OEBPS")).unwrap();
+        fs::write(book.path().join("OEBPS/ch1.xhtml"), "<html/>").unwrap();
+        std::os::unix::fs::symlink(outside.path().join("secret"), book.path().join("OEBPS/secret")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), book.path().join("OEBPS/linked")).unwrap();
+        std::os::unix::fs::symlink("ch1.xhtml", book.path().join("OEBPS/alias.xhtml")).unwrap();
+
+        let mut archive = Archive::open(book.path(), &Options::default(), &mut Vec::new()).unwrap();
+        assert_eq!(archive.read("OEBPS/ch1.xhtml").unwrap(), b"<html/>");
+        assert_eq!(archive.read("OEBPS/alias.xhtml").unwrap(), b"<html/>");
+        for name in ["OEBPS/secret", "OEBPS/linked/secret"] {
+            assert!(!archive.contains(name), "{}", name);
+            assert!(matches!(archive.read(name), Err(EpubToMdError::EpubError(_))), "{}", name);
+        }
+    }
+
+    #[test]
+    fn entry_and_total_sizes_are_limited() {
+        let bytes = zip_bytes(&[("a.txt", b"12345678"), ("b.txt", b"12345678")]);
+        let options = Options {
+            max_entry_size: Some(4),
+            ..Options::default()
+        };
+        let (mut archive, _) = open(&bytes, &options).unwrap();
+        assert!(matches!(archive.read("a.txt"), Err(EpubToMdError::EpubError(_))));
+
+        let options = Options {
+            max_total_size: Some(12),
+            ..Options::default()
+        };
+        let (mut archive, _) = open(&bytes, &options).unwrap();
+        assert_eq!(archive.read("a.txt").unwrap(), b"12345678");
+        assert!(matches!(archive.read("b.txt"), Err(EpubToMdError::EpubError(_))));
+    }
+
+    #[test]
+    fn recovery_reads_local_headers() {
+        let bytes = without_central_directory(&zip_bytes(&[("mimetype", b"application/epub+zip"), ("a.txt", b"text")]));
+        assert!(matches!(open(&bytes, &Options::default()), Err(EpubToMdError::EpubError(_))));
+
+        let (mut archive, lost) = open(&bytes, &recover(None, None)).unwrap();
+        assert!(lost.is_empty(), "{:?}", lost);
+        assert_eq!(archive.names(), ["a.txt", "mimetype"]);
+        assert_eq!(archive.read("a.txt").unwrap(), b"text");
+    }
+
+    #[test]
+    fn recovery_keeps_the_size_limits() {
+        let bytes = without_central_directory(&zip_bytes(&[("a.txt", b"12345678"), ("b.txt", b"12")]));
+        let (archive, lost) = open(&bytes, &recover(Some(4), None)).unwrap();
+        assert_eq!(archive.names(), ["b.txt"]);
+        assert_eq!(lost, ["a.txt: exceeds the maximum entry size of 4 bytes"]);
+
+        assert!(matches!(open(&bytes, &recover(None, Some(9))), Err(EpubToMdError::EpubError(_))));
+    }
+}
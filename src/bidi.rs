@@ -0,0 +1,126 @@
+use markup5ever_rcdom::{Handle, NodeData};
+
+use crate::dom;
+
+// 从右向左书写的语言：阿拉伯文、希伯来文、波斯文、乌尔都文等
+const RTL_LANGUAGES: &[&str] = &["ar", "arc", "ckb", "dv", "fa", "he", "iw", "ks", "ku-arab", "ps", "sd", "ug", "ur", "yi"];
+// 在 Markdown 中各自成为一段的元素
+const BLOCK_TAGS: &[&str] = &[
+    "p", "h1", "h2", "h3", "h4", "h5", "h6", "li", "dt", "dd", "td", "th", "caption", "figcaption", "blockquote", "div",
+];
+
+const LRM: &str = "\u{200E}";
+const RLM: &str = "\u{200F}";
+const LRO: &str = "\u{202D}";
+const RLO: &str = "\u{202E}";
+const PDF: &str = "\u{202C}";
+const LRI: &str = "\u{2066}";
+const RLI: &str = "\u{2067}";
+const FSI: &str = "\u{2068}";
+const PDI: &str = "\u{2069}";
+
+pub fn is_rtl_language(lang: &str) -> bool {
+    let lang = lang.trim().to_ascii_lowercase().replace('_', "-");
+    RTL_LANGUAGES
+        .iter()
+        .any(|rtl| lang == *rtl || lang.strip_prefix(rtl).is_some_and(|rest| rest.starts_with('-')))
+}
+
+fn is_rtl_char(c: char) -> bool {
+    matches!(c,
+        '\u{0590}'..='\u{08FF}'
+        | '\u{FB1D}'..='\u{FDFF}'
+        | '\u{FE70}'..='\u{FEFF}'
+        | '\u{10800}'..='\u{10FFF}'
+        | '\u{1E800}'..='\u{1EFFF}')
+}
+
+// 第一个有方向的字符是否从右向左，数字与标点没有方向，没有文字时为 None
+fn first_strong_rtl(text: &str) -> Option<bool> {
+    text.chars().find(|c| c.is_alphabetic()).map(is_rtl_char)
+}
+
+// dir 属性的值，rtl 为 Some(true)，ltr 为 Some(false)，auto 或未设置为 None
+fn direction(node: &Handle) -> Option<bool> {
+    match dom::attr(node, "dir")?.trim().to_ascii_lowercase().as_str() {
+        "rtl" => Some(true),
+        "ltr" => Some(false),
+        _ => None,
+    }
+}
+
+fn is_block(node: &Handle) -> bool {
+    dom::tag_name(node).is_some_and(|tag| BLOCK_TAGS.contains(&tag.as_str()))
+}
+
+fn wrap_children(node: &Handle, open: &str, close: &str) {
+    dom::prepend(node, dom::text(open));
+    dom::append(node, dom::text(close));
+}
+
+// 段落第一个有方向的字符与段落方向相反时，Markdown 阅读器按首个字符猜出的方向会把整段排反，
+// 开头补一个 RLM 或 LRM
+fn mark_block(node: &Handle, rtl: bool) -> bool {
+    let has_block = dom::descendants(node).iter().skip(1).any(is_block);
+    if has_block || first_strong_rtl(&dom::text_content(node)).is_none_or(|first| first == rtl) {
+        return false;
+    }
+    dom::prepend(node, dom::text(if rtl { RLM } else { LRM }));
+    true
+}
+
+// rtl 为上层声明的方向，没有声明时为 None
+fn mark_children(node: &Handle, rtl: Option<bool>, changed: &mut bool) {
+    let children = node.children.borrow().clone();
+    for child in children {
+        if !matches!(child.data, NodeData::Element { .. }) {
+            continue;
+        }
+        let tag = dom::tag_name(&child).unwrap_or_default();
+        let own = direction(&child);
+        let child_rtl = own.or(rtl);
+        if is_block(&child) {
+            if let Some(child_rtl) = child_rtl {
+                *changed |= mark_block(&child, child_rtl);
+            }
+        } else if tag == "bdo" {
+            // <bdo> 强制按指定方向排列
+            let open = if own == Some(true) { RLO } else { LRO };
+            wrap_children(&child, open, PDF);
+            *changed = true;
+        } else if own.is_some_and(|own| own != rtl.unwrap_or(false))
+            || tag == "bdi"
+            || dom::attr(&child, "dir").is_some_and(|dir| dir.trim().eq_ignore_ascii_case("auto"))
+        {
+            // 方向不同的行内文字用隔离符包起来，其中的标点与数字不会与两侧的文字混排
+            let open = match own {
+                Some(true) => RLI,
+                Some(false) => LRI,
+                None => FSI,
+            };
+            wrap_children(&child, open, PDI);
+            *changed = true;
+        }
+        mark_children(&child, child_rtl, changed);
+    }
+}
+
+// 按 <html>、<body> 的 dir 或 lang 属性确定文档方向，未声明时按书的语言（rtl）；书写方向从右向左的文档中，
+// 方向与文档相反的段落开头加上方向标记，dir 属性与 <bdi>、<bdo> 改为对应的隔离符或覆盖符。没有改动时返回 None
+pub fn mark(html: &str, rtl: bool) -> Option<String> {
+    let document = dom::parse(html);
+    let mut declared = rtl.then_some(true);
+    let mut body = document.document.clone();
+    for node in dom::descendants(&document.document) {
+        if matches!(dom::tag_name(&node).as_deref(), Some("html" | "body")) {
+            if let Some(lang) = dom::attr(&node, "lang").filter(|lang| !lang.trim().is_empty()) {
+                declared = is_rtl_language(&lang).then_some(true);
+            }
+            declared = direction(&node).or(declared);
+            body = node;
+        }
+    }
+    let mut changed = false;
+    mark_children(&body, declared, &mut changed);
+    changed.then(|| dom::inner_html(&document.document))
+}
@@ -0,0 +1,267 @@
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{
+    epub::Metadata,
+    headings,
+    images::ImageLinks,
+    links,
+    options::{Flavor, LinkStyle, Separator},
+    markdown::{self, Reference, ReferenceKind},
+    naming,
+    toc::TocEntry,
+};
+
+// 转换后的整本书
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    pub metadata: Metadata,
+    pub chapters: Vec<Chapter>,
+    // EPUB 目录，pandoc 后端为空
+    pub toc: Vec<TocEntry>,
+    // 需要复制到输出目录的图片等资源，键为压缩包内路径
+    pub resources: BTreeMap<String, Vec<u8>>,
+    // --extract-images 写出的图片，输出时改写指向它们的链接
+    pub images: ImageLinks,
+}
+
+// 转换得到的一个章节，对应一个内容文档
+#[derive(Debug, Clone)]
+pub struct Chapter {
+    // 来源文档在压缩包中的路径，合成的章节（如抢救报告）为其名称
+    pub source: String,
+    // 内容文档的 manifest id
+    pub id: Option<String>,
+    // 取自目录，目录中没有时取文档中的第一个标题
+    pub title: Option<String>,
+    // 内容文档 <html> 或 <body> 的 xml:lang，没有时取自 OPF 的 dc:language
+    pub language: Option<String>,
+    // 含书内引用占位标记的 Markdown，见 markdown::render_references
+    pub markdown: String,
+    pub references: Vec<Reference>,
+    // 带 id 的标题：(id, 标题文字)
+    pub anchors: Vec<(String, String)>,
+    // 被书内链接指向的位置：(片段 id, 输出中的锚点名)，片段为空表示文档开头，见 links::anchor
+    pub link_targets: Vec<(String, String)>,
+}
+
+impl Chapter {
+    // 来源文档不带扩展名的文件名，用于生成输出文件名
+    pub fn stem(&self) -> String {
+        Path::new(&self.source)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("chapter")
+            .to_string()
+    }
+
+    // 把另一部分接在本章末尾，对方的引用序号顺延
+    fn append(&mut self, other: Chapter) {
+        let markdown = markdown::shift_references(&other.markdown, self.references.len());
+        self.markdown = format!("{}\n\n{}", self.markdown, markdown);
+        self.references.extend(other.references);
+        self.anchors.extend(other.anchors);
+        self.link_targets.extend(other.link_targets);
+    }
+
+    // 某个片段 id 对应的标题文字
+    pub fn anchor_heading(&self, fragment: &str) -> Option<&str> {
+        self.anchors
+            .iter()
+            .find(|(id, _)| id == fragment)
+            .map(|(_, heading)| heading.as_str())
+    }
+
+    // 某个片段 id 在输出中的锚点名
+    pub fn link_target(&self, fragment: &str) -> Option<&str> {
+        self.link_targets
+            .iter()
+            .find(|(id, _)| id == fragment)
+            .map(|(_, anchor)| anchor.as_str())
+    }
+}
+
+impl Book {
+    // 引用指向的章节；同一文档被切分成多个部分时，优先选包含目标标题的部分
+    pub fn chapter_index(&self, reference: &Reference) -> Option<usize> {
+        let fragment = reference.fragment.as_deref();
+        self.chapters
+            .iter()
+            .position(|chapter| {
+                chapter.source == reference.path
+                    && fragment.is_some_and(|f| chapter.anchor_heading(f).is_some() || chapter.link_target(f).is_some())
+            })
+            .or_else(|| self.chapters.iter().position(|chapter| chapter.source == reference.path))
+    }
+
+    // 第 index 章按原始链接输出的 Markdown：指向提取出的图片的引用改为相对本章文件的路径，
+    // 书内链接改为指向对应章节中的锚点。file_names 为分章输出时各章的文件名，合并为单个文件时为 None
+    pub fn plain_markdown(&self, index: usize, file_names: Option<&[String]>) -> String {
+        let chapter = &self.chapters[index];
+        let file_name = file_names.map_or("", |names| names[index].as_str());
+        markdown::render_references(&chapter.markdown, &chapter.references, &|reference, text| {
+            let rewritten = self
+                .images
+                .rewrite(reference, file_name)
+                .or_else(|| self.internal_link(reference, index, file_names));
+            rewritten.as_ref().unwrap_or(reference).to_markdown(text)
+        })
+    }
+
+    // 指向书内章节的链接；目标不在输出中时返回 None，保留原链接
+    fn internal_link(&self, reference: &Reference, from: usize, file_names: Option<&[String]>) -> Option<Reference> {
+        if reference.kind != ReferenceKind::Link {
+            return None;
+        }
+        let target = self.chapter_index(reference)?;
+        let chapter = &self.chapters[target];
+        let anchor = reference.fragment.as_deref().and_then(|fragment| chapter.link_target(fragment));
+        let href = match file_names {
+            None => format!("#{}", anchor.or_else(|| chapter.link_target(""))?),
+            Some(names) => {
+                let file = match (target == from, anchor) {
+                    (true, Some(_)) => String::new(),
+                    _ => {
+                        let depth = names[from].matches('/').count();
+                        format!("{}{}", "../".repeat(depth), names[target]).replace(' ', "%20")
+                    }
+                };
+                match anchor {
+                    Some(anchor) => format!("{}#{}", file, anchor),
+                    None => file,
+                }
+            }
+        };
+        Some(Reference {
+            href,
+            ..reference.clone()
+        })
+    }
+
+    // 按在书中首次被引用的顺序列出资源，内容相同的资源因此以先出现的文件名为准
+    pub fn resources_in_order(&self) -> Vec<(&str, &[u8])> {
+        let mut seen = std::collections::HashSet::new();
+        self.chapters
+            .iter()
+            .flat_map(|chapter| &chapter.references)
+            .map(|reference| reference.path.as_str())
+            .chain(self.resources.keys().map(String::as_str))
+            .filter(|path| seen.insert(*path))
+            .filter_map(|path| self.resources.get_key_value(path))
+            .map(|(path, bytes)| (path.as_str(), bytes.as_slice()))
+            .collect()
+    }
+
+    // 按标题级别重新切分章节，不受 EPUB 内容文档划分的影响；
+    // 文档开头没有标题的内容接在上一部分后面。切分后目录不再与文件一一对应，因此清空
+    pub fn split_by_heading(&mut self, level: usize) {
+        let mut parts: Vec<Chapter> = Vec::new();
+        for chapter in std::mem::take(&mut self.chapters) {
+            for (i, (title, markdown)) in split_markdown(&chapter.markdown, level).into_iter().enumerate() {
+                let part = Chapter {
+                    source: chapter.source.clone(),
+                    id: chapter.id.clone(),
+                    title: title.clone().or_else(|| if i == 0 { chapter.title.clone() } else { None }),
+                    language: chapter.language.clone(),
+                    anchors: chapter
+                        .anchors
+                        .iter()
+                        .filter(|(_, heading)| {
+                            markdown.lines().any(|line| line.trim_start_matches('#').trim() == heading)
+                        })
+                        .cloned()
+                        .collect(),
+                    link_targets: chapter
+                        .link_targets
+                        .iter()
+                        .filter(|(fragment, anchor)| match fragment.is_empty() {
+                            true => i == 0,
+                            false => contains_anchor(&markdown, anchor),
+                        })
+                        .cloned()
+                        .collect(),
+                    markdown,
+                    references: chapter.references.clone(),
+                };
+                match parts.last_mut() {
+                    Some(previous) if title.is_none() => previous.append(part),
+                    _ => parts.push(part),
+                }
+            }
+        }
+        self.chapters = parts;
+        self.toc.clear();
+    }
+}
+
+// 在级别不高于 level 的标题处切开，返回 (标题文字, 内容)，代码块中的内容不参与判断
+fn split_markdown(markdown: &str, level: usize) -> Vec<(Option<String>, String)> {
+    let lines: Vec<&str> = markdown.lines().collect();
+    let mut parts: Vec<(Option<String>, Vec<&str>)> = vec![(None, Vec::new())];
+    let mut in_fence = false;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        let after_blank = i == 0 || lines[i - 1].trim().is_empty();
+        let title = match headings::heading_level(line, lines.get(i + 1).copied()) {
+            Some(heading) if !in_fence && after_blank && heading <= level => {
+                Some(line.trim_start_matches('#').trim().to_string())
+            }
+            _ => None,
+        };
+        match (title, parts.last_mut()) {
+            (None, Some((_, part))) => part.push(line),
+            (title, _) => parts.push((title, vec![line])),
+        }
+    }
+
+    parts
+        .into_iter()
+        .map(|(title, part)| (title, part.join("\n").trim().to_string()))
+        .filter(|(title, part)| title.is_some() || !part.is_empty())
+        .collect()
+}
+
+// 插入的锚点或按文字生成同名锚点的标题是否在这段 Markdown 中
+fn contains_anchor(markdown: &str, anchor: &str) -> bool {
+    [Flavor::Gfm, Flavor::Pandoc].iter().any(|flavor| markdown.contains(&flavor.anchor(anchor)))
+        || markdown
+            .lines()
+            .any(|line| naming::heading_anchor(line.trim_start_matches('#').trim()) == anchor)
+}
+
+// 将所有章节合并为单个 Markdown 文档，被链接到开头的章节前插入锚点；
+// 引用式链接的编号在全书中连续，各章的定义放在该章末尾
+pub fn join(book: &Book, separator: &Separator, chapter_headings: bool, link_style: LinkStyle, flavor: Flavor) -> String {
+    let separator = match separator.text() {
+        Some(text) => format!("\n\n{}\n\n", text),
+        None => "\n\n".to_string(),
+    };
+    let mut next_label = 1;
+    book.chapters
+        .iter()
+        .enumerate()
+        .map(|(i, chapter)| {
+            let (markdown, next) = links::restyle(&book.plain_markdown(i, None), link_style, next_label);
+            next_label = next;
+            let markdown = match &chapter.title {
+                Some(title) if chapter_headings && !starts_with_heading(&markdown) => {
+                    format!("# {}\n\n{}", title, markdown)
+                }
+                _ => markdown,
+            };
+            match chapter.link_target("") {
+                Some(anchor) => format!("{}\n\n{}", flavor.anchor(anchor), markdown),
+                None => markdown,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(&separator)
+}
+
+fn starts_with_heading(markdown: &str) -> bool {
+    let mut lines = markdown.trim_start().lines();
+    lines
+        .next()
+        .is_some_and(|first| headings::heading_level(first, lines.next()).is_some())
+}
@@ -0,0 +1,154 @@
+use std::collections::{HashMap, HashSet};
+
+use html2md::{common::get_tag_attr, Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+
+use crate::{dom, markdown::default_handler, options::Bouten};
+
+// 样式表中设有 text-emphasis 的 class，预处理时给使用它们的元素加上这个 class
+pub const BOUTEN_CLASS: &str = "epub2md-bouten";
+// 日文书中表示傍点（着重号）的常见 class，比较时忽略 - 与 _
+const BOUTEN_WORDS: &[&str] = &["sesame", "bouten", "boten", "kenten", "emdot", "emphasisdot", "textemphasis"];
+
+fn is_bouten(tag: &Handle) -> bool {
+    let class = get_tag_attr(tag, "class").unwrap_or_default().to_ascii_lowercase();
+    let style = get_tag_attr(tag, "style").unwrap_or_default().to_ascii_lowercase();
+    class.split_whitespace().any(|token| {
+        let token = token.replace(['-', '_'], "");
+        token == BOUTEN_CLASS.replace('-', "") || BOUTEN_WORDS.iter().any(|word| token.contains(word))
+    }) || style.split(';').any(emphasizes)
+}
+
+// text-emphasis、-epub-text-emphasis-style 等声明，值为 none 的除外
+fn emphasizes(declaration: &str) -> bool {
+    declaration.split_once(':').is_some_and(|(property, value)| {
+        property.trim().trim_start_matches("-epub-").trim_start_matches("-webkit-").starts_with("text-emphasis")
+            && !property.trim().ends_with("-position")
+            && !property.trim().ends_with("-color")
+            && value.trim() != "none"
+    })
+}
+
+// 样式表中声明了着重号的 class 名
+pub fn emphasis_classes(stylesheets: &[String]) -> HashSet<String> {
+    let mut classes = HashSet::new();
+    for css in stylesheets {
+        let css = css.to_ascii_lowercase();
+        for rule in css.split('}') {
+            let Some((selectors, declarations)) = rule.split_once('{') else {
+                continue;
+            };
+            if !declarations.split(';').any(emphasizes) {
+                continue;
+            }
+            // 只看每个选择器最后一段中的 class，例如 "p .bou" 中的 bou
+            for selector in selectors.rsplit("*/").next().unwrap_or_default().split(',') {
+                let last = selector.split_whitespace().last().unwrap_or_default();
+                for class in last.split('.').skip(1) {
+                    let class = class.split([':', '[', '>', '+', '~']).next().unwrap_or_default();
+                    if !class.is_empty() {
+                        classes.insert(class.to_string());
+                    }
+                }
+            }
+        }
+    }
+    classes
+}
+
+// 给 class 在 classes 中的元素加上 BOUTEN_CLASS；没有改动时返回 None
+pub fn mark(html: &str, classes: &HashSet<String>) -> Option<String> {
+    if classes.is_empty() {
+        return None;
+    }
+    let document = dom::parse(html);
+    let mut changed = false;
+    for node in dom::descendants(&document.document) {
+        let Some(class) = dom::attr(&node, "class") else {
+            continue;
+        };
+        if class.split_whitespace().any(|token| classes.contains(&token.to_ascii_lowercase())) {
+            dom::set_attr(&node, "class", &format!("{} {}", class, BOUTEN_CLASS));
+            changed = true;
+        }
+    }
+    changed.then(|| dom::inner_html(&document.document))
+}
+
+// 带着重号的文字按 --bouten 写成 **粗体**、*强调*、普通文字或模板，其余元素交给原来的处理器
+struct BoutenHandler {
+    style: Bouten,
+    bouten: bool,
+    start_pos: usize,
+    fallback: Box<dyn TagHandler>,
+}
+
+impl TagHandler for BoutenHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        self.bouten = is_bouten(tag);
+        self.start_pos = printer.data.len();
+        if !self.bouten {
+            self.fallback.handle(tag, printer);
+        }
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        if !self.bouten {
+            self.fallback.after_handle(printer);
+            return;
+        }
+        // 标记加在首尾空白以内
+        let Some(offset) = printer.data[self.start_pos..].find(|c: char| !c.is_whitespace()) else {
+            return;
+        };
+        let start = self.start_pos + offset;
+        let end = printer.data.trim_end().len();
+        let text = printer.data[start..end].to_string();
+        let marked = match &self.style {
+            Bouten::Bold => format!("**{}**", text),
+            Bouten::Emphasis => format!("*{}*", text),
+            Bouten::Plain => text,
+            Bouten::Template(template) => template.replace("{text}", &text),
+        };
+        printer.data.replace_range(start..end, &marked);
+    }
+
+    fn skip_descendants(&self) -> bool {
+        !self.bouten && self.fallback.skip_descendants()
+    }
+}
+
+struct BoutenHandlerFactory {
+    style: Bouten,
+    tag: &'static str,
+    fallback: Option<Box<dyn TagHandlerFactory>>,
+}
+
+impl TagHandlerFactory for BoutenHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        let fallback = match &self.fallback {
+            Some(factory) => factory.instantiate(),
+            None => default_handler(self.tag),
+        };
+        Box::new(BoutenHandler {
+            style: self.style.clone(),
+            bouten: false,
+            start_pos: 0,
+            fallback,
+        })
+    }
+}
+
+// 需在其他处理器之后注册，没有着重号的 <em>、<span> 等仍交给已注册的处理器
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, style: &Bouten) {
+    for tag in ["em", "i", "b", "strong", "span"] {
+        let fallback = handlers.remove(tag);
+        handlers.insert(
+            tag.to_string(),
+            Box::new(BoutenHandlerFactory {
+                style: style.clone(),
+                tag,
+                fallback,
+            }),
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use markup5ever_rcdom::{Handle, NodeData};
+use zhconv::{get_builtin_converter, Variant, ZhConverter};
+
+use crate::{book::Book, dom, options::Chinese, toc::TocEntry};
+
+// 其中的文字原样保留；标明为日文或韩文的元素中的汉字同样不转换
+pub const SKIPPED_TAGS: &[&str] = &["pre", "code", "kbd", "samp", "var", "script", "style", "math", "svg"];
+
+// zhconv 的繁体与简体规则合并了 MediaWiki 与 OpenCC 的字表和词表，一简多繁按词转换
+fn variant(direction: Chinese) -> Option<Variant> {
+    match direction {
+        Chinese::S2t => Some(Variant::ZhHant),
+        Chinese::T2s => Some(Variant::ZhHans),
+        Chinese::None => None,
+    }
+}
+
+// 转换一段纯文字
+pub fn convert(text: &str, direction: Chinese) -> String {
+    match variant(direction) {
+        Some(variant) => zhconv::zhconv(text, variant),
+        None => text.to_string(),
+    }
+}
+
+fn convert_children(node: &Handle, converter: &ZhConverter) {
+    for child in node.children.borrow().iter() {
+        match &child.data {
+            NodeData::Text { contents } => {
+                let converted = converter.convert(&contents.borrow());
+                *contents.borrow_mut() = converted.as_str().into();
+            }
+            NodeData::Element { .. } => {
+                let skipped = dom::tag_name(child).is_some_and(|tag| SKIPPED_TAGS.contains(&tag.as_str()))
+                    || dom::attr(child, "lang").is_some_and(|lang| lang.starts_with("ja") || lang.starts_with("ko"));
+                if !skipped {
+                    convert_children(child, converter);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// 只转换文档中的文字节点，属性中的链接地址、id 以及代码和公式中的文字保持不变
+pub fn convert_html(html: &str, direction: Chinese) -> String {
+    let Some(variant) = variant(direction) else {
+        return html.to_string();
+    };
+    let document = dom::parse(html);
+    convert_children(&document.document, get_builtin_converter(variant));
+    dom::inner_html(&document.document)
+}
+
+fn convert_toc(entries: &mut [TocEntry], direction: Chinese) {
+    for entry in entries {
+        entry.title = convert(&entry.title, direction);
+        convert_toc(&mut entry.children, direction);
+    }
+}
+
+// 书名、作者等元数据以及目录与章节标题，正文已在转换前按文档转换
+pub fn convert_book(book: &mut Book, direction: Chinese) {
+    if direction == Chinese::None {
+        return;
+    }
+    let metadata = &mut book.metadata;
+    for value in metadata.title.iter_mut().chain(&mut metadata.publisher).chain(&mut metadata.description) {
+        *value = convert(value, direction);
+    }
+    for value in metadata.authors.iter_mut().chain(&mut metadata.subjects) {
+        *value = convert(value, direction);
+    }
+    for chapter in &mut book.chapters {
+        if let Some(title) = chapter.title.as_mut() {
+            *title = convert(title, direction);
+        }
+    }
+    convert_toc(&mut book.toc, direction);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_characters_both_ways() {
+        assert_eq!(convert("爱国书店", Chinese::S2t), "愛國書店");
+        assert_eq!(convert("愛國書店", Chinese::T2s), "爱国书店");
+        assert_eq!(convert("Rust 1.0 与汉字", Chinese::S2t), "Rust 1.0 與漢字");
+        assert_eq!(convert("爱", Chinese::None), "爱");
+    }
+
+    #[test]
+    fn one_to_many_characters_follow_the_phrase() {
+        assert_eq!(convert("头发", Chinese::S2t), "頭髮");
+        assert_eq!(convert("发展", Chinese::S2t), "發展");
+        assert_eq!(convert("皇后", Chinese::S2t), "皇后");
+        assert_eq!(convert("以后", Chinese::S2t), "以後");
+        assert_eq!(convert("干净", Chinese::S2t), "乾淨");
+        assert_eq!(convert("干涉", Chinese::S2t), "干涉");
+        assert_eq!(convert("面条", Chinese::S2t), "麵條");
+        assert_eq!(convert("一只猫", Chinese::S2t), "一隻貓");
+        assert_eq!(convert("公里", Chinese::S2t), "公里");
+        assert_eq!(convert("这里", Chinese::S2t), "這裡");
+    }
+
+    #[test]
+    fn ambiguous_characters_in_running_text() {
+        assert_eq!(convert("把衣服洗干净，她是皇后，剪了头发", Chinese::S2t), "把衣服洗乾淨，她是皇后，剪了頭髮");
+        assert_eq!(convert("他去干活了，后来发现", Chinese::S2t), "他去幹活了，後來發現");
+        assert_eq!(convert("把衣服洗乾淨，她是皇后，剪了頭髮", Chinese::T2s), "把衣服洗干净，她是皇后，剪了头发");
+    }
+
+    #[test]
+    fn traditional_only_characters_convert_back() {
+        assert_eq!(convert("頭髮", Chinese::T2s), "头发");
+        assert_eq!(convert("麵條", Chinese::T2s), "面条");
+        assert_eq!(convert("乾淨", Chinese::T2s), "干净");
+        assert_eq!(convert("乾隆", Chinese::T2s), "乾隆");
+        assert_eq!(convert("裡面", Chinese::T2s), "里面");
+    }
+
+    #[test]
+    fn html_keeps_code_attributes_and_japanese() {
+        let html = r#"<p title="汉字">汉字<code>汉字</code><span lang="ja">漢字</span><a href="汉.xhtml">书</a></p>"#;
+        let converted = convert_html(html, Chinese::S2t);
+        assert!(converted.contains(r#"title="汉字">漢字<code>汉字</code>"#), "{}", converted);
+        assert!(converted.contains(r#"<span lang="ja">漢字</span>"#), "{}", converted);
+        assert!(converted.contains(r#"href="汉.xhtml">書</a>"#), "{}", converted);
+    }
+}
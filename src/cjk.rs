@@ -0,0 +1,308 @@
+use std::rc::Rc;
+
+use markup5ever_rcdom::{Handle, NodeData};
+
+use crate::{
+    book::Book,
+    chinese::SKIPPED_TAGS,
+    dom,
+    options::{Options, Punctuation},
+    toc::TocEntry,
+};
+
+// 行内元素两侧的文字相邻，可能需要加空格；其他元素是独立的区块
+const INLINE_TAGS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "cite", "del", "dfn", "em", "i", "ins", "mark", "q", "s", "small", "span",
+    "strong", "u", "time",
+];
+
+// 汉字、假名、注音与谚文，不含全角标点
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{2E80}'..='\u{2FFF}'
+        | '\u{3040}'..='\u{30FF}'
+        | '\u{3100}'..='\u{312F}'
+        | '\u{3190}'..='\u{31FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2FA1F}')
+}
+
+fn needs_space(before: char, after: char) -> bool {
+    (is_cjk(before) && after.is_ascii_alphanumeric()) || (before.is_ascii_alphanumeric() && is_cjk(after))
+}
+
+fn space_text(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut prev = None;
+    for c in text.chars() {
+        if prev.is_some_and(|p| needs_space(p, c)) {
+            output.push(' ');
+        }
+        output.push(c);
+        prev = Some(c);
+    }
+    output
+}
+
+fn full_width(c: char) -> Option<char> {
+    Some(match c {
+        ',' => '，',
+        '.' => '。',
+        '!' => '！',
+        '?' => '？',
+        ':' => '：',
+        ';' => '；',
+        '(' => '（',
+        ')' => '）',
+        _ => return None,
+    })
+}
+
+fn half_width(c: char) -> Option<char> {
+    Some(match c {
+        '，' | '、' => ',',
+        '。' => '.',
+        '！' => '!',
+        '？' => '?',
+        '：' => ':',
+        '；' => ';',
+        '（' => '(',
+        '）' => ')',
+        _ => return None,
+    })
+}
+
+// 紧跟在中日韩文字之后的半角标点改为全角，全角标点自带间距，其后的空格一并去掉；
+// 括号在其中或其前是中日韩文字时成对改为全角。“1.5”“Dr.”与省略号“...”不变，英文之间的全角标点改为半角
+fn full_text(text: &str, prev: Option<char>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut output = String::with_capacity(text.len());
+    let mut previous = prev.filter(|c| !c.is_whitespace());
+    let mut parens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let after_cjk = previous.is_some_and(|p| is_cjk(p) || half_width(p).is_some());
+        let next = chars[i + 1..].iter().copied().find(|c| !c.is_whitespace());
+        let converted = match c {
+            '(' => {
+                let cjk = after_cjk || next.is_some_and(is_cjk);
+                parens.push(cjk);
+                cjk
+            }
+            ')' => parens.pop().unwrap_or(false) || after_cjk,
+            '.' => after_cjk && chars.get(i + 1) != Some(&'.') && previous != Some('.'),
+            _ => after_cjk && full_width(c).is_some(),
+        };
+        // 英文之间的全角标点改为半角
+        let latin = previous.is_some_and(|p| p.is_ascii_alphanumeric()) && next.is_none_or(|n| n.is_ascii());
+        if let Some(half) = half_width(c).filter(|_| latin) {
+            output.push(half);
+            previous = Some(half);
+            i += 1;
+            if half != '(' && chars.get(i).is_some_and(|c| !c.is_whitespace()) {
+                output.push(' ');
+            }
+            continue;
+        }
+        match full_width(c).filter(|_| converted) {
+            Some(full) => {
+                // 左括号之前与其他全角标点之后的空格
+                if full == '（' {
+                    let trimmed = output.trim_end_matches(' ').len();
+                    output.truncate(trimmed);
+                }
+                output.push(full);
+                previous = Some(full);
+                i += 1;
+                if full != '（' {
+                    while chars.get(i) == Some(&' ') {
+                        i += 1;
+                    }
+                }
+            }
+            None => {
+                output.push(c);
+                if !c.is_whitespace() {
+                    previous = Some(c);
+                }
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+// 全角标点改为半角，句读之后与左括号之前补上西文的空格
+fn half_text(text: &str, prev: Option<char>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut output = String::with_capacity(text.len());
+    let mut previous = prev;
+    for (i, &c) in chars.iter().enumerate() {
+        let Some(half) = half_width(c) else {
+            output.push(c);
+            previous = Some(c);
+            continue;
+        };
+        if half == '(' && previous.is_some_and(|p| !p.is_whitespace() && p != '(') {
+            output.push(' ');
+        }
+        output.push(half);
+        let next = chars.get(i + 1);
+        if half != '(' && next.is_some_and(|&n| !n.is_whitespace() && half_width(n).is_none() && !",.!?:;)".contains(n)) {
+            output.push(' ');
+        }
+        previous = Some(half);
+    }
+    output
+}
+
+fn convert_text(text: &str, prev: Option<char>, options: &Options) -> String {
+    let text = match options.punct {
+        Punctuation::Keep => text.to_string(),
+        Punctuation::Full => full_text(text, prev),
+        Punctuation::Half => half_text(text, prev),
+    };
+    if options.pangu {
+        space_text(&text)
+    } else {
+        text
+    }
+}
+
+// 两段文字之间是否要加空格：中英文之间，或者改为半角的句读之后
+fn boundary_space(before: char, after: char, options: &Options) -> bool {
+    (options.pangu && needs_space(before, after))
+        || (options.punct == Punctuation::Half && ",.!?:;)".contains(before) && is_cjk(after))
+}
+
+// 空格加在两段文字共同的父元素中，不放进 <em> 等元素里，避免写成“* Rust*”
+fn insert_space_between(previous: &Handle, current: &Handle) {
+    let mut ancestors = Vec::new();
+    let mut node = dom::parent(previous);
+    while let Some(parent) = node {
+        node = dom::parent(&parent);
+        ancestors.push(parent);
+    }
+    let mut top = current.clone();
+    while let Some(parent) = dom::parent(&top) {
+        if ancestors.iter().any(|ancestor| Rc::ptr_eq(ancestor, &parent)) {
+            dom::insert_before(&top, dom::text(" "));
+            return;
+        }
+        top = parent;
+    }
+}
+
+// last 为同一区块中前一段文字及其最后一个字符
+fn convert_children(node: &Handle, last: &mut Option<(Handle, char)>, options: &Options) {
+    // 插入空格会改动正在遍历的子节点列表，先复制一份
+    let children = node.children.borrow().clone();
+    for child in children {
+        match &child.data {
+            NodeData::Text { contents } => {
+                let before = last.as_ref().map(|(_, c)| *c);
+                let converted = convert_text(&contents.borrow(), before, options);
+                let Some(first) = converted.chars().next() else {
+                    continue;
+                };
+                if let Some((previous, before)) = last.take() {
+                    if boundary_space(before, first, options) {
+                        insert_space_between(&previous, &child);
+                    }
+                }
+                *last = converted.chars().last().map(|c| (child.clone(), c));
+                *contents.borrow_mut() = converted.as_str().into();
+            }
+            NodeData::Element { .. } => {
+                let tag = dom::tag_name(&child).unwrap_or_default();
+                if SKIPPED_TAGS.contains(&tag.as_str()) {
+                    *last = None;
+                } else if INLINE_TAGS.contains(&tag.as_str()) {
+                    convert_children(&child, last, options);
+                } else {
+                    *last = None;
+                    convert_children(&child, last, options);
+                    *last = None;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// 是否指定了 --pangu 或 --punct
+pub fn enabled(options: &Options) -> bool {
+    options.pangu || options.punct != Punctuation::Keep
+}
+
+// 按 --punct 统一中文标点，按 --pangu 在中日韩文字与相邻的拉丁字母或数字之间加一个空格，
+// 例如“使用Rust编写”→“使用 Rust 编写”。只改动文字节点，代码与链接地址不变，标题改动后书内链接的
+// 锚点随之生成
+pub fn convert_html(html: &str, options: &Options) -> String {
+    let document = dom::parse(html);
+    convert_children(&document.document, &mut None, options);
+    dom::inner_html(&document.document)
+}
+
+fn convert_toc(entries: &mut [TocEntry], options: &Options) {
+    for entry in entries {
+        entry.title = convert_text(&entry.title, None, options);
+        convert_toc(&mut entry.children, options);
+    }
+}
+
+// 目录与章节标题同样处理，与正文中的标题一致
+pub fn convert_book(book: &mut Book, options: &Options) {
+    for chapter in &mut book.chapters {
+        if let Some(title) = chapter.title.as_mut() {
+            *title = convert_text(title, None, options);
+        }
+    }
+    convert_toc(&mut book.toc, options);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(pangu: bool, punct: Punctuation) -> Options {
+        Options {
+            pangu,
+            punct,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn pangu_spaces_cjk_and_latin() {
+        let pangu = options(true, Punctuation::Keep);
+        assert_eq!(convert_text("使用Rust编写的3个工具", None, &pangu), "使用 Rust 编写的 3 个工具");
+        assert_eq!(convert_text("已经 spaced 的文字", None, &pangu), "已经 spaced 的文字");
+        assert_eq!(convert_text("日本語とEnglish", None, &pangu), "日本語と English");
+    }
+
+    #[test]
+    fn pangu_space_goes_outside_inline_elements() {
+        let html = convert_html("<p>使用<em>Rust</em>编写<code>a中b</code></p>", &options(true, Punctuation::Keep));
+        assert_eq!(dom::body(&html), "<p>使用 <em>Rust</em> 编写<code>a中b</code></p>");
+    }
+
+    #[test]
+    fn full_width_punctuation() {
+        let full = options(false, Punctuation::Full);
+        assert_eq!(convert_text("你好, 世界! (测试) 结束.", None, &full), "你好，世界！（测试）结束。");
+        assert_eq!(convert_text("版本1.5.", None, &full), "版本1.5.");
+        assert_eq!(convert_text("Dr. Smith, 你好...", None, &full), "Dr. Smith, 你好...");
+        assert_eq!(convert_text("Hello，world", None, &full), "Hello, world");
+    }
+
+    #[test]
+    fn half_width_punctuation() {
+        let half = options(false, Punctuation::Half);
+        assert_eq!(convert_text("你好，世界！（测试）", None, &half), "你好, 世界! (测试)");
+    }
+}
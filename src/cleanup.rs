@@ -0,0 +1,134 @@
+use crate::typography;
+
+// 写出前整理 Markdown，使其能通过 markdownlint 的常见规则：合并连续空行、去掉行尾空白
+// （硬换行保留两个空格）、无序列表统一用 -、强调统一用 * 与 **，文件以单个换行结尾。
+// 开头的 frontmatter 与代码块中的内容不变
+pub fn format_markdown(content: &str) -> String {
+    let (frontmatter, body) = split_frontmatter(content);
+    let lines: Vec<&str> = body.lines().collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut in_fence = false;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            output.push(line.trim_end().to_string());
+            continue;
+        }
+        if in_fence {
+            output.push(line.to_string());
+            continue;
+        }
+        let next_is_text = lines.get(i + 1).is_some_and(|next| !next.trim().is_empty());
+        let hard_break = line.ends_with("  ") && !line.trim().is_empty() && next_is_text;
+        let mut line = normalize_emphasis(&normalize_bullet(line.trim_end()));
+        if hard_break {
+            line.push_str("  ");
+        }
+        let blank = line.trim().is_empty() || line.trim() == ">";
+        let previous_blank = output.last().is_none_or(|last| last.trim().is_empty());
+        match output.last().map(|last| last.trim()) {
+            // 合并连续空行，开头的空行直接去掉
+            _ if line.trim().is_empty() && previous_blank => {}
+            // 引用块中连续的空行以及引用块开头的空行
+            Some(">") | Some("") | None if line.trim() == ">" => {}
+            _ if blank => output.push(line.trim().to_string()),
+            _ => output.push(line),
+        }
+    }
+    // 引用块末尾的空行
+    for i in (0..output.len()).rev() {
+        let next_quoted = output.get(i + 1).is_some_and(|next| next.starts_with('>'));
+        if output[i] == ">" && !next_quoted {
+            output.remove(i);
+        }
+    }
+    while output.last().is_some_and(|last| last.trim().is_empty()) {
+        output.pop();
+    }
+
+    let mut formatted = frontmatter.to_string();
+    formatted.push_str(&output.join("\n"));
+    formatted.push('\n');
+    formatted
+}
+
+// 开头的 YAML（---）、TOML（+++）或 JSON frontmatter 与其后的正文
+pub fn split_frontmatter(content: &str) -> (&str, &str) {
+    let closing = match content.lines().next() {
+        Some("---") => "\n---\n",
+        Some("+++") => "\n+++\n",
+        Some("{") => "\n}\n",
+        _ => return ("", content),
+    };
+    match content.find(closing) {
+        Some(end) => content.split_at(end + closing.len()),
+        None => ("", content),
+    }
+}
+
+// 以 * 或 + 开头的列表项改为 -，分隔线和空列表项不变
+fn normalize_bullet(line: &str) -> String {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    match rest.as_bytes() {
+        [b'*' | b'+', b' ', ..] if !rest.chars().all(|c| c == '*' || c == ' ') && !rest[2..].trim().is_empty() => {
+            format!("{}-{}", &line[..indent], &rest[1..])
+        }
+        _ => line.to_string(),
+    }
+}
+
+// _强调_ 与 __加粗__ 改为 * 与 **；单词中的下划线、行内代码、公式和链接地址不变
+fn normalize_emphasis(line: &str) -> String {
+    if !line.contains('_') {
+        return line.to_string();
+    }
+    let mut chars: Vec<char> = line.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if let Some(end) = typography::protected_end(&chars, i) {
+            i = end;
+            continue;
+        }
+        if chars[i] != '_' {
+            i += 1;
+            continue;
+        }
+        let run = chars[i..].iter().take_while(|&&c| c == '_').count();
+        let opens = (i == 0 || !chars[i - 1].is_alphanumeric())
+            && chars.get(i + run).is_some_and(|c| !c.is_whitespace());
+        match closing_run(&chars, i + run, run).filter(|_| opens && run <= 2) {
+            Some(close) => {
+                chars[i..i + run].fill('*');
+                chars[close..close + run].fill('*');
+                i = close + run;
+            }
+            None => i += run,
+        }
+    }
+    chars.into_iter().collect()
+}
+
+// 与开头等长、前面不是空白且后面不是字母数字的下划线串
+fn closing_run(chars: &[char], from: usize, run: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if let Some(end) = typography::protected_end(chars, i) {
+            i = end;
+            continue;
+        }
+        if chars[i] != '_' {
+            i += 1;
+            continue;
+        }
+        let length = chars[i..].iter().take_while(|&&c| c == '_').count();
+        let closes = length == run
+            && !chars[i - 1].is_whitespace()
+            && !chars.get(i + length).is_some_and(|c| c.is_alphanumeric());
+        if closes {
+            return Some(i);
+        }
+        i += length;
+    }
+    None
+}
@@ -0,0 +1,1414 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use clap::{
+    error::{ContextKind, ContextValue, ErrorKind},
+    parser::ValueSource,
+    Arg, ArgAction, ArgMatches,
+};
+use regex::Regex;
+
+use crate::{
+    archive::{safe_relative_path, STDIN},
+    completions::Shell,
+    config,
+    error::EpubToMdError,
+    glob,
+    i18n::{self, Locale},
+    images, logging,
+    options::{parse_size, Engine, Format, Options, Separator, Split},
+    output, pages, resume,
+};
+
+// 转换选项：(写法, 说明, --lang zh-CN 时的说明)，命令行解析与 --help 都由这张表生成
+const OPTIONS: &[(&str, &str, &str)] = &[
+    (
+        "-o, --output <path>",
+        "Output Markdown file, output directory with --split, or - for stdout",
+        "输出的Markdown文件，--split时为输出目录，- 表示标准输出",
+    ),
+    ("--stdout", "Write the Markdown to stdout; diagnostics stay on stderr", "把Markdown写到标准输出，诊断信息仍写到标准错误"),
+    ("-q, --quiet", "Print nothing but errors", "只打印错误"),
+    (
+        "-v, --verbose",
+        "Print per-chapter details, Pandoc command lines and skipped resources; -vv also lists every file read",
+        "打印逐章的细节、Pandoc命令行与跳过的资源；-vv还列出读取的每个文件",
+    ),
+    (
+        "--json",
+        "Print the result (outputs written, chapters, images, warnings, duration, error) to stdout as JSON",
+        "把结果（写出的文件、章节数、图片数、警告、用时、错误）以JSON写到标准输出",
+    ),
+    (
+        "--dry-run",
+        "Print the files that would be written without writing anything",
+        "打印将要写出的文件，不写出任何文件",
+    ),
+    ("--profile <name>", "Apply the options of [profile.<name>] in epub2md.toml", "使用epub2md.toml中[profile.<name>]的选项"),
+    (
+        "--recursive <dir>",
+        "Convert every EPUB under a directory tree, mirroring its structure under the output directory",
+        "转换目录树中的每个EPUB，在输出目录下保持原有的目录结构",
+    ),
+    ("--jobs <n>", "Books to convert at the same time with several inputs (default: CPU count)", "有多个输入时同时转换的书数（默认：CPU核数）"),
+    (
+        "--resume[=<file>]",
+        "Record finished books in a state file (default: .epub2md-resume in the output directory) and skip them when run again",
+        "把转换完成的书记录到状态文件（默认：输出目录中的.epub2md-resume），再次运行时跳过它们",
+    ),
+    ("--fail-fast", "Stop at the first book that fails instead of converting the rest", "第一本书失败时就停止，不再转换其余的书"),
+    (
+        "--error-report <file>",
+        "Write the books that failed, with error class and message, to <file> as JSON",
+        "把失败的书及其错误类别与信息以JSON写到<file>",
+    ),
+    ("--engine <pandoc|native|html2md>", "Conversion backend", "转换后端"),
+    (
+        "--md-flavor <flavor>",
+        "Markdown flavor for the pandoc engine (gfm, commonmark_x, markdown_strict)",
+        "pandoc后端的Markdown方言（gfm、commonmark_x、markdown_strict）",
+    ),
+    (
+        "--pandoc-path <path>",
+        "Pandoc binary to use (default: $EPUB2MD_PANDOC or pandoc in PATH)",
+        "使用的Pandoc程序（默认：$EPUB2MD_PANDOC或PATH中的pandoc）",
+    ),
+    ("--pandoc-arg <arg>", "Extra argument appended to the Pandoc command (repeatable)", "追加到Pandoc命令的参数（可重复）"),
+    ("--lua-filter <file.lua>", "Pandoc Lua filter applied during conversion (repeatable)", "转换时使用的Pandoc Lua过滤器（可重复）"),
+    ("--min-pandoc-version <version>", "Minimum accepted Pandoc version (default: 2.0)", "可接受的最低Pandoc版本（默认：2.0）"),
+    (
+        "--order <spine|toc>",
+        "Emit chapters in spine order or table-of-contents order (native engine)",
+        "按spine顺序或目录顺序输出章节（内置解析器）",
+    ),
+    (
+        "--recover",
+        "Best-effort conversion of damaged archives with a report of lost content (native engine)",
+        "尽量转换损坏的压缩包，并报告丢失的内容（内置解析器）",
+    ),
+    (
+        "--max-entry-size <size>",
+        "Maximum decompressed size of a single EPUB entry (default: 256MB, native engine)",
+        "EPUB中单个文件解压后的最大大小（默认：256MB，内置解析器）",
+    ),
+    (
+        "--max-total-size <size>",
+        "Maximum total decompressed size of the EPUB (default: 2GB, native engine)",
+        "EPUB解压后的最大总大小（默认：2GB，内置解析器）",
+    ),
+    (
+        "--rootfile <n|path>",
+        "Rootfile (OPF) to convert when container.xml declares several (native engine)",
+        "container.xml声明了多个OPF时要转换的一个（内置解析器）",
+    ),
+    (
+        "--strict",
+        "Abort on malformed OPF/XHTML, missing manifest items or broken links (native engine)",
+        "OPF/XHTML格式错误、manifest条目缺失或链接失效时中止（内置解析器）",
+    ),
+    (
+        "--split <none|chapter|h1-h6>",
+        "Write each chapter, or each section at the given heading level, to its own file (native engine)",
+        "每章或每个指定级别的小节写成单独的文件（内置解析器）",
+    ),
+    (
+        "--format <format>",
+        "Output layout: markdown, mdbook (book.toml + src/SUMMARY.md) , obsidian (vault notes with wikilinks), hugo, jekyll or zola (site content) (native engine)",
+        "输出布局：markdown、mdbook（book.toml + src/SUMMARY.md）、obsidian（带wikilink的笔记库）、hugo、jekyll或zola（站点内容）（内置解析器）",
+    ),
+    (
+        "--separator <hr|pagebreak|text>",
+        "Separator between chapters in single-file output (native engine)",
+        "单文件输出中章节之间的分隔（内置解析器）",
+    ),
+    (
+        "--chapter-headings",
+        "Insert the TOC title as a heading when a chapter does not start with one (native engine)",
+        "章节不以标题开头时插入目录中的标题（内置解析器）",
+    ),
+    (
+        "--max-file-size <size>",
+        "Split output files into numbered parts at paragraph boundaries above this size",
+        "输出文件超过此大小时在段落边界处分成编号的多个部分",
+    ),
+    (
+        "--index-name <name>",
+        "File name of the chapter index written with --split (default: index.md)",
+        "--split时章节索引的文件名（默认：index.md）",
+    ),
+    (
+        "--archive <file>",
+        "Package the generated files into a .zip, .tar or .tar.gz archive",
+        "把生成的文件打包为.zip、.tar或.tar.gz",
+    ),
+    (
+        "--frontmatter <yaml|toml|json|none>",
+        "Frontmatter written at the top of generated files (default depends on --format, native engine)",
+        "生成的文件开头的frontmatter（默认取决于--format，内置解析器）",
+    ),
+    ("--eol <lf|crlf>", "Line endings of the written Markdown (default: lf)", "写出的Markdown的换行符（默认：lf）"),
+    ("--bom", "Start the written Markdown with a UTF-8 byte order mark", "写出的Markdown以UTF-8 BOM开头"),
+    (
+        "--metadata-file <json|yaml>",
+        "Also write book.json/book.yaml with the full metadata and chapter list (native engine)",
+        "另外写出包含完整元数据与章节列表的book.json/book.yaml（内置解析器）",
+    ),
+    (
+        "--deterministic",
+        "Produce byte-identical output across runs and machines (no timestamps, fixed archive metadata)",
+        "不同时间、不同机器上的输出逐字节相同（不含时间戳，归档元数据固定）",
+    ),
+    (
+        "--name-template <template>",
+        "Chapter file names with --split, e.g. \"{index:03}-{slug}.md\" (placeholders: index, slug, id, book, chapter)",
+        "--split时章节的文件名，例如 \"{index:03}-{slug}.md\"（占位符：index、slug、id、book、chapter）",
+    ),
+    (
+        "--output-template <template>",
+        "Default output path from book metadata, e.g. \"{author}/{title}.md\" or \"{title}/{chapter}.md\" (placeholders: title, author, input, chapter)",
+        "由书的元数据生成的默认输出路径，例如 \"{author}/{title}.md\" 或 \"{title}/{chapter}.md\"（占位符：title、author、input、chapter）",
+    ),
+    (
+        "--extract-images[=<dir>]",
+        "Write referenced images to <dir> (default: assets) next to the Markdown and link to them (native engine)",
+        "把引用的图片写到Markdown旁的<dir>（默认：assets）并链接到它们（内置解析器）",
+    ),
+    (
+        "--embed-images[=<size>]",
+        "Inline images up to <size> (default: 100KB) as data URIs; larger ones are extracted with --extract-images (native engine)",
+        "不超过<size>（默认：100KB）的图片以data URI内嵌，更大的按--extract-images写出（内置解析器）",
+    ),
+    ("--image-format <webp|jpeg|png>", "Re-encode extracted or embedded bitmap images", "重新编码写出或内嵌的位图"),
+    ("--max-image-width <n>", "Downscale extracted or embedded bitmap images wider than n pixels", "缩小宽度超过n像素的位图"),
+    (
+        "--caption-style <italic|plain|alt>",
+        "How figure captions are written below images (default: italic, native and html2md engines)",
+        "图片下方图注的写法（默认：italic，内置解析器与html2md后端）",
+    ),
+    (
+        "--unicode-normalize <nfc|nfkc|none>",
+        "Normalize emitted text to NFC, or NFKC to also fold compatibility characters (default: none)",
+        "把输出的文字规范化为NFC，或用NFKC同时转换兼容字符（默认：none）",
+    ),
+    (
+        "--bouten <STYLE>",
+        "Write text with emphasis dots (bouten) as bold, emphasis, plain or a template such as '=={text}==' (default: bold)",
+        "带着重号（傍点）的文字写成bold、emphasis、plain或模板，例如'=={text}=='（默认：bold）",
+    ),
+    (
+        "--lang <en|zh-CN>",
+        "Language of messages and errors (default: from $EPUB2MD_LANG, $LC_ALL, $LC_MESSAGES or $LANG, else English)",
+        "提示与错误信息的语言（默认：$EPUB2MD_LANG、$LC_ALL、$LC_MESSAGES或$LANG，否则为英文）",
+    ),
+    (
+        "--punct <full|half|keep>",
+        "Normalize Chinese punctuation: full-width after CJK text (，。！？) or half-width everywhere (,.!?) (default: keep)",
+        "统一中文标点：中文之后用全角（，。！？）或全部用半角（,.!?）（默认：keep）",
+    ),
+    (
+        "--pangu",
+        "Insert a space between CJK characters and adjacent Latin letters or digits (使用Rust编写 → 使用 Rust 编写)",
+        "在中日韩文字与相邻的英文字母或数字之间加空格（使用Rust编写 → 使用 Rust 编写）",
+    ),
+    (
+        "--chinese <conversion>",
+        "Convert between Simplified and Traditional Chinese: s2t, t2s or none (default: none); code, URLs and math are left unchanged",
+        "简繁转换：s2t、t2s或none（默认：none），代码、链接地址与公式不变",
+    ),
+    (
+        "--flavor <flavor>",
+        "Markdown dialect for tables, footnotes, math, strikethrough and anchors: commonmark, gfm, pandoc, multimarkdown or obsidian (default: obsidian for --format obsidian, else gfm)",
+        "表格、脚注、公式、删除线与锚点的Markdown方言：commonmark、gfm、pandoc、multimarkdown或obsidian（默认：--format obsidian时为obsidian，否则为gfm）",
+    ),
+    (
+        "--skip-frontmatter",
+        "Omit leading cover, copyright, dedication and contents pages (native engine)",
+        "去掉开头的封面、版权、献词与目录等页面（内置解析器）",
+    ),
+    (
+        "--skip-backmatter",
+        "Omit trailing about-the-author, advertisement and colophon pages (native engine)",
+        "去掉结尾的作者简介、广告与版权记录等页面（内置解析器）",
+    ),
+    (
+        "--interactive",
+        "Choose the chapters to convert from the table of contents in the terminal (native engine)",
+        "在终端中从目录勾选要转换的章节（内置解析器）",
+    ),
+    (
+        "--exclude-title <regex>",
+        "Omit chapters whose table of contents title matches the pattern (repeatable, native engine)",
+        "去掉目录标题与正则表达式匹配的章节（可重复，内置解析器）",
+    ),
+    (
+        "--citations <link|footnote>",
+        "Keep bibliography entries as link targets or turn cited entries into footnotes (default: link)",
+        "参考文献条目保留为链接目标，或把被引用的条目改为脚注（默认：link）",
+    ),
+    (
+        "--page-markers[=<template>]",
+        "Write a marker such as <!-- page 12 --> at each page break of the print edition ({page} is the page number)",
+        "在纸质版的每个分页处写出 <!-- page 12 --> 这样的标记（{page}为页码）",
+    ),
+    (
+        "--underline <html|emphasis|plain>",
+        "Write <u>, <ins> and underlined spans as <u>, *emphasis* or plain text (default: html)",
+        "<u>、<ins>与带下划线的文字写成<u>、*强调*或普通文字（默认：html）",
+    ),
+    (
+        "--sup-sub <html|caret|unicode>",
+        "Write <sup>/<sub> as inline HTML, ^x^/~x~, or Unicode characters such as x² and H₂O (default: html)",
+        "<sup>/<sub>写成行内HTML、^x^/~x~或x²、H₂O这样的Unicode字符（默认：html）",
+    ),
+    (
+        "--line-breaks <spaces|backslash>",
+        "Write <br> and verse lines with two trailing spaces or a trailing backslash (default: spaces)",
+        "<br>与诗行以两个行尾空格或行尾反斜杠表示（默认：spaces）",
+    ),
+    (
+        "--definition-lists <colon|bold>",
+        "Write <dl> as Term / \":   definition\" or as a list of bold terms (default: colon for hugo and jekyll, else bold)",
+        "<dl>写成术语加 \":   定义\" 或粗体术语列表（默认：hugo与jekyll为colon，否则为bold）",
+    ),
+    (
+        "--ruby <paren|html|base>",
+        "Write ruby annotations as 漢字（かんじ）, keep the <ruby> HTML, or drop them (default: paren, native and html2md engines)",
+        "注音写成漢字（かんじ）、保留<ruby> HTML或去掉（默认：paren，内置解析器与html2md后端）",
+    ),
+    (
+        "--admonitions <github|colon|admonish|none>",
+        "How note/tip/warning boxes are written (default: github, native and html2md engines)",
+        "注意、提示、警告框的写法（默认：github，内置解析器与html2md后端）",
+    ),
+    (
+        "--unknown-html <keep|strip|comment>",
+        "Keep, drop or comment out elements Markdown cannot express, e.g. <aside>, <details> (native and html2md engines)",
+        "保留、去掉或注释掉Markdown无法表示的元素，例如<aside>、<details>（内置解析器与html2md后端）",
+    ),
+    (
+        "--normalize-headings",
+        "One H1 per chapter (the book title in single-file output) with no skipped levels below it",
+        "每章一个H1（单文件输出中为书名），其下的级别不跳级",
+    ),
+    (
+        "--heading-offset <n>",
+        "Shift every heading level by n (-5 to 5), applied after --normalize-headings",
+        "所有标题级别加n（-5到5），在--normalize-headings之后进行",
+    ),
+    (
+        "--typography <smart|straight|keep>",
+        "Normalize quotes, dashes and ellipses to typographic or ASCII characters (default: keep)",
+        "引号、破折号与省略号统一为印刷字符或ASCII字符（默认：keep）",
+    ),
+    (
+        "--no-format",
+        "Keep the raw converter output instead of tidying blank lines, list markers and emphasis",
+        "保留转换器的原始输出，不整理空行、列表符号与强调",
+    ),
+    (
+        "--wrap <none|N>",
+        "One line per paragraph, or hard-wrap paragraphs at column N (default: keep the converter's wrapping)",
+        "每段一行，或在第N列处硬换行（默认：保留转换器的换行）",
+    ),
+    (
+        "--link-style <inline|reference>",
+        "Write links inline or as numbered reference definitions at the end of each chapter (default: inline)",
+        "链接写在行内，或写成每章末尾编号的引用定义（默认：inline）",
+    ),
+    ("--force", "Overwrite existing output files", "覆盖已有的输出文件"),
+    ("--update", "Skip the conversion when the output is newer than the input EPUB", "输出比输入的EPUB新时跳过转换"),
+    ("--print-toc", "Print the parsed table of contents and exit", "打印解析得到的目录后退出"),
+    (
+        "--metadata-only",
+        "Print the title, authors, language, identifiers, publisher, date, subjects and cover of each book without converting",
+        "只打印每本书的书名、作者、语言、标识符、出版社、日期、主题与封面，不做转换",
+    ),
+    (
+        "--stats[=<file>]",
+        "Print word, character, image and footnote counts and reading time per chapter after converting, and write them to <file> as JSON",
+        "转换后打印每章的词数、字数、图片数、脚注数与阅读时间，并以JSON写到<file>",
+    ),
+];
+
+// 子命令：(名称, 说明, --lang zh-CN 时的说明)
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("convert", "Convert an EPUB to Markdown (the default command)", "把EPUB转换为Markdown（默认的命令）"),
+    ("batch", "Convert several EPUBs into one output directory", "把多个EPUB转换到同一个输出目录"),
+    ("inspect", "Print the metadata and spine of an EPUB", "打印EPUB的元数据与spine"),
+    ("toc", "Print the parsed table of contents", "打印解析得到的目录"),
+    ("extract", "Unpack the files of an EPUB into a directory", "把EPUB中的文件解压到目录"),
+    (
+        "validate",
+        "Check the OPF, XHTML documents and links of an EPUB without writing anything",
+        "检查EPUB的OPF、XHTML文档与链接，不写出任何文件",
+    ),
+    ("serve", "Convert an EPUB and serve the Markdown over HTTP on localhost", "转换EPUB并在本机通过HTTP提供Markdown"),
+    (
+        "watch",
+        "Watch a directory and convert every new or modified EPUB into the output directory",
+        "监视目录，把新增或修改的EPUB转换到输出目录",
+    ),
+    (
+        "completions",
+        "Print a completion script for bash, zsh, fish or powershell",
+        "打印bash、zsh、fish或powershell的补全脚本",
+    ),
+];
+
+// inspect、toc、extract、validate 只打开 EPUB，接受其中与读取压缩包有关的选项
+const ARCHIVE_OPTIONS: &[&str] = &["rootfile", "max-entry-size", "max-total-size", "recover", "lang", "quiet", "verbose", "profile"];
+
+// serve 未指定 --port 时监听的端口
+pub const DEFAULT_PORT: u16 = 8000;
+
+// 作为输出路径时表示写到标准输出
+pub const STDOUT: &str = "-";
+
+pub struct CliArgs {
+    pub input: String,
+    pub output: Option<String>,
+    pub options: Options,
+    // 只打印目录树，不做转换
+    pub print_toc: bool,
+    // 只打印书籍元数据，不做转换；与 --json 一起使用时以 JSON 打印
+    pub metadata_only: bool,
+    // 转换结果以 JSON 写到标准输出
+    pub json: bool,
+    // 只列出将要写出的文件
+    pub dry_run: bool,
+}
+
+pub enum Command {
+    Convert(CliArgs),
+    Batch(Batch),
+    Inspect { input: String, options: Options, json: bool },
+    Toc { input: String, options: Options },
+    Extract { input: String, output: String, options: Options },
+    Validate { input: String, options: Options },
+    Serve { args: CliArgs, port: u16 },
+    Watch(Watch),
+    // None 时列出配置文件中的预设名，供补全脚本调用
+    Completions { shell: Option<Shell> },
+}
+
+// 每本书各自的参数，以及整批转换的设置
+pub struct Batch {
+    pub books: Vec<CliArgs>,
+    // 同时转换的数量，未指定时按 CPU 核数
+    pub jobs: Option<usize>,
+    // --resume 时记录已完成的书的状态文件
+    pub state: Option<PathBuf>,
+    pub fail_fast: bool,
+    pub error_report: Option<PathBuf>,
+}
+
+// watch 的参数：目录中出现的每本书按 --recursive 的规则写到输出目录下，并保留子目录
+pub struct Watch {
+    pub dir: String,
+    pub output: String,
+    parsed: Parsed,
+}
+
+impl Watch {
+    pub fn book(&self, input: &str) -> Result<CliArgs, EpubToMdError> {
+        book_args(&self.parsed, input.to_string(), Some(&self.output))
+    }
+}
+
+// 从选项表读出的选项，以及 -o、--stdout、--print-toc、--json、--dry-run 与只用于转换多本书的选项
+#[derive(Clone, Default)]
+struct Parsed {
+    options: Options,
+    output_flag: Option<String>,
+    stdout: bool,
+    print_toc: bool,
+    metadata_only: bool,
+    json: bool,
+    dry_run: bool,
+    recursive: Option<String>,
+    jobs: Option<usize>,
+    // 空字符串表示默认位置
+    resume: Option<String>,
+    fail_fast: bool,
+    error_report: Option<String>,
+}
+
+impl Parsed {
+    // 只在转换多本书时有效的选项
+    fn batch_option(&self) -> Option<&'static str> {
+        [
+            (self.resume.is_some(), "--resume"),
+            (self.fail_fast, "--fail-fast"),
+            (self.error_report.is_some(), "--error-report"),
+        ]
+        .into_iter()
+        .find_map(|(set, option)| set.then_some(option))
+    }
+}
+
+fn text(en: &'static str, zh: &'static str) -> &'static str {
+    match i18n::locale() {
+        Locale::En => en,
+        Locale::ZhCn => zh,
+    }
+}
+
+// 写法中的长选项名，例如 "-o, --output <path>" 中的 output
+fn long_name(spec: &'static str) -> &'static str {
+    let long = spec.split_once(", ").map_or(spec, |(_, long)| long);
+    long[2..].split([' ', '[']).next().unwrap_or_default()
+}
+
+fn option_arg(spec: &'static str, help: &'static str) -> Arg {
+    let (short, long) = match spec.split_once(", ") {
+        Some((short, long)) => (short.chars().nth(1), long),
+        None => (None, spec),
+    };
+    let name = long_name(spec);
+    let rest = &long[name.len() + 2..];
+    let mut arg = Arg::new(name).long(name).help(help);
+    if let Some(short) = short {
+        arg = arg.short(short);
+    }
+    let value = rest.trim_start_matches([' ', '[', '=']).trim_end_matches(']');
+    match value.strip_prefix('<').and_then(|value| value.strip_suffix('>')) {
+        // 开关可以重复给出，与原来一样
+        None => arg.action(ArgAction::Count),
+        // --extract-images[=<dir>] 等的值只能用 = 给出，以免把输入文件当作值
+        Some(value) if rest.starts_with('[') => arg
+            .value_name(value)
+            .action(ArgAction::Append)
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value(""),
+        // 与 --flag value 的原有写法一致，下一个参数总是作为值，例如 --pandoc-arg --strip-comments
+        Some(value) => arg.value_name(value).action(ArgAction::Append).allow_hyphen_values(true),
+    }
+}
+
+// 子命令接受的选项：转换类的命令接受全部选项，其余只接受读取压缩包的选项
+fn accepts(command: &str, spec: &'static str) -> bool {
+    let name = long_name(spec);
+    match command {
+        "convert" | "batch" | "serve" | "watch" => true,
+        "extract" => ARCHIVE_OPTIONS.contains(&name) || name == "force",
+        "completions" => name == "lang",
+        "inspect" => ARCHIVE_OPTIONS.contains(&name) || name == "json",
+        _ => ARCHIVE_OPTIONS.contains(&name),
+    }
+}
+
+fn subcommand(name: &'static str, template: &str) -> clap::Command {
+    let (_, about, about_zh) = COMMANDS.iter().copied().find(|(command, _, _)| *command == name).unwrap_or_default();
+    let input = Arg::new("input_path")
+        .value_name("input_epub|epub_dir|-")
+        .help(text("EPUB file, unpacked EPUB directory, or - for stdin", "EPUB文件、解压后的EPUB目录，或 - 表示标准输入"));
+    let arguments = match name {
+        // 缺少输入时与原来一样打印用法说明，不交给 clap 报错
+        // 一个输入时，其后的参数是输出路径；多个输入或通配符时，最后一个不是 EPUB 的参数是输出目录
+        "convert" => vec![input.num_args(1..).help(text(
+            "EPUB files, unpacked EPUB directories, glob patterns, or - for stdin, \
+             followed by the output Markdown file or directory (default: next to the input)",
+            "EPUB文件、解压后的EPUB目录、通配符，或 - 表示标准输入，其后为输出的Markdown文件或目录（默认：输入文件旁）",
+        ))],
+        "batch" => vec![Arg::new("input_path")
+            .value_name("input_epub|glob")
+            .num_args(1..)
+            .required(true)
+            .help(text("EPUB files or unpacked EPUB directories", "EPUB文件或解压后的EPUB目录"))],
+        "watch" => vec![Arg::new("input_path")
+            .value_name("input_dir")
+            .required(true)
+            .help(text("Directory to watch for EPUB files", "要监视的EPUB目录"))],
+        // --list-profiles 由补全脚本调用，不在帮助中列出
+        "completions" => vec![
+            Arg::new("shell")
+                .value_name("bash|zsh|fish|powershell")
+                .required_unless_present("list-profiles")
+                .help(text("Shell to print the completion script for", "要打印补全脚本的shell")),
+            Arg::new("list-profiles").long("list-profiles").action(ArgAction::SetTrue).conflicts_with("shell").hide(true),
+        ],
+        "extract" => vec![
+            input.required(true),
+            Arg::new("output_path").value_name("output_dir").required(true).help(text("Directory to unpack into", "解压到的目录")),
+        ],
+        _ => vec![input.required(true)],
+    };
+    let usage = match name {
+        "convert" => "[convert] [options] <input_epub|epub_dir|-|glob>... [output_md|output_dir]".to_string(),
+        "batch" => "batch [options] -o <output_dir> <input_epub|glob>...".to_string(),
+        "watch" => "watch [options] -o <output_dir> <input_dir>".to_string(),
+        "completions" => "completions <bash|zsh|fish|powershell>".to_string(),
+        "extract" => "extract [options] <input_epub|epub_dir|-> <output_dir>".to_string(),
+        _ => format!("{} [options] <input_epub|epub_dir|->", name),
+    };
+    let usage = format!("epub2md {}", usage.replace("options", text("options", "选项")));
+    let mut command = clap::Command::new(name)
+        .about(text(about, about_zh))
+        .override_usage(usage)
+        .help_template(template.to_string())
+        .disable_help_flag(true)
+        .next_help_heading(text("Arguments", "参数"))
+        .args(arguments)
+        .next_help_heading(text("Options", "选项"))
+        .args(OPTIONS.iter().filter(|(spec, _, _)| accepts(name, spec)).map(|(spec, en, zh)| match (name, *spec) {
+            // inspect 的 --json 打印书的结构，而不是转换结果
+            ("inspect", "--json") => option_arg(
+                spec,
+                text(
+                    "Print the manifest, spine, table of contents and landmarks as JSON",
+                    "以JSON打印清单、spine、目录与地标",
+                ),
+            ),
+            _ => option_arg(spec, text(en, zh)),
+        }))
+        .arg(help_arg());
+    if name == "serve" {
+        command = command.arg(Arg::new("port").long("port").value_name("n").help(text(
+            "Port to listen on at 127.0.0.1 (default: 8000)",
+            "在127.0.0.1上监听的端口（默认：8000）",
+        )));
+    }
+    command
+}
+
+fn help_arg() -> Arg {
+    Arg::new("help").short('h').long("help").action(ArgAction::Help).help(text("Print help", "打印帮助"))
+}
+
+// 完整的命令行定义，--help 与用法说明按 --lang 选定的语言生成
+pub fn command() -> clap::Command {
+    let template = format!("{{about-with-newline}}\n{}{{usage}}\n\n{{all-args}}", text("Usage: ", "用法："));
+    clap::Command::new("epub2md")
+        .about(text("Convert EPUB e-books to Markdown", "把EPUB电子书转换为Markdown"))
+        .override_usage(text(
+            "epub2md [command] [options] <input_epub|epub_dir|-> [output_md|output_dir]",
+            "epub2md [命令] [选项] <input_epub|epub_dir|-> [output_md|output_dir]",
+        ))
+        .help_template(format!("{}\n\n{}", template, text("Run 'epub2md <command> --help' for the options of a command.", "运行“epub2md <命令> --help”查看各命令的选项。")))
+        .disable_help_flag(true)
+        .disable_help_subcommand(true)
+        .subcommand_help_heading(text("Commands", "命令"))
+        .next_help_heading(text("Options", "选项"))
+        .arg(help_arg())
+        .subcommands(COMMANDS.iter().map(|(name, _, _)| subcommand(name, &template)))
+}
+
+// clap 的错误改为原有的错误信息，便于翻译与脚本匹配；--help 直接打印后退出
+fn clap_error(error: clap::Error) -> EpubToMdError {
+    let context = |kind| match error.get(kind) {
+        Some(ContextValue::String(value)) => value.clone(),
+        Some(ContextValue::Strings(values)) => values.join(", "),
+        _ => String::new(),
+    };
+    let argument = context(ContextKind::InvalidArg);
+    let flag = argument.split([' ', '=']).next().unwrap_or_default().to_string();
+    match error.kind() {
+        ErrorKind::DisplayHelp | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand | ErrorKind::DisplayVersion => {
+            error.exit()
+        }
+        ErrorKind::UnknownArgument if !argument.starts_with('-') => {
+            EpubToMdError::ArgumentError(tr!("Unexpected argument: {}", argument))
+        }
+        ErrorKind::UnknownArgument => match context(ContextKind::SuggestedArg) {
+            suggestion if suggestion.is_empty() => EpubToMdError::ArgumentError(tr!("Unknown option: {}", argument)),
+            suggestion => {
+                EpubToMdError::ArgumentError(tr!("Unknown option: {}, did you mean {}?", argument, suggestion))
+            }
+        },
+        ErrorKind::InvalidValue | ErrorKind::NoEquals if context(ContextKind::InvalidValue).is_empty() => {
+            EpubToMdError::ArgumentError(tr!("Missing value for {}.", flag))
+        }
+        ErrorKind::MissingRequiredArgument => {
+            EpubToMdError::ArgumentError(tr!("Missing required argument: {}", argument))
+        }
+        _ => {
+            let rendered = error.render().to_string();
+            let message = rendered.lines().next().unwrap_or_default();
+            EpubToMdError::ArgumentError(message.trim_start_matches("error: ").to_string())
+        }
+    }
+}
+
+// 选项在命令行中的每次出现：(位置, 选项, 值)，按出现顺序排列
+fn occurrences(matches: &ArgMatches, command: &str) -> Vec<(usize, String, Option<String>)> {
+    let mut occurrences = Vec::new();
+    for (spec, _, _) in OPTIONS.iter().filter(|(spec, _, _)| accepts(command, spec)) {
+        let name = long_name(spec);
+        if matches.value_source(name) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let flag = format!("--{}", name);
+        match matches.get_raw(name).filter(|_| spec.contains('<')) {
+            Some(values) => {
+                let indices = matches.indices_of(name).into_iter().flatten();
+                for (index, value) in indices.zip(values) {
+                    let value = Some(value.to_string_lossy().into_owned()).filter(|value| !value.is_empty() || !spec.contains('['));
+                    occurrences.push((index, flag.clone(), value));
+                }
+            }
+            None => occurrences.push((matches.index_of(name).unwrap_or_default(), flag, None)),
+        }
+    }
+    occurrences.sort_by_key(|(index, _, _)| *index);
+    occurrences
+}
+
+fn read_options(matches: &ArgMatches, command: &str) -> Result<Parsed, EpubToMdError> {
+    let Parsed {
+        mut options,
+        mut output_flag,
+        mut stdout,
+        mut print_toc,
+        mut metadata_only,
+        mut json,
+        mut dry_run,
+        mut recursive,
+        mut jobs,
+        mut resume,
+        mut fail_fast,
+        mut error_report,
+    } = Parsed::default();
+    for (_, flag, inline_value) in occurrences(matches, command) {
+        let flag = flag.as_str();
+        let value = || {
+            inline_value
+                .clone()
+                .ok_or_else(|| EpubToMdError::ArgumentError(tr!("Missing value for {}.", flag)))
+        };
+
+        match flag {
+            "--engine" => options.engine = Some(value()?.parse()?),
+            "--md-flavor" => options.md_flavor = Some(value()?.parse()?),
+            "--pandoc-path" => options.pandoc_path = Some(value()?.into()),
+            "--pandoc-arg" => options.pandoc_args.push(value()?),
+            "--lua-filter" => options.lua_filters.push(value()?.into()),
+            "--min-pandoc-version" => options.min_pandoc_version = Some(value()?.parse()?),
+            "--order" => options.order = value()?.parse()?,
+            "--recover" => options.recover = true,
+            "--max-entry-size" => options.max_entry_size = Some(parse_size(&value()?)?),
+            "--max-total-size" => options.max_total_size = Some(parse_size(&value()?)?),
+            "--rootfile" => options.rootfile = Some(value()?),
+            "--strict" => options.strict = true,
+            "--split" => options.split = value()?.parse()?,
+            "--format" => options.format = value()?.parse()?,
+            "--max-file-size" => options.max_file_size = Some(parse_size(&value()?)?),
+            "--index-name" => options.index_name = Some(value()?),
+            "--archive" => options.archive = Some(value()?.into()),
+            "--deterministic" => options.deterministic = true,
+            "--metadata-file" => options.metadata_file = Some(value()?.parse()?),
+            "--frontmatter" => options.frontmatter = Some(value()?.parse()?),
+            "--eol" => options.eol = value()?.parse()?,
+            "--bom" => options.bom = true,
+            // 目录只能用 --extract-images=<dir> 给出，以免把输入文件当作目录
+            "--extract-images" => {
+                options.extract_images = Some(inline_value.clone().unwrap_or_else(|| images::DEFAULT_DIR.to_string()).into())
+            }
+            "--embed-images" => {
+                options.embed_images = Some(match &inline_value {
+                    Some(size) => parse_size(size)?,
+                    None => images::DEFAULT_EMBED_LIMIT,
+                })
+            }
+            "--image-format" => options.image_format = Some(value()?.parse()?),
+            "--max-image-width" => {
+                let width = value()?;
+                options.max_image_width = Some(width.parse().ok().filter(|&width| width > 0).ok_or_else(|| {
+                    EpubToMdError::ArgumentError(tr!("Invalid image width '{}'.", width))
+                })?)
+            }
+            "--caption-style" => options.caption_style = value()?.parse()?,
+            "--unicode-normalize" => options.unicode_normalize = value()?.parse()?,
+            "--bouten" => options.bouten = value()?.parse()?,
+            // 配置文件中的选项已由 with_config 在解析参数之前插入
+            "--profile" => {
+                value()?;
+            }
+            // 输出级别由 parse_args 按解析结果选定
+            "-q" | "--quiet" | "-v" | "--verbose" => {}
+            // 语言已由 i18n::init 在解析参数之前选定，这里只检查取值
+            "--lang" => {
+                value()?.parse::<Locale>()?;
+            }
+            "--punct" => options.punct = value()?.parse()?,
+            "--pangu" => options.pangu = true,
+            "--chinese" => options.chinese = value()?.parse()?,
+            "--flavor" => options.flavor = Some(value()?.parse()?),
+            "--skip-frontmatter" => options.skip_frontmatter = true,
+            "--skip-backmatter" => options.skip_backmatter = true,
+            "--interactive" => options.interactive = true,
+            "--exclude-title" => {
+                let pattern = value()?;
+                let regex = Regex::new(&pattern).map_err(|e| {
+                    EpubToMdError::ArgumentError(tr!("Invalid --exclude-title pattern '{}': {}", pattern, e))
+                })?;
+                options.exclude_titles.push(regex);
+            }
+            "--stats" => {
+                options.stats = true;
+                options.stats_file = inline_value.clone().map(PathBuf::from);
+            }
+            "--citations" => options.citations = value()?.parse()?,
+            // 模板只能用 --page-markers=<template> 给出，以免把输入文件当作模板
+            "--page-markers" => {
+                options.page_markers = Some(inline_value.clone().unwrap_or_else(|| pages::DEFAULT_TEMPLATE.to_string()))
+            }
+            "--underline" => options.underline = value()?.parse()?,
+            "--sup-sub" => options.sup_sub = value()?.parse()?,
+            "--line-breaks" => options.line_breaks = value()?.parse()?,
+            "--definition-lists" => options.definition_lists = Some(value()?.parse()?),
+            "--ruby" => options.ruby = value()?.parse()?,
+            "--admonitions" => options.admonitions = value()?.parse()?,
+            "--unknown-html" => options.unknown_html = Some(value()?.parse()?),
+            "--normalize-headings" => options.normalize_headings = true,
+            "--heading-offset" => {
+                let offset = value()?;
+                options.heading_offset = offset.parse().ok().filter(|offset: &i32| offset.abs() <= 5).ok_or_else(|| {
+                    EpubToMdError::ArgumentError(tr!("Invalid heading offset '{}', expected -5 to 5.", offset))
+                })?
+            }
+            "--typography" => options.typography = value()?.parse()?,
+            "--no-format" => options.no_format = true,
+            "--wrap" => options.wrap = Some(value()?.parse()?),
+            "--link-style" => options.link_style = value()?.parse()?,
+            "--force" => options.force = true,
+            "--update" => options.update = true,
+            "--separator" => options.separator = value()?.parse()?,
+            "--chapter-headings" => options.chapter_headings = true,
+            "--name-template" => options.name_template = Some(value()?.parse()?),
+            "--output-template" => options.output_template = Some(value()?.parse()?),
+            "-o" | "--output" => output_flag = Some(value()?),
+            "--stdout" => stdout = true,
+            "--recursive" => recursive = Some(value()?),
+            "--jobs" => {
+                let value = value()?;
+                jobs = Some(
+                    value
+                        .parse()
+                        .ok()
+                        .filter(|&jobs| jobs > 0)
+                        .ok_or_else(|| EpubToMdError::ArgumentError(tr!("Invalid job count '{}'.", value)))?,
+                );
+            }
+            "--print-toc" => print_toc = true,
+            "--metadata-only" => metadata_only = true,
+            // 状态文件只能用 --resume=<file> 给出，以免把输入文件当作状态文件
+            "--resume" => resume = Some(inline_value.clone().unwrap_or_default()),
+            "--fail-fast" => fail_fast = true,
+            "--error-report" => error_report = Some(value()?),
+            "--json" => json = true,
+            "--dry-run" => dry_run = true,
+            _ => {
+                return Err(EpubToMdError::ArgumentError(tr!("Unknown option: {}", flag)));
+            }
+        }
+    }
+    Ok(Parsed {
+        options,
+        output_flag,
+        stdout,
+        print_toc,
+        metadata_only,
+        json,
+        dry_run,
+        recursive,
+        jobs,
+        resume,
+        fail_fast,
+        error_report,
+    })
+}
+
+// 跳过写在命令之前的 --lang、-q 与 -v，返回命令名（或第一个其他参数）的位置
+fn command_start(args: &[String]) -> usize {
+    let mut first = 0;
+    while let Some(arg) = args.get(first) {
+        match arg.as_str() {
+            "--lang" => first += 2,
+            arg if arg.starts_with("--lang=") => first += 1,
+            "-q" | "--quiet" | "--verbose" => first += 1,
+            arg if arg.len() > 1 && arg.strip_prefix('-').is_some_and(|flags| flags.bytes().all(|c| c == b'v')) => first += 1,
+            _ => break,
+        }
+    }
+    first.min(args.len())
+}
+
+// 配置文件中的一组选项转换为命令行参数，当前命令不接受的选项跳过
+fn config_args(command: &str, entries: &[(String, config::Value)], path: &Path) -> Result<Vec<String>, EpubToMdError> {
+    let mut args = Vec::new();
+    for (key, value) in entries {
+        let spec = OPTIONS
+            .iter()
+            .map(|(spec, _, _)| *spec)
+            .find(|spec| long_name(spec) == key && key != "profile")
+            .ok_or_else(|| EpubToMdError::ArgumentError(tr!("Unknown option '{}' in {}.", key, path.display())))?;
+        if !accepts(command, spec) {
+            continue;
+        }
+        let values = match value {
+            config::Value::List(items) => items.clone(),
+            value => vec![value.clone()],
+        };
+        // 开关写作 true/false，带值的选项写作字符串或数字，可选值的选项两者都可以
+        for value in values {
+            match value {
+                config::Value::Bool(true) if !spec.contains('<') || spec.contains('[') => args.push(format!("--{}", key)),
+                config::Value::Bool(false) if !spec.contains('<') || spec.contains('[') => {}
+                config::Value::String(text) if spec.contains('<') => args.push(format!("--{}={}", key, text)),
+                _ => {
+                    return Err(EpubToMdError::ArgumentError(tr!(
+                        "Invalid value for '{}' in {}.",
+                        key,
+                        path.display()
+                    )));
+                }
+            }
+        }
+    }
+    Ok(args)
+}
+
+// 环境变量 EPUB2MD_<选项名> 设置的选项，例如 EPUB2MD_SPLIT=chapter、EPUB2MD_FORCE=1；
+// 语言仍由 i18n::init 读取 EPUB2MD_LANG，预设由 with_config 读取 EPUB2MD_PROFILE
+fn env_args(command: &str) -> Result<Vec<String>, EpubToMdError> {
+    let mut args = Vec::new();
+    for (spec, _, _) in OPTIONS.iter().filter(|(spec, _, _)| accepts(command, spec)) {
+        let name = long_name(spec);
+        if matches!(name, "lang" | "profile") {
+            continue;
+        }
+        let variable = format!("EPUB2MD_{}", name.to_ascii_uppercase().replace('-', "_"));
+        let Ok(value) = env::var(&variable) else {
+            continue;
+        };
+        let switch = match value.to_ascii_lowercase().as_str() {
+            "" | "0" | "false" | "no" | "off" => Some(false),
+            "1" | "true" | "yes" | "on" => Some(true),
+            _ => None,
+        };
+        let (takes_value, optional) = (spec.contains('<'), spec.contains('['));
+        match switch {
+            Some(true) if !takes_value || optional => args.push(format!("--{}", name)),
+            Some(false) if !takes_value || optional => {}
+            _ if !takes_value => {
+                return Err(EpubToMdError::ArgumentError(tr!(
+                    "Invalid value for {}: '{}', expected true or false.",
+                    variable, value
+                )));
+            }
+            _ if value.is_empty() => {}
+            _ => args.push(format!("--{}={}", name, value)),
+        }
+    }
+    Ok(args)
+}
+
+// 把 epub2md.toml 中的默认选项、--profile 选中的预设与 EPUB2MD_* 环境变量依次插在命令名之后，
+// 命令行上的同名选项写在最后：命令行优先于环境变量，环境变量优先于配置文件
+pub fn with_config(args: &[String]) -> Result<Vec<String>, EpubToMdError> {
+    let first = command_start(args);
+    let (command, position) = match args.get(first).map(String::as_str) {
+        Some("-h" | "--help") => return Ok(args.to_vec()),
+        Some(arg) if COMMANDS.iter().any(|(name, _, _)| *name == arg) => (arg, first + 1),
+        _ => ("convert", first),
+    };
+    let mut profile = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--profile" {
+            profile = iter.next().cloned();
+        } else if let Some(value) = arg.strip_prefix("--profile=") {
+            profile = Some(value.to_string());
+        }
+    }
+    let profile = profile.or_else(|| env::var("EPUB2MD_PROFILE").ok().filter(|profile| !profile.is_empty()));
+
+    let configs = config::load()?;
+    let mut extra = Vec::new();
+    for config in &configs {
+        extra.extend(config_args(command, &config.defaults, &config.path)?);
+    }
+    if let Some(profile) = profile {
+        let mut found = false;
+        for config in &configs {
+            if let Some(entries) = config.profile(&profile) {
+                extra.extend(config_args(command, entries, &config.path)?);
+                found = true;
+            }
+        }
+        if !found {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "Profile '{}' is not defined in {}.",
+                profile,
+                config::FILE_NAME
+            )));
+        }
+    }
+    extra.extend(env_args(command)?);
+    let mut args = args.to_vec();
+    args.splice(position..position, extra);
+    Ok(args)
+}
+
+// 未给出命令时按 convert 处理，兼容原来的 epub2md [options] <input> [output] 写法；
+// 写在命令之前的 --lang、-q 与 -v 移到命令之后，由子命令解析
+fn with_command(args: &[String]) -> Vec<String> {
+    let (lang, rest) = args.split_at(command_start(args));
+    let mut command = vec!["epub2md".to_string()];
+    match rest.first() {
+        Some(arg) if matches!(arg.as_str(), "-h" | "--help") => command.extend_from_slice(rest),
+        Some(arg) if COMMANDS.iter().any(|(name, _, _)| name == arg) => {
+            command.push(arg.clone());
+            command.extend_from_slice(lang);
+            command.extend_from_slice(&rest[1..]);
+        }
+        _ => {
+            command.push("convert".to_string());
+            command.extend_from_slice(lang);
+            command.extend_from_slice(rest);
+        }
+    }
+    command
+}
+
+// 解析命令行参数，选项支持 `--flag value` 与 `--flag=value` 两种写法
+pub fn parse_args(args: &[String]) -> Result<Command, EpubToMdError> {
+    let matches = command().try_get_matches_from(with_command(args)).map_err(clap_error)?;
+    let Some((name, matches)) = matches.subcommand() else {
+        return Err(EpubToMdError::UsageError);
+    };
+    if name == "completions" {
+        let shell = matches.get_one::<String>("shell").map(|shell| shell.parse()).transpose()?;
+        return Ok(Command::Completions { shell });
+    }
+    // 只数解析出的选项，--title -q 这样的取值不算
+    let count = |name| matches.try_get_one::<u8>(name).ok().flatten().copied().unwrap_or_default();
+    logging::set_verbosity(count("quiet") > 0, count("verbose"));
+    let input = matches.get_one::<String>("input_path").cloned();
+    let parsed = read_options(matches, name)?;
+    if parsed.recursive.is_some() && name != "convert" {
+        return Err(EpubToMdError::ArgumentError(tr!("--recursive can only be used with convert.")));
+    }
+    if let Some(option) = parsed.batch_option().filter(|_| !matches!(name, "convert" | "batch")) {
+        return Err(EpubToMdError::ArgumentError(tr!("{} can only be used with convert and batch.", option)));
+    }
+    match name {
+        "convert" => convert(parsed, matches.get_many::<String>("input_path").into_iter().flatten().cloned().collect()),
+        "batch" => {
+            let dir = match (&parsed.output_flag, &parsed.options.output_template) {
+                (Some(dir), None) if !parsed.stdout && dir != STDOUT => Some(dir.clone()),
+                (None, Some(_)) => None,
+                _ => {
+                    return Err(EpubToMdError::ArgumentError(tr!(
+                        "batch needs an output directory given with -o, or --output-template."
+                    )));
+                }
+            };
+            batch(parsed, matches.get_many::<String>("input_path").into_iter().flatten().cloned().collect(), dir)
+        }
+        "serve" => {
+            let port = match matches.get_one::<String>("port") {
+                Some(port) => port
+                    .parse()
+                    .map_err(|_| EpubToMdError::ArgumentError(tr!("Invalid port '{}'.", port)))?,
+                None => DEFAULT_PORT,
+            };
+            if parsed.output_flag.is_some() || parsed.stdout || parsed.print_toc || parsed.metadata_only || parsed.json || parsed.dry_run || parsed.options.archive.is_some() {
+                return Err(EpubToMdError::ArgumentError(tr!(
+                    "serve writes to a temporary directory and cannot be combined with output options."
+                )));
+            }
+            let args = finish(parsed, input.unwrap_or_default(), None)?;
+            Ok(Command::Serve { args, port })
+        }
+        "watch" => {
+            let output = match &parsed.output_flag {
+                Some(dir) if !parsed.stdout && dir != STDOUT && !parsed.print_toc && !parsed.metadata_only && parsed.options.archive.is_none() => {
+                    dir.clone()
+                }
+                _ => {
+                    return Err(EpubToMdError::ArgumentError(tr!(
+                        "watch needs an output directory given with -o and cannot be combined with --stdout, --print-toc, --metadata-only or --archive."
+                    )));
+                }
+            };
+            if let Some(option) = parsed.options.single_book_option() {
+                return Err(EpubToMdError::ArgumentError(tr!("{} cannot be used with several inputs.", option)));
+            }
+            let dir = input.unwrap_or_default();
+            if !std::path::Path::new(&dir).is_dir() {
+                return Err(EpubToMdError::InputError(tr!("'{}' is not a directory.", dir)));
+            }
+            // 已转换且输出比输入新的书不再转换，中途重启时不会重复转换整个目录
+            let mut parsed = Parsed {
+                output_flag: None,
+                recursive: Some(dir.clone()),
+                ..parsed
+            };
+            parsed.options.update = true;
+            let watch = Watch { dir, output, parsed };
+            // 先检查选项的组合，以免等到第一本书出现时才报错
+            watch.book(&std::path::Path::new(&watch.dir).join("book.epub").to_string_lossy())?;
+            Ok(Command::Watch(watch))
+        }
+        _ => {
+            let input = input.unwrap_or_default();
+            let json = parsed.json;
+            let options = parsed.options;
+            Ok(match name {
+                "inspect" => Command::Inspect { input, options, json },
+                "toc" => Command::Toc { input, options },
+                "extract" => Command::Extract {
+                    input,
+                    output: matches.get_one::<String>("output_path").cloned().unwrap_or_default(),
+                    options,
+                },
+                _ => Command::Validate { input, options },
+            })
+        }
+    }
+}
+
+// 可以作为转换输入的参数：EPUB 文件、解压后的 EPUB 目录或标准输入
+fn is_input(path: &str) -> bool {
+    path == STDIN
+        || path.to_ascii_lowercase().ends_with(".epub")
+        || std::path::Path::new(path).join("META-INF").join("container.xml").is_file()
+}
+
+// 含有 *?[ 但确实存在的路径（如 "Book [2020].epub"）按原样使用
+fn is_glob(path: &str) -> bool {
+    glob::is_pattern(path) && !Path::new(path).exists()
+}
+
+// convert 的参数：只有一个输入时与原来一样，其后可以跟输出路径；多个输入或通配符时逐本转换
+fn convert(parsed: Parsed, mut paths: Vec<String>) -> Result<Command, EpubToMdError> {
+    if let Some(root) = &parsed.recursive {
+        return recursive(root.clone(), parsed, paths);
+    }
+    let single = match paths.as_slice() {
+        [] => return Err(EpubToMdError::UsageError),
+        [input] => !is_glob(input),
+        [input, output] => !is_glob(input) && !is_glob(output) && !is_input(output),
+        _ => false,
+    };
+    if single {
+        if let Some(option) = parsed.batch_option() {
+            return Err(EpubToMdError::ArgumentError(tr!("{} only applies when converting several books.", option)));
+        }
+        let input = paths.remove(0);
+        return Ok(Command::Convert(finish(parsed, input, paths.pop())?));
+    }
+    if parsed.stdout || parsed.output_flag.as_deref() == Some(STDOUT) {
+        return Err(EpubToMdError::ArgumentError(tr!("--stdout cannot be used with several inputs.")));
+    }
+    let last = paths.last().filter(|last| !is_glob(last) && !is_input(last)).cloned();
+    let dir = match (parsed.output_flag.clone(), last) {
+        (Some(_), Some(extra)) => {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "Output given both with -o and as an argument: {}",
+                extra
+            )));
+        }
+        (Some(dir), None) => Some(dir),
+        (None, Some(dir)) => {
+            paths.pop();
+            Some(dir)
+        }
+        (None, None) => None,
+    };
+    batch(parsed, paths, dir)
+}
+
+// --recursive：唯一的参数或 -o 为输出目录，都没有时在当前目录下保持原有的目录结构
+fn recursive(root: String, parsed: Parsed, paths: Vec<String>) -> Result<Command, EpubToMdError> {
+    let dir = match (parsed.output_flag.clone(), paths.as_slice()) {
+        (Some(_), [extra, ..]) | (None, [_, extra, ..]) => {
+            return Err(EpubToMdError::ArgumentError(tr!("Unexpected argument: {}", extra)));
+        }
+        (Some(dir), []) => Some(dir),
+        (None, [dir]) => Some(dir.clone()),
+        (None, []) if parsed.options.output_template.is_some() => None,
+        (None, []) => Some(String::new()),
+    };
+    if parsed.stdout || dir.as_deref() == Some(STDOUT) {
+        return Err(EpubToMdError::ArgumentError(tr!("--stdout cannot be used with several inputs.")));
+    }
+    let inputs = glob::find_epubs(std::path::Path::new(&root))?;
+    batch(parsed, inputs, dir)
+}
+
+// 每本书写到输出目录中以输入文件名命名的文件，--recursive 时还保留输入所在的子目录；
+// 没有输出目录时各自写到默认位置。通配符在这里展开
+fn batch(parsed: Parsed, paths: Vec<String>, dir: Option<String>) -> Result<Command, EpubToMdError> {
+    let mut inputs = Vec::with_capacity(paths.len());
+    for path in paths {
+        if is_glob(&path) {
+            inputs.extend(glob::expand(&path)?.into_iter().filter(|path| is_input(path)));
+        } else {
+            inputs.push(path);
+        }
+    }
+    if inputs.is_empty() {
+        return Err(EpubToMdError::InputError(tr!("No EPUB files to convert.")));
+    }
+    if let Some(option) = parsed.options.single_book_option() {
+        return Err(EpubToMdError::ArgumentError(tr!("{} cannot be used with several inputs.", option)));
+    }
+    let jobs = parsed.jobs;
+    let state = parsed.resume.as_ref().map(|file| match file.is_empty() {
+        true => Path::new(dir.as_deref().unwrap_or_default()).join(resume::DEFAULT_NAME),
+        false => PathBuf::from(file),
+    });
+    let mut books: Vec<CliArgs> = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        if input == STDIN {
+            return Err(EpubToMdError::ArgumentError(tr!("batch cannot read an EPUB from stdin.")));
+        }
+        let book = book_args(&parsed, input, dir.as_ref())?;
+        // 不同目录中的同名文件会写到同一个输出
+        let duplicate = book.output.as_ref().filter(|output| books.iter().any(|other| other.output.as_ref() == Some(*output)));
+        if let Some(output) = duplicate {
+            return Err(EpubToMdError::ArgumentError(tr!("Several inputs would be written to {}.", output)));
+        }
+        books.push(book);
+    }
+    Ok(Command::Batch(Batch {
+        books,
+        jobs,
+        state,
+        fail_fast: parsed.fail_fast,
+        error_report: parsed.error_report.map(PathBuf::from),
+    }))
+}
+
+// 一本书的参数，输出写到 dir 下以输入文件名命名的文件
+fn book_args(parsed: &Parsed, input: String, dir: Option<&String>) -> Result<CliArgs, EpubToMdError> {
+    let output = dir.map(|dir| {
+        let path = Path::new(&input);
+        let stem = path.file_stem().unwrap_or(path.as_os_str()).to_string_lossy();
+        let name = if parsed.options.writes_directory() { stem.to_string() } else { format!("{}.md", stem) };
+        let subdir = parsed.recursive.as_ref().and_then(|root| path.parent()?.strip_prefix(root).ok());
+        let dir = Path::new(dir).join(subdir.unwrap_or(Path::new("")));
+        dir.join(name).to_string_lossy().into_owned()
+    });
+    let parsed = Parsed {
+        output_flag: None,
+        ..parsed.clone()
+    };
+    finish(parsed, input, output)
+}
+
+// 校验转换选项之间的组合，并确定输出路径
+fn finish(parsed: Parsed, input: String, output: Option<String>) -> Result<CliArgs, EpubToMdError> {
+    let Parsed {
+        mut options,
+        output_flag,
+        stdout,
+        print_toc,
+        metadata_only,
+        json,
+        dry_run,
+        ..
+    } = parsed;
+    let output = match (output_flag, output) {
+        (Some(_), Some(extra)) => {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "Output given both with -o and as an argument: {}",
+                extra
+            )));
+        }
+        (flag, arg) => flag.or(arg),
+    };
+    if let Some(template) = &options.output_template {
+        if output.is_some() || stdout {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "--output-template replaces the output path and cannot be combined with it."
+            )));
+        }
+        // {chapter} 表示每章一个文件，未指定拆分方式时按章节拆分
+        if template.has_chapter() {
+            if options.name_template.is_some() {
+                return Err(EpubToMdError::ArgumentError(tr!(
+                    "--name-template cannot be combined with {{chapter}} in --output-template."
+                )));
+            }
+            if !options.writes_directory() {
+                options.split = Split::Chapter;
+            }
+        }
+    }
+    if let Some(archive) = &options.archive {
+        if output.is_some() || stdout {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "--archive replaces the output path and cannot be combined with it."
+            )));
+        }
+        output::archive_kind(archive)?;
+    }
+
+    // 从标准输入读取时，未指定输出路径就写到标准输出
+    let output = match output {
+        None if options.archive.is_some() || options.output_template.is_some() => None,
+        None if input == STDIN && options.writes_directory() => {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "An output directory is required when reading the EPUB from stdin."
+            )));
+        }
+        None if input == STDIN => Some(STDOUT.to_string()),
+        output => output,
+    };
+    let output = match output {
+        Some(output) if stdout && output != STDOUT => {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "--stdout cannot be combined with the output path {}.",
+                output
+            )));
+        }
+        _ if stdout => Some(STDOUT.to_string()),
+        output => output,
+    };
+    if output.as_deref() == Some(STDOUT) && (options.writes_directory() || options.max_file_size.is_some()) {
+        return Err(EpubToMdError::ArgumentError(tr!(
+            "Writing to stdout requires single-file output without --max-file-size."
+        )));
+    }
+    if json && (output.as_deref() == Some(STDOUT) || print_toc) {
+        return Err(EpubToMdError::ArgumentError(tr!(
+            "--json prints the result to stdout and cannot be combined with --stdout or --print-toc."
+        )));
+    }
+    if metadata_only && print_toc {
+        return Err(EpubToMdError::ArgumentError(tr!("--metadata-only cannot be combined with --print-toc.")));
+    }
+    if options.interactive && input == STDIN {
+        return Err(EpubToMdError::ArgumentError(tr!(
+            "--interactive reads the selection from stdin and cannot read the EPUB from it."
+        )));
+    }
+    if output.as_deref() == Some(STDOUT) && options.metadata_file.is_some() {
+        return Err(EpubToMdError::ArgumentError(tr!("--metadata-file needs an output path to write next to.")));
+    }
+    if options.md_flavor.is_some() && options.engine.is_some_and(|engine| engine != Engine::Pandoc) {
+        return Err(EpubToMdError::ArgumentError(tr!("--md-flavor requires --engine pandoc.")));
+    }
+
+    // 以下选项作用于内置的 HTML 转换，pandoc 直接输出 Markdown 时不经过它
+    if let Some(option) = options.html_conversion_option() {
+        if options.engine == Some(Engine::Pandoc) || options.md_flavor.is_some() {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "{} requires the native or html2md engine.",
+                option
+            )));
+        }
+    }
+
+    if let Some(option) = options.native_only_option() {
+        if options.engine.is_some_and(|engine| engine != Engine::Native) {
+            return Err(EpubToMdError::ArgumentError(tr!("{} requires --engine native.", option)));
+        }
+        if options.md_flavor.is_some() || !options.lua_filters.is_empty() {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "{} cannot be combined with Pandoc-only options.",
+                option
+            )));
+        }
+    }
+
+    if options.name_template.is_some() && !options.writes_directory() {
+        return Err(EpubToMdError::ArgumentError(tr!("--name-template requires --split or --format.")));
+    }
+
+    if options.writes_directory() && (options.separator != Separator::None || options.chapter_headings) {
+        return Err(EpubToMdError::ArgumentError(tr!(
+            "--separator and --chapter-headings only apply to single-file output."
+        )));
+    }
+
+    if let Some(index_name) = &options.index_name {
+        if options.split == Split::None || options.format != Format::Markdown {
+            return Err(EpubToMdError::ArgumentError(tr!("--index-name requires --split with --format markdown.")));
+        }
+        safe_relative_path(index_name)
+            .map_err(|_| EpubToMdError::ArgumentError(tr!("Invalid index file name '{}'.", index_name)))?;
+    }
+
+    if let Some(frontmatter) = options.frontmatter {
+        if !options.format.supports_frontmatter(frontmatter) {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "--frontmatter {} is not supported by --format {}.",
+                frontmatter.name(),
+                options.format.name()
+            )));
+        }
+    }
+
+    if options.page_markers.as_deref().is_some_and(|template| !template.contains("{page}")) {
+        return Err(EpubToMdError::ArgumentError(tr!("--page-markers template must contain {{page}}.")));
+    }
+
+    if options.max_file_size.is_some() && options.format != Format::Markdown {
+        return Err(EpubToMdError::ArgumentError(tr!("--max-file-size requires --format markdown.")));
+    }
+    if options.max_file_size == Some(0) {
+        return Err(EpubToMdError::ArgumentError(tr!("--max-file-size must be greater than zero.")));
+    }
+
+    if let Some(dir) = &options.extract_images {
+        if output.as_deref() == Some(STDOUT) {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "--extract-images needs an output path to write the images next to."
+            )));
+        }
+        if !matches!(options.format, Format::Markdown | Format::Mdbook) {
+            return Err(EpubToMdError::ArgumentError(tr!(
+                "--format {} already copies images, --extract-images only applies to markdown and mdbook.",
+                options.format.name()
+            )));
+        }
+        safe_relative_path(&dir.to_string_lossy()).map_err(|_| {
+            EpubToMdError::ArgumentError(tr!("Invalid image directory '{}'.", dir.display()))
+        })?;
+    }
+
+    if options.embed_images.is_some() && !matches!(options.format, Format::Markdown | Format::Mdbook) {
+        return Err(EpubToMdError::ArgumentError(tr!(
+            "--embed-images only applies to --format markdown and mdbook, not {}.",
+            options.format.name()
+        )));
+    }
+
+    if (options.image_format.is_some() || options.max_image_width.is_some())
+        && options.extract_images.is_none()
+        && options.embed_images.is_none()
+    {
+        return Err(EpubToMdError::ArgumentError(tr!(
+            "--image-format and --max-image-width require --extract-images or --embed-images."
+        )));
+    }
+
+    if options.update && input == STDIN {
+        return Err(EpubToMdError::ArgumentError(tr!("--update needs an input file to compare against.")));
+    }
+
+    if options.strict && options.recover {
+        return Err(EpubToMdError::ArgumentError(tr!("--strict cannot be combined with --recover.")));
+    }
+
+    if !options.lua_filters.is_empty() && options.engine == Some(Engine::Native) {
+        return Err(EpubToMdError::ArgumentError(tr!("--lua-filter requires a Pandoc engine.")));
+    }
+
+    Ok(CliArgs {
+        input,
+        output,
+        options,
+        print_toc,
+        metadata_only,
+        json,
+        dry_run,
+    })
+}
@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+use markup5ever_rcdom::NodeData;
+
+use crate::dom;
+
+// html2md 最后会合并连续空行、去掉行尾空格；代码块中的空行和行尾空白后加上此标记保护，转换完成后删除
+// 与 markdown 中的引用标记一样用 Unicode 非字符，不会与正文中的私用区字符混淆
+pub const KEEP_MARK: char = '\u{FDD3}';
+
+// 指明代码语言的属性，依次为 O'Reilly 等出版社的 HTMLBook 写法和常见的 data 属性
+const LANGUAGE_ATTRS: &[&str] = &["data-code-language", "data-language", "data-lang"];
+// 类名前缀，例如 language-rust、lang-py、highlight-source-js
+const LANGUAGE_PREFIXES: &[&str] = &["language-", "lang-", "highlight-source-", "highlight-"];
+// 直接写成类名的语言，例如 Pandoc 输出的 class="sourceCode rust" 或出版社的 class="programlisting java"
+const LANGUAGES: &[&str] = &[
+    "bash", "c", "clojure", "cpp", "csharp", "css", "dart", "diff", "elixir", "erlang", "go", "groovy", "haskell",
+    "html", "ini", "java", "javascript", "js", "json", "julia", "kotlin", "latex", "lisp", "lua", "makefile",
+    "markdown", "matlab", "objectivec", "ocaml", "perl", "php", "powershell", "python", "r", "ruby", "rust",
+    "scala", "scheme", "shell", "sql", "swift", "toml", "typescript", "ts", "xml", "yaml",
+];
+
+// 依次查看 <pre> 和其中唯一的 <code>
+fn language(pre: &Handle) -> Option<String> {
+    let elements: Vec<Handle> = pre
+        .children
+        .borrow()
+        .iter()
+        .filter(|child| matches!(child.data, NodeData::Element { .. }))
+        .cloned()
+        .collect();
+    let code = match elements.as_slice() {
+        [code] if dom::tag_name(code).as_deref() == Some("code") => Some(code.clone()),
+        _ => None,
+    };
+    [Some(pre.clone()), code].into_iter().flatten().find_map(|node| {
+        if let Some(language) = LANGUAGE_ATTRS.iter().find_map(|name| dom::attr(&node, name)) {
+            return Some(language.trim().to_ascii_lowercase()).filter(|language| !language.is_empty());
+        }
+        let class = dom::attr(&node, "class")?.to_ascii_lowercase();
+        // SyntaxHighlighter 的 class="brush: js; gutter: false"
+        if let Some((_, rest)) = class.split_once("brush:") {
+            let language = rest.split([';', ' ']).map(str::trim).find(|token| !token.is_empty());
+            return language.map(str::to_string);
+        }
+        let tokens: Vec<&str> = class.split_whitespace().collect();
+        tokens
+            .iter()
+            .find_map(|token| {
+                LANGUAGE_PREFIXES
+                    .iter()
+                    .find_map(|prefix| token.strip_prefix(prefix))
+                    .filter(|language| !language.is_empty())
+            })
+            .or_else(|| tokens.iter().copied().find(|token| LANGUAGES.contains(token)))
+            .map(str::to_string)
+    })
+}
+
+// 代码的原始文本；语法高亮用的 <span> 等只取文字，<br> 换行
+fn code_text(node: &Handle, text: &mut String) {
+    for child in node.children.borrow().iter() {
+        match child.data {
+            NodeData::Text { ref contents } => text.push_str(&contents.borrow()),
+            NodeData::Element { .. } if dom::tag_name(child).as_deref() == Some("br") => text.push('\n'),
+            NodeData::Element { .. } => code_text(child, text),
+            _ => {}
+        }
+    }
+}
+
+// 比内容中最长的连续反引号多一个，且不少于 minimum 个
+fn fence(code: &str, minimum: usize) -> String {
+    let longest = code
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    "`".repeat((longest + 1).max(minimum))
+}
+
+// <pre> 输出为带语言标记的围栏代码块，原样保留缩进与空行
+struct CodeBlockHandler;
+
+impl TagHandler for CodeBlockHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        let mut code = String::new();
+        code_text(tag, &mut code);
+        // 出版社常用不换行空格缩进代码
+        let code = code.replace('\u{a0}', " ");
+        let code = code.strip_prefix('\n').unwrap_or(&code).trim_end_matches(['\n', '\r']);
+        let protected: Vec<String> = code
+            .lines()
+            .map(|line| match line.ends_with(char::is_whitespace) || line.is_empty() {
+                true => format!("{}{}", line, KEEP_MARK),
+                false => line.to_string(),
+            })
+            .collect();
+        let fence = fence(code, 3);
+        printer.insert_newline();
+        printer.insert_newline();
+        printer.append_str(&format!(
+            "{}{}\n{}\n{}",
+            fence,
+            language(tag).unwrap_or_default(),
+            protected.join("\n"),
+            fence
+        ));
+        printer.insert_newline();
+        printer.insert_newline();
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+
+    fn skip_descendants(&self) -> bool {
+        true
+    }
+}
+
+// 行内代码按内容选择反引号个数，内容中含有反引号时也不会提前结束
+#[derive(Default)]
+struct InlineCodeHandler {
+    start_pos: usize,
+}
+
+impl TagHandler for InlineCodeHandler {
+    fn handle(&mut self, _tag: &Handle, printer: &mut StructuredPrinter) {
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        let code = printer.data.split_off(self.start_pos);
+        if code.is_empty() {
+            return;
+        }
+        let fence = fence(&code, 1);
+        let padding = if code.starts_with('`') || code.ends_with('`') { " " } else { "" };
+        printer.append_str(&format!("{}{}{}{}{}", fence, padding, code, padding, fence));
+    }
+}
+
+struct CodeBlockHandlerFactory;
+
+impl TagHandlerFactory for CodeBlockHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(CodeBlockHandler)
+    }
+}
+
+struct InlineCodeHandlerFactory;
+
+impl TagHandlerFactory for InlineCodeHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(InlineCodeHandler::default())
+    }
+}
+
+// 注册代码相关元素的处理器，替换 html2md 自带的转换
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>) {
+    handlers.insert("pre".to_string(), Box::new(CodeBlockHandlerFactory));
+    handlers.insert("code".to_string(), Box::new(InlineCodeHandlerFactory));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 与转换完成后一样删除保护标记
+    fn convert(html: &str) -> String {
+        let mut handlers = HashMap::new();
+        register(&mut handlers);
+        html2md::parse_html_custom(html, &handlers).replace(KEEP_MARK, "")
+    }
+
+    #[test]
+    fn blocks_keep_blank_lines_and_trailing_spaces() {
+        let html = "<pre><code class=\"language-rust\">fn main() {\n\n    let x = 1;  \n}</code></pre>";
+        assert_eq!(convert(html), "```rust\nfn main() {\n\n    let x = 1;  \n}\n```");
+        let html = "<pre><span class=\"k\">let</span>\u{a0}\u{a0}x<br/>y</pre>";
+        assert_eq!(convert(html), "```\nlet  x\ny\n```");
+    }
+
+    #[test]
+    fn language_comes_from_attributes_or_classes() {
+        assert_eq!(convert(r#"<pre class="programlisting java">x</pre>"#), "```java\nx\n```");
+        assert_eq!(convert(r#"<pre class="brush: js; gutter: false">x</pre>"#), "```js\nx\n```");
+        assert_eq!(convert(r#"<pre data-code-language="Python">x</pre>"#), "```python\nx\n```");
+    }
+
+    #[test]
+    fn fences_are_longer_than_backticks_in_the_code() {
+        assert_eq!(convert("<pre>```\nx</pre>"), "````\n```\nx\n````");
+        assert_eq!(convert("<p>Use <code>a`b</code> and <code>`x</code></p>"), "Use ``a`b`` and `` `x ``");
+    }
+}
@@ -0,0 +1,246 @@
+use std::{fs, path::Path};
+
+use crate::{
+    archive::{self, Archive},
+    epub::{Epub, Metadata},
+    error::EpubToMdError,
+    native,
+    options::Options,
+    sidecar::{self, Value},
+    toc,
+};
+
+fn print_field(label: &str, value: &str) {
+    println!("{} {}", label, value);
+}
+
+// 书名、作者等元数据字段，每个一行，没有的字段不打印
+fn print_metadata(metadata: &Metadata) {
+    let fields = [
+        (tr!("Title:"), metadata.title.clone()),
+        (tr!("Authors:"), Some(metadata.authors.join(", ")).filter(|authors| !authors.is_empty())),
+        (tr!("Language:"), metadata.language.clone()),
+        (tr!("Identifiers:"), Some(metadata.identifiers.join(", ")).filter(|ids| !ids.is_empty())),
+        (tr!("Publisher:"), metadata.publisher.clone()),
+        (tr!("Date:"), metadata.date.clone()),
+        (tr!("Subjects:"), Some(metadata.subjects.join(", ")).filter(|subjects| !subjects.is_empty())),
+        (tr!("Cover:"), metadata.cover.clone()),
+    ];
+    for (label, value) in fields {
+        if let Some(value) = value {
+            print_field(&label, &value);
+        }
+    }
+}
+
+// 打印 OPF 元数据与 spine 中各文档的路径；json 时以 JSON 打印完整的清单、spine、目录，
+// 以及导航文档中的 landmarks 与 EPUB 2 的 <guide>
+pub fn inspect(input: &str, options: &Options, json: bool) -> Result<(), EpubToMdError> {
+    let mut epub = Epub::open(Path::new(input), options)?;
+    if json {
+        print!("{}", sidecar::to_json(&inspect_value(&mut epub)?));
+        return Ok(());
+    }
+    print_field(&tr!("Package:"), &epub.opf_path);
+    print_metadata(&epub.metadata);
+    if epub.fixed_layout {
+        print_field(&tr!("Layout:"), "pre-paginated");
+    }
+    print_field(&tr!("Spine:"), &epub.spine.len().to_string());
+    for (index, item) in epub.spine.iter().enumerate() {
+        let path = epub.manifest_item(&item.idref).map_or("?", |item| item.path.as_str());
+        println!("  {:>3}. {} ({})", index + 1, path, item.idref);
+    }
+    Ok(())
+}
+
+fn inspect_value(epub: &mut Epub) -> Result<Value, EpubToMdError> {
+    let manifest = epub
+        .manifest
+        .iter()
+        .map(|item| {
+            Value::Map(vec![
+                ("id", Value::String(item.id.clone())),
+                ("href", Value::String(item.href.clone())),
+                ("path", Value::String(item.path.clone())),
+                ("media_type", Value::String(item.media_type.clone())),
+                ("properties", item.properties.clone().map_or(Value::Null, Value::String)),
+            ])
+        })
+        .collect();
+    // idref 不在清单中时 path 与 media_type 为 null；layout 为 itemref 上覆盖整书版式的设置
+    let spine = epub
+        .spine
+        .iter()
+        .map(|spine_item| {
+            let item = epub.manifest_item(&spine_item.idref);
+            let layout = spine_item.layout.map(|fixed| if fixed { "pre-paginated" } else { "reflowable" });
+            Value::Map(vec![
+                ("idref", Value::String(spine_item.idref.clone())),
+                ("path", item.map_or(Value::Null, |item| Value::String(item.path.clone()))),
+                ("media_type", item.map_or(Value::Null, |item| Value::String(item.media_type.clone()))),
+                ("layout", layout.map_or(Value::Null, |layout| Value::String(layout.to_string()))),
+            ])
+        })
+        .collect();
+    let toc = epub.toc()?;
+    let references = |entries: Vec<(String, String)>| {
+        Value::List(
+            entries
+                .into_iter()
+                .map(|(path, kind)| Value::Map(vec![("type", Value::String(kind)), ("path", Value::String(path))]))
+                .collect(),
+        )
+    };
+    let landmarks = references(epub.nav_landmarks());
+    let guide = references(epub.guide.clone());
+    Ok(Value::Map(vec![
+        ("package", Value::String(epub.opf_path.clone())),
+        ("metadata", Value::Map(sidecar::metadata_fields(&epub.metadata))),
+        ("layout", Value::String(if epub.fixed_layout { "pre-paginated" } else { "reflowable" }.to_string())),
+        ("page_progression", Value::String(if epub.rtl { "rtl" } else { "ltr" }.to_string())),
+        ("spine_toc", epub.spine_toc.clone().map_or(Value::Null, Value::String)),
+        ("manifest", Value::List(manifest)),
+        ("spine", Value::List(spine)),
+        ("toc", toc::tree_value(&toc)),
+        ("landmarks", landmarks),
+        ("guide", guide),
+    ]))
+}
+
+// --metadata-only：只读取 OPF 中的元数据，不做转换
+pub fn metadata(input: &str, options: &Options) -> Result<(), EpubToMdError> {
+    let epub = Epub::open(Path::new(input), options)?;
+    print_metadata(&epub.metadata);
+    if epub.metadata.cover.is_none() {
+        print_field(&tr!("Cover:"), &tr!("none"));
+    }
+    Ok(())
+}
+
+// --metadata-only --json 时打印的对象，cover 为封面路径，没有封面时为 null
+pub fn metadata_value(input: &str, options: &Options) -> Result<Value, EpubToMdError> {
+    let epub = Epub::open(Path::new(input), options)?;
+    let mut fields = vec![("input", Value::String(input.to_string()))];
+    fields.extend(sidecar::metadata_fields(&epub.metadata));
+    fields.push(("has_cover", Value::Bool(epub.metadata.cover.is_some())));
+    Ok(Value::Map(fields))
+}
+
+// 解析 nav.xhtml / toc.ncx 并打印目录树
+pub fn print_toc(input: &str, options: &Options) -> Result<(), EpubToMdError> {
+    let mut epub = Epub::open(Path::new(input), options)?;
+    let entries = epub.toc()?;
+    if entries.is_empty() {
+        log::info!("{}", tr!("The EPUB does not contain a table of contents."));
+    }
+    toc::print_tree(&entries);
+    Ok(())
+}
+
+// 按原有的目录结构解压所有文件，条目名经过校验，不会写出目标目录；
+// 写出之前先检查所有目标，没有 --force 时不会留下解压了一半的目录
+pub fn extract(input: &str, output: &str, options: &Options) -> Result<(), EpubToMdError> {
+    let mut lost = Vec::new();
+    let mut archive = Archive::open(Path::new(input), options, &mut lost)?;
+    let root = Path::new(output);
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for name in archive.names() {
+        let path = root.join(archive::safe_relative_path(&name)?);
+        // 压缩包中的目录条目以 / 结尾，只建目录
+        if name.ends_with('/') {
+            dirs.push(path);
+        } else {
+            files.push((name, path));
+        }
+    }
+    if !options.force {
+        if let Some((_, path)) = files.iter().find(|(_, path)| path.exists()) {
+            return Err(EpubToMdError::FileIOError(tr!(
+                "Output '{}' already exists, use --force to overwrite it.",
+                path.display()
+            )));
+        }
+    }
+    let create_dir =
+        |dir: &Path| fs::create_dir_all(dir).map_err(|e| EpubToMdError::FileIOError(tr!("Failed to create directory: {}", e)));
+    for dir in &dirs {
+        create_dir(dir)?;
+    }
+    for (name, path) in &files {
+        let bytes = archive.read(name)?;
+        if let Some(parent) = path.parent() {
+            create_dir(parent)?;
+        }
+        fs::write(path, bytes).map_err(|e| EpubToMdError::FileIOError(tr!("Failed to write file: {}", e)))?;
+    }
+    for entry in lost {
+        log::warn!("{}", tr!("could not recover {}", entry));
+    }
+    log::info!("{}", tr!("Extracted {} files to {}", files.len(), output));
+    Ok(())
+}
+
+// 以 --strict 方式解析整本书，发现的第一个问题作为错误返回
+pub fn validate(input: &str, options: &Options) -> Result<(), EpubToMdError> {
+    let options = Options {
+        strict: true,
+        ..options.clone()
+    };
+    native::convert(Path::new(input), &options)?;
+    println!("{}", tr!("No problems found."));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_epub(dir: &Path) -> String {
+        let entries: &[(&str, &[u8])] = &[
+            ("mimetype", b"application/epub+zip"),
+            ("META-INF/", b""),
+            ("META-INF/container.xml", b"<container/>"),
+            ("OEBPS/", b""),
+            ("OEBPS/images/", b""),
+            ("OEBPS/ch1.xhtml", b"<html/>"),
+        ];
+        let path = dir.join("book.epub");
+        fs::write(&path, archive::zip_bytes(entries)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn extract_creates_directory_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = write_epub(dir.path());
+        let output = dir.path().join("out");
+        extract(&input, &output.to_string_lossy(), &Options::default()).unwrap();
+        assert_eq!(fs::read(output.join("OEBPS/ch1.xhtml")).unwrap(), b"<html/>");
+        assert!(output.join("OEBPS/images").is_dir());
+        assert_eq!(fs::read(output.join("mimetype")).unwrap(), b"application/epub+zip");
+    }
+
+    #[test]
+    fn extract_checks_every_target_before_writing() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = write_epub(dir.path());
+        let output = dir.path().join("out");
+        fs::create_dir_all(output.join("OEBPS")).unwrap();
+        fs::write(output.join("OEBPS/ch1.xhtml"), "mine").unwrap();
+
+        let error = extract(&input, &output.to_string_lossy(), &Options::default()).unwrap_err();
+        assert!(matches!(error, EpubToMdError::FileIOError(_)));
+        assert_eq!(fs::read_to_string(output.join("OEBPS/ch1.xhtml")).unwrap(), "mine");
+        assert!(!output.join("mimetype").exists());
+        assert!(!output.join("META-INF").exists());
+
+        let options = Options {
+            force: true,
+            ..Options::default()
+        };
+        extract(&input, &output.to_string_lossy(), &options).unwrap();
+        assert_eq!(fs::read_to_string(output.join("OEBPS/ch1.xhtml")).unwrap(), "<html/>");
+    }
+}
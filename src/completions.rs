@@ -0,0 +1,452 @@
+use std::{fmt::Write, path::Path, str::FromStr};
+
+use clap::ArgAction;
+
+use crate::{cli, config, error::EpubToMdError};
+
+// 写法中没有列出可选值、但取值固定的选项
+const VALUES: &[(&str, &[&str])] = &[
+    ("md-flavor", &["gfm", "commonmark_x", "markdown_strict"]),
+    ("format", &["markdown", "mdbook", "obsidian", "hugo", "jekyll", "zola"]),
+    ("chinese", &["s2t", "t2s", "none"]),
+    ("flavor", &["commonmark", "gfm", "pandoc", "multimarkdown", "obsidian"]),
+    ("bouten", &["bold", "emphasis", "plain"]),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl FromStr for Shell {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            "powershell" => Ok(Shell::Powershell),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown shell '{}', expected one of: bash, zsh, fish, powershell.",
+                s
+            ))),
+        }
+    }
+}
+
+// 选项值或参数的补全方式
+#[derive(Clone, PartialEq)]
+enum Values {
+    // 任意值，不补全
+    Any,
+    Files,
+    Dirs,
+    List(Vec<String>),
+    // 配置文件中的预设名，补全时由脚本调用 completions --list-profiles 读取
+    Profiles,
+}
+
+struct Flag {
+    long: String,
+    short: Option<char>,
+    help: String,
+    // None 表示开关
+    value: Option<(String, Values)>,
+    // --extract-images[=<dir>] 等的值只能用 = 给出
+    optional: bool,
+    repeatable: bool,
+    // 接受该选项的子命令
+    commands: Vec<String>,
+}
+
+struct Subcommand {
+    name: String,
+    about: String,
+    inputs: Values,
+}
+
+fn values(long: &str, name: &str) -> Values {
+    if long == "profile" {
+        return Values::Profiles;
+    }
+    if let Some((_, values)) = VALUES.iter().find(|(option, _)| *option == long) {
+        return Values::List(values.iter().map(|value| value.to_string()).collect());
+    }
+    if name.contains('|') {
+        let items: Vec<String> = name
+            .split('|')
+            .flat_map(|item| match item {
+                "h1-h6" => (1..=6).map(|level| format!("h{}", level)).collect(),
+                // 数字与路径等占位符无法补全
+                "n" | "N" | "path" => Vec::new(),
+                item => vec![item.to_string()],
+            })
+            .collect();
+        return if items.is_empty() { Values::Any } else { Values::List(items) };
+    }
+    match name {
+        "path" | "file" | "file.lua" => Values::Files,
+        "dir" => Values::Dirs,
+        _ => Values::Any,
+    }
+}
+
+// 从命令行定义读出子命令与选项；各子命令的同名选项合并为一项，记下接受它的子命令
+fn definition() -> (Vec<Subcommand>, Vec<Flag>) {
+    let command = cli::command();
+    let mut subcommands = Vec::new();
+    let mut flags: Vec<Flag> = Vec::new();
+    for subcommand in command.get_subcommands() {
+        let name = subcommand.get_name().to_string();
+        let input = subcommand
+            .get_positionals()
+            .next()
+            .and_then(|arg| arg.get_value_names())
+            .and_then(|names| names.first())
+            .map(|name| name.to_string())
+            .unwrap_or_default();
+        let inputs = match input.as_str() {
+            "input_dir" => Values::Dirs,
+            input if input.starts_with("input_epub") => Values::Files,
+            input => values("", input),
+        };
+        subcommands.push(Subcommand {
+            name: name.clone(),
+            about: subcommand.get_about().map(|about| about.to_string()).unwrap_or_default(),
+            inputs,
+        });
+        for arg in subcommand.get_arguments().filter(|arg| !arg.is_hide_set()) {
+            let Some(long) = arg.get_long() else {
+                continue;
+            };
+            if let Some(flag) = flags.iter_mut().find(|flag| flag.long == long) {
+                flag.commands.push(name.clone());
+                continue;
+            }
+            let value = arg.get_action().takes_values().then(|| {
+                let value_name = arg.get_value_names().and_then(|names| names.first()).map(|name| name.to_string()).unwrap_or_default();
+                let values = values(long, &value_name);
+                (value_name, values)
+            });
+            flags.push(Flag {
+                long: long.to_string(),
+                short: arg.get_short(),
+                help: arg.get_help().map(|help| help.to_string()).unwrap_or_default(),
+                value,
+                optional: arg.get_num_args().is_some_and(|range| range.min_values() == 0),
+                repeatable: matches!(arg.get_action(), ArgAction::Count | ArgAction::Append),
+                commands: vec![name.clone()],
+            });
+        }
+    }
+    (subcommands, flags)
+}
+
+// 脚本中的函数名只用字母、数字与下划线
+fn identifier(program: &str) -> String {
+    program.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+fn words(flag: &Flag) -> Vec<String> {
+    flag.short.map(|short| format!("-{}", short)).into_iter().chain([format!("--{}", flag.long)]).collect()
+}
+
+fn bash(program: &str, subcommands: &[Subcommand], flags: &[Flag]) -> String {
+    let names: Vec<&str> = subcommands.iter().map(|subcommand| subcommand.name.as_str()).collect();
+    let mut script = String::new();
+    let _ = writeln!(script, "_{}() {{", identifier(program));
+    script.push_str(concat!(
+        "    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\" key\n",
+        "    # --option=value is split into three words\n",
+        "    if [[ $cur == = ]]; then\n",
+        "        cur=\n",
+        "        key=\"$prev=\"\n",
+        "    elif [[ $prev == = ]]; then\n",
+        "        key=\"${COMP_WORDS[COMP_CWORD-2]}=\"\n",
+        "    else\n",
+        "        key=$prev\n",
+        "    fi\n",
+        "    local command=convert i\n",
+        "    for ((i = 1; i < COMP_CWORD; i++)); do\n",
+        "        case \"${COMP_WORDS[i]}\" in\n",
+    ));
+    let _ = writeln!(script, "            {}) command=\"${{COMP_WORDS[i]}}\"; break ;;", names.join("|"));
+    script.push_str("        esac\n    done\n    case \"$key\" in\n");
+    for flag in flags {
+        let Some((_, values)) = &flag.value else {
+            continue;
+        };
+        let mut patterns = vec![format!("--{}=", flag.long)];
+        if !flag.optional {
+            patterns.extend(words(flag));
+        }
+        let reply = match values {
+            Values::Any => "return".to_string(),
+            Values::Files => "COMPREPLY=($(compgen -f -- \"$cur\")); return".to_string(),
+            Values::Dirs => "COMPREPLY=($(compgen -d -- \"$cur\")); return".to_string(),
+            Values::List(items) => format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return", items.join(" ")),
+            Values::Profiles => format!(
+                "COMPREPLY=($(compgen -W \"$({} completions --list-profiles 2>/dev/null)\" -- \"$cur\")); return",
+                program
+            ),
+        };
+        let _ = writeln!(script, "        {}) {} ;;", patterns.join("|"), reply);
+    }
+    script.push_str("    esac\n    if [[ $cur == -* ]]; then\n        case $command in\n");
+    for subcommand in subcommands {
+        let options: Vec<String> =
+            flags.iter().filter(|flag| flag.commands.contains(&subcommand.name)).flat_map(words).collect();
+        let _ = writeln!(script, "            {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;", subcommand.name, options.join(" "));
+    }
+    script.push_str("        esac\n        return\n    fi\n    case $command in\n");
+    for subcommand in subcommands {
+        let reply = match &subcommand.inputs {
+            Values::Dirs => "COMPREPLY=($(compgen -d -- \"$cur\"))".to_string(),
+            Values::List(items) => format!("COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))", items.join(" ")),
+            _ => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+        };
+        let _ = writeln!(script, "        {}) {} ;;", subcommand.name, reply);
+    }
+    script.push_str("    esac\n    # no command yet: the first input may also be a command name\n");
+    let _ = writeln!(script, "    if ((i == COMP_CWORD)); then\n        COMPREPLY+=($(compgen -W \"{}\" -- \"$cur\"))\n    fi", names.join(" "));
+    let _ = writeln!(script, "}}\ncomplete -o filenames -F _{} {}", identifier(program), program);
+    script
+}
+
+fn zsh_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+fn zsh(program: &str, subcommands: &[Subcommand], flags: &[Flag]) -> String {
+    let function = format!("_{}", identifier(program));
+    let action = |values: &Values| match values {
+        Values::Any => " ".to_string(),
+        Values::Files => "_files".to_string(),
+        Values::Dirs => "_files -/".to_string(),
+        Values::List(items) => format!("({})", items.join(" ")),
+        Values::Profiles => format!("{}_profiles", function),
+    };
+    let mut script = format!("#compdef {}\n\n", program);
+    let _ = writeln!(script, "{}_profiles() {{\n    local -a profiles", function);
+    let _ = writeln!(script, "    profiles=(${{(f)\"$({} completions --list-profiles 2>/dev/null)\"}})\n    compadd -a profiles\n}}\n", program);
+    let _ = writeln!(script, "{}_commands() {{\n    local -a commands\n    commands=(", function);
+    for subcommand in subcommands {
+        let _ = writeln!(script, "        {}", zsh_quote(&format!("{}:{}", subcommand.name, subcommand.about.replace(':', "\\:"))));
+    }
+    let _ = writeln!(script, "    )\n    _describe -t commands command commands\n}}\n");
+    let names: Vec<&str> = subcommands.iter().map(|subcommand| subcommand.name.as_str()).collect();
+    let _ = writeln!(script, "{}() {{\n    local -a names\n    names=({})", function, names.join(" "));
+    let _ = writeln!(script, "    local command=convert inputs='_alternative \"commands:command:{0}_commands\" \"files:file:_files\"'", function);
+    script.push_str(concat!(
+        "    local -i i\n",
+        "    for ((i = 2; i < CURRENT; i++)); do\n",
+        "        if ((${names[(Ie)${words[i]}]})); then\n",
+        "            command=${words[i]}\n",
+        "            inputs=_files\n",
+        "            words=(\"${(@)words[i,-1]}\")\n",
+        "            ((CURRENT -= i - 1))\n",
+        "            break\n",
+        "        fi\n",
+        "    done\n",
+        "    case $command in\n",
+    ));
+    for subcommand in subcommands {
+        let _ = writeln!(script, "        {})\n            _arguments -s -S \\", subcommand.name);
+        for flag in flags.iter().filter(|flag| flag.commands.contains(&subcommand.name)) {
+            let help = flag.help.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]");
+            let (suffix, value) = match &flag.value {
+                None => (String::new(), String::new()),
+                Some((name, values)) if flag.optional => ("=-".to_string(), format!("::{}:{}", name, action(values))),
+                Some((name, values)) => ("=".to_string(), format!(":{}:{}", name, action(values))),
+            };
+            let repeat = if flag.repeatable { "*" } else { "" };
+            let mut specs = vec![format!("{}--{}{}[{}]{}", repeat, flag.long, suffix, help, value)];
+            if let Some(short) = flag.short {
+                let suffix = if flag.value.is_some() { "+" } else { "" };
+                specs.insert(0, format!("{}-{}{}[{}]{}", repeat, short, suffix, help, value));
+            }
+            for spec in specs {
+                let _ = writeln!(script, "                {} \\", zsh_quote(&spec));
+            }
+        }
+        let inputs = match &subcommand.inputs {
+            Values::Files if subcommand.name == "convert" => "\"*:input:$inputs\"".to_string(),
+            values => zsh_quote(&format!("*:input:{}", action(values))),
+        };
+        let _ = writeln!(script, "                {}\n            ;;", inputs);
+    }
+    let _ = writeln!(script, "    esac\n}}\n\n{} \"$@\"", function);
+    script
+}
+
+fn fish_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+fn fish(program: &str, subcommands: &[Subcommand], flags: &[Flag]) -> String {
+    let function = format!("__{}", identifier(program));
+    let names: Vec<&str> = subcommands.iter().map(|subcommand| subcommand.name.as_str()).collect();
+    let mut script = String::new();
+    let _ = writeln!(script, "function {}_command\n    set -l tokens (commandline -opc)\n    set -e tokens[1]\n    for token in $tokens", function);
+    let _ = writeln!(script, "        if contains -- $token {}\n            echo $token\n            return\n        end\n    end\n    return 1\nend\n", names.join(" "));
+    let _ = writeln!(script, "function {0}_using\n    set -l command ({0}_command; or echo convert)\n    contains -- $command $argv\nend\n", function);
+    for subcommand in subcommands {
+        let _ = writeln!(
+            script,
+            "complete -c {} -n 'not {}_command' -a {} -d {}",
+            program,
+            function,
+            subcommand.name,
+            fish_quote(&subcommand.about)
+        );
+        match &subcommand.inputs {
+            Values::Dirs => {
+                let _ = writeln!(script, "complete -c {} -n '{}_using {}' -f -a '(__fish_complete_directories)'", program, function, subcommand.name);
+            }
+            Values::List(items) => {
+                let _ = writeln!(script, "complete -c {} -n '{}_using {}' -f -a {}", program, function, subcommand.name, fish_quote(&items.join(" ")));
+            }
+            _ => {}
+        }
+    }
+    script.push('\n');
+    for flag in flags {
+        let mut line = format!("complete -c {} -n '{}_using {}'", program, function, flag.commands.join(" "));
+        if let Some(short) = flag.short {
+            let _ = write!(line, " -s {}", short);
+        }
+        let _ = write!(line, " -l {}", flag.long);
+        // fish 不补全只能用 = 给出的可选值，按开关处理
+        match flag.value.as_ref().filter(|_| !flag.optional) {
+            None => {}
+            Some((_, Values::Any)) => line.push_str(" -x"),
+            Some((_, Values::Files)) => line.push_str(" -r -F"),
+            Some((_, Values::Dirs)) => line.push_str(" -x -a '(__fish_complete_directories)'"),
+            Some((_, Values::List(items))) => {
+                let _ = write!(line, " -x -a {}", fish_quote(&items.join(" ")));
+            }
+            Some((_, Values::Profiles)) => {
+                let _ = write!(line, " -x -a '({} completions --list-profiles 2>/dev/null)'", program);
+            }
+        }
+        let _ = writeln!(script, "{} -d {}", line, fish_quote(&flag.help));
+    }
+    script
+}
+
+fn powershell_quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn powershell(program: &str, subcommands: &[Subcommand], flags: &[Flag]) -> String {
+    let mut script = format!(
+        "Register-ArgumentCompleter -Native -CommandName {} -ScriptBlock {{\n    param($wordToComplete, $commandAst, $cursorPosition)\n",
+        powershell_quote(program)
+    );
+    script.push_str("    $commands = [ordered]@{\n");
+    for subcommand in subcommands {
+        let _ = writeln!(script, "        {} = {}", powershell_quote(&subcommand.name), powershell_quote(&subcommand.about));
+    }
+    script.push_str("    }\n    $inputs = @{\n");
+    for subcommand in subcommands {
+        if let Values::List(items) = &subcommand.inputs {
+            let items: Vec<String> = items.iter().map(|item| powershell_quote(item)).collect();
+            let _ = writeln!(script, "        {} = @({})", powershell_quote(&subcommand.name), items.join(", "));
+        }
+    }
+    // 每个选项：写法、说明、接受它的子命令
+    script.push_str("    }\n    $options = @(\n");
+    let mut lines = Vec::new();
+    for flag in flags {
+        for word in words(flag) {
+            lines.push(format!(
+                "        @({}, {}, {})",
+                powershell_quote(&word),
+                powershell_quote(&flag.help),
+                powershell_quote(&flag.commands.join(" "))
+            ));
+        }
+    }
+    script.push_str(&lines.join(",\n"));
+    script.push_str("\n    )\n    $values = @{\n");
+    for flag in flags.iter().filter(|flag| !flag.optional) {
+        let items = match &flag.value {
+            Some((_, Values::List(items))) => items.iter().map(|item| powershell_quote(item)).collect::<Vec<_>>().join(", "),
+            Some((_, Values::Profiles)) => format!("& {} completions --list-profiles 2>$null", powershell_quote(program)),
+            _ => continue,
+        };
+        for word in words(flag) {
+            let _ = writeln!(script, "        {} = @({})", powershell_quote(&word), items);
+        }
+    }
+    script.push_str(concat!(
+        "    }\n",
+        "    $words = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object { $_.ToString() })\n",
+        "    if ($wordToComplete -ne '') {\n",
+        "        $words = @($words | Select-Object -SkipLast 1)\n",
+        "    }\n",
+        "    $command = $null\n",
+        "    foreach ($word in $words) {\n",
+        "        if ($commands.Contains($word)) {\n",
+        "            $command = $word\n",
+        "            break\n",
+        "        }\n",
+        "    }\n",
+        "    $previous = if ($words.Count -gt 0) { $words[-1] } else { '' }\n",
+        "    $current = if ($command) { $command } else { 'convert' }\n",
+        "    $results = @()\n",
+        "    if ($values.ContainsKey($previous)) {\n",
+        "        $results = @($values[$previous] | ForEach-Object { , @($_, $_, 'ParameterValue') })\n",
+        "    } elseif ($wordToComplete -like '-*') {\n",
+        "        $results = @($options | Where-Object { ($_[2] -split ' ') -contains $current } |\n",
+        "            ForEach-Object { , @($_[0], $_[1], 'ParameterName') })\n",
+        "    } elseif ($inputs.Contains($current)) {\n",
+        "        $results = @($inputs[$current] | ForEach-Object { , @($_, $_, 'ParameterValue') })\n",
+        "    } elseif (-not $command -and $wordToComplete) {\n",
+        "        $results = @($commands.Keys | ForEach-Object { , @($_, $commands[$_], 'Command') })\n",
+        "    }\n",
+        "    # nothing matched: PowerShell falls back to completing file names\n",
+        "    $results | Where-Object { $_[0] -like \"$wordToComplete*\" } | ForEach-Object {\n",
+        "        [System.Management.Automation.CompletionResult]::new($_[0], $_[0], $_[2], $_[1])\n",
+        "    }\n",
+        "}\n",
+    ));
+    script
+}
+
+pub fn generate(shell: Shell, program: &str) -> String {
+    let (subcommands, flags) = definition();
+    match shell {
+        Shell::Bash => bash(program, &subcommands, &flags),
+        Shell::Zsh => zsh(program, &subcommands, &flags),
+        Shell::Fish => fish(program, &subcommands, &flags),
+        Shell::Powershell => powershell(program, &subcommands, &flags),
+    }
+}
+
+// 补全脚本注册到用户输入的程序名上，例如 epub2md_rs 或改名后的 epub2md
+pub fn program() -> String {
+    std::env::args()
+        .next()
+        .and_then(|arg| Path::new(&arg).file_stem().map(|name| name.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "epub2md_rs".to_string())
+}
+
+// 打印补全脚本；未给出 shell 时每行打印一个配置文件中的预设名，供补全脚本调用
+pub fn run(shell: Option<Shell>) -> Result<(), EpubToMdError> {
+    match shell {
+        Some(shell) => print!("{}", generate(shell, &program())),
+        None => {
+            for config in config::load()? {
+                for (name, _) in &config.profiles {
+                    println!("{}", name);
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,338 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::EpubToMdError;
+
+pub const FILE_NAME: &str = "epub2md.toml";
+
+// 配置项的取值；数组用于可重复的选项，例如 pandoc-arg
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Bool(bool),
+    List(Vec<Value>),
+}
+
+// 顶层的键是默认选项，[profile.<name>] 表中的键只在 --profile <name> 时使用
+#[derive(Debug, Default)]
+pub struct Config {
+    pub path: PathBuf,
+    pub defaults: Vec<(String, Value)>,
+    pub profiles: Vec<(String, Vec<(String, Value)>)>,
+}
+
+impl Config {
+    pub fn profile(&self, name: &str) -> Option<&[(String, Value)]> {
+        self.profiles.iter().find(|(profile, _)| profile == name).map(|(_, entries)| entries.as_slice())
+    }
+}
+
+// 全局配置在前，当前目录的 epub2md.toml 在后，后者的同名选项覆盖前者
+pub fn load() -> Result<Vec<Config>, EpubToMdError> {
+    let global = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").filter(|_| cfg!(windows)).map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").or_else(|| env::var_os("USERPROFILE")).map(|home| Path::new(&home).join(".config")))
+        .map(|dir| dir.join("epub2md").join(FILE_NAME));
+    let mut configs = Vec::new();
+    for path in global.into_iter().chain([PathBuf::from(FILE_NAME)]) {
+        if path.is_file() {
+            let text = fs::read_to_string(&path)
+                .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to read {}: {}", path.display(), e)))?;
+            configs.push(parse(&path, &text)?);
+        }
+    }
+    Ok(configs)
+}
+
+// TOML 的一个子集：注释、[profile.<name>] 表、字符串、整数、布尔值与数组
+struct Parser<'a> {
+    path: &'a Path,
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: String) -> EpubToMdError {
+        EpubToMdError::ArgumentError(tr!("{}:{}: {}", self.path.display(), self.line, message))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    // 跳过空白与注释；newlines 为 false 时停在行尾
+    fn skip(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            match c {
+                ' ' | '\t' | '\r' => {}
+                '\n' if newlines => {}
+                '#' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.pos += 1;
+                    }
+                    continue;
+                }
+                _ => break,
+            }
+            self.next();
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), EpubToMdError> {
+        self.skip(false);
+        match self.next() {
+            None | Some('\n') => Ok(()),
+            Some(c) => Err(self.error(tr!("unexpected '{}'.", c))),
+        }
+    }
+
+    fn key(&mut self) -> Result<String, EpubToMdError> {
+        match self.peek() {
+            Some('"') | Some('\'') => self.string(),
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
+                    self.pos += 1;
+                }
+                match start == self.pos {
+                    true => Err(self.error(tr!("expected a key."))),
+                    false => Ok(self.chars[start..self.pos].iter().collect()),
+                }
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, EpubToMdError> {
+        let quote = self.next().unwrap_or_default();
+        let mut text = String::new();
+        loop {
+            // 在行尾之前报错，错误中的行号是字符串所在的行
+            let Some(c) = self.peek().filter(|&c| c != '\n') else {
+                return Err(self.error(tr!("unterminated string.")));
+            };
+            self.next();
+            match c {
+                c if c == quote => return Ok(text),
+                // 单引号字符串不处理转义
+                '\\' if quote == '"' => {
+                    let escaped = match self.next() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some(u @ ('u' | 'U')) => {
+                            let length = if u == 'u' { 4 } else { 8 };
+                            let digits: String = (0..length).filter_map(|_| self.next()).collect();
+                            u32::from_str_radix(&digits, 16)
+                                .ok()
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| self.error(tr!("invalid escape \\{}{}.", u, digits)))?
+                        }
+                        Some(c) => return Err(self.error(tr!("invalid escape \\{}.", c))),
+                        None => return Err(self.error(tr!("unterminated string."))),
+                    };
+                    text.push(escaped);
+                }
+                c => text.push(c),
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, EpubToMdError> {
+        match self.peek() {
+            Some('"') | Some('\'') => Ok(Value::String(self.string()?)),
+            Some('[') => {
+                self.next();
+                let mut items = Vec::new();
+                loop {
+                    self.skip(true);
+                    if self.peek() == Some(']') {
+                        self.next();
+                        return Ok(Value::List(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip(true);
+                    match self.next() {
+                        Some(',') => {}
+                        Some(']') => return Ok(Value::List(items)),
+                        _ => return Err(self.error(tr!("expected ',' or ']' in array."))),
+                    }
+                }
+            }
+            _ => {
+                let start = self.pos;
+                while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '+' | '.')) {
+                    self.pos += 1;
+                }
+                let word: String = self.chars[start..self.pos].iter().collect();
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    // 数字按原样交给命令行解析，下划线分隔符去掉
+                    _ if word.starts_with(|c: char| c.is_ascii_digit() || c == '-' || c == '+') => {
+                        Ok(Value::String(word.replace('_', "")))
+                    }
+                    _ => Err(self.error(tr!("invalid value '{}'.", word))),
+                }
+            }
+        }
+    }
+}
+
+pub fn parse(path: &Path, text: &str) -> Result<Config, EpubToMdError> {
+    let mut parser = Parser {
+        path,
+        chars: text.chars().collect(),
+        pos: 0,
+        line: 1,
+    };
+    let mut config = Config {
+        path: path.to_path_buf(),
+        ..Config::default()
+    };
+    // None 表示顶层
+    let mut profile: Option<usize> = None;
+    loop {
+        parser.skip(true);
+        let Some(c) = parser.peek() else {
+            break;
+        };
+        if c == '[' {
+            parser.next();
+            parser.skip(false);
+            let table = parser.key()?;
+            parser.skip(false);
+            if table != "profile" || parser.next() != Some('.') {
+                return Err(parser.error(tr!("only [profile.<name>] tables are supported.")));
+            }
+            parser.skip(false);
+            let name = parser.key()?;
+            parser.skip(false);
+            if parser.next() != Some(']') {
+                return Err(parser.error(tr!("expected ']'.")));
+            }
+            parser.end_of_line()?;
+            if config.profile(&name).is_some() {
+                return Err(parser.error(tr!("profile '{}' is defined twice.", name)));
+            }
+            config.profiles.push((name, Vec::new()));
+            profile = Some(config.profiles.len() - 1);
+            continue;
+        }
+        let line = parser.line;
+        let key = parser.key()?;
+        parser.skip(false);
+        if parser.next() != Some('=') {
+            return Err(parser.error(tr!("expected '=' after '{}'.", key)));
+        }
+        parser.skip(false);
+        let value = parser.value()?;
+        parser.end_of_line()?;
+        let entries = match profile {
+            Some(index) => &mut config.profiles[index].1,
+            None => &mut config.defaults,
+        };
+        if entries.iter().any(|(other, _)| *other == key) {
+            parser.line = line;
+            return Err(parser.error(tr!("'{}' is set twice.", key)));
+        }
+        entries.push((key, value));
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(text: &str) -> Result<Config, EpubToMdError> {
+        parse(Path::new(FILE_NAME), text)
+    }
+
+    fn error(text: &str) -> String {
+        match parse_str(text) {
+            Err(EpubToMdError::ArgumentError(message)) => message,
+            other => panic!("expected an error, got {:?}", other.map(|config| config.defaults)),
+        }
+    }
+
+    fn string(text: &str) -> Value {
+        Value::String(text.to_string())
+    }
+
+    #[test]
+    fn parses_strings_and_escapes() {
+        let config = parse_str(
+            r#"
+title = "A \"quoted\" \\ title\tтаб\u00e9\U0001F600"
+literal = 'C:\books\no-escapes'
+"quoted-key" = "x"
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.defaults,
+            vec![
+                ("title".to_string(), string("A \"quoted\" \\ title\tтабé😀")),
+                ("literal".to_string(), string(r"C:\books\no-escapes")),
+                ("quoted-key".to_string(), string("x")),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_numbers_booleans_and_arrays() {
+        let config = parse_str("jobs = 4\nmax-file-size = 1_000_000\nbom = true\nstrict = false\npandoc-arg = [\n  \"--wrap=none\", # comment\n  '--columns=80',\n]\nempty = []\n").unwrap();
+        assert_eq!(
+            config.defaults,
+            vec![
+                ("jobs".to_string(), string("4")),
+                ("max-file-size".to_string(), string("1000000")),
+                ("bom".to_string(), Value::Bool(true)),
+                ("strict".to_string(), Value::Bool(false)),
+                ("pandoc-arg".to_string(), Value::List(vec![string("--wrap=none"), string("--columns=80")])),
+                ("empty".to_string(), Value::List(Vec::new())),
+            ]
+        );
+    }
+
+    #[test]
+    fn comments_and_profiles() {
+        let config = parse_str(
+            "# defaults\nformat = \"markdown\" # trailing\n\n[profile.blog]\nformat = 'hugo'\n[ profile . \"zh tw\" ]  # comment\nchinese = \"s2t\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.defaults, vec![("format".to_string(), string("markdown"))]);
+        assert_eq!(config.profile("blog"), Some(&[("format".to_string(), string("hugo"))][..]));
+        assert_eq!(config.profile("zh tw"), Some(&[("chinese".to_string(), string("s2t"))][..]));
+        assert_eq!(config.profile("missing"), None);
+    }
+
+    #[test]
+    fn errors_report_the_line() {
+        assert_eq!(error("a = 1\nb = \"open\nc = 2"), "epub2md.toml:2: unterminated string.");
+        assert_eq!(error("\n\nformat markdown"), "epub2md.toml:3: expected '=' after 'format'.");
+        assert_eq!(error("a = yes"), "epub2md.toml:1: invalid value 'yes'.");
+        assert_eq!(error("a = \"\\q\""), "epub2md.toml:1: invalid escape \\q.");
+        assert_eq!(error("a = [1,\n2\n3]"), "epub2md.toml:3: expected ',' or ']' in array.");
+        assert_eq!(error("a = 1 2"), "epub2md.toml:1: unexpected '2'.");
+        assert_eq!(error("a = 1\n\na = 2"), "epub2md.toml:3: 'a' is set twice.");
+        assert_eq!(error("[output]"), "epub2md.toml:1: only [profile.<name>] tables are supported.");
+        assert_eq!(error("[profile.a]\n[profile.a]"), "epub2md.toml:2: profile 'a' is defined twice.");
+    }
+}
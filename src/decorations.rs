@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use html2md::{common::get_tag_attr, Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+
+use crate::{
+    dom,
+    markdown::default_handler,
+    options::{Flavor, Underline},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Decoration {
+    Strike,
+    Underline,
+}
+
+// 标签本身或 class、行内样式表示的删除线与下划线
+fn decoration(tag: &Handle) -> Option<Decoration> {
+    match dom::tag_name(tag).as_deref() {
+        Some("s" | "del" | "strike") => return Some(Decoration::Strike),
+        Some("u" | "ins") => return Some(Decoration::Underline),
+        _ => {}
+    }
+    let class = get_tag_attr(tag, "class").unwrap_or_default().to_ascii_lowercase();
+    let style = get_tag_attr(tag, "style").unwrap_or_default().to_ascii_lowercase();
+    let decorated = |words: &[&str]| {
+        class.split_whitespace().any(|token| words.contains(&token.replace(['-', '_'], "").as_str()))
+            || style.split(';').any(|declaration| {
+                declaration.split_once(':').is_some_and(|(property, value)| {
+                    property.trim().starts_with("text-decoration")
+                        && words.iter().any(|word| value.replace('-', "").contains(word))
+                })
+            })
+    };
+    if decorated(&["linethrough", "strike", "strikethrough", "deleted"]) {
+        Some(Decoration::Strike)
+    } else if decorated(&["underline", "underlined", "inserted"]) {
+        Some(Decoration::Underline)
+    } else {
+        None
+    }
+}
+
+// 删除线写成 ~~文字~~ 或 <del>，下划线按 --underline 写成 <u>、*强调* 或普通文字
+struct DecorationHandler {
+    underline: Underline,
+    // 方言不支持 ~~删除线~~ 时写成 <del>
+    strikethrough: bool,
+    decoration: Option<Decoration>,
+    start_pos: usize,
+    // 没有修饰的 <span> 等交给原来的处理器
+    fallback: Box<dyn TagHandler>,
+}
+
+impl TagHandler for DecorationHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        self.decoration = decoration(tag);
+        self.start_pos = printer.data.len();
+        self.fallback.handle(tag, printer);
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        self.fallback.after_handle(printer);
+        let (open, close) = match (self.decoration, self.underline) {
+            (Some(Decoration::Strike), _) if self.strikethrough => ("~~", "~~"),
+            (Some(Decoration::Strike), _) => ("<del>", "</del>"),
+            (Some(Decoration::Underline), Underline::Html) => ("<u>", "</u>"),
+            (Some(Decoration::Underline), Underline::Emphasis) => ("*", "*"),
+            (Some(Decoration::Underline), Underline::Plain) | (None, _) => return,
+        };
+        // 标记加在首尾空白以内
+        let Some(offset) = printer.data[self.start_pos..].find(|c: char| !c.is_whitespace()) else {
+            return;
+        };
+        let end = printer.data.trim_end().len();
+        printer.data.insert_str(end, close);
+        printer.data.insert_str(self.start_pos + offset, open);
+    }
+
+    fn skip_descendants(&self) -> bool {
+        self.fallback.skip_descendants()
+    }
+}
+
+struct DecorationHandlerFactory {
+    underline: Underline,
+    strikethrough: bool,
+    tag: &'static str,
+    fallback: Option<Box<dyn TagHandlerFactory>>,
+}
+
+impl TagHandlerFactory for DecorationHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        let fallback = match &self.fallback {
+            Some(factory) => factory.instantiate(),
+            None => default_handler(self.tag),
+        };
+        Box::new(DecorationHandler {
+            underline: self.underline,
+            strikethrough: self.strikethrough,
+            decoration: None,
+            start_pos: 0,
+            fallback,
+        })
+    }
+}
+
+// 需在其他处理器之后注册，<span> 的首字下沉等处理仍交给已注册的处理器
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, underline: Underline, flavor: Flavor) {
+    let strikethrough = flavor.strikethrough();
+    for tag in ["s", "del", "strike", "u", "ins", "span"] {
+        let fallback = handlers.remove(tag);
+        handlers.insert(
+            tag.to_string(),
+            Box::new(DecorationHandlerFactory {
+                underline,
+                strikethrough,
+                tag,
+                fallback,
+            }),
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+
+use crate::options::DefinitionListStyle;
+
+enum Entry {
+    Term(String),
+    Definition(String),
+}
+
+// 正在转换的各层 <dl> 中已收集的术语与释义，嵌套的 <dl> 位于栈顶
+type Lists = Rc<RefCell<Vec<Vec<Entry>>>>;
+
+#[derive(Clone, Copy)]
+enum Part {
+    List,
+    Term,
+    Definition,
+}
+
+struct DefinitionListHandler {
+    part: Part,
+    style: DefinitionListStyle,
+    lists: Lists,
+    start_pos: usize,
+}
+
+impl TagHandler for DefinitionListHandler {
+    fn handle(&mut self, _tag: &Handle, printer: &mut StructuredPrinter) {
+        if let Part::List = self.part {
+            self.lists.borrow_mut().push(Vec::new());
+            printer.insert_newline();
+            printer.insert_newline();
+        }
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        // <dt>、<dd> 之间的空白等不属于任何条目的内容一并丢弃
+        let content = printer.data.split_off(self.start_pos);
+        let content = content.trim_matches('\n').trim_end().to_string();
+        let mut lists = self.lists.borrow_mut();
+        if let Part::List = self.part {
+            let entries = lists.pop().unwrap_or_default();
+            printer.append_str(&render(&entries, self.style));
+            printer.insert_newline();
+            printer.insert_newline();
+            return;
+        }
+        // 不在 <dl> 中的 <dt>、<dd> 保留其内容
+        let Some(entries) = lists.last_mut() else {
+            printer.append_str(&content);
+            return;
+        };
+        entries.push(match self.part {
+            Part::Term => Entry::Term(content.split_whitespace().collect::<Vec<_>>().join(" ")),
+            _ => Entry::Definition(content),
+        });
+    }
+}
+
+fn render(entries: &[Entry], style: DefinitionListStyle) -> String {
+    let mut groups: Vec<(Vec<&str>, Vec<&str>)> = Vec::new();
+    for entry in entries {
+        match entry {
+            // 连续的多个 <dt> 共用后面的释义
+            Entry::Term(term) => match groups.last_mut() {
+                Some((terms, definitions)) if definitions.is_empty() => terms.push(term),
+                _ => groups.push((vec![term], Vec::new())),
+            },
+            Entry::Definition(definition) => match groups.last_mut() {
+                Some((_, definitions)) => definitions.push(definition),
+                None => groups.push((Vec::new(), vec![definition])),
+            },
+        }
+    }
+    let groups: Vec<String> = groups
+        .iter()
+        .map(|(terms, definitions)| match style {
+            // Pandoc 与 PHP Markdown Extra：术语各占一行，释义以 : 开头，后续行缩进四个空格
+            DefinitionListStyle::Colon => {
+                let definitions: Vec<String> =
+                    definitions.iter().map(|definition| indent(definition, ":   ", "    ")).collect();
+                // 多段的释义之后需要空一行，否则下一条释义会被当作上一段的延续
+                let separator = match definitions.iter().any(|definition| definition.contains('\n')) {
+                    true => "\n\n",
+                    false => "\n",
+                };
+                let mut lines: Vec<String> = terms.iter().map(|term| term.to_string()).collect();
+                if !definitions.is_empty() {
+                    lines.push(definitions.join(separator));
+                }
+                lines.join("\n")
+            }
+            // 其他 Markdown：加粗的术语作为列表项，释义接在后面并缩进
+            DefinitionListStyle::Bold => {
+                let terms: Vec<String> = terms.iter().map(|term| format!("**{}**", term)).collect();
+                let mut item = format!("- {}", terms.join(", "));
+                for (i, definition) in definitions.iter().enumerate() {
+                    match i {
+                        0 if !terms.is_empty() => item.push_str(&indent(definition, ": ", "  ")),
+                        0 => item = indent(definition, "- ", "  "),
+                        _ => item.push_str(&indent(definition, "\n\n  ", "  ")),
+                    }
+                }
+                item
+            }
+        })
+        .collect();
+    groups.join("\n\n")
+}
+
+// 首行加上 first，其余非空行加上 rest
+fn indent(text: &str, first: &str, rest: &str) -> String {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| match (i, line.trim().is_empty()) {
+            (0, _) => format!("{}{}", first, line),
+            (_, true) => String::new(),
+            _ => format!("{}{}", rest, line),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+struct DefinitionListHandlerFactory {
+    part: Part,
+    style: DefinitionListStyle,
+    lists: Lists,
+}
+
+impl TagHandlerFactory for DefinitionListHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(DefinitionListHandler {
+            part: self.part,
+            style: self.style,
+            lists: self.lists.clone(),
+            start_pos: 0,
+        })
+    }
+}
+
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, style: DefinitionListStyle) {
+    let lists = Lists::default();
+    for (tag, part) in [("dl", Part::List), ("dt", Part::Term), ("dd", Part::Definition)] {
+        handlers.insert(
+            tag.to_string(),
+            Box::new(DefinitionListHandlerFactory {
+                part,
+                style,
+                lists: lists.clone(),
+            }),
+        );
+    }
+}
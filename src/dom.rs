@@ -0,0 +1,176 @@
+use std::{cell::RefCell, rc::Rc};
+
+use html5ever::{
+    namespace_url, ns, parse_document,
+    serialize::{serialize, SerializeOpts, TraversalScope},
+    tendril::TendrilSink,
+    Attribute, LocalName, QualName,
+};
+use markup5ever_rcdom::{Handle, Node, NodeData, RcDom, SerializableHandle};
+
+// 以 HTML5 规则解析文档，容忍不规范的 XHTML
+pub fn parse(html: &str) -> RcDom {
+    parse_document(RcDom::default(), Default::default()).one(html)
+}
+
+pub fn tag_name(node: &Handle) -> Option<String> {
+    match node.data {
+        NodeData::Element { ref name, .. } => Some(name.local.to_string()),
+        _ => None,
+    }
+}
+
+// 按本地名查找属性，忽略命名空间，例如 epub:type 用 "type"、xlink:href 用 "href"；
+// HTML 解析器不处理 XHTML 的命名空间前缀，前缀会留在本地名中
+pub fn attr(node: &Handle, name: &str) -> Option<String> {
+    match node.data {
+        NodeData::Element { ref attrs, .. } => attrs
+            .borrow()
+            .iter()
+            .find(|attr| {
+                let local = &*attr.name.local;
+                local == name || local.rsplit_once(':').is_some_and(|(_, local)| local == name)
+            })
+            .map(|attr| attr.value.to_string()),
+        _ => None,
+    }
+}
+
+// 设置属性，已有同名属性时替换其值
+pub fn set_attr(node: &Handle, name: &str, value: &str) {
+    if let NodeData::Element { ref attrs, .. } = node.data {
+        let mut attrs = attrs.borrow_mut();
+        match attrs.iter_mut().find(|attr| &*attr.name.local == name) {
+            Some(attr) => attr.value = value.into(),
+            None => attrs.push(Attribute {
+                name: QualName::new(None, ns!(), LocalName::from(name)),
+                value: value.into(),
+            }),
+        }
+    }
+}
+
+// 拼接节点下所有文本并合并空白
+pub fn text_content(node: &Handle) -> String {
+    let mut text = String::new();
+    for descendant in descendants(node) {
+        if let NodeData::Text { ref contents } = descendant.data {
+            text.push_str(&contents.borrow());
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// 先序遍历返回所有后代节点（不含自身）
+pub fn descendants(node: &Handle) -> Vec<Handle> {
+    let mut result = Vec::new();
+    for child in node.children.borrow().iter() {
+        result.push(child.clone());
+        result.extend(descendants(child));
+    }
+    result
+}
+
+pub fn parent(node: &Handle) -> Option<Handle> {
+    let weak = node.parent.take();
+    let parent = weak.as_ref().and_then(|weak| weak.upgrade());
+    node.parent.set(weak);
+    parent
+}
+
+// 从父节点中移除
+pub fn detach(node: &Handle) {
+    if let Some(parent) = parent(node) {
+        parent.children.borrow_mut().retain(|child| !Rc::ptr_eq(child, node));
+    }
+    node.parent.set(None);
+}
+
+// 用另一个节点替换该节点在父节点中的位置
+pub fn replace(node: &Handle, replacement: Handle) {
+    let Some(parent) = parent(node) else {
+        return;
+    };
+    replacement.parent.set(Some(Rc::downgrade(&parent)));
+    for child in parent.children.borrow_mut().iter_mut() {
+        if Rc::ptr_eq(child, node) {
+            *child = replacement.clone();
+        }
+    }
+    node.parent.set(None);
+}
+
+// 插入为第一个子节点
+pub fn prepend(parent: &Handle, child: Handle) {
+    child.parent.set(Some(Rc::downgrade(parent)));
+    parent.children.borrow_mut().insert(0, child);
+}
+
+// 插入为最后一个子节点
+pub fn append(parent: &Handle, child: Handle) {
+    child.parent.set(Some(Rc::downgrade(parent)));
+    parent.children.borrow_mut().push(child);
+}
+
+// 插入到该节点之前，成为其前一个兄弟节点
+pub fn insert_before(node: &Handle, sibling: Handle) {
+    let Some(parent) = parent(node) else {
+        return;
+    };
+    sibling.parent.set(Some(Rc::downgrade(&parent)));
+    let mut children = parent.children.borrow_mut();
+    let position = children.iter().position(|child| Rc::ptr_eq(child, node)).unwrap_or(0);
+    children.insert(position, sibling);
+}
+
+// 新建一个不含子节点的元素
+pub fn element(name: &str, attrs: &[(&str, &str)]) -> Handle {
+    Node::new(NodeData::Element {
+        name: QualName::new(None, ns!(html), LocalName::from(name)),
+        attrs: RefCell::new(
+            attrs
+                .iter()
+                .map(|(name, value)| Attribute {
+                    name: QualName::new(None, ns!(), LocalName::from(*name)),
+                    value: (*value).into(),
+                })
+                .collect(),
+        ),
+        template_contents: RefCell::new(None),
+        mathml_annotation_xml_integration_point: false,
+    })
+}
+
+pub fn text(contents: &str) -> Handle {
+    Node::new(NodeData::Text {
+        contents: RefCell::new(contents.into()),
+    })
+}
+
+// 把节点的所有子节点序列化为 HTML
+pub fn inner_html(node: &Handle) -> String {
+    serialize_node(node, TraversalScope::ChildrenOnly(None))
+}
+
+// 把节点连同其自身的标签序列化为 HTML
+pub fn outer_html(node: &Handle) -> String {
+    serialize_node(node, TraversalScope::IncludeNode)
+}
+
+fn serialize_node(node: &Handle, traversal_scope: TraversalScope) -> String {
+    let mut bytes = Vec::new();
+    let options = SerializeOpts {
+        traversal_scope,
+        ..Default::default()
+    };
+    // 写入 Vec 不会失败
+    let _ = serialize(&mut bytes, &SerializableHandle::from(node.clone()), options);
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+// 测试用：序列化后的文档中 <body> 与 </body> 之间的部分
+#[cfg(test)]
+pub fn body(html: &str) -> &str {
+    let start = html.find("<body>").map_or(0, |start| start + "<body>".len());
+    &html[start..html.rfind("</body>").unwrap_or(html.len())]
+}
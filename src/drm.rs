@@ -0,0 +1,110 @@
+use std::path::Path;
+
+use crate::{archive::Archive, encoding, epub::parse_xml, error::EpubToMdError, options::Options};
+
+// 字体混淆算法只用于保护嵌入字体，不影响正文
+const FONT_OBFUSCATION_ALGORITHMS: &[&str] = &["http://www.idpf.org/2008/embedding", "http://ns.adobe.com/pdf/enc#RC"];
+// 报错信息中最多列出的加密文件数
+const MAX_LISTED_FILES: usize = 10;
+
+// 在转换前检查 EPUB 是否受 DRM 保护，避免 pandoc 给出难以理解的错误
+pub fn check_drm(epub_path: &Path, options: &Options) -> Result<(), EpubToMdError> {
+    let mut archive = Archive::open(epub_path, options, &mut Vec::new())?;
+
+    let scheme = if archive.contains("META-INF/rights.xml") {
+        Some("Adobe ADEPT")
+    } else if archive.contains("META-INF/sinf.xml") {
+        Some("Apple FairPlay")
+    } else {
+        None
+    };
+
+    let encrypted = match archive.read("META-INF/encryption.xml") {
+        Ok(bytes) => encrypted_files(&encoding::decode(&bytes))?,
+        Err(_) => Vec::new(),
+    };
+
+    if scheme.is_none() && encrypted.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = tr!("The EPUB is protected by {}.", scheme.unwrap_or("DRM"));
+    if !encrypted.is_empty() {
+        let listed: Vec<&str> = encrypted.iter().take(MAX_LISTED_FILES).map(String::as_str).collect();
+        let mut files = listed.join(", ");
+        if encrypted.len() > MAX_LISTED_FILES {
+            files = tr!("{} and {} more", files, encrypted.len() - MAX_LISTED_FILES);
+        }
+        message.push(' ');
+        message.push_str(&tr!("Encrypted files: {}.", files));
+    }
+    Err(EpubToMdError::DrmProtected(message))
+}
+
+// 列出 encryption.xml 中被加密（而非仅做字体混淆）的文件
+fn encrypted_files(encryption_xml: &str) -> Result<Vec<String>, EpubToMdError> {
+    let doc = parse_xml(encryption_xml, "META-INF/encryption.xml")?;
+    let mut files = Vec::new();
+    for data in doc.descendants().filter(|n| n.has_tag_name("EncryptedData")) {
+        let algorithm = data
+            .descendants()
+            .find(|n| n.has_tag_name("EncryptionMethod"))
+            .and_then(|n| n.attribute("Algorithm"))
+            .unwrap_or_default();
+        if FONT_OBFUSCATION_ALGORITHMS.contains(&algorithm) {
+            continue;
+        }
+        if let Some(uri) = data
+            .descendants()
+            .find(|n| n.has_tag_name("CipherReference"))
+            .and_then(|n| n.attribute("URI"))
+        {
+            files.push(uri.to_string());
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::zip_bytes;
+
+    fn encryption(algorithm: &str, uri: &str) -> String {
+        format!(
+            concat!(
+                r#"<encryption xmlns="urn:oasis:names:tc:opendocument:xmlns:container" "#,
+                r#"xmlns:enc="http://www.w3.org/2001/04/xmlenc#"><enc:EncryptedData>"#,
+                r#"<enc:EncryptionMethod Algorithm="{}"/><enc:CipherData><enc:CipherReference URI="{}"/>"#,
+                r#"</enc:CipherData></enc:EncryptedData></encryption>"#,
+            ),
+            algorithm, uri
+        )
+    }
+
+    fn check(entries: &[(&str, &[u8])]) -> Result<(), EpubToMdError> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), zip_bytes(entries)).unwrap();
+        check_drm(file.path(), &Options::default())
+    }
+
+    #[test]
+    fn obfuscated_fonts_are_not_drm() {
+        let xml = encryption("http://www.idpf.org/2008/embedding", "OEBPS/font.otf");
+        assert!(check(&[("META-INF/encryption.xml", xml.as_bytes())]).is_ok());
+        assert!(check(&[("mimetype", b"application/epub+zip")]).is_ok());
+    }
+
+    #[test]
+    fn encrypted_files_and_schemes_are_reported() {
+        let xml = encryption("http://www.w3.org/2001/04/xmlenc#aes128-cbc", "OEBPS/ch1.xhtml");
+        let entries: [(&str, &[u8]); 2] = [("META-INF/encryption.xml", xml.as_bytes()), ("META-INF/rights.xml", b"")];
+        match check(&entries) {
+            Err(EpubToMdError::DrmProtected(message)) => {
+                assert!(message.contains("Adobe ADEPT"), "{}", message);
+                assert!(message.contains("OEBPS/ch1.xhtml"), "{}", message);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
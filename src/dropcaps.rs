@@ -0,0 +1,156 @@
+use std::{collections::HashMap, rc::Rc};
+
+use html2md::{images::ImgHandler, Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+use markup5ever_rcdom::NodeData;
+
+use crate::dom;
+
+// 首字下沉常用的 class 名，比较时忽略大小写与 - _
+const DROP_CAP_CLASSES: &[&str] = &["dropcap", "initial", "firstletter", "bigletter", "lettrine", "dropcapital"];
+// 本身就是一个单词的字母，后面的空格要保留
+const ONE_LETTER_WORDS: &[&str] = &["A", "I"];
+
+fn has_drop_cap_class(tag: &Handle) -> bool {
+    dom::attr(tag, "class").is_some_and(|class| {
+        class.split_whitespace().any(|token| {
+            let token = token.to_ascii_lowercase().replace(['-', '_'], "");
+            DROP_CAP_CLASSES.iter().any(|name| token.contains(name))
+        })
+    })
+}
+
+// 首字下沉的字母：带相应 class、只含一两个字符（可带引号）的 <span> 等，或者替代文字为单个字母、
+// 位于段落开头且后面紧跟文字的图片
+fn letter(tag: &Handle) -> Option<String> {
+    let image = matches!(dom::tag_name(tag).as_deref(), Some("img" | "image"));
+    let text = match image {
+        true => dom::attr(tag, "alt").unwrap_or_default().trim().to_string(),
+        false => dom::text_content(tag),
+    };
+    let letters = text.chars().filter(|c| c.is_alphanumeric()).count();
+    if letters != 1 || text.chars().count() > 3 {
+        return None;
+    }
+    if has_drop_cap_class(tag) {
+        return Some(text);
+    }
+    let starts_block = dom::parent(tag).is_some_and(|parent| {
+        parent
+            .children
+            .borrow()
+            .iter()
+            .find(|child| !matches!(&child.data, NodeData::Text { contents } if contents.borrow().trim().is_empty()))
+            .is_some_and(|first| Rc::ptr_eq(first, tag))
+    });
+    let followed_by_word = next_text(tag).is_some_and(|next| next.trim_start().starts_with(char::is_alphabetic));
+    (image && starts_block && followed_by_word).then_some(text)
+}
+
+// 后面第一个非空白的文字节点的内容
+fn next_text(tag: &Handle) -> Option<String> {
+    following_text_nodes(tag).into_iter().find_map(|node| match &node.data {
+        NodeData::Text { contents } if !contents.borrow().trim().is_empty() => Some(contents.borrow().to_string()),
+        _ => None,
+    })
+}
+
+// 同一父元素中位于该节点之后的文字节点，包括后面元素中的
+fn following_text_nodes(tag: &Handle) -> Vec<Handle> {
+    let Some(parent) = dom::parent(tag) else {
+        return Vec::new();
+    };
+    let siblings = parent.children.borrow();
+    siblings
+        .iter()
+        .skip_while(|sibling| !Rc::ptr_eq(sibling, tag))
+        .skip(1)
+        .flat_map(|sibling| std::iter::once(sibling.clone()).chain(dom::descendants(sibling)))
+        .filter(|node| matches!(node.data, NodeData::Text { .. }))
+        .collect()
+}
+
+// 去掉首字与后面文字之间因排版产生的空白，使 "T he" 还原为 "The"；A、I 等单字母单词后的空格保留
+fn join_following(tag: &Handle, letter: &str) {
+    let Some(next) = following_text_nodes(tag)
+        .into_iter()
+        .find(|node| matches!(&node.data, NodeData::Text { contents } if !contents.borrow().trim().is_empty()))
+    else {
+        return;
+    };
+    let NodeData::Text { ref contents } = next.data else {
+        return;
+    };
+    let text = contents.borrow().to_string();
+    let word_letter = letter.trim_matches(|c: char| !c.is_alphanumeric());
+    let separate_word = ONE_LETTER_WORDS.contains(&word_letter) && text.starts_with(char::is_whitespace);
+    if !separate_word && text.trim_start().starts_with(char::is_lowercase) {
+        for node in following_text_nodes(tag) {
+            if Rc::ptr_eq(&node, &next) {
+                break;
+            }
+            // 首字与正文之间只有空白的文字节点
+            if let NodeData::Text { ref contents } = node.data {
+                contents.borrow_mut().clear();
+            }
+        }
+        *contents.borrow_mut() = text.trim_start().into();
+    }
+}
+
+// 首字下沉的 <span> 与后面的文字连起来
+struct DropCapHandler;
+
+impl TagHandler for DropCapHandler {
+    fn handle(&mut self, tag: &Handle, _printer: &mut StructuredPrinter) {
+        if let Some(letter) = letter(tag) {
+            join_following(tag, &letter);
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+}
+
+struct DropCapHandlerFactory;
+
+impl TagHandlerFactory for DropCapHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(DropCapHandler)
+    }
+}
+
+// 首字下沉的图片输出为替代文字中的字母，其他图片交给 html2md
+#[derive(Default)]
+struct DropCapImageHandler {
+    fallback: ImgHandler,
+}
+
+impl TagHandler for DropCapImageHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        match replace_image(tag) {
+            Some(letter) => printer.append_str(&letter),
+            None => self.fallback.handle(tag, printer),
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+}
+
+// 图片是首字下沉时返回字母，并把它与后面的文字连起来
+pub fn replace_image(tag: &Handle) -> Option<String> {
+    let letter = letter(tag)?;
+    join_following(tag, &letter);
+    Some(letter)
+}
+
+struct DropCapImageHandlerFactory;
+
+impl TagHandlerFactory for DropCapImageHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(DropCapImageHandler::default())
+    }
+}
+
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>) {
+    handlers.insert("span".to_string(), Box::new(DropCapHandlerFactory));
+    handlers.insert("img".to_string(), Box::new(DropCapImageHandlerFactory));
+}
@@ -0,0 +1,49 @@
+use encoding_rs::{Encoding, UTF_8};
+
+// 只在文档开头查找编码声明
+const SNIFF_LEN: usize = 1024;
+
+// 按 BOM、XML 声明、<meta charset> 的顺序判断编码并转为 UTF-8
+pub fn decode(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        return encoding.decode_without_bom_handling(&bytes[bom_len..]).0.into_owned();
+    }
+
+    let encoding = sniff_declared_encoding(bytes).unwrap_or(UTF_8);
+    let (text, _, had_errors) = encoding.decode(bytes);
+    if had_errors && encoding == UTF_8 {
+        // 未声明编码又不是合法 UTF-8 时，中文书籍最常见的是 GB18030/GBK
+        let (fallback, _, fallback_errors) = encoding_rs::GB18030.decode(bytes);
+        if !fallback_errors {
+            return fallback.into_owned();
+        }
+    }
+    text.into_owned()
+}
+
+fn sniff_declared_encoding(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(SNIFF_LEN)]).to_ascii_lowercase();
+
+    let label = if head.starts_with("<?xml") {
+        let declaration = &head[..head.find("?>")?];
+        attribute_value(declaration, "encoding")
+    } else {
+        None
+    };
+    let label = label.or_else(|| {
+        let start = head.find("charset=")? + "charset=".len();
+        let value = head[start..].trim_start_matches(['"', '\'']);
+        let end = value.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))?;
+        Some(&value[..end])
+    })?;
+
+    Encoding::for_label(label.trim().as_bytes())
+}
+
+fn attribute_value<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let start = text.find(name)? + name.len();
+    let rest = text[start..].trim_start().strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &rest[1..];
+    Some(&value[..value.find(quote)?])
+}
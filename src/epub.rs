@@ -0,0 +1,494 @@
+use std::path::Path;
+
+use percent_encoding::percent_decode_str;
+use roxmltree::{Document, Node, ParsingOptions};
+
+use crate::{
+    archive::Archive,
+    encoding,
+    error::{warn_or_fail, EpubToMdError},
+    options::Options,
+    toc::{self, TocEntry},
+};
+
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+const APPLE_DISPLAY_OPTIONS_PATH: &str = "META-INF/com.apple.ibooks.display-options.xml";
+
+#[derive(Debug, Clone)]
+pub struct ManifestItem {
+    pub id: String,
+    // OPF 中原样的 href
+    pub href: String,
+    // 相对于压缩包根目录的完整路径
+    pub path: String,
+    pub media_type: String,
+    pub properties: Option<String>,
+}
+
+impl ManifestItem {
+    pub fn is_document(&self) -> bool {
+        self.media_type == "application/xhtml+xml" || self.media_type == "text/html"
+    }
+
+    pub fn has_property(&self, property: &str) -> bool {
+        self.properties
+            .as_deref()
+            .is_some_and(|properties| properties.split_whitespace().any(|p| p == property))
+    }
+}
+
+// OPF 中的 Dublin Core 元数据
+#[derive(Debug, Clone, Default)]
+pub struct Metadata {
+    pub title: Option<String>,
+    // dc:creator，按出现顺序
+    pub authors: Vec<String>,
+    // dc:subject
+    pub subjects: Vec<String>,
+    // dc:date，保留原始写法
+    pub date: Option<String>,
+    pub language: Option<String>,
+    // dc:identifier，例如 ISBN、UUID
+    pub identifiers: Vec<String>,
+    pub publisher: Option<String>,
+    pub description: Option<String>,
+    // 封面图片在压缩包中的路径
+    pub cover: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SpineItem {
+    pub idref: String,
+    // rendition:layout-pre-paginated / rendition:layout-reflowable 覆盖整书的版式设置
+    pub layout: Option<bool>,
+}
+
+pub struct Epub {
+    archive: Archive,
+    pub opf_path: String,
+    pub metadata: Metadata,
+    pub manifest: Vec<ManifestItem>,
+    pub spine: Vec<SpineItem>,
+    // spine 的 toc 属性，指向 EPUB 2 的 NCX 文件
+    pub spine_toc: Option<String>,
+    // EPUB 2 OPF 中 <guide> 的条目：(文档路径, type)
+    pub guide: Vec<(String, String)>,
+    // 整书是否为固定版式（rendition:layout=pre-paginated）
+    pub fixed_layout: bool,
+    // spine 的 page-progression-direction 为 rtl，即从右向左翻页
+    pub rtl: bool,
+    // --recover 模式下无法恢复的内容
+    pub lost: Vec<String>,
+    // --strict 模式下结构问题直接报错
+    strict: bool,
+}
+
+impl Epub {
+    // 打开 EPUB 压缩包并解析 container.xml 与 OPF
+    pub fn open(path: &Path, options: &Options) -> Result<Self, EpubToMdError> {
+        let mut lost = Vec::new();
+        let archive = Archive::open(path, options, &mut lost)?;
+
+        let mut epub = Epub {
+            archive,
+            opf_path: String::new(),
+            metadata: Metadata::default(),
+            manifest: Vec::new(),
+            spine: Vec::new(),
+            spine_toc: None,
+            guide: Vec::new(),
+            fixed_layout: false,
+            rtl: false,
+            lost,
+            strict: options.strict,
+        };
+
+        epub.opf_path = match epub.read_text(CONTAINER_PATH).and_then(|container| parse_container(&container)) {
+            Ok(rootfiles) => select_rootfile(rootfiles, options.rootfile.as_deref())?,
+            // container.xml 损坏时尝试直接寻找 OPF 文件
+            Err(e) if options.recover => {
+                let opf_path = epub.archive.names().into_iter().find(|name| name.ends_with(".opf")).ok_or(e)?;
+                epub.lost.push(format!("{}: unreadable, using {}", CONTAINER_PATH, opf_path));
+                opf_path
+            }
+            Err(e) => return Err(e),
+        };
+
+        let opf = epub.read_text(&epub.opf_path.clone())?;
+        epub.parse_opf(&opf)?;
+
+        // 旧版 iBooks 固定版式书籍使用单独的配置文件
+        if let Ok(display_options) = epub.read_text(APPLE_DISPLAY_OPTIONS_PATH) {
+            epub.fixed_layout |= display_options.contains("name=\"fixed-layout\">true<");
+        }
+        Ok(epub)
+    }
+
+    pub fn read_bytes(&mut self, name: &str) -> Result<Vec<u8>, EpubToMdError> {
+        self.archive.read(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.archive.contains(name)
+    }
+
+    pub fn read_text(&mut self, name: &str) -> Result<String, EpubToMdError> {
+        let bytes = self.read_bytes(name)?;
+        Ok(encoding::decode(&bytes))
+    }
+
+    pub fn manifest_item(&self, id: &str) -> Option<&ManifestItem> {
+        self.manifest.iter().find(|item| item.id == id)
+    }
+
+    // 判断某个内容文档是否按固定版式排版
+    pub fn is_fixed_layout(&self, item: &ManifestItem) -> bool {
+        self.spine
+            .iter()
+            .find(|itemref| itemref.idref == item.id)
+            .and_then(|itemref| itemref.layout)
+            .unwrap_or(self.fixed_layout)
+    }
+
+    pub fn has_fixed_layout(&self) -> bool {
+        self.spine_documents().iter().any(|item| self.is_fixed_layout(item))
+    }
+
+    // 按 spine 顺序返回所有内容文档
+    pub fn spine_documents(&self) -> Vec<ManifestItem> {
+        self.spine
+            .iter()
+            .filter_map(|itemref| self.manifest_item(&itemref.idref))
+            .filter(|item| item.is_document())
+            .cloned()
+            .collect()
+    }
+
+    // 读取目录：优先使用 EPUB 3 的 nav.xhtml，失败时退回 EPUB 2 的 toc.ncx
+    pub fn toc(&mut self) -> Result<Vec<TocEntry>, EpubToMdError> {
+        let nav = self.manifest.iter().find(|item| item.has_property("nav")).cloned();
+        if let Some(nav) = nav {
+            let text = self.read_text(&nav.path)?;
+            match toc::parse_nav(&text, &nav.path) {
+                Ok(entries) if !entries.is_empty() => return Ok(entries),
+                Ok(_) => {}
+                Err(e) => log::warn!("{}", e),
+            }
+        }
+
+        let ncx = self
+            .spine_toc
+            .as_deref()
+            .and_then(|id| self.manifest_item(id))
+            .or_else(|| self.manifest.iter().find(|item| item.media_type == "application/x-dtbncx+xml"))
+            .cloned();
+        match ncx {
+            Some(ncx) => {
+                let text = self.read_text(&ncx.path)?;
+                toc::parse_ncx(&text, &ncx.path)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    // 读取页码列表：EPUB 3 导航文档中的 page-list 或 NCX 中的 pageList，没有时为空；
+    // 导航文档的错误在读取目录时已经警告过
+    pub fn page_list(&mut self) -> Vec<TocEntry> {
+        let nav = self.manifest.iter().find(|item| item.has_property("nav")).cloned();
+        if let Some(nav) = nav {
+            let pages = self.read_text(&nav.path).and_then(|text| toc::parse_page_list(&text, &nav.path));
+            if let Some(pages) = pages.ok().filter(|pages| !pages.is_empty()) {
+                return pages;
+            }
+        }
+        let ncx = self
+            .spine_toc
+            .as_deref()
+            .and_then(|id| self.manifest_item(id))
+            .or_else(|| self.manifest.iter().find(|item| item.media_type == "application/x-dtbncx+xml"))
+            .cloned();
+        ncx.and_then(|ncx| self.read_text(&ncx.path).and_then(|text| toc::parse_ncx_page_list(&text, &ncx.path)).ok())
+            .unwrap_or_default()
+    }
+
+    // 书中的地标：EPUB 3 导航文档中 landmarks 的条目与 EPUB 2 的 <guide>，(文档路径, 类型)
+    pub fn landmarks(&mut self) -> Vec<(String, String)> {
+        let mut landmarks = self.nav_landmarks();
+        landmarks.extend(self.guide.iter().cloned());
+        landmarks
+    }
+
+    // 只有导航文档中的 landmarks，没有导航文档或无法解析时为空
+    pub fn nav_landmarks(&mut self) -> Vec<(String, String)> {
+        let nav = self.manifest.iter().find(|item| item.has_property("nav")).cloned();
+        nav.and_then(|nav| self.read_text(&nav.path).and_then(|text| toc::parse_landmarks(&text, &nav.path)).ok())
+            .unwrap_or_default()
+    }
+
+    // 清单中所有 CSS 样式表的内容，读取失败的跳过
+    pub fn stylesheets(&mut self) -> Vec<String> {
+        let paths: Vec<String> =
+            self.manifest.iter().filter(|item| item.media_type == "text/css").map(|item| item.path.clone()).collect();
+        paths.iter().filter_map(|path| self.read_text(path).ok()).collect()
+    }
+
+    fn parse_opf(&mut self, opf: &str) -> Result<(), EpubToMdError> {
+        let doc = parse_xml(opf, &self.opf_path)?;
+        let base_dir = parent_dir(&self.opf_path);
+
+        for node in doc.descendants().filter(|n| n.has_tag_name("item") && is_child_of(n, "manifest")) {
+            let (Some(id), Some(href)) = (node.attribute("id"), node.attribute("href")) else {
+                warn_or_fail(
+                    self.strict,
+                    EpubToMdError::EpubError(tr!("Manifest item without id or href in '{}'.", self.opf_path)),
+                )?;
+                continue;
+            };
+            self.manifest.push(ManifestItem {
+                id: id.to_string(),
+                href: href.to_string(),
+                path: resolve_href(base_dir, href),
+                media_type: node.attribute("media-type").unwrap_or_default().to_string(),
+                properties: node.attribute("properties").map(str::to_string),
+            });
+        }
+
+        let metadata_texts = |name: &str| -> Vec<String> {
+            doc.descendants()
+                .filter(|n| n.has_tag_name(name) && is_child_of(n, "metadata"))
+                .filter_map(|n| n.text())
+                .map(|text| text.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|text| !text.is_empty())
+                .collect()
+        };
+        self.metadata = Metadata {
+            title: metadata_texts("title").into_iter().next(),
+            authors: metadata_texts("creator"),
+            subjects: metadata_texts("subject"),
+            date: metadata_texts("date").into_iter().next(),
+            language: metadata_texts("language").into_iter().next(),
+            identifiers: metadata_texts("identifier"),
+            publisher: metadata_texts("publisher").into_iter().next(),
+            description: metadata_texts("description").into_iter().next(),
+            cover: None,
+        };
+        // EPUB 3 用 cover-image 属性标记封面，EPUB 2 用 <meta name="cover" content="id">
+        let cover_id = doc
+            .descendants()
+            .find(|n| n.has_tag_name("meta") && n.attribute("name") == Some("cover"))
+            .and_then(|n| n.attribute("content"));
+        self.metadata.cover = self
+            .manifest
+            .iter()
+            .find(|item| item.has_property("cover-image"))
+            .or_else(|| cover_id.and_then(|id| self.manifest_item(id)))
+            .map(|item| item.path.clone());
+
+        self.fixed_layout = doc.descendants().any(|n| {
+            n.has_tag_name("meta")
+                && n.attribute("property") == Some("rendition:layout")
+                && n.text().map(str::trim) == Some("pre-paginated")
+        });
+
+        let spine = doc.descendants().find(|n| n.has_tag_name("spine"));
+        self.spine_toc = spine.and_then(|n| n.attribute("toc")).map(str::to_string);
+        self.rtl = spine.and_then(|n| n.attribute("page-progression-direction")) == Some("rtl");
+
+        self.guide = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("reference") && is_child_of(n, "guide"))
+            .filter_map(|n| {
+                let href = n.attribute("href")?.split('#').next()?;
+                Some((resolve_href(base_dir, href), n.attribute("type")?.to_ascii_lowercase()))
+            })
+            .collect();
+
+        for node in doc.descendants().filter(|n| n.has_tag_name("itemref")) {
+            if let Some(idref) = node.attribute("idref") {
+                let properties = node.attribute("properties").unwrap_or_default();
+                let layout = properties.split_whitespace().find_map(|property| match property {
+                    "rendition:layout-pre-paginated" => Some(true),
+                    "rendition:layout-reflowable" => Some(false),
+                    _ => None,
+                });
+                self.spine.push(SpineItem {
+                    idref: idref.to_string(),
+                    layout,
+                });
+            }
+        }
+
+        if self.spine.is_empty() {
+            return Err(EpubToMdError::EpubError(tr!("OPF package has an empty spine.")));
+        }
+        for itemref in &self.spine {
+            if self.manifest_item(&itemref.idref).is_none() {
+                warn_or_fail(
+                    self.strict,
+                    EpubToMdError::EpubError(tr!("Spine references missing manifest item '{}'.", itemref.idref)),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// 从 container.xml 中找到所有 OPF 文件路径
+fn parse_container(container: &str) -> Result<Vec<String>, EpubToMdError> {
+    let doc = parse_xml(container, CONTAINER_PATH)?;
+    let rootfiles: Vec<String> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name("rootfile"))
+        .filter(|n| n.attribute("media-type").is_none_or(|t| t == "application/oebps-package+xml"))
+        .filter_map(|n| n.attribute("full-path"))
+        .map(|p| p.to_string())
+        .collect();
+    if rootfiles.is_empty() {
+        return Err(EpubToMdError::EpubError(tr!("container.xml does not declare a rootfile.")));
+    }
+    Ok(rootfiles)
+}
+
+// 按 --rootfile 选择 OPF：可以是从 1 开始的序号，也可以是完整路径；未指定时使用第一个
+fn select_rootfile(rootfiles: Vec<String>, selector: Option<&str>) -> Result<String, EpubToMdError> {
+    let Some(selector) = selector else {
+        if rootfiles.len() > 1 {
+            log::warn!("{}", tr!("container.xml declares {} rootfiles, using the first one:", rootfiles.len()));
+            for (i, rootfile) in rootfiles.iter().enumerate() {
+                log::info!("  {}: {}", i + 1, rootfile);
+            }
+            log::info!("{}", tr!("Use --rootfile <n> to choose another rendition."));
+        }
+        return Ok(rootfiles.into_iter().next().unwrap_or_default());
+    };
+
+    let index = selector.parse::<usize>().ok().and_then(|n| n.checked_sub(1));
+    let found = match index {
+        Some(i) => rootfiles.get(i).cloned(),
+        None => rootfiles.iter().find(|rootfile| rootfile.as_str() == selector).cloned(),
+    };
+    found.ok_or_else(|| {
+        EpubToMdError::InputError(tr!(
+            "Rootfile '{}' not found, available rootfiles: {}",
+            selector,
+            rootfiles.join(", ")
+        ))
+    })
+}
+
+pub fn parse_xml<'a>(text: &'a str, name: &str) -> Result<Document<'a>, EpubToMdError> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..ParsingOptions::default()
+    };
+    Document::parse_with_options(text, options)
+        .map_err(|e| EpubToMdError::EpubError(tr!("Failed to parse '{}': {}", name, e)))
+}
+
+fn is_child_of(node: &Node, parent: &str) -> bool {
+    node.parent_element().is_some_and(|p| p.has_tag_name(parent))
+}
+
+pub fn parent_dir(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(i) => &path[..=i],
+        None => "",
+    }
+}
+
+// 将相对 href 解析为压缩包内的规范路径
+pub fn resolve_href(base_dir: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default();
+    let href = percent_decode_str(href).decode_utf8_lossy();
+    let joined = if href.starts_with('/') {
+        href.trim_start_matches('/').to_string()
+    } else {
+        format!("{}{}", base_dir, href)
+    };
+
+    let mut parts: Vec<&str> = Vec::new();
+    for part in joined.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::zip_bytes;
+
+    const CONTAINER: &str = r#"<container xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles>
+        <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+    </rootfiles></container>"#;
+
+    const OPF: &str = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+        <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <dc:title>  A   Title </dc:title><dc:creator>One</dc:creator><dc:creator>Two</dc:creator>
+            <dc:language>en</dc:language><meta name="cover" content="cover"/>
+        </metadata>
+        <manifest>
+            <item id="cover" href="images/cover%20art.jpg" media-type="image/jpeg"/>
+            <item id="b" href="text/b.xhtml" media-type="application/xhtml+xml"/>
+            <item id="a" href="text/a.xhtml" media-type="application/xhtml+xml"/>
+        </manifest>
+        <spine><itemref idref="a"/><itemref idref="cover"/><itemref idref="b"/></spine>
+    </package>"#;
+
+    fn open(opf: &str, options: &Options) -> Result<Epub, EpubToMdError> {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let entries = [(CONTAINER_PATH, CONTAINER.as_bytes()), ("OEBPS/content.opf", opf.as_bytes())];
+        std::fs::write(file.path(), zip_bytes(&entries)).unwrap();
+        Epub::open(file.path(), options)
+    }
+
+    #[test]
+    fn reads_metadata_and_spine_from_the_opf() {
+        let epub = open(OPF, &Options::default()).unwrap();
+        assert_eq!(epub.metadata.title.as_deref(), Some("A Title"));
+        assert_eq!(epub.metadata.authors, ["One", "Two"]);
+        assert_eq!(epub.metadata.cover.as_deref(), Some("OEBPS/images/cover art.jpg"));
+        // spine 中的图片不是内容文档
+        let documents: Vec<String> = epub.spine_documents().into_iter().map(|item| item.path).collect();
+        assert_eq!(documents, ["OEBPS/text/a.xhtml", "OEBPS/text/b.xhtml"]);
+    }
+
+    #[test]
+    fn hrefs_resolve_inside_the_archive() {
+        assert_eq!(resolve_href("OEBPS/text/", "../images/a%20b.png#x"), "OEBPS/images/a b.png");
+        assert_eq!(resolve_href("OEBPS/", "./ch1.xhtml"), "OEBPS/ch1.xhtml");
+        assert_eq!(resolve_href("OEBPS/", "/META-INF/x.xml"), "META-INF/x.xml");
+        assert_eq!(resolve_href("", "../../etc/passwd"), "etc/passwd");
+        assert_eq!(parent_dir("OEBPS/content.opf"), "OEBPS/");
+        assert_eq!(parent_dir("content.opf"), "");
+    }
+
+    #[test]
+    fn rootfiles_are_chosen_by_number_or_path() {
+        let rootfiles = || vec!["a.opf".to_string(), "b/b.opf".to_string()];
+        assert_eq!(select_rootfile(rootfiles(), None).unwrap(), "a.opf");
+        assert_eq!(select_rootfile(rootfiles(), Some("2")).unwrap(), "b/b.opf");
+        assert_eq!(select_rootfile(rootfiles(), Some("b/b.opf")).unwrap(), "b/b.opf");
+        assert!(matches!(select_rootfile(rootfiles(), Some("3")), Err(EpubToMdError::InputError(_))));
+        let container = r#"<container><rootfiles><rootfile full-path="x.opf" media-type="text/plain"/>
+            <rootfile full-path="a.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+        assert_eq!(parse_container(container).unwrap(), ["a.opf"]);
+    }
+
+    #[test]
+    fn strict_mode_rejects_missing_spine_items() {
+        let opf = OPF.replace(r#"<itemref idref="b"/>"#, r#"<itemref idref="missing"/>"#);
+        assert!(open(&opf, &Options::default()).is_ok());
+        let strict = Options {
+            strict: true,
+            ..Options::default()
+        };
+        assert!(matches!(open(&opf, &strict), Err(EpubToMdError::EpubError(_))));
+    }
+}
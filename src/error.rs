@@ -0,0 +1,71 @@
+use std::error::Error;
+
+use crate::i18n;
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub enum EpubToMdError {
+    InputError(String),
+    PandocError(String),
+    FileIOError(String),
+    UsageError,
+    PandocCheckError(String),
+    EpubError(String),
+    ArgumentError(String),
+    DrmProtected(String),
+}
+
+impl std::fmt::Display for EpubToMdError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EpubToMdError::InputError(msg) => write!(f, "{}", tr!("Input Error: {}", msg)),
+            EpubToMdError::PandocError(msg) => write!(f, "{}", tr!("Pandoc Error: {}", msg)),
+            EpubToMdError::FileIOError(msg) => write!(f, "{}", tr!("File IO Error: {}", msg)),
+            EpubToMdError::UsageError => write!(f, "{}", i18n::usage()),
+            EpubToMdError::PandocCheckError(msg) => write!(f, "{}", tr!("Pandoc Check Error: {}", msg)),
+            EpubToMdError::EpubError(msg) => write!(f, "{}", tr!("EPUB Error: {}", msg)),
+            EpubToMdError::ArgumentError(msg) => write!(f, "{}", tr!("Argument Error: {}", msg)),
+            EpubToMdError::DrmProtected(msg) => write!(f, "{}", tr!("DRM Protected: {}", msg)),
+        }
+    }
+}
+
+impl Error for EpubToMdError {}
+
+impl EpubToMdError {
+    // 错误的类别，写入 --error-report，便于脚本按类别处理
+    pub fn class(&self) -> &'static str {
+        match self {
+            EpubToMdError::InputError(_) => "input",
+            EpubToMdError::PandocError(_) => "pandoc",
+            EpubToMdError::FileIOError(_) => "io",
+            EpubToMdError::UsageError => "usage",
+            EpubToMdError::PandocCheckError(_) => "pandoc_missing",
+            EpubToMdError::EpubError(_) => "epub",
+            EpubToMdError::ArgumentError(_) => "argument",
+            EpubToMdError::DrmProtected(_) => "drm",
+        }
+    }
+
+    // 进程的退出状态，按类别区分，便于脚本判断失败的原因；README 中列出各个取值
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            EpubToMdError::UsageError | EpubToMdError::ArgumentError(_) => 2,
+            EpubToMdError::PandocCheckError(_) => 3,
+            EpubToMdError::InputError(_) => 4,
+            EpubToMdError::FileIOError(_) => 5,
+            EpubToMdError::EpubError(_) => 6,
+            EpubToMdError::DrmProtected(_) => 7,
+            EpubToMdError::PandocError(_) => 8,
+        }
+    }
+}
+
+// 宽松模式下只打印警告并继续，--strict 模式下直接返回错误
+pub fn warn_or_fail(strict: bool, error: EpubToMdError) -> Result<(), EpubToMdError> {
+    if strict {
+        return Err(error);
+    }
+    log::warn!("{}", error);
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use crate::{dom, markdown::html_to_markdown, options::Options};
+
+// 固定版式页面通常只有一张整页图片，输出为带页码标题的图片
+pub fn gallery_page(html: &str, page_number: usize, options: &Options) -> String {
+    let dom = dom::parse(html);
+    let images: Vec<String> = dom::descendants(&dom.document)
+        .iter()
+        .filter_map(|node| match dom::tag_name(node).as_deref() {
+            Some("img") => dom::attr(node, "src"),
+            Some("image") => dom::attr(node, "href"),
+            _ => None,
+        })
+        .collect();
+
+    let label = format!("Page {}", page_number);
+    // 没有图片的页面（例如纯文字的版权页）按普通内容转换
+    if images.is_empty() {
+        return format!("## {}\n\n{}", label, html_to_markdown(html, options));
+    }
+
+    let mut page = format!("## {}", label);
+    for src in images {
+        page.push_str(&format!("\n\n![{}]({})", label, src));
+    }
+    page
+}
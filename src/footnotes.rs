@@ -0,0 +1,347 @@
+use std::{collections::HashMap, rc::Rc};
+
+use markup5ever_rcdom::{Handle, RcDom};
+
+use crate::{
+    dom,
+    epub::{parent_dir, resolve_href},
+    options::Citations,
+};
+
+// 改写后交给 html2md 的自定义元素，见 markdown.rs 中的对应处理器
+pub const NOTEREF_TAG: &str = "epub2md-noteref";
+pub const FOOTNOTE_TAG: &str = "epub2md-footnote";
+pub const LABEL_ATTR: &str = "data-label";
+
+// epub:type 与 ARIA role 中表示脚注、尾注正文的取值
+const NOTE_TYPES: &[&str] = &["footnote", "endnote", "rearnote", "note", "doc-footnote", "doc-endnote"];
+// 参考文献一节、其中的条目以及引用条目的链接
+const BIBLIOGRAPHY_TYPES: &[&str] = &["bibliography", "doc-bibliography"];
+const BIBLIOENTRY_TYPES: &[&str] = &["biblioentry", "doc-biblioentry"];
+const BIBLIOREF_TYPES: &[&str] = &["biblioref", "doc-biblioref"];
+// 脚注正文可能所在的块级元素；目标 id 落在行内元素上时向上找到最近的块
+const BLOCK_TAGS: &[&str] = &["p", "div", "li", "aside", "section", "dd", "dt", "blockquote", "td"];
+
+struct Document {
+    path: String,
+    dom: RcDom,
+}
+
+struct Noteref {
+    document: usize,
+    anchor: Handle,
+    note: usize,
+    // 作者—年份式的引用保留原文字，脚注标记接在后面
+    text: Option<String>,
+}
+
+// 识别书中的脚注：epub:type="noteref" / role="doc-noteref" 的链接，指向 epub:type="footnote" 等元素的链接，
+// 以及正文中有回链指向引用处的常见锚点写法。引用改写为 [^n]，正文移到首次引用它的文档末尾；
+// 参考文献的引用按 citations 保留为链接或同样改为脚注。返回与输入一一对应的 HTML，没有脚注的文档原样返回
+pub fn rewrite(sources: &[(&str, &str)], citations: Citations) -> Vec<String> {
+    let documents: Vec<Document> = sources
+        .iter()
+        .map(|(path, html)| Document {
+            path: path.to_string(),
+            dom: dom::parse(html),
+        })
+        .collect();
+
+    let mut ids: HashMap<(&str, String), (usize, Handle)> = HashMap::new();
+    for (i, document) in documents.iter().enumerate() {
+        for node in dom::descendants(&document.dom.document) {
+            if let Some(id) = dom::attr(&node, "id") {
+                ids.entry((document.path.as_str(), id)).or_insert((i, node));
+            }
+        }
+    }
+
+    let mut notes: Vec<Handle> = Vec::new();
+    // 内容有变动的文档才重新序列化
+    let mut changed = vec![false; documents.len()];
+    let mut noterefs: Vec<Noteref> = Vec::new();
+    for (i, document) in documents.iter().enumerate() {
+        for anchor in dom::descendants(&document.dom.document) {
+            if dom::tag_name(&anchor).as_deref() != Some("a") {
+                continue;
+            }
+            let Some(href) = dom::attr(&anchor, "href").filter(|href| !href.contains(':')) else {
+                continue;
+            };
+            let Some((path, fragment)) = href.split_once('#') else {
+                continue;
+            };
+            let path = match path {
+                "" => document.path.clone(),
+                _ => resolve_href(parent_dir(&document.path), path),
+            };
+            let Some((target_document, target)) = ids.get(&(path.as_str(), fragment.to_string())) else {
+                continue;
+            };
+            // 参考文献条目中指回引用处的链接不是脚注引用
+            if in_bibliography(&anchor) || has_type(target, BIBLIOREF_TYPES) {
+                continue;
+            }
+            let citation = has_type(&anchor, BIBLIOREF_TYPES) || in_bibliography(target);
+            if citation && citations == Citations::Link {
+                continue;
+            }
+            let Some(note) = note_element(target) else {
+                continue;
+            };
+            if contains(&note, &anchor) || !(citation || is_noteref(&anchor, target, &note)) {
+                continue;
+            }
+            changed[i] = true;
+            changed[*target_document] = true;
+            let index = match notes.iter().position(|known| Rc::ptr_eq(known, &note)) {
+                Some(index) => index,
+                None => {
+                    notes.push(note);
+                    notes.len() - 1
+                }
+            };
+            let text = dom::text_content(&anchor);
+            let numbered = text.trim_matches(['[', ']', '(', ')']).chars().all(|c| c.is_ascii_digit());
+            noterefs.push(Noteref {
+                document: i,
+                anchor,
+                note: index,
+                text: (citation && !numbered).then_some(text),
+            });
+        }
+    }
+    if notes.is_empty() {
+        return sources.iter().map(|(_, html)| html.to_string()).collect();
+    }
+
+    // 去掉正文中指回引用处的链接，再取出正文
+    let bodies: Vec<String> = notes
+        .iter()
+        .enumerate()
+        .map(|(i, note)| {
+            let referrers: Vec<String> = noterefs
+                .iter()
+                .filter(|noteref| noteref.note == i)
+                .flat_map(|noteref| {
+                    [Some(noteref.anchor.clone()), dom::parent(&noteref.anchor)]
+                        .into_iter()
+                        .flatten()
+                        .filter_map(|node| dom::attr(&node, "id"))
+                })
+                .collect();
+            for link in dom::descendants(note) {
+                if dom::tag_name(&link).as_deref() != Some("a") {
+                    continue;
+                }
+                let backlink = has_type(&link, &["backlink", "doc-backlink"])
+                    || dom::attr(&link, "href")
+                        .and_then(|href| href.split_once('#').map(|(_, fragment)| fragment.to_string()))
+                        .is_some_and(|fragment| referrers.contains(&fragment));
+                if backlink {
+                    dom::detach(&link);
+                }
+            }
+            dom::inner_html(note)
+        })
+        .collect();
+
+    // 条目都改为脚注后参考文献一节只剩标题，标题保留，目录中指向它的条目仍然有效
+    for note in &notes {
+        remove_with_empty_ancestors(note);
+    }
+
+    // 编号按全书首次引用的顺序分配，合并为单个文件时也不会重复
+    let mut labels: Vec<Option<usize>> = vec![None; notes.len()];
+    let mut next_label = 1;
+    let mut definitions: Vec<Vec<usize>> = vec![Vec::new(); documents.len()];
+    for noteref in &noterefs {
+        let label = *labels[noteref.note].get_or_insert_with(|| {
+            next_label += 1;
+            next_label - 1
+        });
+        if !definitions[noteref.document].contains(&noteref.note) {
+            definitions[noteref.document].push(noteref.note);
+        }
+        let label = label.to_string();
+        // 只包着引用链接的 <sup> 一并替换，避免留下空的上标
+        let wrapper = dom::parent(&noteref.anchor)
+            .filter(|parent| dom::tag_name(parent).as_deref() == Some("sup") && parent.children.borrow().len() == 1);
+        let target = wrapper.as_ref().unwrap_or(&noteref.anchor);
+        if let Some(text) = &noteref.text {
+            dom::insert_before(target, dom::text(text));
+        }
+        dom::replace(target, dom::element(NOTEREF_TAG, &[(LABEL_ATTR, &label)]));
+    }
+
+    documents
+        .iter()
+        .zip(definitions)
+        .enumerate()
+        .map(|(i, (document, definitions))| {
+            if !changed[i] {
+                return sources[i].1.to_string();
+            }
+            let mut html = dom::inner_html(&document.dom.document);
+            let footnotes: String = definitions
+                .iter()
+                .map(|&note| {
+                    format!(
+                        "<{tag} {attr}=\"{label}\">{body}</{tag}>",
+                        tag = FOOTNOTE_TAG,
+                        attr = LABEL_ATTR,
+                        label = labels[note].unwrap_or_default(),
+                        body = bodies[note]
+                    )
+                })
+                .collect();
+            let end = html.rfind("</body>").unwrap_or(html.len());
+            html.insert_str(end, &footnotes);
+            html
+        })
+        .collect()
+}
+
+// 目标 id 所在的脚注正文元素
+fn note_element(target: &Handle) -> Option<Handle> {
+    let mut node = target.clone();
+    loop {
+        let tag = dom::tag_name(&node)?;
+        if BLOCK_TAGS.contains(&tag.as_str()) || has_type(&node, NOTE_TYPES) {
+            return Some(node);
+        }
+        if matches!(tag.as_str(), "body" | "html") {
+            return None;
+        }
+        node = dom::parent(&node)?;
+    }
+}
+
+// 节点所在的参考文献一节
+fn bibliography(node: &Handle) -> Option<Handle> {
+    let mut node = node.clone();
+    loop {
+        if has_type(&node, BIBLIOGRAPHY_TYPES) {
+            return Some(node);
+        }
+        node = dom::parent(&node)?;
+    }
+}
+
+fn in_bibliography(node: &Handle) -> bool {
+    has_type(node, BIBLIOENTRY_TYPES) || bibliography(node).is_some()
+}
+
+// epub:type 或 role 中是否含有给定取值之一
+fn has_type(node: &Handle, types: &[&str]) -> bool {
+    ["type", "role"]
+        .iter()
+        .filter_map(|name| dom::attr(node, name))
+        .any(|value| value.split_whitespace().any(|token| types.contains(&token)))
+}
+
+fn is_noteref(anchor: &Handle, target: &Handle, note: &Handle) -> bool {
+    if has_type(anchor, &["noteref", "doc-noteref"]) || has_type(target, NOTE_TYPES) || has_type(note, NOTE_TYPES) {
+        return true;
+    }
+    // 没有语义标记时，要求正文中有链接指回引用处（链接本身或外层的 <sup> 等）；
+    // 含有标题的元素是章节而不是脚注，避免把“返回目录”式的回链误认
+    // 正文开头的编号链接是脚注指回引用处的回链，不是引用
+    let block = dom::parent(anchor).as_ref().and_then(note_element);
+    if block.is_some_and(|block| dom::text_content(&block).starts_with(&dom::text_content(anchor))) {
+        return false;
+    }
+    let has_heading = dom::descendants(note)
+        .iter()
+        .any(|node| matches!(dom::tag_name(node).as_deref(), Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6")));
+    if has_heading || matches!(dom::tag_name(note).as_deref(), Some("section")) {
+        return false;
+    }
+    let referrer_ids: Vec<String> = [Some(anchor.clone()), dom::parent(anchor)]
+        .into_iter()
+        .flatten()
+        .filter_map(|node| dom::attr(&node, "id"))
+        .collect();
+    !referrer_ids.is_empty()
+        && dom::descendants(note).iter().any(|link| {
+            dom::tag_name(link).as_deref() == Some("a")
+                && dom::attr(link, "href")
+                    .and_then(|href| href.split_once('#').map(|(_, fragment)| fragment.to_string()))
+                    .is_some_and(|fragment| referrer_ids.contains(&fragment))
+        })
+}
+
+fn contains(ancestor: &Handle, node: &Handle) -> bool {
+    dom::descendants(ancestor).iter().any(|descendant| Rc::ptr_eq(descendant, node))
+}
+
+// 移除脚注正文；只装着脚注的列表、区块等容器随之移除
+fn remove_with_empty_ancestors(note: &Handle) {
+    let mut parent = dom::parent(note);
+    dom::detach(note);
+    while let Some(node) = parent {
+        if matches!(dom::tag_name(&node).as_deref(), None | Some("body" | "html"))
+            || !dom::text_content(&node).is_empty()
+            || dom::descendants(&node)
+                .iter()
+                .any(|child| matches!(dom::tag_name(child).as_deref(), Some("img" | "image" | "svg")))
+        {
+            break;
+        }
+        parent = dom::parent(&node);
+        dom::detach(&node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAPTER: &str = r#"<html><body><p>A<sup><a epub:type="noteref" href="notes.xhtml#n2" id="r2">2</a></sup> B<a epub:type="noteref" href="notes.xhtml#n1">1</a></p></body></html>"#;
+    const SECOND: &str = r#"<html><body><p>C<a epub:type="noteref" href="notes.xhtml#n2">2</a></p></body></html>"#;
+    const NOTES: &str = r#"<html><body><aside epub:type="footnote" id="n1"><p>One</p></aside><aside epub:type="footnote" id="n2"><p><a href="a.xhtml#r2">↩</a> Two</p></aside><p>Rest</p></body></html>"#;
+
+    #[test]
+    fn notes_are_numbered_by_first_reference() {
+        let rewritten = rewrite(&[("a.xhtml", CHAPTER), ("b.xhtml", SECOND), ("notes.xhtml", NOTES)], Citations::Link);
+        assert_eq!(
+            dom::body(&rewritten[0]),
+            concat!(
+                r#"<p>A<epub2md-noteref data-label="1"></epub2md-noteref> B<epub2md-noteref data-label="2"></epub2md-noteref></p>"#,
+                r#"<epub2md-footnote data-label="1"><p> Two</p></epub2md-footnote>"#,
+                r#"<epub2md-footnote data-label="2"><p>One</p></epub2md-footnote>"#,
+            )
+        );
+        // 另一章再次引用时沿用同一编号
+        assert_eq!(
+            dom::body(&rewritten[1]),
+            r#"<p>C<epub2md-noteref data-label="1"></epub2md-noteref></p><epub2md-footnote data-label="1"><p> Two</p></epub2md-footnote>"#
+        );
+        assert_eq!(dom::body(&rewritten[2]), "<p>Rest</p>");
+    }
+
+    #[test]
+    fn documents_without_notes_are_unchanged() {
+        let html = r#"<html><body><p><a href="b.xhtml#x">see</a></p></body></html>"#;
+        assert_eq!(rewrite(&[("a.xhtml", html)], Citations::Link), [html]);
+    }
+
+    #[test]
+    fn citations_become_footnotes_on_request() {
+        let chapter = r#"<html><body><p>As shown <a epub:type="biblioref" href="bib.xhtml#smith">(Smith 2001)</a>.</p></body></html>"#;
+        let bibliography = r#"<html><body><section epub:type="bibliography"><h1>Bibliography</h1><ul><li epub:type="biblioentry" id="smith">Smith, J. 2001.</li></ul></section></body></html>"#;
+        let sources = [("a.xhtml", chapter), ("bib.xhtml", bibliography)];
+        assert_eq!(rewrite(&sources, Citations::Link), [chapter, bibliography]);
+
+        let rewritten = rewrite(&sources, Citations::Footnote);
+        assert!(
+            dom::body(&rewritten[0]).starts_with(r#"<p>As shown (Smith 2001)<epub2md-noteref data-label="1"></epub2md-noteref>.</p>"#),
+            "{}",
+            rewritten[0]
+        );
+        assert!(rewritten[0].contains("Smith, J. 2001."), "{}", rewritten[0]);
+        // 参考文献一节的标题保留
+        assert!(rewritten[1].contains("<h1>Bibliography</h1>"), "{}", rewritten[1]);
+        assert!(!rewritten[1].contains("Smith, J."), "{}", rewritten[1]);
+    }
+}
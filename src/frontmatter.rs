@@ -0,0 +1,120 @@
+use crate::options::FrontmatterFormat;
+
+// 章节文件开头的元数据块，各种输出格式共用
+#[derive(Debug, Default)]
+pub struct Frontmatter(Vec<(String, Value)>);
+
+#[derive(Debug)]
+enum Value {
+    String(String),
+    Integer(i64),
+    List(Vec<String>),
+}
+
+impl Frontmatter {
+    pub fn string(&mut self, key: &str, value: Option<&str>) -> &mut Self {
+        if let Some(value) = value {
+            self.0.push((key.to_string(), Value::String(value.to_string())));
+        }
+        self
+    }
+
+    pub fn integer(&mut self, key: &str, value: i64) -> &mut Self {
+        self.0.push((key.to_string(), Value::Integer(value)));
+        self
+    }
+
+    // 空列表不输出
+    pub fn list(&mut self, key: &str, values: &[String]) -> &mut Self {
+        if !values.is_empty() {
+            self.0.push((key.to_string(), Value::List(values.to_vec())));
+        }
+        self
+    }
+
+    // 以 --- 包围的 YAML，字符串一律加双引号以免被解析成其他类型
+    pub fn to_yaml(&self) -> String {
+        let mut yaml = String::from("---\n");
+        for (key, value) in &self.0 {
+            match value {
+                Value::String(text) => yaml.push_str(&format!("{}: {}\n", key, quote(text))),
+                Value::Integer(n) => yaml.push_str(&format!("{}: {}\n", key, n)),
+                Value::List(items) => {
+                    yaml.push_str(&format!("{}:\n", key));
+                    for item in items {
+                        yaml.push_str(&format!("  - {}\n", quote(item)));
+                    }
+                }
+            }
+        }
+        yaml.push_str("---\n");
+        yaml
+    }
+
+    // 以 +++ 包围的 TOML，键名可以是 taxonomies.tags 这样的点分键
+    pub fn to_toml(&self) -> String {
+        let mut toml = String::from("+++\n");
+        for (key, value) in &self.0 {
+            let value = match value {
+                Value::String(text) => quote(text),
+                Value::Integer(n) => n.to_string(),
+                Value::List(items) => format!("[{}]", items.iter().map(|item| quote(item)).collect::<Vec<_>>().join(", ")),
+            };
+            toml.push_str(&format!("{} = {}\n", key, value));
+        }
+        toml.push_str("+++\n");
+        toml
+    }
+
+    // 文件开头的 JSON 对象，Hugo 据此识别 JSON frontmatter
+    pub fn to_json(&self) -> String {
+        let fields: Vec<String> = self
+            .0
+            .iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(text) => quote(text),
+                    Value::Integer(n) => n.to_string(),
+                    Value::List(items) => format!("[{}]", items.iter().map(|item| quote(item)).collect::<Vec<_>>().join(", ")),
+                };
+                format!("  {}: {}", quote(key), value)
+            })
+            .collect();
+        format!("{{\n{}\n}}\n", fields.join(",\n"))
+    }
+
+    pub fn render(&self, format: FrontmatterFormat) -> String {
+        match format {
+            FrontmatterFormat::Yaml => self.to_yaml(),
+            FrontmatterFormat::Toml => self.to_toml(),
+            FrontmatterFormat::Json => self.to_json(),
+            FrontmatterFormat::None => String::new(),
+        }
+    }
+
+    // 把元数据块放在正文前，中间空一行；正文为空时只输出元数据块
+    pub fn prepend(&self, format: FrontmatterFormat, body: &str) -> String {
+        match (self.render(format), body.is_empty()) {
+            (header, _) if header.is_empty() => body.to_string(),
+            (header, true) => header,
+            (header, false) => format!("{}\n{}", header, body),
+        }
+    }
+}
+
+// YAML、TOML 与 JSON 通用的双引号字符串转义
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04X}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,202 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::EpubToMdError;
+
+// Windows 的命令行不展开通配符，由程序自己展开
+pub fn is_pattern(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+// [abc]、[a-z]、[!a] 等字符集，返回是否匹配与字符集之后的模式
+fn match_class(class: &[char], c: char) -> Option<(bool, usize)> {
+    let end = class.iter().skip(1).position(|&c| c == ']')? + 1;
+    let (negated, items) = match class[..end].split_first() {
+        Some((_, items)) if matches!(items.first(), Some('!' | '^')) => (true, &items[1..]),
+        Some((_, items)) => (false, items),
+        None => return None,
+    };
+    let mut matched = false;
+    let mut index = 0;
+    while index < items.len() {
+        if items.get(index + 1) == Some(&'-') && index + 2 < items.len() {
+            matched |= (items[index]..=items[index + 2]).contains(&c);
+            index += 3;
+        } else {
+            matched |= items[index] == c;
+            index += 1;
+        }
+    }
+    Some((matched != negated, end + 1))
+}
+
+// 单个路径组成部分的匹配：* 匹配任意字符，? 匹配一个字符；以 . 开头的隐藏文件只由 . 开头的模式匹配
+fn matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| matches(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && matches(&pattern[1..], &name[1..]),
+        Some('[') => match (name.first(), match_class(pattern, name.first().copied().unwrap_or_default())) {
+            (Some(_), Some((matched, length))) => matched && matches(&pattern[length..], &name[1..]),
+            (None, _) => false,
+            // 没有闭合的 [ 按普通字符处理
+            (Some(&c), None) => c == '[' && matches(&pattern[1..], &name[1..]),
+        },
+        Some(&c) => name.first() == Some(&c) && matches(&pattern[1..], &name[1..]),
+    }
+}
+
+fn matches_name(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    (pattern.first() == Some(&'.') || name.first() != Some(&'.')) && matches(&pattern, &name)
+}
+
+fn children(dir: &Path) -> Vec<PathBuf> {
+    let mut children: Vec<PathBuf> = fs::read_dir(if dir.as_os_str().is_empty() { Path::new(".") } else { dir })
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| dir.join(entry.file_name()))
+        .collect();
+    children.sort();
+    children
+}
+
+// ** 匹配零个或多个目录
+fn expand_from(base: PathBuf, parts: &[&str], output: &mut Vec<PathBuf>) {
+    let Some((part, rest)) = parts.split_first() else {
+        output.push(base);
+        return;
+    };
+    if *part == "**" {
+        expand_from(base.clone(), rest, output);
+        for child in children(&base).into_iter().filter(|child| child.is_dir()) {
+            let hidden = child.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if !hidden {
+                expand_from(child, parts, output);
+            }
+        }
+    } else if is_pattern(part) {
+        for child in children(&base) {
+            let name = child.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            if matches_name(part, &name) && (rest.is_empty() || child.is_dir()) {
+                expand_from(child, rest, output);
+            }
+        }
+    } else {
+        let child = base.join(part);
+        if rest.is_empty() && !child.exists() {
+            return;
+        }
+        expand_from(child, rest, output);
+    }
+}
+
+// 展开路径中的通配符，结果按路径排序；没有匹配任何文件时报错
+pub fn expand(pattern: &str) -> Result<Vec<String>, EpubToMdError> {
+    let mut parts: Vec<&str> = pattern.split(['/', std::path::MAIN_SEPARATOR]).filter(|part| !part.is_empty()).collect();
+    let base = match parts.first() {
+        // C:\books\*.epub
+        Some(drive) if drive.len() == 2 && drive.ends_with(':') => {
+            let base = PathBuf::from(format!("{}{}", drive, std::path::MAIN_SEPARATOR));
+            parts.remove(0);
+            base
+        }
+        _ if pattern.starts_with(['/', std::path::MAIN_SEPARATOR]) => PathBuf::from(std::path::MAIN_SEPARATOR_STR),
+        _ => PathBuf::new(),
+    };
+    let mut paths = Vec::new();
+    expand_from(base, &parts, &mut paths);
+    paths.sort();
+    paths.dedup();
+    if paths.is_empty() {
+        return Err(EpubToMdError::InputError(tr!("No files match '{}'.", pattern)));
+    }
+    Ok(paths.into_iter().map(|path| path.to_string_lossy().into_owned()).collect())
+}
+
+fn find_in(dir: &Path, output: &mut Vec<String>) {
+    for child in children(dir) {
+        let name = child.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        if name.starts_with('.') {
+            continue;
+        }
+        if child.join("META-INF").join("container.xml").is_file() {
+            output.push(child.to_string_lossy().into_owned());
+        } else if child.is_dir() {
+            find_in(&child, output);
+        } else if name.to_ascii_lowercase().ends_with(".epub") {
+            output.push(child.to_string_lossy().into_owned());
+        }
+    }
+}
+
+// 目录树中的每个 EPUB 文件与解压后的 EPUB 目录，跳过隐藏的文件与目录
+pub fn find_epubs(root: &Path) -> Result<Vec<String>, EpubToMdError> {
+    if !root.is_dir() {
+        return Err(EpubToMdError::InputError(tr!("'{}' is not a directory.", root.display())));
+    }
+    let mut output = Vec::new();
+    find_in(root, &mut output);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert!(is_pattern("*.epub"));
+        assert!(is_pattern("book?.epub"));
+        assert!(is_pattern("[ab].epub"));
+        assert!(!is_pattern("books/book.epub"));
+    }
+
+    #[test]
+    fn matches_names() {
+        assert!(matches_name("*.epub", "book.epub"));
+        assert!(!matches_name("*.epub", "book.epub.bak"));
+        assert!(matches_name("book?.epub", "book1.epub"));
+        assert!(!matches_name("book?.epub", "book.epub"));
+        assert!(matches_name("[a-c]*", "banana"));
+        assert!(!matches_name("[!a-c]*", "banana"));
+        assert!(matches_name("[^a-c]*", "durian"));
+        assert!(matches_name("第[一二]章", "第二章"));
+        // 没有闭合的 [ 按普通字符处理
+        assert!(matches_name("[draft", "[draft"));
+        // 隐藏文件只由 . 开头的模式匹配
+        assert!(!matches_name("*", ".hidden"));
+        assert!(matches_name(".*", ".hidden"));
+    }
+
+    #[test]
+    fn expands_in_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in ["a.epub", "b.epub", "notes.txt", "sub/c.epub", "sub/deep/d.epub", ".hidden/e.epub"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let matched = |pattern: &str| {
+            let pattern = format!("{}/{}", root.display(), pattern);
+            let paths = expand(&pattern).unwrap();
+            paths.iter().map(|path| path[root.to_string_lossy().len() + 1..].to_string()).collect::<Vec<_>>()
+        };
+        assert_eq!(matched("*.epub"), ["a.epub", "b.epub"]);
+        assert_eq!(matched("*/c.epub"), ["sub/c.epub"]);
+        assert_eq!(matched("**/*.epub"), ["a.epub", "b.epub", "sub/c.epub", "sub/deep/d.epub"]);
+        let missing = format!("{}/*.mobi", root.display());
+        let Err(EpubToMdError::InputError(message)) = expand(&missing) else {
+            panic!("expected an input error");
+        };
+        assert!(message.starts_with("No files match"), "{}", message);
+
+        let found = find_epubs(root).unwrap();
+        assert_eq!(found.len(), 4);
+    }
+}
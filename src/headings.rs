@@ -0,0 +1,129 @@
+// ATX 标题（# 标题）或 setext 标题（下一行为 === / ---）的级别
+pub fn heading_level(line: &str, next: Option<&str>) -> Option<usize> {
+    let hashes = line.chars().take_while(|&c| c == '#').count();
+    if (1..=6).contains(&hashes) && line[hashes..].starts_with(' ') {
+        return Some(hashes);
+    }
+    let underline = next?.trim();
+    if line.trim().is_empty() || underline.is_empty() {
+        return None;
+    }
+    if underline.chars().all(|c| c == '=') {
+        Some(1)
+    } else if underline.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+// ATX 标题的文字，去掉开头的 # 与可选的结尾 # 序列；结尾的 # 之前没有空白时是文字的一部分，例如 C#
+fn atx_text(line: &str) -> &str {
+    let text = line.trim_start_matches('#').trim();
+    let stripped = text.trim_end_matches('#');
+    if stripped.is_empty() {
+        ""
+    } else if stripped.ends_with([' ', '\t']) {
+        stripped.trim_end()
+    } else {
+        text
+    }
+}
+
+// 调整标题级别：normalize 时最高一级的标题变为 H1，下级标题依次嵌套、不跳级；
+// 再整体加上 offset，结果限制在 H1-H6。调整后的标题统一写成 ATX 形式，代码块中的内容不变
+pub fn adjust(markdown: &str, normalize: bool, offset: i32) -> String {
+    if !normalize && offset == 0 {
+        return markdown.to_string();
+    }
+    let lines: Vec<&str> = markdown.lines().collect();
+    let mut output = Vec::with_capacity(lines.len());
+    let mut in_fence = false;
+    // 当前标题的各级上级标题的原始级别
+    let mut parents: Vec<usize> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        // ATX 标题可以打断段落，setext 标题只在空行之后识别，避免把段落的最后一行当作标题
+        let atx = line.starts_with('#');
+        let after_blank = i == 0 || lines[i - 1].trim().is_empty();
+        let level = match heading_level(line, lines.get(i + 1).copied()) {
+            Some(level) if !in_fence && (atx || after_blank) => level,
+            _ => {
+                output.push(line.to_string());
+                i += 1;
+                continue;
+            }
+        };
+        let text = if atx { atx_text(line) } else { line.trim() };
+        let mut new_level = level;
+        if normalize {
+            while parents.last().is_some_and(|&parent| parent >= level) {
+                parents.pop();
+            }
+            parents.push(level);
+            new_level = parents.len();
+        }
+        let new_level = (new_level as i32 + offset).clamp(1, 6) as usize;
+        output.push(format!("{} {}", "#".repeat(new_level), text).trim_end().to_string());
+        // setext 标题跳过下划线
+        i += if atx { 1 } else { 2 };
+    }
+    output.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shifting_levels_drops_the_closing_sequence() {
+        assert_eq!(adjust("## deep ##", false, 2), "#### deep");
+        assert_eq!(adjust("#### bdeep ####", false, -1), "### bdeep");
+        assert_eq!(adjust("# Intro #\n\n### Sub ###", true, 0), "# Intro\n\n## Sub");
+    }
+
+    #[test]
+    fn trailing_hashes_inside_text_are_kept() {
+        assert_eq!(adjust("## Learning C#", false, 1), "### Learning C#");
+        assert_eq!(adjust("## ##", false, 1), "###");
+    }
+
+    #[test]
+    fn detects_atx_and_setext_levels() {
+        assert_eq!(heading_level("### Title", None), Some(3));
+        assert_eq!(heading_level("#hashtag", None), None);
+        assert_eq!(heading_level("####### seven", None), None);
+        assert_eq!(heading_level("Title", Some("=====")), Some(1));
+        assert_eq!(heading_level("Title", Some("---")), Some(2));
+        assert_eq!(heading_level("Title", Some("text")), None);
+        assert_eq!(heading_level("", Some("---")), None);
+    }
+
+    #[test]
+    fn normalizing_nests_without_skipping() {
+        let markdown = "### Part\n\n##### Chapter\n\n#### Section\n\n### Part two";
+        assert_eq!(adjust(markdown, true, 0), "# Part\n\n## Chapter\n\n## Section\n\n# Part two");
+        assert_eq!(adjust("Title\n=====\n\nSub\n---", true, 1), "## Title\n\n### Sub");
+        assert_eq!(adjust("# One\n\n# Two", false, 9), "###### One\n\n###### Two");
+    }
+
+    #[test]
+    fn code_and_paragraph_lines_are_unchanged() {
+        let markdown = "```\n# comment\n```\n\ntext\n#hashtag";
+        assert_eq!(adjust(markdown, false, 1), markdown);
+        assert_eq!(adjust("# Title", false, 0), "# Title");
+    }
+
+    #[test]
+    fn atx_headings_interrupt_paragraphs() {
+        assert_eq!(adjust("text
+# Heading
+more", false, 1), "text
+## Heading
+more");
+    }
+}
@@ -0,0 +1,563 @@
+use std::{env, str::FromStr, sync::RwLock};
+
+use crate::{cli, error::EpubToMdError};
+
+// 按当前语言格式化一条消息，用法与 format! 相同，但只接受字面量模板与位置参数；
+// 模板是目录中查找译文的键
+macro_rules! tr {
+    ($template:literal $(, $arg:expr)* $(,)?) => {{
+        // 不会执行，只让编译器按 format! 检查模板与参数
+        if false {
+            let _ = format!($template $(, $arg)*);
+        }
+        $crate::i18n::format($template, &[$(($arg).to_string()),*])
+    }};
+}
+
+// 提示与错误信息的语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    #[default]
+    En,
+    ZhCn,
+}
+
+impl FromStr for Locale {
+    type Err = EpubToMdError;
+
+    // 也接受环境变量中 zh_CN.UTF-8 这样的写法
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let language = s.split(['.', '@']).next().unwrap_or_default().replace('_', "-").to_ascii_lowercase();
+        match language.as_str() {
+            "en" | "c" | "posix" => Ok(Locale::En),
+            _ if language.starts_with("en-") => Ok(Locale::En),
+            "zh" | "zh-cn" | "zh-hans" | "zh-sg" => Ok(Locale::ZhCn),
+            _ => Err(EpubToMdError::ArgumentError(tr!("Unknown language '{}', expected one of: en, zh-CN.", s))),
+        }
+    }
+}
+
+static LOCALE: RwLock<Locale> = RwLock::new(Locale::En);
+
+// 按 --lang、EPUB2MD_LANG、LC_ALL、LC_MESSAGES、LANG 的顺序选择语言，需在解析其余参数之前调用，
+// 参数错误也按所选语言提示。无法识别的 --lang 留给参数解析报错，无法识别的环境变量按英文处理；
+// 消息在产生时按当时的语言格式化，读取配置文件前后各调用一次，配置文件中的 --lang 从读取之后生效
+pub fn init(args: &[String]) {
+    let mut iter = args.iter();
+    let mut flag = None;
+    while let Some(arg) = iter.next() {
+        if arg == "--lang" {
+            flag = iter.next().cloned();
+        } else if let Some(value) = arg.strip_prefix("--lang=") {
+            flag = Some(value.to_string());
+        }
+    }
+    let locale = flag
+        .into_iter()
+        .chain(["EPUB2MD_LANG", "LC_ALL", "LC_MESSAGES", "LANG"].iter().filter_map(|name| env::var(name).ok()))
+        .find(|value| !value.is_empty())
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default();
+    *LOCALE.write().unwrap_or_else(|e| e.into_inner()) = locale;
+}
+
+pub fn locale() -> Locale {
+    *LOCALE.read().unwrap_or_else(|e| e.into_inner())
+}
+
+// 用法说明由命令行定义生成，与 --help 相同
+pub fn usage() -> String {
+    cli::command().render_help().to_string()
+}
+
+// tr! 的英文模板与中文译文，键与源码中的模板字面量完全相同，译文中的 {0}、{1} 可以调整参数的顺序
+const ZH_CN: &[(&str, &str)] = &[
+    // 前缀
+    ("Error: {}", "错误：{}"),
+    ("Error: {}: {}", "错误：{}：{}"),
+    ("Warning: {}", "警告：{}"),
+    ("Input Error: {}", "输入错误：{}"),
+    ("Pandoc Error: {}", "Pandoc 错误：{}"),
+    ("File IO Error: {}", "文件读写错误：{}"),
+    ("Pandoc Check Error: {}", "Pandoc 检查错误：{}"),
+    ("EPUB Error: {}", "EPUB 错误：{}"),
+    ("Argument Error: {}", "参数错误：{}"),
+    ("DRM Protected: {}", "受 DRM 保护：{}"),
+    // 进度
+    ("EPUB to Markdown conversion successful!", "EPUB 已成功转换为 Markdown！"),
+    ("Falling back to the native EPUB parser.", "改用内置的 EPUB 解析器。"),
+    ("Skipping {}: {} is up to date.", "跳过 {}：{} 已是最新。"),
+    ("Skipping {}: already converted in an earlier run.", "跳过 {}：之前已经转换过。"),
+    ("Skipping front matter '{}'.", "跳过开头的样板页“{}”。"),
+    ("Skipping back matter '{}'.", "跳过结尾的样板页“{}”。"),
+    ("Skipping '{}', its title '{}' matches --exclude-title.", "跳过“{}”，其标题“{}”与 --exclude-title 匹配。"),
+    ("Vertical writing mode detected, writing horizontal Markdown in reading order.", "检测到竖排版式，按阅读顺序写出横排的 Markdown。"),
+    ("Right-to-left page progression detected, chapters are written in reading order.", "检测到从右向左翻页，章节按阅读顺序写出。"),
+    (
+        "Right-to-left language detected, adding direction marks where text changes direction.",
+        "检测到从右向左书写的语言，在文字方向改变处加上方向标记。",
+    ),
+    ("The EPUB does not contain a table of contents.", "EPUB 中没有目录。"),
+    ("Running {}", "运行 {}"),
+    ("Converted {} ({} lines)", "已转换 {}（{} 行）"),
+    ("Skipping {}, no text after conversion", "跳过 {}，转换后没有文字"),
+    ("Embedding image {} ({} bytes)", "内嵌图片 {}（{} 字节）"),
+    ("Skipping image {}, keeping its original link", "跳过图片 {}，保留原链接"),
+    ("Skipping image {}, same content as {}", "跳过图片 {}，内容与 {} 相同"),
+    (
+        "{} image(s) exceed the --embed-images limit and keep their original links; add --extract-images to write them out.",
+        "{} 张图片超过 --embed-images 的大小上限，保留原链接；加上 --extract-images 可以把它们写出。",
+    ),
+    ("Reading time", "阅读时间"),
+    ("Reading {}", "读取 {}"),
+    ("Converting", "正在转换"),
+    ("chapters", "章"),
+    ("books", "本书"),
+    (", ETA {}", "，预计还需 {}"),
+    ("Finished {} {} in {}", "已完成 {} {}，用时 {}"),
+    ("Summary: {} books, {} converted, {} up to date, {} failed", "汇总：共 {} 本，转换 {} 本，已是最新 {} 本，失败 {} 本"),
+    ("  converted: {} -> {}", "  已转换：{} -> {}"),
+    ("  converted: {}", "  已转换：{}"),
+    ("  up to date: {}", "  已是最新：{}"),
+    ("{} ({} chapters) would write:", "{}（{} 章）将写出："),
+    ("  failed: {}: {}", "  失败：{}：{}"),
+    ("{} of {} books failed to convert.", "{} 本书转换失败，共 {} 本。"),
+    ("Stopped after the first failure; {} books were not converted.", "第一本书失败后已停止，{} 本书没有转换。"),
+    ("No files match '{}'.", "没有与“{}”匹配的文件。"),
+    ("No EPUB files to convert.", "没有要转换的 EPUB 文件。"),
+    ("'{}' is not a directory.", "“{}”不是目录。"),
+    ("Extracted {} files to {}", "已把 {} 个文件解压到 {}"),
+    ("could not recover {}", "无法恢复 {}"),
+    ("No problems found.", "没有发现问题。"),
+    ("Serving the Markdown at http://127.0.0.1:{}/ (press Ctrl+C to stop)", "正在 http://127.0.0.1:{}/ 提供 Markdown（按 Ctrl+C 停止）"),
+    ("Watching {} for EPUB files, writing to {} (press Ctrl+C to stop)", "正在监视 {} 中的 EPUB 文件，写到 {}（按 Ctrl+C 停止）"),
+    ("Failed to watch {}: {}", "无法监视 {}：{}"),
+    ("Converted {} -> {}", "已转换：{} -> {}"),
+    ("request failed: {}", "请求失败：{}"),
+    ("Failed to listen on port {}: {}", "无法监听端口 {}：{}"),
+    ("Failed to create directory: {}", "无法创建目录：{}"),
+    ("Failed to write file: {}", "无法写入文件：{}"),
+    // inspect
+    ("Package:", "OPF 文件："),
+    ("Title:", "书名："),
+    ("Authors:", "作者："),
+    ("Language:", "语言："),
+    ("Identifiers:", "标识符："),
+    ("Publisher:", "出版者："),
+    ("Date:", "日期："),
+    ("Subjects:", "主题："),
+    ("Cover:", "封面："),
+    ("none", "无"),
+    ("Layout:", "版式："),
+    ("Spine:", "Spine 文档数："),
+    ("the EPUB has no table of contents, using spine order.", "EPUB 中没有目录，按 spine 顺序输出。"),
+    ("fixed-layout EPUB detected; use --engine native for an image-gallery fallback.", "检测到固定版式的 EPUB；使用 --engine native 可按图片逐页输出。"),
+    ("fixed-layout EPUB detected, pre-paginated pages are emitted as one image per page.", "检测到固定版式的 EPUB，固定版式的页面逐页输出为图片。"),
+    ("every document looks like front or back matter, nothing is skipped.", "所有文档都像样板页，不跳过任何文档。"),
+    ("container.xml declares {} rootfiles, using the first one:", "container.xml 声明了 {} 个 OPF 文件，使用第一个："),
+    ("Use --rootfile <n> to choose another rendition.", "使用 --rootfile <n> 选择其他版本。"),
+    ("Failed to read EPUB archive ({}), scanning for readable entries.", "无法读取 EPUB 压缩包（{}），正在查找可读取的文件。"),
+    ("failed to re-encode image '{}': {}", "无法重新编码图片“{}”：{}"),
+    // 参数
+    ("Unknown option: {}, did you mean {}?", "未知的选项：{}，是否要用 {}？"),
+    ("Unknown option: {}", "未知的选项：{}"),
+    ("Unexpected argument: {}", "多余的参数：{}"),
+    ("Missing value for {}.", "{} 缺少取值。"),
+    ("Missing required argument: {}", "缺少必需的参数：{}"),
+    ("batch needs an output directory given with -o, or --output-template.", "batch 需要用 -o 指定输出目录，或使用 --output-template。"),
+    ("batch cannot read an EPUB from stdin.", "batch 不能从标准输入读取 EPUB。"),
+    ("--stdout cannot be used with several inputs.", "有多个输入时不能使用 --stdout。"),
+    ("--recursive can only be used with convert.", "--recursive 只能用于 convert。"),
+    ("{} can only be used with convert and batch.", "{} 只能用于 convert 与 batch。"),
+    ("{} only applies when converting several books.", "{} 只用于转换多本书。"),
+    ("Failed to write {}: {}", "无法写入 {}：{}"),
+    ("Several inputs would be written to {}.", "多个输入会写到同一个输出 {}。"),
+    (
+        "serve writes to a temporary directory and cannot be combined with output options.",
+        "serve 写到临时目录，不能与输出选项同时使用。",
+    ),
+    ("Invalid port '{}'.", "无效的端口“{}”。"),
+    (
+        "watch needs an output directory given with -o and cannot be combined with --stdout, --print-toc, --metadata-only or --archive.",
+        "watch 需要用 -o 指定输出目录，不能与 --stdout、--print-toc、--metadata-only 或 --archive 同时使用。",
+    ),
+    // 配置文件
+    ("Unknown option '{}' in {}.", "{1} 中有未知的选项“{0}”。"),
+    ("Invalid value for '{}' in {}.", "{1} 中“{0}”的取值无效。"),
+    ("Profile '{}' is not defined in {}.", "{1} 中没有定义预设“{0}”。"),
+    ("Invalid value for {}: '{}', expected true or false.", "{} 的取值“{}”无效，应为 true 或 false。"),
+    ("{}:{}: {}", "{}:{}：{}"),
+    ("unexpected '{}'.", "多余的“{}”。"),
+    ("expected a key.", "缺少键名。"),
+    ("unterminated string.", "字符串没有结束。"),
+    ("invalid escape \\{}.", "无效的转义 \\{}。"),
+    ("invalid escape \\{}{}.", "无效的转义 \\{}{}。"),
+    ("expected ',' or ']' in array.", "数组中缺少“,”或“]”。"),
+    ("invalid value '{}'.", "无效的取值“{}”。"),
+    ("only [profile.<name>] tables are supported.", "只支持 [profile.<name>] 表。"),
+    ("expected ']'.", "缺少“]”。"),
+    ("profile '{}' is defined twice.", "预设“{}”定义了两次。"),
+    ("expected '=' after '{}'.", "“{}”之后缺少“=”。"),
+    ("'{}' is set twice.", "“{}”设置了两次。"),
+    ("Failed to read {}: {}", "无法读取 {}：{}"),
+    ("Invalid job count '{}'.", "无效的并行数“{}”。"),
+    ("--strict cannot be combined with --recover.", "--strict 不能与 --recover 同时使用。"),
+    ("--update needs an input file to compare against.", "--update 需要一个输入文件用来比较。"),
+    (
+        "--output-template replaces the output path and cannot be combined with it.",
+        "--output-template 代替输出路径，不能与输出路径同时使用。",
+    ),
+    (
+        "--name-template cannot be combined with {{chapter}} in --output-template.",
+        "--name-template 不能与 --output-template 中的 {{chapter}} 同时使用。",
+    ),
+    ("--name-template requires --split or --format.", "--name-template 需要 --split 或 --format。"),
+    ("--archive replaces the output path and cannot be combined with it.", "--archive 代替输出路径，不能与输出路径同时使用。"),
+    (
+        "--separator and --chapter-headings only apply to single-file output.",
+        "--separator 与 --chapter-headings 只用于输出单个文件。",
+    ),
+    ("--extract-images needs an output path to write the images next to.", "--extract-images 需要输出路径，图片写在它旁边。"),
+    ("--metadata-file needs an output path to write next to.", "--metadata-file 需要输出路径，元数据文件写在它旁边。"),
+    (
+        "--format {} already copies images, --extract-images only applies to markdown and mdbook.",
+        "--format {} 已会复制图片，--extract-images 只用于 markdown 与 mdbook。",
+    ),
+    (
+        "--embed-images only applies to --format markdown and mdbook, not {}.",
+        "--embed-images 只用于 --format markdown 与 mdbook，不能用于 {}。",
+    ),
+    (
+        "--image-format and --max-image-width require --extract-images or --embed-images.",
+        "--image-format 与 --max-image-width 需要 --extract-images 或 --embed-images。",
+    ),
+    ("--frontmatter {} is not supported by --format {}.", "--format {1} 不支持 --frontmatter {0}。"),
+    ("--index-name requires --split with --format markdown.", "--index-name 需要 --split，并且 --format 为 markdown。"),
+    ("--max-file-size must be greater than zero.", "--max-file-size 必须大于零。"),
+    ("--max-file-size requires --format markdown.", "--max-file-size 需要 --format markdown。"),
+    (
+        "Writing to stdout requires single-file output without --max-file-size.",
+        "写到标准输出时只能输出单个文件，并且不能使用 --max-file-size。",
+    ),
+    ("--lua-filter requires a Pandoc engine.", "--lua-filter 需要 Pandoc 后端。"),
+    ("--page-markers template must contain {{page}}.", "--page-markers 的模板中必须有 {{page}}。"),
+    (
+        "Unknown shell '{}', expected one of: bash, zsh, fish, powershell.",
+        "未知的 shell“{}”，可选值：bash、zsh、fish、powershell。",
+    ),
+    ("Unknown language '{}', expected one of: en, zh-CN.", "未知的语言“{}”，可选值：en、zh-CN。"),
+    (
+        "Unknown placeholder '{{{}}}' in output template, expected title, author, input or chapter.",
+        "输出模板中有未知的占位符“{{{}}}”，可选值：title、author、input、chapter。",
+    ),
+    ("Unclosed placeholder in output template '{}'.", "输出模板“{}”中的占位符没有闭合。"),
+    ("Output template may contain {{chapter}} only once.", "输出模板中只能有一个 {{chapter}}。"),
+    ("Output template produced an unsafe path '{}'.", "输出模板生成了不安全的路径“{}”。"),
+    (
+        "Unknown placeholder '{{{}}}' in name template, expected index, slug, id, book or chapter.",
+        "文件名模板中有未知的占位符“{{{}}}”，可选值：index、slug、id、book、chapter。",
+    ),
+    ("Unclosed placeholder in name template '{}'.", "文件名模板“{}”中的占位符没有闭合。"),
+    ("Invalid index width '{}' in name template.", "文件名模板中的序号宽度“{}”无效。"),
+    ("Name template produced an unsafe path '{}'.", "文件名模板生成了不安全的路径“{}”。"),
+    ("{} requires --engine native.", "{} 需要 --engine native。"),
+    ("{} cannot be used with several inputs.", "{} 不能用于多个输入。"),
+    ("Statistics for {}:", "{} 的统计："),
+    ("Chapter", "章节"),
+    ("Words", "词数"),
+    ("Characters", "字数"),
+    ("Images", "图片"),
+    ("Footnotes", "脚注"),
+    ("{} min", "{} 分钟"),
+    ("Total ({} chapters)", "合计（{} 章）"),
+    (
+        "--interactive reads the selection from stdin and cannot read the EPUB from it.",
+        "--interactive 从标准输入读取勾选，不能同时从标准输入读取 EPUB。",
+    ),
+    ("--interactive needs a terminal.", "--interactive 需要在终端中运行。"),
+    ("Invalid --exclude-title pattern '{}': {}", "无效的 --exclude-title 模式“{}”：{}"),
+    ("--exclude-title matches every chapter.", "--exclude-title 匹配了所有章节。"),
+    ("--metadata-only cannot be combined with --print-toc.", "--metadata-only 不能与 --print-toc 同时使用。"),
+    ("Failed to read the metadata of {} of {} books.", "{} 本书（共 {} 本）的元数据读取失败。"),
+    ("File: {}", "文件：{}"),
+    ("Space: toggle, a: all or none, Enter: convert, q: quit", "空格：切换勾选，a：全选或全不选，回车：转换，q：退出"),
+    ("Select at least one chapter.", "请至少选择一章。"),
+    ("Failed to read from the terminal: {}", "无法从终端读取：{}"),
+    ("No chapters were selected.", "没有选择任何章节。"),
+    ("{} requires the native or html2md engine.", "{} 需要内置解析器或 html2md 后端。"),
+    ("{} cannot be combined with Pandoc-only options.", "{} 不能与仅用于 Pandoc 的选项同时使用。"),
+    ("--md-flavor requires --engine pandoc.", "--md-flavor 需要 --engine pandoc。"),
+    ("--stdout cannot be combined with the output path {}.", "--stdout 不能与输出路径 {} 同时使用。"),
+    (
+        "--json prints the result to stdout and cannot be combined with --stdout or --print-toc.",
+        "--json 把结果写到标准输出，不能与 --stdout 或 --print-toc 同时使用。",
+    ),
+    ("Output given both with -o and as an argument: {}", "同时用 -o 和参数指定了输出：{}"),
+    ("Output '{}' already exists, use --force to overwrite it.", "输出“{}”已存在，使用 --force 覆盖。"),
+    ("An output directory is required when reading the EPUB from stdin.", "从标准输入读取 EPUB 时需要指定输出目录。"),
+    ("Unknown line ending '{}', expected lf or crlf.", "未知的换行符“{}”，可选值：lf、crlf。"),
+    ("Unknown link style '{}', expected inline or reference.", "未知的链接写法“{}”，可选值：inline、reference。"),
+    ("Unknown order '{}', expected spine or toc.", "未知的章节顺序“{}”，可选值：spine、toc。"),
+    ("Unknown split mode '{}', expected none, chapter or h1-h6.", "未知的拆分方式“{}”，可选值：none、chapter、h1-h6。"),
+    ("Unknown engine '{}', expected one of: pandoc, native, html2md.", "未知的转换后端“{}”，可选值：pandoc、native、html2md。"),
+    (
+        "Unknown markdown flavor '{}', expected one of: gfm, commonmark_x, markdown_strict.",
+        "未知的 Markdown 方言“{}”，可选值：gfm、commonmark_x、markdown_strict。",
+    ),
+    (
+        "Unknown Markdown flavor '{}', expected one of: commonmark, gfm, pandoc, multimarkdown, obsidian.",
+        "未知的 Markdown 方言“{}”，可选值：commonmark、gfm、pandoc、multimarkdown、obsidian。",
+    ),
+    (
+        "Unknown format '{}', expected one of: markdown, mdbook, obsidian, hugo, jekyll, zola.",
+        "未知的输出格式“{}”，可选值：markdown、mdbook、obsidian、hugo、jekyll、zola。",
+    ),
+    (
+        "Unknown frontmatter format '{}', expected one of: yaml, toml, json, none.",
+        "未知的 frontmatter 格式“{}”，可选值：yaml、toml、json、none。",
+    ),
+    ("Unknown metadata format '{}', expected one of: json, yaml.", "未知的元数据文件格式“{}”，可选值：json、yaml。"),
+    ("Unknown image format '{}', expected one of: webp, jpeg, png.", "未知的图片格式“{}”，可选值：webp、jpeg、png。"),
+    ("Unknown caption style '{}', expected one of: italic, plain, alt.", "未知的图注写法“{}”，可选值：italic、plain、alt。"),
+    ("Unknown typography '{}', expected one of: smart, straight, keep.", "未知的排版符号写法“{}”，可选值：smart、straight、keep。"),
+    (
+        "Unknown Unicode normalization '{}', expected one of: nfc, nfkc, none.",
+        "未知的 Unicode 规范化形式“{}”，可选值：nfc、nfkc、none。",
+    ),
+    (
+        "Unknown bouten style '{}', expected one of: bold, emphasis, plain, or a template containing {{text}}.",
+        "未知的着重号写法“{}”，可选值：bold、emphasis、plain 或含有 {{text}} 的模板。",
+    ),
+    ("Unknown punctuation style '{}', expected one of: full, half, keep.", "未知的标点写法“{}”，可选值：full、half、keep。"),
+    ("Unknown Chinese conversion '{}', expected one of: s2t, t2s, none.", "未知的简繁转换方式“{}”，可选值：s2t、t2s、none。"),
+    ("Unknown citation style '{}', expected one of: link, footnote.", "未知的参考文献引用写法“{}”，可选值：link、footnote。"),
+    ("Unknown underline style '{}', expected one of: html, emphasis, plain.", "未知的下划线写法“{}”，可选值：html、emphasis、plain。"),
+    ("Unknown superscript style '{}', expected one of: html, caret, unicode.", "未知的上下标写法“{}”，可选值：html、caret、unicode。"),
+    ("Unknown line break style '{}', expected one of: spaces, backslash.", "未知的换行写法“{}”，可选值：spaces、backslash。"),
+    ("Unknown definition list style '{}', expected one of: colon, bold.", "未知的定义列表写法“{}”，可选值：colon、bold。"),
+    ("Unknown ruby style '{}', expected one of: paren, html, base.", "未知的注音写法“{}”，可选值：paren、html、base。"),
+    (
+        "Unknown admonition style '{}', expected one of: github, colon, admonish, none.",
+        "未知的提示框写法“{}”，可选值：github、colon、admonish、none。",
+    ),
+    ("Unknown HTML policy '{}', expected one of: keep, strip, comment.", "未知的 HTML 处理方式“{}”，可选值：keep、strip、comment。"),
+    ("Invalid size '{}'.", "无效的大小“{}”。"),
+    ("Invalid wrap '{}', expected none or a column number.", "无效的换行宽度“{}”，可选值：none 或列数。"),
+    ("Invalid heading offset '{}', expected -5 to 5.", "无效的标题偏移“{}”，取值范围为 -5 到 5。"),
+    ("Invalid image width '{}'.", "无效的图片宽度“{}”。"),
+    ("Invalid image directory '{}'.", "无效的图片目录“{}”。"),
+    ("Invalid index file name '{}'.", "无效的索引文件名“{}”。"),
+    ("Invalid Pandoc version '{}'.", "无效的 Pandoc 版本“{}”。"),
+    ("Lua filter not found: {}", "找不到 Lua 过滤器：{}"),
+    // 输入与 EPUB
+    ("Input file must be an EPUB file.", "输入文件必须是 EPUB 文件。"),
+    ("Invalid input file name.", "无效的输入文件名。"),
+    ("Input directory must contain META-INF/container.xml.", "输入目录中必须有 META-INF/container.xml。"),
+    ("Exploded EPUB directories require --engine native.", "解压后的 EPUB 目录需要 --engine native。"),
+    ("No EPUB data on stdin.", "标准输入中没有 EPUB 数据。"),
+    ("The EPUB is protected by {}.", "EPUB 受 {} 保护。"),
+    ("Encrypted files: {}.", "加密的文件：{}。"),
+    ("{} and {} more", "{}，另有 {} 个"),
+    ("OPF package has an empty spine.", "OPF 的 spine 为空。"),
+    ("container.xml does not declare a rootfile.", "container.xml 没有声明 OPF 文件。"),
+    ("Rootfile '{}' not found, available rootfiles: {}", "找不到 OPF 文件“{}”，可用的有：{}"),
+    ("Spine references missing manifest item '{}'.", "spine 引用了不存在的 manifest 条目“{}”。"),
+    ("Manifest item without id or href in '{}'.", "“{}”中有缺少 id 或 href 的 manifest 条目。"),
+    ("Broken link in '{}': {}", "“{}”中的链接失效：{}"),
+    ("Unsafe path in EPUB archive: {}", "EPUB 压缩包中有不安全的路径：{}"),
+    ("'{}' exceeds the maximum entry size of {} bytes.", "“{}”超过了单个文件 {} 字节的上限。"),
+    ("EPUB content exceeds the maximum total size of {} bytes.", "EPUB 内容超过了 {} 字节的总大小上限。"),
+    ("Failed to find '{}' in EPUB: entry is missing or damaged", "在 EPUB 中找不到“{}”：文件缺失或已损坏"),
+    ("Failed to find '{}' in EPUB: {}", "在 EPUB 中找不到“{}”：{}"),
+    ("Failed to read '{}' from EPUB: {}", "无法从 EPUB 中读取“{}”：{}"),
+    ("Failed to parse '{}': {}", "无法解析“{}”：{}"),
+    ("Failed to open EPUB file: {}", "无法打开 EPUB 文件：{}"),
+    ("Failed to read EPUB file: {}", "无法读取 EPUB 文件：{}"),
+    ("Failed to read EPUB archive: {}", "无法读取 EPUB 压缩包：{}"),
+    ("{}: exceeds the maximum entry size of {} bytes", "{}：超过了单个文件 {} 字节的上限"),
+    ("{} damaged archive entries could not be identified", "有 {} 个损坏的条目无法识别"),
+    ("{}: {}", "{}：{}"),
+    ("Failed to read EPUB from stdin: {}", "无法从标准输入读取 EPUB：{}"),
+    ("Failed to read directory '{}': {}", "无法读取目录“{}”：{}"),
+    // 输出
+    ("Failed to write Markdown file: {}", "无法写入 Markdown 文件：{}"),
+    ("Failed to write to stdout: {}", "无法写到标准输出：{}"),
+    ("Failed to create output directory '{}': {}", "无法创建输出目录“{}”：{}"),
+    ("Failed to write image '{}': {}", "无法写入图片“{}”：{}"),
+    ("Failed to write resource '{}': {}", "无法写入资源“{}”：{}"),
+    ("Failed to write attachment '{}': {}", "无法写入附件“{}”：{}"),
+    ("Failed to write archive: {}", "无法写入归档：{}"),
+    ("Unsupported archive '{}', expected a .zip, .tar or .tar.gz file.", "不支持的归档“{}”，应为 .zip、.tar 或 .tar.gz 文件。"),
+    ("Failed to create temporary file: {}", "无法创建临时文件：{}"),
+    ("Failed to write temporary file: {}", "无法写入临时文件：{}"),
+    ("Failed to create temporary directory: {}", "无法创建临时目录：{}"),
+    ("Failed to get current directory: {}", "无法获取当前目录：{}"),
+    // Pandoc
+    ("Pandoc is not installed or not in PATH. Please ensure pandoc is installed and accessible.", "没有安装 Pandoc 或不在 PATH 中，请确认已安装 pandoc 并且可以运行。"),
+    ("Pandoc {} is older than the required minimum {}. Please upgrade pandoc or lower --min-pandoc-version.", "Pandoc {} 低于要求的最低版本 {}，请升级 pandoc 或降低 --min-pandoc-version。"),
+    ("Pandoc binary '{}' could not be executed.", "无法运行 Pandoc 程序“{}”。"),
+    ("Pandoc command failed to execute.", "Pandoc 命令执行失败。"),
+    ("Failed to detect the Pandoc version.", "无法检测 Pandoc 的版本。"),
+    ("pandoc command failed: {}", "pandoc 命令失败：{}"),
+    ("Failed to execute pandoc: {}", "无法运行 pandoc：{}"),
+    ("pandoc produced invalid UTF-8: {}", "pandoc 输出的不是有效的 UTF-8：{}"),
+    ("Lua filters require Pandoc {} or newer, found {}.", "Lua 过滤器需要 Pandoc {} 或更新的版本，当前为 {}。"),
+    ("The commonmark_x flavor requires Pandoc {} or newer, found {}.", "commonmark_x 方言需要 Pandoc {} 或更新的版本，当前为 {}。"),
+];
+
+// 在目录中查找英文模板的译文，目录中没有的模板按英文输出
+fn lookup(template: &'static str) -> &'static str {
+    match locale() {
+        Locale::En => template,
+        Locale::ZhCn => {
+            ZH_CN.iter().find(|(english, _)| *english == template).map_or(template, |(_, translation)| translation)
+        }
+    }
+}
+
+// 与 format! 相同，{} 依次填入参数，{0}、{1} 按序号填入，{{ 与 }} 写出花括号
+fn fill(template: &str, args: &[String]) -> String {
+    let mut output = String::with_capacity(template.len());
+    let mut next = 0;
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        output.push_str(&rest[..start]);
+        let brace = &rest[start..start + 1];
+        if rest[start + 1..].starts_with(brace) {
+            output.push_str(brace);
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let end = match (brace, rest[start..].find('}')) {
+            ("{", Some(end)) => start + end,
+            _ => {
+                output.push_str(brace);
+                rest = &rest[start + 1..];
+                continue;
+            }
+        };
+        let index = match &rest[start + 1..end] {
+            "" => {
+                next += 1;
+                next - 1
+            }
+            digits => digits.parse().unwrap_or(usize::MAX),
+        };
+        output.push_str(args.get(index).map_or("", String::as_str));
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+    output
+}
+
+// 供 tr! 调用：按当前语言取模板，再原样填入参数；参数中的书名、路径等不会被翻译
+pub fn format(template: &'static str, args: &[String]) -> String {
+    fill(lookup(template), args)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+
+    // 源码中 tr! 的模板字面量，按字符串字面量的规则去掉转义
+    fn templates() -> Vec<String> {
+        let needle = concat!("tr", "!(");
+        let mut templates = Vec::new();
+        for entry in fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("src")).unwrap() {
+            let source = fs::read_to_string(entry.unwrap().path()).unwrap();
+            for (start, _) in source.match_indices(needle) {
+                let rest = source[start + needle.len()..].trim_start();
+                let Some(rest) = rest.strip_prefix('"') else {
+                    continue;
+                };
+                let mut template = String::new();
+                let mut chars = rest.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some('n') => template.push('\n'),
+                            Some('t') => template.push('\t'),
+                            // 行尾的反斜杠连同下一行开头的空白一起去掉
+                            Some('\n') => {
+                                let trimmed = chars.as_str().trim_start();
+                                chars = trimmed.chars();
+                            }
+                            Some(c) => template.push(c),
+                            None => break,
+                        },
+                        c => template.push(c),
+                    }
+                }
+                templates.push(template);
+            }
+        }
+        templates
+    }
+
+    // 模板用到的参数个数：{} 依次计数，{0}、{1} 按最大的序号计
+    fn arity(template: &str) -> usize {
+        let template = template.replace("{{", "").replace("}}", "");
+        let mut next = 0;
+        let mut count = 0;
+        for (i, _) in template.match_indices('{') {
+            let end = template[i..].find('}').unwrap() + i;
+            let index = match &template[i + 1..end] {
+                "" => {
+                    next += 1;
+                    next - 1
+                }
+                digits => digits.parse().unwrap(),
+            };
+            count = count.max(index + 1);
+        }
+        count
+    }
+
+    #[test]
+    fn every_template_is_translated() {
+        let missing: Vec<String> = templates()
+            .into_iter()
+            .filter(|template| template.chars().any(|c| c.is_ascii_alphabetic()))
+            .filter(|template| !ZH_CN.iter().any(|(english, _)| english == template))
+            .collect();
+        assert!(missing.is_empty(), "templates without a zh-CN translation: {:#?}", missing);
+    }
+
+    #[test]
+    fn catalog_only_has_used_templates() {
+        let templates = templates();
+        let unused: Vec<&str> =
+            ZH_CN.iter().map(|(english, _)| *english).filter(|english| !templates.iter().any(|t| t == english)).collect();
+        assert!(unused.is_empty(), "zh-CN entries no tr! uses: {:#?}", unused);
+        for (i, (english, _)) in ZH_CN.iter().enumerate() {
+            assert!(!ZH_CN[..i].iter().any(|(other, _)| other == english), "duplicate entry {:?}", english);
+        }
+    }
+
+    #[test]
+    fn translations_take_the_same_arguments() {
+        for (english, translation) in ZH_CN {
+            assert_eq!(arity(english), arity(translation), "{:?} -> {:?}", english, translation);
+        }
+    }
+
+    #[test]
+    fn fill_substitutes_arguments_verbatim() {
+        let args = ["Warning: {}".to_string(), "front".to_string()];
+        assert_eq!(fill("跳过“{}”（{}）", &args), "跳过“Warning: {}”（front）");
+        assert_eq!(fill("{1} 中的“{0}”", &args), "front 中的“Warning: {}”");
+        assert_eq!(fill("占位符“{{{}}}”与 {{text}}", &args[1..]), "占位符“{front}”与 {text}");
+    }
+
+    #[test]
+    fn english_is_the_template_itself() {
+        assert_eq!(
+            tr!("Unknown placeholder '{{{}}}' in name template, expected index, slug, id, book or chapter.", "x"),
+            format!("Unknown placeholder '{{{}}}' in name template, expected index, slug, id, book or chapter.", "x")
+        );
+        assert_eq!(tr!("{}:{}: {}", "a.toml", 3, "expected a key."), "a.toml:3: expected a key.");
+    }
+}
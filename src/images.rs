@@ -0,0 +1,240 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{
+    codecs::{jpeg::JpegEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    ImageFormat as DecodedFormat,
+};
+
+use crate::{
+    book::Book,
+    error::EpubToMdError,
+    markdown::Reference,
+    naming,
+    options::{ImageFormat, Options},
+    output,
+};
+
+// 有损压缩为 JPEG 时的质量
+const JPEG_QUALITY: u8 = 85;
+
+// --extract-images 未给出目录时使用的目录名
+pub const DEFAULT_DIR: &str = "assets";
+// --embed-images 未给出大小时内嵌的图片大小上限
+pub const DEFAULT_EMBED_LIMIT: u64 = 100 * 1024;
+
+#[derive(Debug, Clone)]
+enum Target {
+    // 相对输出根目录、以 / 分隔的路径
+    File(String),
+    DataUri(String),
+}
+
+// 提取到输出目录或内嵌到 Markdown 中的图片，键为压缩包内路径
+#[derive(Debug, Clone, Default)]
+pub struct ImageLinks(BTreeMap<String, Target>);
+
+impl ImageLinks {
+    // 从 file_name（相对输出根目录）所在的 Markdown 文件指向提取出的图片的引用，未处理的资源返回 None
+    pub fn rewrite(&self, reference: &Reference, file_name: &str) -> Option<Reference> {
+        let href = match self.0.get(&reference.path)? {
+            Target::DataUri(uri) => uri.clone(),
+            Target::File(target) => {
+                let depth = file_name.matches('/').count();
+                let mut href = format!("{}{}", "../".repeat(depth), target);
+                if let Some(fragment) = &reference.fragment {
+                    href = format!("{}#{}", href, fragment);
+                }
+                href.replace(' ', "%20")
+            }
+        };
+        Some(Reference {
+            href,
+            ..reference.clone()
+        })
+    }
+}
+
+// 按扩展名判断图片类型，用于 data: URI
+fn media_type(path: &str) -> &'static str {
+    let extension = path.rsplit_once('.').map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        Some("webp") => "image/webp",
+        Some("bmp") => "image/bmp",
+        _ => "application/octet-stream",
+    }
+}
+
+// 不超过 --embed-images 上限的图片内嵌为 data: URI；其余图片在指定了 --extract-images 时
+// 写到 root/dir 下，按原文件名命名，重名时追加 -2、-3 等后缀，否则保留原链接。
+// 内容相同的图片只写一次，所有引用都指向首次出现时的文件名
+pub fn prepare(book: &Book, root: &Path, options: &Options) -> Result<ImageLinks, EpubToMdError> {
+    let mut used = HashSet::new();
+    let mut written: HashMap<Vec<u8>, String> = HashMap::new();
+    let mut links = BTreeMap::new();
+    let mut linked = 0;
+    for (resource, bytes) in book.resources_in_order() {
+        let (resource_name, bytes) = match transcode(bytes, options) {
+            Ok(Some((extension, bytes))) => (with_extension(resource, extension), bytes),
+            Ok(None) => (resource.to_string(), bytes.to_vec()),
+            Err(e) => {
+                log::warn!("{}", tr!("failed to re-encode image '{}': {}", resource, e));
+                (resource.to_string(), bytes.to_vec())
+            }
+        };
+        if options.embed_images.is_some_and(|limit| bytes.len() as u64 <= limit) {
+            let uri = format!("data:{};base64,{}", media_type(&resource_name), STANDARD.encode(&bytes));
+            links.insert(resource.to_string(), Target::DataUri(uri));
+            log::debug!("{}", tr!("Embedding image {} ({} bytes)", resource, bytes.len()));
+            continue;
+        }
+        let Some(dir) = &options.extract_images else {
+            linked += 1;
+            log::debug!("{}", tr!("Skipping image {}, keeping its original link", resource));
+            continue;
+        };
+        if let Some(target) = written.get(&bytes) {
+            links.insert(resource.to_string(), Target::File(target.clone()));
+            log::debug!("{}", tr!("Skipping image {}, same content as {}", resource, target));
+            continue;
+        }
+        let file_name = naming::unique_name(&mut used, resource_name.rsplit('/').next().unwrap_or(&resource_name));
+        let path = root.join(dir).join(&file_name);
+        output::write_resource(&path, &bytes, |e| {
+            EpubToMdError::FileIOError(tr!("Failed to write image '{}': {}", path.display(), e))
+        })?;
+        let target = format!("{}/{}", dir_name(dir), file_name);
+        links.insert(resource.to_string(), Target::File(target.clone()));
+        written.insert(bytes, target);
+    }
+    if linked > 0 {
+        log::warn!(
+            "{}",
+            tr!(
+                "{} image(s) exceed the --embed-images limit and keep their original links; add --extract-images to write them out.",
+                linked
+            )
+        );
+    }
+    Ok(ImageLinks(links))
+}
+
+// 按 --image-format 与 --max-image-width 重新编码位图，返回新的扩展名与内容；无需改动或不是位图时返回 None
+fn transcode(bytes: &[u8], options: &Options) -> Result<Option<(&'static str, Vec<u8>)>, image::ImageError> {
+    if options.image_format.is_none() && options.max_image_width.is_none() {
+        return Ok(None);
+    }
+    let Ok(source_format) = image::guess_format(bytes) else {
+        return Ok(None);
+    };
+    let target = match (options.image_format, source_format) {
+        (Some(format), _) => format,
+        (None, DecodedFormat::Png) => ImageFormat::Png,
+        (None, DecodedFormat::Jpeg) => ImageFormat::Jpeg,
+        (None, DecodedFormat::WebP) => ImageFormat::Webp,
+        // 其他格式（例如可能是动画的 GIF）只在指定了 --image-format 时转换
+        _ => return Ok(None),
+    };
+    let mut image = image::load_from_memory_with_format(bytes, source_format)?;
+    let too_wide = options.max_image_width.filter(|&max| image.width() > max);
+    let same_format = matches!(
+        (target, source_format),
+        (ImageFormat::Png, DecodedFormat::Png)
+            | (ImageFormat::Jpeg, DecodedFormat::Jpeg)
+            | (ImageFormat::Webp, DecodedFormat::WebP)
+    );
+    if too_wide.is_none() && same_format {
+        return Ok(None);
+    }
+    if let Some(max) = too_wide {
+        image = image.resize(max, u32::MAX, FilterType::Lanczos3);
+    }
+
+    let mut encoded = Vec::new();
+    match target {
+        // JPEG 不支持透明通道
+        ImageFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY))?,
+        ImageFormat::Webp => image.to_rgba8().write_with_encoder(WebPEncoder::new_lossless(&mut encoded))?,
+        ImageFormat::Png => image.write_to(&mut std::io::Cursor::new(&mut encoded), DecodedFormat::Png)?,
+    }
+    Ok(Some((target.extension(), encoded)))
+}
+
+fn with_extension(path: &str, extension: &str) -> String {
+    let file_start = path.rfind('/').map_or(0, |slash| slash + 1);
+    let stem_end = path[file_start..].rfind('.').filter(|&dot| dot > 0).map_or(path.len(), |dot| file_start + dot);
+    format!("{}.{}", &path[..stem_end], extension)
+}
+
+fn dir_name(dir: &Path) -> String {
+    dir.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::markdown::ReferenceKind;
+
+    fn book(resources: &[(&str, &[u8])]) -> Book {
+        Book {
+            resources: resources.iter().map(|(path, bytes)| (path.to_string(), bytes.to_vec())).collect(),
+            ..Book::default()
+        }
+    }
+
+    fn href(links: &ImageLinks, path: &str, file_name: &str) -> Option<String> {
+        let reference = Reference {
+            kind: ReferenceKind::Image,
+            href: path.to_string(),
+            path: path.to_string(),
+            fragment: None,
+            title: None,
+        };
+        links.rewrite(&reference, file_name).map(|reference| reference.href)
+    }
+
+    #[test]
+    fn extracted_images_get_unique_names_and_relative_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let resources: [(&str, &[u8]); 3] =
+            [("OEBPS/a/cover.png", b"one"), ("OEBPS/b/cover.png", b"two"), ("OEBPS/same.png", b"one")];
+        let book = book(&resources);
+        let options = Options {
+            extract_images: Some(DEFAULT_DIR.into()),
+            ..Options::default()
+        };
+        let links = prepare(&book, dir.path(), &options).unwrap();
+        assert_eq!(href(&links, "OEBPS/a/cover.png", "book.md").as_deref(), Some("assets/cover.png"));
+        assert_eq!(href(&links, "OEBPS/b/cover.png", "chapters/01.md").as_deref(), Some("../assets/cover-2.png"));
+        // 内容相同的图片指向首次写出的文件
+        assert_eq!(href(&links, "OEBPS/same.png", "book.md").as_deref(), Some("assets/cover.png"));
+        assert_eq!(std::fs::read(dir.path().join("assets/cover-2.png")).unwrap(), b"two");
+        assert!(!dir.path().join("assets/same.png").exists());
+    }
+
+    #[test]
+    fn small_images_are_embedded_and_others_keep_their_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let book = book(&[("OEBPS/dot.gif", b"GIF89a"), ("OEBPS/big.jpg", &[0; 64])]);
+        let options = Options {
+            embed_images: Some(16),
+            ..Options::default()
+        };
+        let links = prepare(&book, dir.path(), &options).unwrap();
+        assert_eq!(href(&links, "OEBPS/dot.gif", "book.md").as_deref(), Some("data:image/gif;base64,R0lGODlh"));
+        assert_eq!(href(&links, "OEBPS/big.jpg", "book.md"), None);
+    }
+}
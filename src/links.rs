@@ -0,0 +1,322 @@
+use std::collections::{HashMap, HashSet};
+
+use markup5ever_rcdom::{Handle, NodeData};
+
+use crate::{
+    dom,
+    epub::{parent_dir, resolve_href},
+    naming,
+    options::LinkStyle,
+    typography,
+};
+
+// 插入到链接目标处的自定义元素，输出为 <a id="..."></a>，见 markdown.rs 中的对应处理器
+pub const ANCHOR_TAG: &str = "epub2md-anchor";
+
+const HEADING_TAGS: &[&str] = &["h1", "h2", "h3", "h4", "h5", "h6"];
+// 不能包含子节点的元素，锚点插在它们前面
+const VOID_TAGS: &[&str] = &["img", "br", "hr", "input", "image"];
+
+// 插入锚点后的文档
+pub struct Anchored {
+    pub html: String,
+    // 被书内链接指向的位置：(片段 id, 输出中的锚点名)，片段为空表示文档开头
+    pub targets: Vec<(String, String)>,
+}
+
+// 找出书内链接指向的位置并分配锚点名。目标是标题或以标题开头的元素时使用 Markdown 渲染器
+// 按标题文字生成的锚点，其他元素中插入全书唯一的 ANCHOR_TAG；只指向文档本身或片段不存在的链接
+// 算作指向文档开头。返回与输入一一对应的结果，没有被链接的文档原样返回
+pub fn anchor(sources: &[(&str, &str)]) -> Vec<Anchored> {
+    let documents: Vec<_> = sources.iter().map(|(_, html)| dom::parse(html)).collect();
+    let index: HashMap<&str, usize> = sources.iter().enumerate().map(|(i, (path, _))| (*path, i)).collect();
+    let ids: Vec<HashMap<String, Handle>> = documents
+        .iter()
+        .map(|document| {
+            let mut ids = HashMap::new();
+            for node in dom::descendants(&document.document) {
+                if let Some(id) = dom::attr(&node, "id") {
+                    ids.entry(id).or_insert(node);
+                }
+            }
+            ids
+        })
+        .collect();
+
+    let mut linked: Vec<HashSet<String>> = vec![HashSet::new(); documents.len()];
+    for (i, document) in documents.iter().enumerate() {
+        for node in dom::descendants(&document.document) {
+            if dom::tag_name(&node).as_deref() != Some("a") {
+                continue;
+            }
+            let Some(href) = dom::attr(&node, "href").filter(|href| !href.is_empty() && !href.contains(':')) else {
+                continue;
+            };
+            let (path, fragment) = href.split_once('#').unwrap_or((&href, ""));
+            let target = match path {
+                "" => i,
+                _ => match index.get(resolve_href(parent_dir(sources[i].0), path).as_str()) {
+                    Some(&target) => target,
+                    None => continue,
+                },
+            };
+            if !fragment.is_empty() && ids[target].contains_key(fragment) {
+                linked[target].insert(fragment.to_string());
+            } else if !path.is_empty() {
+                linked[target].insert(String::new());
+            }
+        }
+    }
+
+    let mut used = HashSet::new();
+    documents
+        .iter()
+        .enumerate()
+        .map(|(i, document)| {
+            let mut targets = Vec::new();
+            if linked[i].remove("") {
+                let stem = sources[i].0.rsplit('/').next().unwrap_or_default();
+                let stem = stem.rsplit_once('.').map_or(stem, |(stem, _)| stem);
+                let name = Some(naming::heading_anchor(stem)).filter(|name| !name.is_empty());
+                targets.push((String::new(), unique(&mut used, name.as_deref().unwrap_or("chapter"))));
+            }
+            let mut changed = false;
+            // 按在文档中出现的顺序分配，输出稳定
+            for node in dom::descendants(&document.document) {
+                let Some(id) = dom::attr(&node, "id").filter(|id| linked[i].remove(id)) else {
+                    continue;
+                };
+                if let Some(heading) = leading_heading(&node) {
+                    targets.push((id, naming::heading_anchor(&heading)));
+                    continue;
+                }
+                let name = unique(&mut used, &id);
+                let anchor = dom::element(ANCHOR_TAG, &[("id", &name)]);
+                match dom::tag_name(&node) {
+                    Some(tag) if VOID_TAGS.contains(&tag.as_str()) => dom::insert_before(&node, anchor),
+                    _ => dom::prepend(&node, anchor),
+                }
+                changed = true;
+                targets.push((id, name));
+            }
+            let html = match changed {
+                true => dom::inner_html(&document.document),
+                false => sources[i].1.to_string(),
+            };
+            Anchored { html, targets }
+        })
+        .collect()
+}
+
+// 指向已从输出中去掉的文档的链接没有目标，改为保留 id 与文字的 <span>；没有这样的链接时返回 None
+pub fn unlink(path: &str, html: &str, removed: &HashSet<String>) -> Option<String> {
+    let document = dom::parse(html);
+    let mut changed = false;
+    for node in dom::descendants(&document.document) {
+        if dom::tag_name(&node).as_deref() != Some("a") {
+            continue;
+        }
+        let Some(href) = dom::attr(&node, "href").filter(|href| !href.contains(':')) else {
+            continue;
+        };
+        let target = href.split('#').next().unwrap_or_default();
+        if target.is_empty() || !removed.contains(&resolve_href(parent_dir(path), target)) {
+            continue;
+        }
+        let id = dom::attr(&node, "id");
+        let span = dom::element("span", &id.iter().map(|id| ("id", id.as_str())).collect::<Vec<_>>());
+        for child in node.children.take() {
+            dom::append(&span, child);
+        }
+        dom::replace(&node, span);
+        changed = true;
+    }
+    changed.then(|| dom::inner_html(&document.document))
+}
+
+// 元素本身是标题，或第一个子元素（逐层向下）是标题时的标题文字，例如 EPUB 3 常见的 <section id><h2>
+fn leading_heading(node: &Handle) -> Option<String> {
+    let mut node = node.clone();
+    loop {
+        let tag = dom::tag_name(&node)?;
+        if HEADING_TAGS.contains(&tag.as_str()) {
+            return Some(dom::text_content(&node)).filter(|text| !text.is_empty());
+        }
+        let first = node
+            .children
+            .borrow()
+            .iter()
+            .find(|child| match child.data {
+                NodeData::Element { .. } => true,
+                NodeData::Text { ref contents } => !contents.borrow().trim().is_empty(),
+                _ => false,
+            })
+            .cloned()?;
+        node = first;
+    }
+}
+
+fn unique(used: &mut HashSet<String>, name: &str) -> String {
+    let mut candidate = name.to_string();
+    let mut n = 2;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}-{}", name, n);
+        n += 1;
+    }
+    candidate
+}
+
+// 按 --link-style 改写链接：reference 时行内链接和图片改为编号的引用式链接，定义附在末尾。
+// 编号从 first 开始，返回改写后的文本与下一个可用编号；data: URI 与代码中的内容保持不变
+pub fn restyle(markdown: &str, style: LinkStyle, first: usize) -> (String, usize) {
+    if style == LinkStyle::Inline {
+        return (markdown.to_string(), first);
+    }
+    let mut definitions = Definitions {
+        destinations: Vec::new(),
+        first,
+    };
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        match in_fence || line.trim_start().starts_with("```") {
+            true => lines.push(line.to_string()),
+            false => lines.push(reference_links(&line.chars().collect::<Vec<_>>(), &mut definitions)),
+        }
+    }
+    let next = first + definitions.destinations.len();
+    if definitions.destinations.is_empty() {
+        return (markdown.to_string(), next);
+    }
+    let mut text = lines.join("\n").trim_end().to_string();
+    text.push('\n');
+    for (i, destination) in definitions.destinations.iter().enumerate() {
+        text.push_str(&format!("\n[{}]: {}", first + i, destination));
+    }
+    (text, next)
+}
+
+// 一段文字中已分配编号的链接地址，同一地址共用一个编号
+struct Definitions {
+    destinations: Vec<String>,
+    first: usize,
+}
+
+impl Definitions {
+    fn label(&mut self, destination: &str) -> usize {
+        let index = match self.destinations.iter().position(|known| known == destination) {
+            Some(index) => index,
+            None => {
+                self.destinations.push(destination.to_string());
+                self.destinations.len() - 1
+            }
+        };
+        self.first + index
+    }
+}
+
+fn reference_links(chars: &[char], definitions: &mut Definitions) -> String {
+    let mut output = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' | '`' => {
+                let end = skip_code_or_escape(chars, i);
+                output.extend(&chars[i..end]);
+                i = end;
+            }
+            '[' => match inline_link(chars, i) {
+                Some((text_end, link_end)) => {
+                    // 链接文字中可能嵌套图片
+                    let text = reference_links(&chars[i + 1..text_end], definitions);
+                    let destination: String = chars[text_end + 2..link_end].iter().collect();
+                    let destination = destination.trim();
+                    if destination.starts_with("data:") {
+                        output.push_str(&format!("[{}]({})", text, destination));
+                    } else {
+                        output.push_str(&format!("[{}][{}]", text, definitions.label(destination)));
+                    }
+                    i = link_end + 1;
+                }
+                None => {
+                    output.push('[');
+                    i += 1;
+                }
+            },
+            c => {
+                output.push(c);
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+// 转义字符或行内代码之后的位置
+fn skip_code_or_escape(chars: &[char], start: usize) -> usize {
+    typography::protected_end(chars, start).unwrap_or(start + 1)
+}
+
+// [文字](地址) 中右方括号与右圆括号的位置；脚注引用 [^n] 与引用式链接不算
+fn inline_link(chars: &[char], start: usize) -> Option<(usize, usize)> {
+    if chars.get(start + 1) == Some(&'^') {
+        return None;
+    }
+    let mut depth = 0;
+    let mut i = start;
+    let text_end = loop {
+        match chars.get(i)? {
+            '\\' | '`' => {
+                i = skip_code_or_escape(chars, i);
+                continue;
+            }
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    break i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    };
+    if chars.get(text_end + 1) != Some(&'(') {
+        return None;
+    }
+    let mut depth = 0;
+    let mut i = text_end + 1;
+    loop {
+        match chars.get(i)? {
+            '\\' => i += 1,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((text_end, i));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_into_removed_documents_become_text() {
+        let removed = HashSet::from(["text/ch2.xhtml".to_string()]);
+        let html = r#"<p><a id="k" href="ch2.xhtml#x">two</a> <a href="ch3.xhtml">three</a> <a href="https://example.com/ch2.xhtml">web</a></p>"#;
+        let unlinked = unlink("text/ch1.xhtml", html, &removed).unwrap();
+        assert!(unlinked.contains(r#"<span id="k">two</span>"#), "{}", unlinked);
+        assert!(unlinked.contains(r#"<a href="ch3.xhtml">three</a>"#), "{}", unlinked);
+        assert!(unlinked.contains(r#"<a href="https://example.com/ch2.xhtml">web</a>"#), "{}", unlinked);
+        assert!(unlink("text/ch1.xhtml", r#"<a href="ch3.xhtml">three</a>"#, &removed).is_none());
+    }
+}
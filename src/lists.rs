@@ -0,0 +1,194 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use html2md::{common::get_tag_attr, Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+
+use crate::dom;
+
+// 列表项中除嵌套列表外的块级元素；有两个以上时列表项之间空一行（松散列表）
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "pre", "blockquote", "table", "figure", "dl", "h1", "h2", "h3", "h4", "h5", "h6", "section", "aside",
+];
+
+struct List {
+    ordered: bool,
+    // 下一项的编号，reversed 列表逐项递减
+    next: i64,
+    reversed: bool,
+    loose: bool,
+    items: Vec<String>,
+}
+
+// 正在转换的各层列表，嵌套的列表位于栈顶
+type Lists = Rc<RefCell<Vec<List>>>;
+
+#[derive(Clone, Copy)]
+enum Part {
+    List,
+    Item,
+}
+
+struct ListHandler {
+    part: Part,
+    lists: Lists,
+    start_pos: usize,
+    loose: bool,
+}
+
+impl TagHandler for ListHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        match self.part {
+            Part::List => {
+                let ordered = dom::tag_name(tag).as_deref() == Some("ol");
+                let reversed = get_tag_attr(tag, "reversed").is_some();
+                let items = tag
+                    .children
+                    .borrow()
+                    .iter()
+                    .filter(|child| dom::tag_name(child).as_deref() == Some("li"))
+                    .count() as i64;
+                let start = get_tag_attr(tag, "start").and_then(|start| start.trim().parse().ok());
+                let next = start.unwrap_or(if reversed { items } else { 1 });
+                self.lists.borrow_mut().push(List {
+                    ordered,
+                    next,
+                    reversed,
+                    loose: false,
+                    items: Vec::new(),
+                });
+            }
+            Part::Item => {
+                let blocks = tag
+                    .children
+                    .borrow()
+                    .iter()
+                    .filter(|child| dom::tag_name(child).is_some_and(|tag| BLOCK_TAGS.contains(&tag.as_str())))
+                    .count();
+                self.loose = blocks >= 2;
+                if let Some(value) = get_tag_attr(tag, "value").and_then(|value| value.trim().parse().ok()) {
+                    if let Some(list) = self.lists.borrow_mut().last_mut() {
+                        list.next = value;
+                    }
+                }
+            }
+        }
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        let content = printer.data.split_off(self.start_pos);
+        let mut lists = self.lists.borrow_mut();
+        match self.part {
+            Part::Item => {
+                let content = content.trim_matches(|c: char| c == '\n' || c == ' ').trim_end();
+                let Some(list) = lists.last_mut() else {
+                    // 不在列表中的 <li>
+                    printer.append_str(&item("-", content));
+                    return;
+                };
+                let marker = match list.ordered {
+                    true => format!("{}.", list.next),
+                    false => "-".to_string(),
+                };
+                list.next += if list.reversed { -1 } else { 1 };
+                list.loose |= self.loose;
+                list.items.push(item(&marker, content));
+            }
+            Part::List => {
+                // <li> 之间的空白等不属于任何列表项的内容一并丢弃
+                let Some(list) = lists.pop() else {
+                    return;
+                };
+                let separator = if list.loose { "\n\n" } else { "\n" };
+                let text = list.items.join(separator);
+                // 嵌套列表紧接上一行，只有从 1 开始的有序列表才能打断段落
+                let interrupts = !list.ordered || text.starts_with("1. ") || text == "1.";
+                match lists.is_empty() {
+                    false if interrupts && !list.loose => printer.append_str("\n"),
+                    _ => {
+                        printer.insert_newline();
+                        printer.insert_newline();
+                    }
+                }
+                printer.append_str(&text);
+                printer.insert_newline();
+                printer.insert_newline();
+            }
+        }
+    }
+}
+
+// 列表项的后续行按标记的宽度缩进，代码块等多段内容保持在列表项内
+fn item(marker: &str, content: &str) -> String {
+    let indent = " ".repeat(marker.chars().count() + 1);
+    let lines: Vec<String> = content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| match (i, line.trim().is_empty()) {
+            (0, _) => format!("{} {}", marker, line).trim_end().to_string(),
+            (_, true) => String::new(),
+            _ => format!("{}{}", indent, line),
+        })
+        .collect();
+    match lines.is_empty() {
+        true => marker.to_string(),
+        false => lines.join("\n"),
+    }
+}
+
+struct ListHandlerFactory {
+    part: Part,
+    lists: Lists,
+}
+
+impl TagHandlerFactory for ListHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(ListHandler {
+            part: self.part,
+            lists: self.lists.clone(),
+            start_pos: 0,
+            loose: false,
+        })
+    }
+}
+
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>) {
+    let lists = Lists::default();
+    for (tag, part) in [("ul", Part::List), ("ol", Part::List), ("menu", Part::List), ("li", Part::Item)] {
+        handlers.insert(
+            tag.to_string(),
+            Box::new(ListHandlerFactory {
+                part,
+                lists: lists.clone(),
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(html: &str) -> String {
+        let mut handlers = HashMap::new();
+        register(&mut handlers);
+        html2md::parse_html_custom(html, &handlers)
+    }
+
+    #[test]
+    fn nested_items_are_indented_under_their_marker() {
+        assert_eq!(convert("<ul><li>one<ul><li>inner</li></ul></li><li>two</li></ul>"), "- one\n  - inner\n- two");
+        assert_eq!(convert("<ol><li>a<ol><li>b</li></ol></li></ol>"), "1. a\n   1. b");
+    }
+
+    #[test]
+    fn ordered_lists_keep_their_numbers() {
+        assert_eq!(convert(r#"<ol start="3"><li>c</li><li>d</li></ol>"#), "3. c\n4. d");
+        assert_eq!(convert(r#"<ol reversed><li>a</li><li>b</li><li value="10">c</li></ol>"#), "3. a\n2. b\n10. c");
+    }
+
+    #[test]
+    fn items_with_several_blocks_make_a_loose_list() {
+        let html = "<ul><li><p>first</p><p>more</p></li><li>second</li></ul>";
+        assert_eq!(convert(html), "- first\n\n  more\n\n- second");
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use crate::{progress, report};
+
+// 所有提示都写到标准错误，消息在产生时已按当前语言格式化；警告加上 "Warning: " 前缀
+struct Logger;
+
+// 打印到标准错误的最低级别；log 的级别至少为警告，-q 时警告仍会记入 --json 的结果，只是不打印
+static SHOWN: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+
+// -q 与 -v 选择的级别下是否打印这一级的消息
+pub fn shown(level: Level) -> bool {
+    level as usize <= SHOWN.load(Ordering::Relaxed)
+}
+
+impl Log for Logger {
+    // html5ever 等依赖也通过 log 输出调试信息，只处理本程序的消息
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level() && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() == Level::Warn {
+            report::warning(&record.args().to_string());
+        }
+        if !shown(record.level()) {
+            return;
+        }
+        let message = match record.level() {
+            Level::Warn => tr!("Warning: {}", record.args()),
+            _ => record.args().to_string(),
+        };
+        progress::clear();
+        eprintln!("{}", message);
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
+// 在解析参数之前注册，解析出错时也能打印；级别先按默认的提示与警告
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+// 按解析出的 -q/--quiet 与 -v/--verbose 选择级别：
+// -q 只打印错误，默认打印提示与警告，-v 加上逐章的细节、Pandoc 命令行与跳过的资源，-vv 再加上读取的每个文件
+fn shown_level(quiet: bool, verbose: u8) -> LevelFilter {
+    match (quiet, verbose) {
+        (true, _) => LevelFilter::Error,
+        (false, 0) => LevelFilter::Info,
+        (false, 1) => LevelFilter::Debug,
+        (false, _) => LevelFilter::Trace,
+    }
+}
+
+pub fn set_verbosity(quiet: bool, verbose: u8) {
+    let shown = shown_level(quiet, verbose);
+    SHOWN.store(shown as usize, Ordering::Relaxed);
+    log::set_max_level(shown.max(LevelFilter::Warn));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_hides_warnings_but_still_records_them() {
+        assert!(shown_level(true, 0) < Level::Warn);
+        assert!(shown_level(false, 0) >= Level::Warn);
+        init();
+        set_verbosity(true, 0);
+        report::start(false, true);
+        log::warn!("kept for --json");
+        assert_eq!(report::finish().warnings, ["kept for --json"]);
+        set_verbosity(false, 0);
+    }
+}
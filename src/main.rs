@@ -1,131 +1,724 @@
+// tr! 宏须先于其他模块定义
+#[macro_use]
+mod i18n;
+mod admonitions;
+mod archive;
+mod bidi;
+mod book;
+mod bouten;
+mod chinese;
+mod cjk;
+mod cli;
+mod decorations;
+mod deflists;
+mod cleanup;
+mod code;
+mod commands;
+mod completions;
+mod config;
+mod drm;
+mod dropcaps;
+mod dom;
+mod encoding;
+mod epub;
+mod error;
+mod fixed_layout;
+mod footnotes;
+mod frontmatter;
+mod glob;
+mod headings;
+mod images;
+mod links;
+mod lists;
+mod logging;
+mod markdown;
+mod math;
+mod matter;
+mod mdbook;
+mod naming;
+mod native;
+mod normalize;
+mod obsidian;
+mod options;
+mod output;
+mod pages;
+mod picker;
+mod pandoc;
+mod progress;
+mod quotes;
+mod report;
+mod resume;
+mod ruby;
+mod serve;
+mod sidecar;
+mod stats;
+mod site;
+mod supsub;
+mod tables;
+mod toc;
+mod typography;
+mod verse;
+mod vertical;
+mod watch;
+mod wrap;
+
 use std::{
+    collections::VecDeque,
     env,
-    error::Error,
-    fs,
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::ExitCode,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
 };
-use html2md::parse_html;
-
-#[derive(Debug)]
-enum EpubToMdError {
-    InputError(String),
-    PandocError(String),
-    FileIOError(String),
-    UsageError,
-    PandocCheckError(String),
-}
-
-impl std::fmt::Display for EpubToMdError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            EpubToMdError::InputError(msg) => write!(f, "Input Error: {}", msg),
-            EpubToMdError::PandocError(msg) => write!(f, "Pandoc Error: {}", msg),
-            EpubToMdError::FileIOError(msg) => write!(f, "File IO Error: {}", msg),
-            EpubToMdError::UsageError => write!(f, "Usage: epub2md <input_epub> [output_md]"),
-            EpubToMdError::PandocCheckError(msg) => write!(f, "Pandoc Check Error: {}", msg),
-        }
-    }
-}
-
-impl Error for EpubToMdError {}
-
-// 检查 pandoc 是否安装
-fn check_pandoc() -> Result<(), EpubToMdError> {
-    match Command::new("pandoc")
-        .arg("--version")
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
-        Ok(mut child) => {
-            let status = child.wait().map_err(|e| EpubToMdError::PandocCheckError(format!("Failed to wait for pandoc process: {}", e)))?;
-            if status.success() {
-                Ok(())
+
+use book::{Book, Chapter};
+use cli::{CliArgs, Command};
+use error::EpubToMdError;
+use frontmatter::Frontmatter;
+use markdown::html_to_markdown;
+use options::{Engine, Format, Options, Split};
+use pandoc::check_pandoc;
+
+// 返回 false 表示 --update 时输出已是最新，没有转换
+fn convert_epub_to_md(epub_path_str: &str, md_path_str: Option<&str>, options: &Options) -> Result<bool, EpubToMdError> {
+    let epub_path = Path::new(epub_path_str);
+    let stdin = epub_path_str == archive::STDIN;
+
+    // 检查输入是否为 EPUB 文件，或包含 META-INF/container.xml 的解压目录
+    let exploded = !stdin && epub_path.is_dir();
+    if stdin {
+        // 标准输入没有扩展名可检查，交给后面的压缩包解析报错
+    } else if exploded {
+        if !epub_path.join("META-INF").join("container.xml").is_file() {
+            return Err(EpubToMdError::InputError(tr!("Input directory must contain META-INF/container.xml.")));
+        }
+        if options.engine.is_some_and(|engine| engine != Engine::Native) {
+            return Err(EpubToMdError::InputError(tr!("Exploded EPUB directories require --engine native.")));
+        }
+    } else if epub_path.extension().and_then(|s| s.to_str()) != Some("epub") {
+        return Err(EpubToMdError::InputError(tr!("Input file must be an EPUB file.")));
+    }
+
+    // 受 DRM 保护的书无法转换，提前给出明确的错误
+    if !options.recover {
+        drm::check_drm(epub_path, options)?;
+    }
+
+    if let Some(filter) = options.lua_filters.iter().find(|filter| !filter.is_file()) {
+        return Err(EpubToMdError::InputError(tr!("Lua filter not found: {}", filter.display())));
+    }
+
+    let current_dir = env::current_dir().map_err(|e| EpubToMdError::FileIOError(tr!("Failed to get current directory: {}", e)))?;
+    // 打包输出时先写到临时目录，最后整体打包
+    let staging_dir = match options.archive {
+        Some(_) => Some(
+            tempfile::tempdir()
+                .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to create temporary directory: {}", e)))?,
+        ),
+        None => None,
+    };
+    let output_dir = staging_dir.as_ref().map_or(current_dir.as_path(), |dir| dir.path());
+    let file_name = match stdin {
+        true => "book",
+        false => epub_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| EpubToMdError::InputError(tr!("Invalid input file name.")))?,
+    };
+    let stem = file_name.trim_end_matches(".epub");
+
+    // 输出路径模板要用到书的元数据，先单独读取 OPF
+    let templated_options;
+    let (md_path, options) = match (md_path_str, &options.output_template) {
+        (Some(p), _) => (PathBuf::from(p), options),
+        (None, Some(template)) => {
+            let metadata = epub::Epub::open(epub_path, options)?.metadata;
+            let (path, chapter_template) = template.render(&metadata, stem)?;
+            let md_path = output_dir.join(path);
+            match chapter_template {
+                Some(chapter_template) => {
+                    templated_options = Options {
+                        name_template: Some(chapter_template),
+                        ..options.clone()
+                    };
+                    (md_path, &templated_options)
+                }
+                None => (md_path, options),
+            }
+        }
+        // 拆分输出时默认写入与书同名的目录
+        (None, None) if options.writes_directory() => (output_dir.join(stem), options),
+        (None, None) => (output_dir.join(stem.to_string() + ".md"), options),
+    };
+
+    // 默认不覆盖已有的输出；--update 时输出比输入新就跳过转换
+    if md_path_str != Some(cli::STDOUT) {
+        let first_part = output::part_path(&md_path, 1, 2);
+        let target = match &options.archive {
+            Some(archive) => archive.as_path(),
+            // 超过 --max-file-size 时写出的是 book-01.md 等文件
+            None if options.max_file_size.is_some() && !md_path.exists() => first_part.as_path(),
+            None => md_path.as_path(),
+        };
+        if let Ok(target_metadata) = target.metadata() {
+            let newer = |input: std::fs::Metadata| -> Option<bool> {
+                Some(target_metadata.modified().ok()? >= input.modified().ok()?)
+            };
+            if options.update && epub_path.metadata().ok().and_then(newer) == Some(true) {
+                log::info!("{}", tr!("Skipping {}: {} is up to date.", epub_path_str, target.display()));
+                return Ok(false);
+            }
+            if !options.force && !options.update {
+                return Err(EpubToMdError::FileIOError(tr!(
+                    "Output '{}' already exists, use --force to overwrite it.",
+                    target.display()
+                )));
+            }
+        }
+    }
+    if md_path_str.is_none() && options.output_template.is_some() {
+        output::create_parent_dir(&md_path)?;
+    }
+
+    // 指定了仅某个后端支持的选项时直接选用该后端
+    let requested_engine = if options.md_flavor.is_some() {
+        options.engine.or(Some(Engine::Pandoc))
+    } else if !options.lua_filters.is_empty() {
+        options.engine.or(Some(Engine::Html2md))
+    } else if exploded || options.native_only_option().is_some() {
+        options.engine.or(Some(Engine::Native))
+    } else {
+        options.engine
+    };
+    let (engine, pandoc_version) = match requested_engine {
+        Some(Engine::Native) => (Engine::Native, None),
+        // 如果 pandoc 未安装或版本过旧，直接返回错误
+        Some(engine) => (engine, Some(check_pandoc(options)?)),
+        // 未安装 pandoc 时退回到内置的 EPUB 解析器
+        None => match check_pandoc(options) {
+            Ok(version) => (Engine::Html2md, Some(version)),
+            Err(e) => {
+                log::info!("{}", e);
+                log::info!("{}", tr!("Falling back to the native EPUB parser."));
+                (Engine::Native, None)
+            }
+        },
+    };
+
+    // pandoc 无法很好地处理固定版式书籍，提示改用内置解析器
+    if engine != Engine::Native && epub::Epub::open(epub_path, options).is_ok_and(|epub| epub.has_fixed_layout()) {
+        log::warn!("{}", tr!("fixed-layout EPUB detected; use --engine native for an image-gallery fallback."));
+    }
+
+    // 从标准输入读取时，pandoc 改为读取临时文件
+    let stdin_copy = match engine {
+        Engine::Native => None,
+        _ if stdin => Some(archive::stdin_temp_file()?),
+        _ => None,
+    };
+    let pandoc_input = stdin_copy.as_ref().map_or(epub_path, |(_, path)| path.as_path());
+
+    // pandoc 后端把整本书作为一个章节返回
+    let mut book = match (engine, pandoc_version) {
+        (Engine::Pandoc, Some(version)) => {
+            single_chapter_book(epub_path_str, pandoc::epub_to_markdown(options, version, pandoc_input)?)
+        }
+        (Engine::Html2md, Some(_)) => {
+            let html_content = pandoc::epub_to_html(options, pandoc_input)?;
+            // pandoc 输出的脚注同样改写为 [^n]
+            let html_content = footnotes::rewrite(&[("", &html_content)], options.citations).remove(0);
+            let html_content = normalize::normalize_html(&html_content, options.unicode_normalize);
+            let html_content = chinese::convert_html(&html_content, options.chinese);
+            let html_content = if cjk::enabled(options) { cjk::convert_html(&html_content, options) } else { html_content };
+            // 使用 html2md 转换为 Markdown
+            single_chapter_book(epub_path_str, html_to_markdown(&html_content, options))
+        }
+        _ => native::convert(epub_path, options)?,
+    };
+
+    normalize::normalize_book(&mut book, options.unicode_normalize);
+    chinese::convert_book(&mut book, options.chinese);
+    if cjk::enabled(options) {
+        cjk::convert_book(&mut book, options);
+    }
+
+    if let Split::Heading(level) = options.split {
+        book.split_by_heading(level);
+    }
+    report::chapters(book.chapters.len());
+
+    // 合并为单个文件时标题偏移在合并后统一处理，章节前插入的标题也一并调整
+    let single_file = options.format == Format::Markdown && options.split == Split::None;
+    let chapter_offset = if single_file { 0 } else { options.heading_offset };
+    for chapter in &mut book.chapters {
+        chapter.markdown = headings::adjust(&chapter.markdown, options.normalize_headings, chapter_offset);
+        chapter.markdown = typography::apply(&chapter.markdown, options.typography);
+    }
+    let stats = options.stats.then(|| stats::collect(&book));
+
+    // 图片写到 Markdown 所在的目录中，mdBook 写到 src/ 下
+    if options.extract_images.is_some() || options.embed_images.is_some() {
+        let root = match (options.format, options.writes_directory()) {
+            (Format::Mdbook, _) => md_path.join("src"),
+            (_, true) => md_path.clone(),
+            (_, false) => md_path.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+        };
+        book.images = images::prepare(&book, &root, options)?;
+    }
+
+    // 写入 Markdown 文件
+    match (options.format, options.split) {
+        (Format::Mdbook, _) => mdbook::write_book(&md_path, &book, options)?,
+        (Format::Hugo | Format::Jekyll | Format::Zola, _) => site::write_site(&md_path, &book, options)?,
+        (Format::Obsidian, _) => obsidian::write_vault(&md_path, &book, options)?,
+        (Format::Markdown, Split::None) => {
+            let mut markdown = book::join(&book, &options.separator, options.chapter_headings, options.link_style, options.flavor());
+            if let Some(title) = book.metadata.title.as_deref().filter(|_| options.normalize_headings) {
+                markdown = format!("# {}\n\n{}", title, headings::adjust(&markdown, false, 1));
+            }
+            let markdown = headings::adjust(&markdown, false, options.heading_offset);
+            // 单文件输出默认不写 frontmatter，指定 --frontmatter 时写入整本书的元数据
+            let mut frontmatter = Frontmatter::default();
+            frontmatter
+                .string("title", book.metadata.title.as_deref())
+                .list("author", &book.metadata.authors)
+                .string("date", book.metadata.date.as_deref())
+                .string("language", book.metadata.language.as_deref())
+                .list("tags", &book.metadata.subjects);
+            let markdown = frontmatter.prepend(options.frontmatter_format(), &markdown);
+            if md_path_str == Some(cli::STDOUT) {
+                output::write_stdout(&output::encode_text(&markdown, options))?
             } else {
-                Err(EpubToMdError::PandocCheckError("Pandoc command failed to execute.".to_string()))
+                output::write_markdown(&md_path, &markdown, options)?
             }
         }
-        Err(_) => Err(EpubToMdError::PandocCheckError(
-            "Pandoc is not installed or not in PATH. Please ensure pandoc is installed and accessible.".to_string(),
-        )),
+        (Format::Markdown, _) => {
+            output::write_chapters(&md_path, &book, options)?
+        }
+    }
+
+    if let Some(format) = options.metadata_file {
+        sidecar::write_sidecar(&sidecar::sidecar_path(&md_path, options.writes_directory(), format), &book, format)?;
+    }
+
+    if let (Some(archive), Some(staging_dir)) = (&options.archive, &staging_dir) {
+        output::write_archive(staging_dir.path(), archive, options.deterministic)?;
     }
+
+    // --json 时统计写入结果中；Markdown 写到标准输出时表格改写到标准错误
+    if let Some(stats) = stats {
+        if let Some(path) = &options.stats_file {
+            stats::write(path, &stats)?;
+        }
+        if !report::json() {
+            let title = book.metadata.title.as_deref().unwrap_or(epub_path_str);
+            let table = format!("{}\n{}", tr!("Statistics for {}:", title), stats.table());
+            if md_path_str == Some(cli::STDOUT) {
+                progress::clear();
+                eprint!("{}", table);
+            } else {
+                print!("{}", table);
+            }
+        }
+        report::stats(stats);
+    }
+
+    Ok(true)
 }
 
+fn single_chapter_book(epub_path_str: &str, markdown: String) -> Book {
+    Book {
+        metadata: Default::default(),
+        chapters: vec![Chapter {
+            source: epub_path_str.to_string(),
+            id: None,
+            title: None,
+            language: None,
+            markdown,
+            references: Vec::new(),
+            anchors: Vec::new(),
+            link_targets: Vec::new(),
+        }],
+        toc: Vec::new(),
+        resources: Default::default(),
+        images: Default::default(),
+    }
+}
 
-fn convert_epub_to_md(epub_path_str: &str, md_path_str: Option<&str>) -> Result<(), EpubToMdError> {
-    if let Err(e) = check_pandoc() {
-        eprintln!("{}", e);
-        return Err(e); // 如果 pandoc 未安装，直接返回错误
+// 转换一本书，返回 false 表示 --update 时输出已是最新
+fn convert_book(cli_args: CliArgs) -> Result<bool, EpubToMdError> {
+    if cli_args.print_toc {
+        commands::print_toc(&cli_args.input, &cli_args.options)?;
+        return Ok(true);
     }
 
-    let epub_path = Path::new(epub_path_str);
+    convert_epub_to_md(&cli_args.input, cli_args.output.as_deref(), &cli_args.options)
+}
+
+// 一本书的转换结果，以及写出的文件、章节数、图片数、警告与用时
+struct Outcome {
+    input: String,
+    output: Option<String>,
+    result: Result<bool, EpubToMdError>,
+    report: report::Report,
+    duration: Duration,
+}
+
+impl Outcome {
+    // 没有开始转换的书：出错，或 --resume 时已在之前转换过
+    fn unconverted(args: CliArgs, result: Result<bool, EpubToMdError>) -> Outcome {
+        Outcome {
+            input: args.input,
+            output: args.output,
+            result,
+            report: Default::default(),
+            duration: Duration::ZERO,
+        }
+    }
 
-    // 检查输入文件是否为 EPUB 格式
-    if epub_path.extension().and_then(|s| s.to_str()) != Some("epub") {
-        return Err(EpubToMdError::InputError("Input file must be an EPUB file.".to_string()));
+    fn to_value(&self) -> sidecar::Value {
+        report::to_value(&self.input, &self.result, &self.report, self.duration)
     }
 
-    let current_dir = env::current_dir().map_err(|e| EpubToMdError::FileIOError(format!("Failed to get current directory: {}", e)))?;
-    let html_path = current_dir.join("temp_epub.html");
-    let md_path = match md_path_str {
-        Some(p) => PathBuf::from(p),
-        None => {
-            let file_name = epub_path
-                .file_name()
-                .and_then(|name| name.to_str())
-                .ok_or_else(|| EpubToMdError::InputError("Invalid input file name.".to_string()))?;
-            let md_file_name = file_name.trim_end_matches(".epub").to_string() + ".md";
-            current_dir.join(md_file_name)
+    // --dry-run 时列出要写出的文件
+    fn print_plan(&self) {
+        if self.result.as_ref().is_ok_and(|converted| *converted) {
+            println!("{}", tr!("{} ({} chapters) would write:", self.input, self.report.chapters));
+            for output in &self.report.outputs {
+                println!("  {}", output);
+            }
         }
+    }
+}
+
+fn convert_recorded(cli_args: CliArgs) -> Outcome {
+    let (input, output) = (cli_args.input.clone(), cli_args.output.clone());
+    let start = Instant::now();
+    report::start(cli_args.dry_run, cli_args.json);
+    let result = convert_book(cli_args);
+    Outcome {
+        input,
+        output,
+        result,
+        report: report::finish(),
+        duration: start.elapsed(),
+    }
+}
+
+// 批量转换与 watch 中的一本书，输出目录不存在时先创建
+fn convert_into(args: CliArgs) -> Outcome {
+    let parent = args.output.as_deref().and_then(|output| Path::new(output).parent()).filter(|_| !args.dry_run);
+    let outcome = match parent.map_or(Ok(()), std::fs::create_dir_all) {
+        Ok(()) => convert_recorded(args),
+        Err(e) => Outcome::unconverted(args, Err(EpubToMdError::FileIOError(tr!("Failed to create directory: {}", e)))),
     };
+    if let Err(e) = &outcome.result {
+        log::error!("{}", tr!("Error: {}: {}", outcome.input, e));
+    }
+    outcome
+}
 
-    // 执行 pandoc 命令将 EPUB 转换为 HTML
-    let pandoc_output = Command::new("pandoc")
-        .arg(epub_path)
-        .arg("-o")
-        .arg(&html_path)
-        .output()
-        .map_err(|e| EpubToMdError::PandocError(format!("Failed to execute pandoc: {}", e)))?;
+// --resume：跳过状态文件中记录的内容相同的书，转换完成的书追加到状态文件
+fn convert_resumed(args: CliArgs, state: &Mutex<resume::State>) -> Outcome {
+    let hash = match resume::content_hash(&args.input) {
+        Ok(hash) => hash,
+        Err(e) => {
+            log::error!("{}", tr!("Error: {}: {}", args.input, e));
+            return Outcome::unconverted(args, Err(e));
+        }
+    };
+    if state.lock().unwrap_or_else(|e| e.into_inner()).is_done(&args.input, &hash) {
+        log::info!("{}", tr!("Skipping {}: already converted in an earlier run.", args.input));
+        return Outcome::unconverted(args, Ok(false));
+    }
+    let dry_run = args.dry_run;
+    let mut outcome = convert_into(args);
+    if outcome.result.is_ok() && !dry_run {
+        if let Err(e) = state.lock().unwrap_or_else(|e| e.into_inner()).record(&outcome.input, &hash) {
+            log::error!("{}", tr!("Error: {}: {}", outcome.input, e));
+            outcome.result = Err(e);
+        }
+    }
+    outcome
+}
 
-    if !pandoc_output.status.success() {
-        let error_message = String::from_utf8_lossy(&pandoc_output.stderr);
-        return Err(EpubToMdError::PandocError(format!("pandoc command failed: {}", error_message)));
+fn run_convert(cli_args: CliArgs) -> Result<(), EpubToMdError> {
+    if cli_args.metadata_only {
+        if cli_args.json {
+            print!("{}", sidecar::to_json(&commands::metadata_value(&cli_args.input, &cli_args.options)?));
+            return Ok(());
+        }
+        return commands::metadata(&cli_args.input, &cli_args.options);
+    }
+    if cli_args.json || cli_args.dry_run {
+        let json = cli_args.json;
+        let outcome = convert_recorded(cli_args);
+        if json {
+            print!("{}", sidecar::to_json(&outcome.to_value()));
+        } else {
+            outcome.print_plan();
+        }
+        return outcome.result.map(|_| ());
+    }
+    let print_toc = cli_args.print_toc;
+    let stdout = cli_args.output.as_deref() == Some(cli::STDOUT);
+    // -q 时只打印错误
+    if !convert_book(cli_args)? || print_toc || !logging::shown(log::Level::Info) {
+        return Ok(());
     }
 
-    // 读取 HTML 文件内容
-    let html_content = fs::read_to_string(&html_path)
-        .map_err(|e| EpubToMdError::FileIOError(format!("Failed to read HTML file: {}", e)))?;
+    // 输出到标准输出时，提示信息改写到标准错误，以免混入 Markdown
+    if stdout {
+        log::info!("{}", tr!("EPUB to Markdown conversion successful!"));
+    } else {
+        println!("{}", tr!("EPUB to Markdown conversion successful!"));
+    }
+    Ok(())
+}
 
-    // 使用 html2md 转换为 Markdown
-    let markdown_content = parse_html(&html_content);
+// 转换到临时目录，再在本机提供其中的文件
+fn run_serve(mut args: CliArgs, port: u16) -> Result<(), EpubToMdError> {
+    let dir = tempfile::tempdir()
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to create temporary directory: {}", e)))?;
+    let root = dir.path().join("book");
+    args.output = Some(if args.options.writes_directory() { root.clone() } else { root.join("index.md") })
+        .map(|path| path.to_string_lossy().into_owned());
+    std::fs::create_dir_all(&root)
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to create directory: {}", e)))?;
+    args.options.force = true;
+    run_convert(args)?;
+    serve::serve(&root, port)
+}
 
-    // 写入 Markdown 文件
-    fs::write(&md_path, markdown_content.as_bytes())
-        .map_err(|e| EpubToMdError::FileIOError(format!("Failed to write Markdown file: {}", e)))?;
+// 逐本转换，一本失败不影响其他书，最后列出每本书的结果
+fn run_batch(batch: cli::Batch) -> Result<(), EpubToMdError> {
+    let cli::Batch {
+        books,
+        jobs,
+        state,
+        fail_fast,
+        error_report,
+    } = batch;
+    if books.first().is_some_and(|book| book.metadata_only) {
+        return run_metadata(&books);
+    }
+    let total = books.len();
+    let json = books.first().is_some_and(|book| book.json);
+    let dry_run = books.first().is_some_and(|book| book.dry_run);
+    let start = Instant::now();
+    let jobs = jobs
+        .or_else(|| thread::available_parallelism().ok().map(usize::from))
+        .unwrap_or(1)
+        .clamp(1, total.max(1));
+    let state = state.as_deref().map(resume::State::load).transpose()?.map(Mutex::new);
+    let queue = Mutex::new(books.into_iter().enumerate().collect::<VecDeque<_>>());
+    // --fail-fast 时第一本书失败后不再取新的书，正在转换的书照常完成
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+    let mut results = Vec::with_capacity(total);
+    let mut progress = progress::Progress::new(tr!("Converting"), tr!("books"), total);
+    progress::show_chapters(false);
+    // 每个线程从队列中取下一本书，结果按输入顺序汇总，书与书之间不共享临时文件
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let (queue, state, stop, sender) = (&queue, &state, &stop, sender.clone());
+            scope.spawn(move || loop {
+                if stop.load(Ordering::Relaxed) {
+                    break;
+                }
+                let next = queue.lock().unwrap_or_else(|e| e.into_inner()).pop_front();
+                let Some((index, args)) = next else {
+                    break;
+                };
+                let outcome = match state {
+                    Some(state) => convert_resumed(args, state),
+                    None => convert_into(args),
+                };
+                if fail_fast && outcome.result.is_err() {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                let _ = sender.send((index, outcome));
+            });
+        }
+        drop(sender);
+        for result in receiver {
+            progress.inc();
+            results.push(result);
+        }
+    });
+    progress.finish();
+    results.sort_by_key(|(index, _)| *index);
+    let results: Vec<Outcome> = results.into_iter().map(|(_, outcome)| outcome).collect();
+    let skipped = total - results.len();
+    if skipped > 0 {
+        log::warn!("{}", tr!("Stopped after the first failure; {} books were not converted.", skipped));
+    }
+    if let Some(path) = &error_report {
+        write_error_report(path, &results)?;
+    }
 
-    // 删除临时 HTML 文件
-    fs::remove_file(&html_path).map_err(|e| EpubToMdError::FileIOError(format!("Failed to remove temporary HTML file: {}", e)))?;
+    let count = |converted: Option<bool>| {
+        results.iter().filter(|outcome| outcome.result.as_ref().ok().copied() == converted).count()
+    };
+    let failed = count(None);
+    let summary = tr!(
+        "Summary: {} books, {} converted, {} up to date, {} failed",
+        total,
+        count(Some(true)),
+        count(Some(false)),
+        failed
+    );
+    let lines = results.iter().map(|outcome| match (&outcome.result, &outcome.output) {
+        (Ok(true), Some(output)) => tr!("  converted: {} -> {}", outcome.input, output),
+        (Ok(true), None) => tr!("  converted: {}", outcome.input),
+        (Ok(false), _) => tr!("  up to date: {}", outcome.input),
+        (Err(e), _) => tr!("  failed: {}: {}", outcome.input, e),
+    });
+    if json {
+        let value = sidecar::Value::Map(vec![
+            ("books", sidecar::Value::List(results.iter().map(Outcome::to_value).collect())),
+            ("converted", sidecar::Value::Integer(count(Some(true)) as i64)),
+            ("up_to_date", sidecar::Value::Integer(count(Some(false)) as i64)),
+            ("failed", sidecar::Value::Integer(failed as i64)),
+            ("duration_ms", sidecar::Value::Integer(start.elapsed().as_millis() as i64)),
+        ]);
+        print!("{}", sidecar::to_json(&value));
+    } else {
+        if dry_run {
+            results.iter().for_each(Outcome::print_plan);
+        }
+        // -q 时失败的书已作为错误逐个打印，不再汇总
+        if logging::shown(log::Level::Info) {
+            for line in std::iter::once(summary).chain(lines) {
+                println!("{}", line);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(EpubToMdError::InputError(tr!("{} of {} books failed to convert.", failed, total)));
+    }
+    Ok(())
+}
 
+// --metadata-only 时依次打印每本书的元数据，一本无法读取不影响其他书；--json 时打印为一个列表
+fn run_metadata(books: &[CliArgs]) -> Result<(), EpubToMdError> {
+    let json = books.first().is_some_and(|book| book.json);
+    let mut values = Vec::with_capacity(books.len());
+    let mut failed = 0;
+    for (index, book) in books.iter().enumerate() {
+        let result = if json {
+            commands::metadata_value(&book.input, &book.options).map(|value| values.push(value))
+        } else {
+            if index > 0 {
+                println!();
+            }
+            println!("{}", tr!("File: {}", book.input));
+            commands::metadata(&book.input, &book.options)
+        };
+        if let Err(e) = result {
+            failed += 1;
+            log::error!("{}", tr!("Error: {}: {}", book.input, e));
+            if json {
+                values.push(sidecar::Value::Map(vec![
+                    ("input", sidecar::Value::String(book.input.clone())),
+                    ("error", sidecar::Value::String(e.to_string())),
+                ]));
+            }
+        }
+    }
+    if json {
+        print!("{}", sidecar::to_json(&sidecar::Value::List(values)));
+    }
+    if failed > 0 {
+        return Err(EpubToMdError::InputError(tr!(
+            "Failed to read the metadata of {} of {} books.",
+            failed,
+            books.len()
+        )));
+    }
     Ok(())
 }
 
-fn main() -> Result<(), EpubToMdError> {
-    let args: Vec<String> = env::args().collect();
+// 失败的书及其错误类别，没有失败时写出空列表，便于脚本判断
+fn write_error_report(path: &Path, results: &[Outcome]) -> Result<(), EpubToMdError> {
+    let failed = results
+        .iter()
+        .filter_map(|outcome| {
+            let e = outcome.result.as_ref().err()?;
+            Some(sidecar::Value::Map(vec![
+                ("input", sidecar::Value::String(outcome.input.clone())),
+                ("class", sidecar::Value::String(e.class().to_string())),
+                ("message", sidecar::Value::String(e.to_string())),
+            ]))
+        })
+        .collect();
+    output::write_output(path, &sidecar::to_json(&sidecar::Value::Map(vec![("failed", sidecar::Value::List(failed))])))
+}
 
-    if args.len() < 2 {
-        eprintln!("{}", EpubToMdError::UsageError);
-        return Err(EpubToMdError::UsageError);
-    }
+// 监视目录，每转换一本书就打印它的结果；一本失败不影响之后的书
+fn run_watch(args: cli::Watch) -> Result<(), EpubToMdError> {
+    log::info!("{}", tr!("Watching {} for EPUB files, writing to {} (press Ctrl+C to stop)", args.dir, args.output));
+    watch::watch(Path::new(&args.dir), |input| {
+        let book = match args.book(input) {
+            Ok(book) => book,
+            Err(e) => {
+                log::error!("{}", tr!("Error: {}: {}", input, e));
+                return;
+            }
+        };
+        let (json, dry_run) = (book.json, book.dry_run);
+        let outcome = convert_into(book);
+        if json {
+            print!("{}", sidecar::to_json(&outcome.to_value()));
+        } else if dry_run {
+            outcome.print_plan();
+        } else if let (Ok(true), Some(output)) = (&outcome.result, &outcome.output) {
+            if logging::shown(log::Level::Info) {
+                println!("{}", tr!("Converted {} -> {}", outcome.input, output));
+            }
+        }
+    })
+}
 
-    let epub_path = &args[1];
-    let md_path = args.get(2).map(|s| s.as_str());
+fn run(command: Command) -> Result<(), EpubToMdError> {
+    match command {
+        Command::Convert(args) => run_convert(args),
+        Command::Batch(batch) => run_batch(batch),
+        Command::Inspect { input, options, json } => commands::inspect(&input, &options, json),
+        Command::Toc { input, options } => commands::print_toc(&input, &options),
+        Command::Extract { input, output, options } => commands::extract(&input, &output, &options),
+        Command::Validate { input, options } => commands::validate(&input, &options),
+        Command::Serve { args, port } => run_serve(args, port),
+        Command::Watch(args) => run_watch(args),
+        Command::Completions { shell } => completions::run(shell),
+    }
+}
 
-    if let Err(e) = convert_epub_to_md(epub_path, md_path) {
-        eprintln!("Error: {}", e); // 打印详细错误信息
-        return Err(e);
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    // 配置文件也可以指定 --lang：先按原有参数选择，读取配置文件时的错误用这个语言，读取之后再按展开的参数选择
+    i18n::init(&args);
+    let expanded = cli::with_config(&args);
+    if let Ok(expanded) = &expanded {
+        i18n::init(expanded);
     }
+    logging::init();
 
-    println!("EPUB to Markdown conversion successful!");
-    Ok(())
-}
\ No newline at end of file
+    let command = match expanded.and_then(|args| cli::parse_args(&args)) {
+        Ok(command) => command,
+        Err(e) => {
+            log::error!("{}", e);
+            return ExitCode::from(e.exit_code());
+        }
+    };
+
+    if let Err(e) = run(command) {
+        log::error!("{}", tr!("Error: {}", e)); // 打印详细错误信息
+        return ExitCode::from(e.exit_code());
+    }
+    ExitCode::SUCCESS
+}
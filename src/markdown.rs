@@ -0,0 +1,585 @@
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use html2md::{
+    anchors::AnchorHandler, common::get_tag_attr, containers::ContainerHandler, dummy::DummyHandler,
+    paragraphs::ParagraphHandler, parse_html_custom, quotes::QuoteHandler, styles::StyleHandler, Handle,
+    StructuredPrinter, TagHandler, TagHandlerFactory,
+};
+
+use crate::{
+    admonitions, bouten, code, decorations, deflists, dom, dropcaps,
+    epub::resolve_href,
+    footnotes, links, lists, math,
+    options::{CaptionStyle, Flavor, Options, UnknownHtml},
+    pages, quotes, ruby, supsub, tables, verse,
+};
+
+// Markdown 无法表达的元素，指定 --unknown-html 时按其处理；此外还包括名称中带 - 的自定义元素
+const UNSUPPORTED_TAGS: &[&str] = &[
+    "aside", "details", "dialog", "form", "button", "input", "select", "textarea", "audio", "video", "object",
+    "embed", "canvas", "iframe",
+];
+// 其中单独成段的元素
+const UNSUPPORTED_BLOCKS: &[&str] = &["aside", "details", "dialog", "form", "audio", "video", "object", "canvas", "iframe"];
+
+// 书内引用在 Markdown 中的占位标记：START 序号 TEXT 文字 END，最终写法由输出格式决定。
+// 用 Unicode 非字符而不是私用区字符：中日韩 EPUB 常用私用区字符表示生僻字，非字符不会出现在正文中，
+// 原文中如果有，转换前先删去
+const REF_START: char = '\u{FDD0}';
+const REF_TEXT: char = '\u{FDD1}';
+const REF_END: char = '\u{FDD2}';
+const MARKS: [char; 4] = [REF_START, REF_TEXT, REF_END, code::KEEP_MARK];
+
+fn strip_marks(html: &str) -> Cow<'_, str> {
+    match html.contains(MARKS) {
+        true => Cow::Owned(html.replace(MARKS, "")),
+        false => Cow::Borrowed(html),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Link,
+    Image,
+}
+
+// 指向书内其他文档或资源的链接、图片
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub kind: ReferenceKind,
+    // 原始的 href / src
+    pub href: String,
+    // 压缩包内的完整路径，不含片段
+    pub path: String,
+    pub fragment: Option<String>,
+    pub title: Option<String>,
+}
+
+impl Reference {
+    // 保持原样的 Markdown 写法
+    pub fn to_markdown(&self, text: &str) -> String {
+        let title = self.title.as_ref().map(|title| format!(" \"{}\"", title)).unwrap_or_default();
+        match self.kind {
+            ReferenceKind::Link => format!("[{}]({}{})", text, self.href, title),
+            ReferenceKind::Image => format!("![{}]({}{})", text, self.href.replace(' ', "%20"), title),
+        }
+    }
+}
+
+// 转换单个内容文档的结果
+pub struct Converted {
+    pub markdown: String,
+    pub references: Vec<Reference>,
+}
+
+// 跳过整个子树，例如 <head> 中的 <title> 不应出现在正文里
+struct SkipHandler;
+
+impl TagHandler for SkipHandler {
+    fn handle(&mut self, _tag: &Handle, _printer: &mut StructuredPrinter) {}
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+
+    fn skip_descendants(&self) -> bool {
+        true
+    }
+}
+
+pub struct SkipHandlerFactory;
+
+impl TagHandlerFactory for SkipHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(SkipHandler)
+    }
+}
+
+// 转换过程中共享的引用表
+#[derive(Clone)]
+struct ReferenceContext {
+    document_path: String,
+    references: Rc<RefCell<Vec<Reference>>>,
+}
+
+impl ReferenceContext {
+    // 外部链接（带协议）和 data: URI 不做记录
+    fn record(&self, kind: ReferenceKind, href: &str, title: Option<String>) -> Option<usize> {
+        if href.is_empty() || href.contains(':') {
+            return None;
+        }
+        let path = if href.starts_with('#') {
+            self.document_path.clone()
+        } else {
+            resolve_href(crate::epub::parent_dir(&self.document_path), href)
+        };
+        let mut references = self.references.borrow_mut();
+        references.push(Reference {
+            kind,
+            href: href.to_string(),
+            path,
+            fragment: href.split_once('#').map(|(_, fragment)| fragment.to_string()).filter(|f| !f.is_empty()),
+            title,
+        });
+        Some(references.len() - 1)
+    }
+}
+
+// 书内链接用占位标记包住链接文字，其余链接交给 html2md 默认处理
+struct LinkHandler {
+    context: ReferenceContext,
+    start_pos: usize,
+    index: Option<usize>,
+    fallback: AnchorHandler,
+}
+
+impl TagHandler for LinkHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        let href = get_tag_attr(tag, "href").unwrap_or_default();
+        self.index = match get_tag_attr(tag, "name") {
+            Some(_) => None,
+            None => self.context.record(ReferenceKind::Link, &href, get_tag_attr(tag, "title")),
+        };
+        match self.index {
+            Some(_) => self.start_pos = printer.data.len(),
+            None => self.fallback.handle(tag, printer),
+        }
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        match self.index {
+            Some(index) => {
+                printer.insert_str(self.start_pos, &format!("{}{}{}", REF_START, index, REF_TEXT));
+                printer.append_str(&REF_END.to_string());
+            }
+            None => self.fallback.after_handle(printer),
+        }
+    }
+}
+
+struct LinkHandlerFactory(ReferenceContext);
+
+impl TagHandlerFactory for LinkHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(LinkHandler {
+            context: self.0.clone(),
+            start_pos: 0,
+            index: None,
+            fallback: AnchorHandler::default(),
+        })
+    }
+}
+
+// 书内图片输出为占位标记，替代文字放在标记中
+struct ImageHandler(ReferenceContext);
+
+impl TagHandler for ImageHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        if let Some(letter) = dropcaps::replace_image(tag) {
+            printer.append_str(&letter);
+            return;
+        }
+        let src = get_tag_attr(tag, "src").unwrap_or_default();
+        match self.0.record(ReferenceKind::Image, &src, get_tag_attr(tag, "title")) {
+            Some(index) => printer.append_str(&format!(
+                "{}{}{}{}{}",
+                REF_START,
+                index,
+                REF_TEXT,
+                get_tag_attr(tag, "alt").unwrap_or_default(),
+                REF_END
+            )),
+            None => html2md::images::ImgHandler::default().handle(tag, printer),
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+}
+
+struct ImageHandlerFactory(ReferenceContext);
+
+impl TagHandlerFactory for ImageHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(ImageHandler(self.0.clone()))
+    }
+}
+
+// 脚注引用输出为 [^n]，见 footnotes::rewrite；方言不支持脚注时输出为指向脚注正文的上标链接
+struct NoterefHandler(Flavor);
+
+impl TagHandler for NoterefHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        if let Some(label) = get_tag_attr(tag, footnotes::LABEL_ATTR) {
+            match self.0.footnotes() {
+                true => printer.append_str(&format!("[^{}]", label)),
+                false => printer.append_str(&format!("[<sup>{}</sup>](#fn-{})", label, label)),
+            }
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+}
+
+struct NoterefHandlerFactory(Flavor);
+
+impl TagHandlerFactory for NoterefHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(NoterefHandler(self.0))
+    }
+}
+
+// 书内链接的目标位置输出为空锚点，见 links::anchor
+struct AnchorTargetHandler(Flavor);
+
+impl TagHandler for AnchorTargetHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        if let Some(id) = get_tag_attr(tag, "id") {
+            printer.append_str(&self.0.anchor(&id));
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+}
+
+struct AnchorTargetHandlerFactory(Flavor);
+
+impl TagHandlerFactory for AnchorTargetHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(AnchorTargetHandler(self.0))
+    }
+}
+
+// 脚注正文输出为 [^n]: 定义，后续行缩进四个空格；方言不支持脚注时输出为带锚点与上标编号的段落
+struct FootnoteHandler {
+    flavor: Flavor,
+    label: String,
+    start_pos: usize,
+}
+
+impl TagHandler for FootnoteHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        self.label = get_tag_attr(tag, footnotes::LABEL_ATTR).unwrap_or_default();
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        let body = printer.data.split_off(self.start_pos);
+        // 去掉回链被移除后残留在开头的编号标点，例如 "1." 中的 "."
+        let body = body.trim().trim_start_matches(['.', ')', ']', ':', '：', '、']).trim_start();
+        let indent = if self.flavor.footnotes() { "    " } else { "" };
+        let body: Vec<String> = body
+            .lines()
+            .enumerate()
+            .map(|(i, line)| match (i, line.trim().is_empty()) {
+                (0, _) | (_, true) => line.trim_end().to_string(),
+                _ => format!("{}{}", indent, line.trim_end()),
+            })
+            .collect();
+        printer.insert_newline();
+        printer.insert_newline();
+        match self.flavor.footnotes() {
+            true => printer.append_str(&format!("[^{}]: {}", self.label, body.join("\n"))),
+            false => printer.append_str(&format!(
+                "{}<sup>{}</sup> {}",
+                self.flavor.anchor(&format!("fn-{}", self.label)),
+                self.label,
+                body.join("\n")
+            )),
+        }
+        printer.insert_newline();
+        printer.insert_newline();
+    }
+}
+
+struct FootnoteHandlerFactory(Flavor);
+
+impl TagHandlerFactory for FootnoteHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(FootnoteHandler {
+            flavor: self.0,
+            label: String::new(),
+            start_pos: 0,
+        })
+    }
+}
+
+// <figure> 单独成段；其中没有替代文字的图片用图注作为替代文字
+struct FigureHandler;
+
+impl TagHandler for FigureHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        let descendants = dom::descendants(tag);
+        let caption = descendants
+            .iter()
+            .find(|node| dom::tag_name(node).as_deref() == Some("figcaption"))
+            .map(dom::text_content)
+            .filter(|caption| !caption.is_empty());
+        if let Some(caption) = caption {
+            let images = descendants.iter().filter(|node| dom::tag_name(node).as_deref() == Some("img"));
+            for image in images.filter(|image| dom::attr(image, "alt").is_none_or(|alt| alt.trim().is_empty())) {
+                dom::set_attr(image, "alt", &caption);
+            }
+        }
+        printer.insert_newline();
+        printer.insert_newline();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        printer.insert_newline();
+        printer.insert_newline();
+    }
+}
+
+struct FigureHandlerFactory;
+
+impl TagHandlerFactory for FigureHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(FigureHandler)
+    }
+}
+
+// 图注按 --caption-style 输出为图片下方单独的一段
+struct CaptionHandler {
+    style: CaptionStyle,
+    start_pos: usize,
+}
+
+impl TagHandler for CaptionHandler {
+    fn handle(&mut self, _tag: &Handle, printer: &mut StructuredPrinter) {
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        let caption = printer.data.split_off(self.start_pos);
+        let caption = caption.split_whitespace().collect::<Vec<_>>().join(" ");
+        let caption = match self.style {
+            _ if caption.is_empty() => return,
+            CaptionStyle::Italic => format!("*{}*", caption),
+            CaptionStyle::Plain => caption,
+            CaptionStyle::Alt => return,
+        };
+        printer.insert_newline();
+        printer.insert_newline();
+        printer.append_str(&caption);
+        printer.insert_newline();
+        printer.insert_newline();
+    }
+}
+
+struct CaptionHandlerFactory(CaptionStyle);
+
+impl TagHandlerFactory for CaptionHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(CaptionHandler {
+            style: self.0,
+            start_pos: 0,
+        })
+    }
+}
+
+// 按 --unknown-html 原样保留、删除或注释掉整个元素
+struct UnknownHtmlHandler {
+    policy: UnknownHtml,
+    block: bool,
+}
+
+impl TagHandler for UnknownHtmlHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        // 空行会提前结束 Markdown 中的 HTML 块
+        let html: Vec<String> = dom::outer_html(tag)
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(str::to_string)
+            .collect();
+        let html = match self.policy {
+            UnknownHtml::Keep => html.join("\n"),
+            UnknownHtml::Strip => return,
+            // 注释中不能出现 --
+            UnknownHtml::Comment => format!("<!-- {} -->", html.join("\n").replace("--", "- -")),
+        };
+        if self.block {
+            printer.insert_newline();
+            printer.insert_newline();
+        }
+        printer.append_str(&html);
+        if self.block {
+            printer.insert_newline();
+            printer.insert_newline();
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+
+    fn skip_descendants(&self) -> bool {
+        true
+    }
+}
+
+struct UnknownHtmlHandlerFactory {
+    policy: UnknownHtml,
+    block: bool,
+}
+
+impl TagHandlerFactory for UnknownHtmlHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(UnknownHtmlHandler {
+            policy: self.policy,
+            block: self.block,
+        })
+    }
+}
+
+// 文档中名称带 - 的自定义元素，本程序内部使用的 epub2md-* 元素除外
+fn custom_elements(html: &str) -> HashSet<String> {
+    html.match_indices('<')
+        .filter_map(|(i, _)| {
+            let name: String = html[i + 1..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+                .collect();
+            let custom = name.starts_with(|c: char| c.is_ascii_alphabetic())
+                && name.contains('-')
+                && !name.starts_with("epub2md-");
+            custom.then(|| name.to_ascii_lowercase())
+        })
+        .collect()
+}
+
+fn base_handlers(html: &str, options: &Options) -> HashMap<String, Box<dyn TagHandlerFactory>> {
+    let mut handlers: HashMap<String, Box<dyn TagHandlerFactory>> = HashMap::new();
+    handlers.insert("head".to_string(), Box::new(SkipHandlerFactory));
+    handlers.insert("figure".to_string(), Box::new(FigureHandlerFactory));
+    handlers.insert("figcaption".to_string(), Box::new(CaptionHandlerFactory(options.caption_style)));
+    let flavor = options.flavor();
+    handlers.insert(footnotes::NOTEREF_TAG.to_string(), Box::new(NoterefHandlerFactory(flavor)));
+    handlers.insert(footnotes::FOOTNOTE_TAG.to_string(), Box::new(FootnoteHandlerFactory(flavor)));
+    handlers.insert(links::ANCHOR_TAG.to_string(), Box::new(AnchorTargetHandlerFactory(flavor)));
+    tables::register(&mut handlers, flavor.pipe_tables());
+    lists::register(&mut handlers);
+    code::register(&mut handlers);
+    dropcaps::register(&mut handlers);
+    math::register(&mut handlers, flavor);
+    ruby::register(&mut handlers, options.ruby);
+    supsub::register(&mut handlers, options.sup_sub);
+    deflists::register(&mut handlers, options.definition_list_style());
+    if let Some(policy) = options.unknown_html {
+        let tags = UNSUPPORTED_TAGS.iter().map(|tag| tag.to_string()).chain(custom_elements(html));
+        for tag in tags {
+            let block = UNSUPPORTED_BLOCKS.contains(&tag.as_str());
+            handlers.insert(tag, Box::new(UnknownHtmlHandlerFactory { policy, block }));
+        }
+    }
+    admonitions::register(&mut handlers, options.admonitions);
+    verse::register(&mut handlers, options.line_breaks);
+    decorations::register(&mut handlers, options.underline, flavor);
+    quotes::register(&mut handlers);
+    bouten::register(&mut handlers, &options.bouten);
+    if let Some(template) = &options.page_markers {
+        pages::register(&mut handlers, template);
+    }
+    handlers
+}
+
+// html2md 对该标签的默认处理器，供包装它的处理器交还处理
+pub fn default_handler(tag: &str) -> Box<dyn TagHandler> {
+    match tag {
+        "div" | "section" | "header" | "footer" => Box::new(ContainerHandler),
+        "p" | "br" | "hr" => Box::new(ParagraphHandler::default()),
+        "q" | "cite" | "blockquote" => Box::new(QuoteHandler::default()),
+        "b" | "i" | "strong" | "em" => Box::new(StyleHandler::default()),
+        _ => Box::new(DummyHandler),
+    }
+}
+
+// 将 HTML/XHTML 文档转换为 Markdown
+pub fn html_to_markdown(html: &str, options: &Options) -> String {
+    let html = strip_marks(html);
+    parse_html_custom(&html, &base_handlers(&html, options)).replace(code::KEEP_MARK, "")
+}
+
+// 转换 EPUB 中的内容文档，并记录指向书内文档和资源的引用，供输出格式改写
+pub fn convert_document(html: &str, document_path: &str, options: &Options) -> Converted {
+    let context = ReferenceContext {
+        document_path: document_path.to_string(),
+        references: Rc::new(RefCell::new(Vec::new())),
+    };
+    let html = strip_marks(html);
+    let mut handlers = base_handlers(&html, options);
+    handlers.insert("a".to_string(), Box::new(LinkHandlerFactory(context.clone())));
+    handlers.insert("img".to_string(), Box::new(ImageHandlerFactory(context.clone())));
+    let markdown = parse_html_custom(&html, &handlers).replace(code::KEEP_MARK, "");
+    Converted {
+        markdown,
+        references: context.references.take(),
+    }
+}
+
+// 给占位标记中的序号加上偏移，用于合并两段各自带引用表的 Markdown
+pub fn shift_references(markdown: &str, offset: usize) -> String {
+    let mut output = String::with_capacity(markdown.len());
+    let mut chars = markdown.chars();
+    while let Some(c) = chars.next() {
+        output.push(c);
+        if c == REF_START {
+            let index: String = chars.by_ref().take_while(|&c| c != REF_TEXT).collect();
+            let index = index.parse::<usize>().map_or(index, |i| (i + offset).to_string());
+            output.push_str(&index);
+            output.push(REF_TEXT);
+        }
+    }
+    output
+}
+
+// 把占位标记替换为最终写法；链接文字中可以嵌套图片，由内向外替换
+pub fn render_references(markdown: &str, references: &[Reference], render: &dyn Fn(&Reference, &str) -> String) -> String {
+    let mut chars = markdown.chars().peekable();
+    render_until_end(&mut chars, references, render)
+}
+
+fn render_until_end(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    references: &[Reference],
+    render: &dyn Fn(&Reference, &str) -> String,
+) -> String {
+    let mut output = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            REF_START => {
+                let index: String = chars.by_ref().take_while(|&c| c != REF_TEXT).collect();
+                let text = render_until_end(chars, references, render);
+                match index.parse::<usize>().ok().and_then(|i| references.get(i)) {
+                    Some(reference) => output.push_str(&render(reference, &text)),
+                    None => output.push_str(&text),
+                }
+            }
+            REF_END => break,
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(html: &str) -> String {
+        let converted = convert_document(html, "OEBPS/ch1.xhtml", &Options::default());
+        render_references(&converted.markdown, &converted.references, &|reference, text| reference.to_markdown(text))
+    }
+
+    #[test]
+    fn private_use_characters_are_kept() {
+        let html = "<p>\u{E000} here and <a href=\"ch2.xhtml#x\">\u{E001}link\u{E002}</a> there, \u{E003}</p>";
+        assert_eq!(convert(html), "\u{E000} here and [\u{E001}link\u{E002}](ch2.xhtml#x) there, \u{E003}");
+    }
+
+    #[test]
+    fn marks_in_the_source_are_dropped() {
+        let html = "<p>a\u{FDD0}b <a href=\"ch2.xhtml\">c\u{FDD2}d</a> e\u{FDD3}</p>";
+        assert_eq!(convert(html), "ab [cd](ch2.xhtml) e");
+    }
+}
@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+use markup5ever_rcdom::NodeData;
+
+use crate::{dom, options::Flavor};
+
+// 有对应 LaTeX 命令的字符：希腊字母、运算符、关系符与箭头等
+const SYMBOLS: &[(char, &str)] = &[
+    ('α', "\\alpha"), ('β', "\\beta"), ('γ', "\\gamma"), ('δ', "\\delta"), ('ε', "\\epsilon"),
+    ('ϵ', "\\epsilon"), ('ζ', "\\zeta"), ('η', "\\eta"), ('θ', "\\theta"), ('ϑ', "\\vartheta"),
+    ('ι', "\\iota"), ('κ', "\\kappa"), ('λ', "\\lambda"), ('μ', "\\mu"), ('ν', "\\nu"), ('ξ', "\\xi"),
+    ('π', "\\pi"), ('ϖ', "\\varpi"), ('ρ', "\\rho"), ('σ', "\\sigma"), ('ς', "\\varsigma"), ('τ', "\\tau"),
+    ('υ', "\\upsilon"), ('φ', "\\phi"), ('ϕ', "\\phi"), ('χ', "\\chi"), ('ψ', "\\psi"), ('ω', "\\omega"),
+    ('Γ', "\\Gamma"), ('Δ', "\\Delta"), ('Θ', "\\Theta"), ('Λ', "\\Lambda"), ('Ξ', "\\Xi"), ('Π', "\\Pi"),
+    ('Σ', "\\Sigma"), ('Υ', "\\Upsilon"), ('Φ', "\\Phi"), ('Ψ', "\\Psi"), ('Ω', "\\Omega"),
+    ('∑', "\\sum"), ('∏', "\\prod"), ('∐', "\\coprod"), ('∫', "\\int"), ('∬', "\\iint"), ('∭', "\\iiint"),
+    ('∮', "\\oint"), ('⋃', "\\bigcup"), ('⋂', "\\bigcap"),
+    ('±', "\\pm"), ('∓', "\\mp"), ('×', "\\times"), ('÷', "\\div"), ('⋅', "\\cdot"), ('·', "\\cdot"),
+    ('∗', "\\ast"), ('∘', "\\circ"), ('∙', "\\bullet"), ('⊕', "\\oplus"), ('⊗', "\\otimes"), ('∧', "\\wedge"),
+    ('∨', "\\vee"), ('∩', "\\cap"), ('∪', "\\cup"), ('∖', "\\setminus"), ('−', "-"), ('\u{2062}', ""), ('\u{2061}', ""),
+    ('\u{2063}', ","), ('≤', "\\leq"), ('≥', "\\geq"), ('≠', "\\neq"), ('≈', "\\approx"), ('≡', "\\equiv"),
+    ('∼', "\\sim"), ('≃', "\\simeq"), ('≅', "\\cong"), ('∝', "\\propto"), ('≪', "\\ll"), ('≫', "\\gg"),
+    ('∈', "\\in"), ('∉', "\\notin"), ('∋', "\\ni"), ('⊂', "\\subset"), ('⊃', "\\supset"), ('⊆', "\\subseteq"),
+    ('⊇', "\\supseteq"), ('∀', "\\forall"), ('∃', "\\exists"), ('¬', "\\neg"), ('∅', "\\emptyset"),
+    ('∞', "\\infty"), ('∂', "\\partial"), ('∇', "\\nabla"), ('√', "\\surd"), ('′', "'"), ('″', "''"),
+    ('…', "\\ldots"), ('⋯', "\\cdots"), ('⋮', "\\vdots"), ('⋱', "\\ddots"), ('ℏ', "\\hbar"), ('ℓ', "\\ell"),
+    ('→', "\\to"), ('←', "\\leftarrow"), ('↔', "\\leftrightarrow"), ('⇒', "\\Rightarrow"), ('⇐', "\\Leftarrow"),
+    ('⇔', "\\Leftrightarrow"), ('↦', "\\mapsto"), ('↑', "\\uparrow"), ('↓', "\\downarrow"),
+    ('⟨', "\\langle"), ('⟩', "\\rangle"), ('⌈', "\\lceil"), ('⌉', "\\rceil"), ('⌊', "\\lfloor"), ('⌋', "\\rfloor"),
+    ('∥', "\\parallel"), ('⊥', "\\perp"), ('∠', "\\angle"), ('°', "^\\circ"), ('{', "\\{"), ('}', "\\}"),
+    ('%', "\\%"), ('#', "\\#"), ('&', "\\&"), ('$', "\\$"), ('_', "\\_"),
+];
+
+// 多字母的 <mi> 中 LaTeX 已有的函数名
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh", "log", "ln",
+    "lg", "exp", "lim", "liminf", "limsup", "max", "min", "sup", "inf", "det", "dim", "ker", "gcd", "deg", "arg",
+    "Pr",
+];
+
+// <mover> / <munder> 中作为重音符号的字符
+const ACCENTS: &[(char, &str)] = &[
+    ('^', "\\hat"), ('ˆ', "\\hat"), ('~', "\\tilde"), ('˜', "\\tilde"), ('¯', "\\bar"), ('‾', "\\overline"),
+    ('→', "\\vec"), ('\u{20d7}', "\\vec"), ('˙', "\\dot"), ('¨', "\\ddot"), ('⏞', "\\overbrace"), ('⏟', "\\underbrace"),
+    ('_', "\\underline"),
+];
+
+fn symbol(c: char) -> Option<&'static str> {
+    SYMBOLS.iter().find(|(symbol, _)| *symbol == c).map(|(_, latex)| *latex)
+}
+
+fn element_children(node: &Handle) -> Vec<Handle> {
+    node.children
+        .borrow()
+        .iter()
+        .filter(|child| matches!(child.data, NodeData::Element { .. }))
+        .cloned()
+        .collect()
+}
+
+// 拼接 LaTeX 片段；命令后紧跟字母时补一个空格，避免 \alpha x 被读成 \alphax
+fn push(latex: &mut String, piece: &str) {
+    let command_end = latex
+        .rsplit_once('\\')
+        .is_some_and(|(_, command)| !command.is_empty() && command.chars().all(|c| c.is_ascii_alphabetic()));
+    if command_end && piece.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        latex.push(' ');
+    }
+    latex.push_str(piece);
+}
+
+// 多于一个记号时加花括号
+fn group(latex: String) -> String {
+    let single = latex.chars().count() == 1
+        || (latex.starts_with('\\') && latex[1..].chars().all(|c| c.is_ascii_alphabetic()));
+    match single {
+        true => latex,
+        false => format!("{{{}}}", latex),
+    }
+}
+
+fn text(node: &Handle) -> String {
+    dom::text_content(node)
+}
+
+fn token(text: &str) -> String {
+    let mut latex = String::new();
+    for c in text.chars() {
+        match symbol(c) {
+            Some(command) => push(&mut latex, command),
+            None => latex.push(c),
+        }
+    }
+    latex
+}
+
+fn children(node: &Handle) -> String {
+    let mut latex = String::new();
+    for child in element_children(node) {
+        push(&mut latex, &convert(&child));
+    }
+    latex
+}
+
+// 第 index 个子元素转换后的参数，缺失时为空
+fn argument(arguments: &[Handle], index: usize) -> String {
+    group(arguments.get(index).map(convert).unwrap_or_default())
+}
+
+// 大型运算符的上下限写作 _ 与 ^，其余写作 \underset / \overset
+fn is_large_operator(base: &Handle) -> bool {
+    let text = text(base);
+    match dom::tag_name(base).as_deref() {
+        Some("mo") => text.chars().count() == 1 && "∑∏∐∫∬∭∮⋃⋂".contains(&text),
+        Some("mi") => matches!(text.as_str(), "lim" | "liminf" | "limsup" | "max" | "min" | "sup" | "inf"),
+        _ => false,
+    }
+}
+
+fn accent(node: &Handle) -> Option<&'static str> {
+    let text = text(node);
+    let mut chars = text.chars();
+    let c = chars.next()?;
+    match chars.next() {
+        None => ACCENTS.iter().find(|(accent, _)| *accent == c).map(|(_, latex)| *latex),
+        Some(_) => None,
+    }
+}
+
+fn convert(node: &Handle) -> String {
+    let tag = dom::tag_name(node).unwrap_or_default();
+    let arguments = element_children(node);
+    match tag.as_str() {
+        "mi" => {
+            let text = text(node);
+            let variant = dom::attr(node, "mathvariant");
+            let latex = match text.chars().count() {
+                1 => token(&text),
+                _ if FUNCTIONS.contains(&text.as_str()) => format!("\\{}", text),
+                _ => format!("\\mathrm{{{}}}", token(&text)),
+            };
+            match variant.as_deref() {
+                Some("bold") => format!("\\mathbf{{{}}}", latex),
+                Some("normal") if text.chars().count() == 1 => format!("\\mathrm{{{}}}", latex),
+                Some("double-struck") => format!("\\mathbb{{{}}}", latex),
+                Some("script") => format!("\\mathcal{{{}}}", latex),
+                Some("fraktur") => format!("\\mathfrak{{{}}}", latex),
+                _ => latex,
+            }
+        }
+        "mn" => token(&text(node)),
+        "mo" => {
+            let text = text(node);
+            match text.as_str() {
+                "(" | ")" | "[" | "]" | "|" => text,
+                _ => token(&text),
+            }
+        }
+        "mtext" | "ms" => {
+            let text = text(node);
+            match text.trim().is_empty() {
+                true => "\\ ".to_string(),
+                false => format!("\\text{{{}}}", text),
+            }
+        }
+        "mspace" => "\\,".to_string(),
+        "msup" => format!("{}^{}", argument(&arguments, 0), argument(&arguments, 1)),
+        "msub" => format!("{}_{}", argument(&arguments, 0), argument(&arguments, 1)),
+        "msubsup" => format!(
+            "{}_{}^{}",
+            argument(&arguments, 0),
+            argument(&arguments, 1),
+            argument(&arguments, 2)
+        ),
+        "mfrac" => {
+            let command = match dom::attr(node, "linethickness").as_deref() {
+                Some("0" | "0px" | "0em") => "\\binom",
+                _ => "\\frac",
+            };
+            let numerator = arguments.first().map(convert).unwrap_or_default();
+            let denominator = arguments.get(1).map(convert).unwrap_or_default();
+            format!("{}{{{}}}{{{}}}", command, numerator, denominator)
+        }
+        "msqrt" => format!("\\sqrt{{{}}}", children(node)),
+        "mroot" => format!(
+            "\\sqrt[{}]{{{}}}",
+            arguments.get(1).map(convert).unwrap_or_default(),
+            arguments.first().map(convert).unwrap_or_default()
+        ),
+        "mover" | "munder" | "munderover" => {
+            let Some(base) = arguments.first() else {
+                return String::new();
+            };
+            let (under, over) = match tag.as_str() {
+                "mover" => (None, arguments.get(1)),
+                "munder" => (arguments.get(1), None),
+                _ => (arguments.get(1), arguments.get(2)),
+            };
+            if is_large_operator(base) {
+                let mut latex = group(convert(base));
+                if let Some(under) = under {
+                    latex.push_str(&format!("_{}", group(convert(under))));
+                }
+                if let Some(over) = over {
+                    latex.push_str(&format!("^{}", group(convert(over))));
+                }
+                return latex;
+            }
+            let mut latex = convert(base);
+            if let Some(under) = under {
+                latex = match accent(under) {
+                    Some(command) => format!("{}{{{}}}", command, latex),
+                    None => format!("\\underset{{{}}}{{{}}}", convert(under), latex),
+                };
+            }
+            if let Some(over) = over {
+                latex = match accent(over) {
+                    Some(command) => format!("{}{{{}}}", command, latex),
+                    None => format!("\\overset{{{}}}{{{}}}", convert(over), latex),
+                };
+            }
+            latex
+        }
+        "mfenced" => {
+            let open = dom::attr(node, "open").unwrap_or_else(|| "(".to_string());
+            let close = dom::attr(node, "close").unwrap_or_else(|| ")".to_string());
+            let separators = dom::attr(node, "separators").unwrap_or_else(|| ",".to_string());
+            let separators: Vec<char> = separators.chars().filter(|c| !c.is_whitespace()).collect();
+            let mut latex = String::new();
+            for (i, argument) in arguments.iter().enumerate() {
+                if i > 0 {
+                    if let Some(separator) = separators.get(i - 1).or(separators.last()) {
+                        latex.push(*separator);
+                    }
+                }
+                push(&mut latex, &convert(argument));
+            }
+            format!("\\left{} {} \\right{}", fence(&open), latex, fence(&close))
+        }
+        "mtable" => {
+            let rows: Vec<String> = arguments
+                .iter()
+                .map(|row| {
+                    element_children(row)
+                        .iter()
+                        .map(convert)
+                        .collect::<Vec<_>>()
+                        .join(" & ")
+                })
+                .collect();
+            format!("\\begin{{matrix}} {} \\end{{matrix}}", rows.join(" \\\\ "))
+        }
+        "menclose" => match dom::attr(node, "notation").as_deref() {
+            Some("box" | "roundedbox") => format!("\\boxed{{{}}}", children(node)),
+            Some("updiagonalstrike" | "downdiagonalstrike") => format!("\\cancel{{{}}}", children(node)),
+            _ => children(node),
+        },
+        "semantics" => tex_annotation(node)
+            .or_else(|| arguments.first().map(convert))
+            .unwrap_or_default(),
+        "annotation" | "annotation-xml" | "mphantom" | "none" | "mprescripts" => String::new(),
+        // mrow、mstyle、mpadded 等只起分组作用
+        _ => children(node),
+    }
+}
+
+fn fence(delimiter: &str) -> String {
+    match delimiter {
+        "" => ".".to_string(),
+        "{" => "\\{".to_string(),
+        "}" => "\\}".to_string(),
+        _ => token(delimiter),
+    }
+}
+
+// 出版时保留下来的 TeX 源码比重新转换更准确
+fn tex_annotation(node: &Handle) -> Option<String> {
+    element_children(node)
+        .into_iter()
+        .find(|child| {
+            dom::tag_name(child).as_deref() == Some("annotation")
+                && dom::attr(child, "encoding").is_some_and(|encoding| encoding.contains("tex"))
+        })
+        .map(|annotation| text(&annotation).trim().to_string())
+}
+
+// 整个 <math> 元素转换为 LaTeX，不含 $ 定界符
+pub fn to_latex(math: &Handle) -> String {
+    let latex = match element_children(math).as_slice() {
+        [semantics] if dom::tag_name(semantics).as_deref() == Some("semantics") => convert(semantics),
+        _ => children(math),
+    };
+    latex.trim().to_string()
+}
+
+fn is_display(math: &Handle) -> bool {
+    dom::attr(math, "display").as_deref() == Some("block") || dom::attr(math, "mode").as_deref() == Some("display")
+}
+
+// 行内公式输出为 $...$，display="block" 的公式输出为单独成段的 $$...$$，定界符随 --flavor 改变
+struct MathHandler(Flavor);
+
+impl TagHandler for MathHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        let latex = to_latex(tag);
+        if latex.is_empty() {
+            return;
+        }
+        if is_display(tag) {
+            printer.insert_newline();
+            printer.insert_newline();
+            printer.append_str(&self.0.math(&latex, true));
+            printer.insert_newline();
+            printer.insert_newline();
+        } else {
+            printer.append_str(&self.0.math(&latex, false));
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+
+    fn skip_descendants(&self) -> bool {
+        true
+    }
+}
+
+struct MathHandlerFactory(Flavor);
+
+impl TagHandlerFactory for MathHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(MathHandler(self.0))
+    }
+}
+
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, flavor: Flavor) {
+    handlers.insert("math".to_string(), Box::new(MathHandlerFactory(flavor)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latex(mathml: &str) -> String {
+        let document = dom::parse(mathml);
+        let math = dom::descendants(&document.document)
+            .into_iter()
+            .find(|node| dom::tag_name(node).as_deref() == Some("math"))
+            .expect("math element");
+        to_latex(&math)
+    }
+
+    #[test]
+    fn converts_layout_elements() {
+        assert_eq!(latex("<math><mfrac><mi>a</mi><mi>b</mi></mfrac></math>"), r"\frac{a}{b}");
+        let power = "<math><msup><mi>x</mi><mn>2</mn></msup><mo>+</mo><msqrt><mi>y</mi></msqrt></math>";
+        assert_eq!(latex(power), r"x^2+\sqrt{y}");
+        let sum = concat!(
+            "<math><munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover>",
+            "<msub><mi>x</mi><mi>i</mi></msub></math>",
+        );
+        assert_eq!(latex(sum), r"\sum_{i=1}^nx_i");
+        assert_eq!(latex("<math><mfenced><mi>a</mi><mi>b</mi></mfenced></math>"), r"\left( a,b \right)");
+    }
+
+    #[test]
+    fn symbols_become_commands() {
+        assert_eq!(latex("<math><mi>α</mi><mo>≤</mo><mover><mi>v</mi><mo>→</mo></mover></math>"), r"\alpha\leq\vec{v}");
+        assert_eq!(latex("<math><mo>≤</mo><mi>v</mi></math>"), r"\leq v");
+    }
+
+    #[test]
+    fn tex_annotations_are_preferred() {
+        let mathml = concat!(
+            r#"<math><semantics><mi>α</mi>"#,
+            r#"<annotation encoding="application/x-tex">\alpha_0</annotation></semantics></math>"#,
+        );
+        assert_eq!(latex(mathml), r"\alpha_0");
+    }
+}
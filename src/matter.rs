@@ -0,0 +1,101 @@
+use regex::Regex;
+
+use crate::{dom, epub::ManifestItem, toc::TocEntry};
+
+// 地标、<guide> 与文档 epub:type 中表示出版社样板页的类型
+const BOILERPLATE_TYPES: &[&str] = &[
+    "cover", "titlepage", "title-page", "halftitlepage", "copyright-page", "copyright", "imprint", "dedication",
+    "toc", "loi", "lot", "seriespage", "colophon", "other-credits", "acknowledgments", "acknowledgements",
+    "contributors",
+];
+// 样板页常见的标题开头，比较时忽略大小写
+const BOILERPLATE_TITLES: &[&str] = &[
+    "copyright", "cover", "title page", "dedication", "table of contents", "contents", "about the author",
+    "about the authors", "also by", "other books by", "advertisement", "colophon", "acknowledgments",
+    "acknowledgements", "版权", "封面", "扉页", "献词", "目录", "作者简介", "关于作者", "广告", "致谢",
+];
+
+// 文档是否为样板页：地标或 <guide> 指明的类型、<body> 及其第一个区块的 epub:type，或者标题
+fn is_boilerplate(item: &ManifestItem, html: &str, landmarks: &[(String, String)], toc: &[TocEntry]) -> bool {
+    let boilerplate_type = |types: &str| types.split_whitespace().any(|t| BOILERPLATE_TYPES.contains(&t));
+    if landmarks.iter().any(|(path, kind)| *path == item.path && boilerplate_type(kind)) {
+        return true;
+    }
+    let document = dom::parse(html);
+    let nodes = dom::descendants(&document.document);
+    let body = nodes.iter().find(|node| dom::tag_name(node).as_deref() == Some("body"));
+    let first_block = body.and_then(|body| body.children.borrow().iter().find(|c| dom::tag_name(c).is_some()).cloned());
+    if body.into_iter().chain(first_block.as_ref()).any(|node| dom::attr(node, "type").is_some_and(|t| boilerplate_type(&t))) {
+        return true;
+    }
+    let title = TocEntry::flatten(toc)
+        .into_iter()
+        .find(|entry| entry.path == item.path && !entry.title.is_empty())
+        .map(|entry| entry.title.clone())
+        .or_else(|| {
+            nodes
+                .iter()
+                .find(|node| matches!(dom::tag_name(node).as_deref(), Some("h1" | "h2" | "h3" | "title")))
+                .map(dom::text_content)
+        })
+        .unwrap_or_default()
+        .to_lowercase();
+    // 英文标题按整词比较，"Covering" 不算 "Cover"
+    BOILERPLATE_TITLES.iter().any(|prefix| {
+        title
+            .strip_prefix(prefix)
+            .is_some_and(|rest| !prefix.is_ascii() || !rest.starts_with(|c: char| c.is_ascii_alphanumeric()))
+    })
+}
+
+// 去掉开头（skip_front）和结尾（skip_back）连续的样板页；全书都像样板页时不做处理
+pub fn skip(
+    loaded: Vec<(ManifestItem, String)>,
+    landmarks: &[(String, String)],
+    toc: &[TocEntry],
+    skip_front: bool,
+    skip_back: bool,
+) -> Vec<(ManifestItem, String)> {
+    let boilerplate: Vec<bool> =
+        loaded.iter().map(|(item, html)| is_boilerplate(item, html, landmarks, toc)).collect();
+    let Some(first) = boilerplate.iter().position(|&b| !b) else {
+        log::warn!("{}", tr!("every document looks like front or back matter, nothing is skipped."));
+        return loaded;
+    };
+    let last = boilerplate.iter().rposition(|&b| !b).unwrap_or(first);
+    let start = if skip_front { first } else { 0 };
+    let end = if skip_back { last + 1 } else { loaded.len() };
+    loaded
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, document)| {
+            if (start..end).contains(&i) {
+                return Some(document);
+            }
+            if i < start {
+                log::info!("{}", tr!("Skipping front matter '{}'.", document.0.path));
+            } else {
+                log::info!("{}", tr!("Skipping back matter '{}'.", document.0.path));
+            }
+            None
+        })
+        .collect()
+}
+
+// 去掉目录标题与任一模式匹配的文档；不在目录中的文档是前一章拆出的后续部分，跟随前一章
+pub fn exclude(loaded: Vec<(ManifestItem, String)>, toc: &[TocEntry], patterns: &[Regex]) -> Vec<(ManifestItem, String)> {
+    let entries = TocEntry::flatten(toc);
+    let mut excluded = false;
+    loaded
+        .into_iter()
+        .filter(|(item, _)| {
+            if let Some(entry) = entries.iter().find(|entry| entry.path == item.path) {
+                excluded = patterns.iter().any(|pattern| pattern.is_match(&entry.title));
+                if excluded {
+                    log::info!("{}", tr!("Skipping '{}', its title '{}' matches --exclude-title.", item.path, entry.title));
+                }
+            }
+            !excluded
+        })
+        .collect()
+}
@@ -0,0 +1,82 @@
+use std::{collections::HashSet, path::Path};
+
+use crate::{
+    book::Book,
+    error::EpubToMdError,
+    links,
+    naming,
+    options::Options,
+    output::{self, write_output, write_text},
+    toc::TocEntry,
+};
+
+// 生成可以直接用 mdBook 构建的目录：book.toml、src/SUMMARY.md 以及每章一个文件
+pub fn write_book(out_dir: &Path, book: &Book, options: &Options) -> Result<(), EpubToMdError> {
+    let src_dir = out_dir.join("src");
+    let file_names = naming::chapter_file_names(
+        &book.chapters,
+        options.name_template.as_ref(),
+        book.metadata.title.as_deref(),
+        &["SUMMARY.md"],
+    )?;
+    for (i, file_name) in file_names.iter().enumerate() {
+        let path = src_dir.join(file_name);
+        output::create_parent_dir(&path)?;
+        let (body, _) = links::restyle(&book.plain_markdown(i, Some(&file_names)), options.link_style, 1);
+        write_text(&path, &body, options)?;
+    }
+
+    write_text(&src_dir.join("SUMMARY.md"), &summary(book, &file_names), options)?;
+    write_output(&out_dir.join("book.toml"), &book_toml(book.metadata.title.as_deref()))?;
+    Ok(())
+}
+
+// SUMMARY.md 的嵌套与 EPUB 目录一致；目录未引用的章节放在开头作为前言或追加到末尾
+fn summary(book: &Book, file_names: &[String]) -> String {
+    let file_for = |path: &str| {
+        book.chapters
+            .iter()
+            .position(|chapter| chapter.source == path)
+            .map(|i| file_names[i].as_str())
+    };
+    let entries: Vec<&TocEntry> = TocEntry::flatten(&book.toc)
+        .into_iter()
+        .filter(|entry| !entry.title.is_empty())
+        .collect();
+    let in_toc: HashSet<&str> = entries.iter().filter_map(|entry| file_for(&entry.path)).collect();
+    let first_in_toc = file_names.iter().position(|name| in_toc.contains(name.as_str()));
+
+    let mut summary = String::from("# Summary\n\n");
+    let mut numbered = Vec::new();
+    for (i, (chapter, file_name)) in book.chapters.iter().zip(file_names).enumerate() {
+        if in_toc.contains(file_name.as_str()) {
+            continue;
+        }
+        let title = escape_title(chapter.title.as_deref().unwrap_or(&chapter.stem()));
+        match first_in_toc {
+            Some(first) if i < first => summary.push_str(&format!("[{}]({})\n\n", title, file_name)),
+            _ => numbered.push(format!("- [{}]({})", title, file_name)),
+        }
+    }
+
+    // 同一文件的后续目录条目（指向文件内锚点）作为草稿章节，mdBook 不支持链接到锚点
+    let mut linked = HashSet::new();
+    let mut toc_lines = Vec::new();
+    for entry in entries {
+        let link = file_for(&entry.path).filter(|name| linked.insert(*name)).unwrap_or_default();
+        toc_lines.push(format!("{}- [{}]({})", "  ".repeat(entry.depth), escape_title(&entry.title), link));
+    }
+    toc_lines.extend(numbered);
+    summary.push_str(&toc_lines.join("\n"));
+    summary.push('\n');
+    summary
+}
+
+fn escape_title(title: &str) -> String {
+    title.replace('\\', "\\\\").replace('[', "\\[").replace(']', "\\]")
+}
+
+fn book_toml(title: Option<&str>) -> String {
+    let title = title.unwrap_or("Untitled").replace('\\', "\\\\").replace('"', "\\\"");
+    format!("[book]\ntitle = \"{}\"\nsrc = \"src\"\n", title)
+}
@@ -0,0 +1,375 @@
+use std::{collections::HashSet, path::PathBuf, str::FromStr};
+
+use crate::{archive::safe_relative_path, book::Chapter, epub::Metadata, error::EpubToMdError};
+
+// 文件名的最大字符数，过长的标题会被截断
+const MAX_FILE_NAME_CHARS: usize = 100;
+
+// 文件名模板中的一段
+#[derive(Debug, Clone)]
+enum Part {
+    Literal(String),
+    // {index} 或 {index:03}，从 1 开始
+    Index(usize),
+    // 章节标题的 slug
+    Slug,
+    // 内容文档的 manifest id
+    Id,
+    // 书名的 slug
+    Book,
+    // 可直接用作文件名的章节标题
+    Chapter,
+}
+
+// 拆分输出时的文件名模板，例如 "{index:03}-{slug}.md"
+#[derive(Debug, Clone)]
+pub struct NameTemplate(Vec<Part>);
+
+impl FromStr for NameTemplate {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| EpubToMdError::ArgumentError(tr!("Unclosed placeholder in name template '{}'.", s)))?;
+            let placeholder = &rest[start + 1..end];
+            parts.push(match placeholder.split_once(':') {
+                Some(("index", width)) => Part::Index(width.parse().map_err(|_| {
+                    EpubToMdError::ArgumentError(tr!("Invalid index width '{}' in name template.", width))
+                })?),
+                None if placeholder == "index" => Part::Index(0),
+                None if placeholder == "slug" => Part::Slug,
+                None if placeholder == "id" => Part::Id,
+                None if placeholder == "book" => Part::Book,
+                None if placeholder == "chapter" => Part::Chapter,
+                _ => {
+                    return Err(EpubToMdError::ArgumentError(tr!(
+                        "Unknown placeholder '{{{}}}' in name template, expected index, slug, id, book or chapter.",
+                        placeholder
+                    )))
+                }
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(NameTemplate(parts))
+    }
+}
+
+impl NameTemplate {
+    fn render(&self, index: usize, chapter: &Chapter, book_slug: &str) -> String {
+        self.0
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.clone(),
+                Part::Index(width) => format!("{:0width$}", index, width = *width),
+                Part::Slug => slugify(chapter.title.as_deref().unwrap_or(&chapter.stem())),
+                Part::Id => chapter.id.clone().unwrap_or_else(|| chapter.stem()),
+                Part::Book => book_slug.to_string(),
+                Part::Chapter => sanitize_file_name(chapter.title.as_deref().unwrap_or(&chapter.stem())),
+            })
+            .collect()
+    }
+}
+
+// 为每个章节生成文件名；未指定模板时使用 "01-<文件名>.md"，与其他章节或 reserved 中的名称重复时追加 -2、-3 等后缀
+pub fn chapter_file_names(
+    chapters: &[Chapter],
+    template: Option<&NameTemplate>,
+    book_title: Option<&str>,
+    reserved: &[&str],
+) -> Result<Vec<String>, EpubToMdError> {
+    let width = chapters.len().to_string().len().max(2);
+    let book_slug = slugify(book_title.unwrap_or("book"));
+    let mut used: HashSet<String> = reserved.iter().map(|name| name.to_lowercase()).collect();
+    let mut names = Vec::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        let name = match template {
+            Some(template) => template.render(i + 1, chapter, &book_slug),
+            None => format!("{:0width$}-{}.md", i + 1, chapter.stem(), width = width),
+        };
+        // 模板可以包含子目录，但不能越出输出目录
+        safe_relative_path(&name)
+            .map_err(|_| EpubToMdError::ArgumentError(tr!("Name template produced an unsafe path '{}'.", name)))?;
+
+        names.push(unique_name(&mut used, &name));
+    }
+    Ok(names)
+}
+
+// 输出路径模板中的一段
+#[derive(Debug, Clone)]
+enum PathPart {
+    Literal(String),
+    Title,
+    Author,
+    // 输入文件名（不含 .epub）
+    Input,
+    Chapter,
+}
+
+// 未指定输出路径时由书的元数据生成默认路径，例如 "{author}/{title}.md"；
+// 包含 {chapter} 时按章节拆分，{chapter} 所在的一段作为章节文件名
+#[derive(Debug, Clone)]
+pub struct OutputTemplate(Vec<PathPart>);
+
+impl FromStr for OutputTemplate {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(PathPart::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .map(|end| start + end)
+                .ok_or_else(|| EpubToMdError::ArgumentError(tr!("Unclosed placeholder in output template '{}'.", s)))?;
+            parts.push(match &rest[start + 1..end] {
+                "title" => PathPart::Title,
+                "author" => PathPart::Author,
+                "input" => PathPart::Input,
+                "chapter" => PathPart::Chapter,
+                placeholder => {
+                    return Err(EpubToMdError::ArgumentError(tr!(
+                        "Unknown placeholder '{{{}}}' in output template, expected title, author, input or chapter.",
+                        placeholder
+                    )))
+                }
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(PathPart::Literal(rest.to_string()));
+        }
+        let chapters = parts.iter().filter(|part| matches!(part, PathPart::Chapter)).count();
+        if chapters > 1 {
+            return Err(EpubToMdError::ArgumentError(tr!("Output template may contain {{chapter}} only once.")));
+        }
+        Ok(OutputTemplate(parts))
+    }
+}
+
+impl OutputTemplate {
+    pub fn has_chapter(&self) -> bool {
+        self.0.iter().any(|part| matches!(part, PathPart::Chapter))
+    }
+
+    // 返回输出路径，以及包含 {chapter} 时的章节文件名模板；替换进来的元数据都经过文件名清理
+    pub fn render(&self, metadata: &Metadata, input: &str) -> Result<(PathBuf, Option<NameTemplate>), EpubToMdError> {
+        let value = |part: &PathPart| match part {
+            PathPart::Literal(text) => text.clone(),
+            PathPart::Title => sanitize_file_name(metadata.title.as_deref().unwrap_or(input)),
+            PathPart::Author => sanitize_file_name(metadata.authors.first().map_or("Unknown", String::as_str)),
+            PathPart::Input => sanitize_file_name(input),
+            PathPart::Chapter => String::new(),
+        };
+
+        // {chapter} 之前最后一个 / 把模板分为输出目录和章节文件名两部分
+        let (dir_parts, name_parts) = match self.0.iter().position(|part| matches!(part, PathPart::Chapter)) {
+            Some(chapter) => {
+                let mut dir = String::new();
+                let mut name = Vec::new();
+                for part in &self.0[..chapter] {
+                    dir.push_str(&value(part));
+                }
+                if let Some(slash) = dir.rfind('/') {
+                    name.push(Part::Literal(dir[slash + 1..].to_string()));
+                    dir.truncate(slash);
+                } else {
+                    name.push(Part::Literal(std::mem::take(&mut dir)));
+                }
+                name.push(Part::Chapter);
+                name.extend(self.0[chapter + 1..].iter().map(|part| Part::Literal(value(part))));
+                (dir, Some(NameTemplate(name)))
+            }
+            None => (self.0.iter().map(value).collect(), None),
+        };
+
+        let path = safe_relative_path(&dir_parts)
+            .map_err(|_| EpubToMdError::ArgumentError(tr!("Output template produced an unsafe path '{}'.", dir_parts)))?;
+        Ok((path, name_parts))
+    }
+}
+
+// 去掉在 Windows、macOS 或 Linux 上不能出现在文件名中的字符，并避开 Windows 的保留设备名
+pub fn sanitize_file_name(text: &str) -> String {
+    let cleaned: String = text
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let mut name: String = cleaned.split_whitespace().collect::<Vec<_>>().join(" ");
+    name = name.chars().take(MAX_FILE_NAME_CHARS).collect();
+    let name = name.trim_end_matches(['.', ' ']).trim_start_matches(' ').to_string();
+    let stem = name.split('.').next().unwrap_or_default().to_ascii_uppercase();
+    let reserved = matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL")
+        || (stem.len() == 4
+            && (stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.as_bytes()[3].is_ascii_digit());
+    match name.as_str() {
+        "" | "." | ".." => "untitled".to_string(),
+        _ if reserved => format!("{}_", name),
+        _ => name,
+    }
+}
+
+// 与已用名称（不区分大小写）重复时在扩展名前追加 -2、-3 等后缀
+pub fn unique_name(used: &mut HashSet<String>, name: &str) -> String {
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 && !name[dot..].contains('/') => name.split_at(dot),
+        _ => (name, ""),
+    };
+    let mut unique = name.to_string();
+    let mut suffix = 2;
+    while !used.insert(unique.to_lowercase()) {
+        unique = format!("{}-{}{}", stem, suffix, extension);
+        suffix += 1;
+    }
+    unique
+}
+
+// 转为小写并用连字符连接字母数字，保留中文等非 ASCII 文字
+pub fn slugify(text: &str) -> String {
+    let slug = text
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else {
+        slug
+    }
+}
+
+// GitHub、Hugo、kramdown 风格的标题锚点：转小写，去掉标点，空格换成连字符
+pub fn heading_anchor(heading: &str) -> String {
+    heading
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric() || *c == ' ' || *c == '-' || *c == '_')
+        .map(|c| if c == ' ' { '-' } else { c })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chapter(source: &str, id: Option<&str>, title: Option<&str>) -> Chapter {
+        Chapter {
+            source: source.to_string(),
+            id: id.map(str::to_string),
+            title: title.map(str::to_string),
+            language: None,
+            markdown: String::new(),
+            references: Vec::new(),
+            anchors: Vec::new(),
+            link_targets: Vec::new(),
+        }
+    }
+
+    fn message(error: EpubToMdError) -> String {
+        match error {
+            EpubToMdError::ArgumentError(message) => message,
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn name_template_placeholders() {
+        let chapters = [
+            chapter("text/ch1.xhtml", Some("c1"), Some("The Beginning: Part 1")),
+            chapter("text/ch2.xhtml", None, None),
+        ];
+        let template: NameTemplate = "{book}/{index:03}-{slug}-{id}-{chapter}.md".parse().unwrap();
+        let names = chapter_file_names(&chapters, Some(&template), Some("My Book"), &[]).unwrap();
+        assert_eq!(
+            names,
+            ["my-book/001-the-beginning-part-1-c1-The Beginning_ Part 1.md", "my-book/002-ch2-ch2-ch2.md"]
+        );
+    }
+
+    #[test]
+    fn default_names_are_numbered_and_unique() {
+        let chapters = [chapter("a/intro.xhtml", None, None), chapter("b/intro.xhtml", None, None)];
+        let names = chapter_file_names(&chapters, None, None, &["02-INTRO.md"]).unwrap();
+        assert_eq!(names, ["01-intro.md", "02-intro-2.md"]);
+    }
+
+    #[test]
+    fn name_template_errors() {
+        let error = |template: &str| message(template.parse::<NameTemplate>().unwrap_err());
+        assert_eq!(error("{index"), "Unclosed placeholder in name template '{index'.");
+        assert_eq!(error("{index:x}"), "Invalid index width 'x' in name template.");
+        assert!(error("{title}").starts_with("Unknown placeholder '{title}' in name template"));
+        let template: NameTemplate = "../{slug}.md".parse().unwrap();
+        let chapters = [chapter("ch1.xhtml", None, Some("One"))];
+        let error = chapter_file_names(&chapters, Some(&template), None, &[]).unwrap_err();
+        assert_eq!(message(error), "Name template produced an unsafe path '../one.md'.");
+    }
+
+    #[test]
+    fn output_template_renders_metadata() {
+        let metadata = Metadata {
+            title: Some("A/B: C?".to_string()),
+            ..Metadata::default()
+        };
+        let template: OutputTemplate = "{author}/{title} ({input}).md".parse().unwrap();
+        let (path, chapters) = template.render(&metadata, "book").unwrap();
+        assert_eq!(path, PathBuf::from("Unknown/A_B_ C_ (book).md"));
+        assert!(chapters.is_none());
+
+        let template: OutputTemplate = "{title}/notes/{chapter}.md".parse().unwrap();
+        assert!(template.has_chapter());
+        let (path, chapters) = template.render(&metadata, "book").unwrap();
+        assert_eq!(path, PathBuf::from("A_B_ C_/notes"));
+        let names = chapter_file_names(&[chapter("ch1.xhtml", None, Some("One"))], chapters.as_ref(), None, &[]).unwrap();
+        assert_eq!(names, ["One.md"]);
+    }
+
+    #[test]
+    fn output_template_errors() {
+        let error = |template: &str| message(template.parse::<OutputTemplate>().unwrap_err());
+        assert_eq!(error("{title"), "Unclosed placeholder in output template '{title'.");
+        assert!(error("{slug}").starts_with("Unknown placeholder '{slug}' in output template"));
+        assert_eq!(error("{chapter}/{chapter}"), "Output template may contain {chapter} only once.");
+        let template: OutputTemplate = "../{title}.md".parse().unwrap();
+        let error = template.render(&Metadata::default(), "book").unwrap_err();
+        assert_eq!(message(error), "Output template produced an unsafe path '../book.md'.");
+    }
+
+    #[test]
+    fn file_names_are_sanitized() {
+        assert_eq!(sanitize_file_name("a/b\\c:d*e?f\"g<h>i|j"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(sanitize_file_name("  Title \n\t end.  "), "Title end");
+        assert_eq!(sanitize_file_name("con.md"), "con.md_");
+        assert_eq!(sanitize_file_name("COM1"), "COM1_");
+        assert_eq!(sanitize_file_name(".."), "untitled");
+        assert_eq!(sanitize_file_name(&"长".repeat(150)).chars().count(), MAX_FILE_NAME_CHARS);
+    }
+
+    #[test]
+    fn slugs_and_anchors() {
+        assert_eq!(slugify("Hello, World! 第一章"), "hello-world-第一章");
+        assert_eq!(slugify("?!"), "untitled");
+        assert_eq!(heading_anchor("1.2 What's New? (Draft)"), "12-whats-new-draft");
+        assert_eq!(heading_anchor("第一章 开始"), "第一章-开始");
+    }
+}
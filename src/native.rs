@@ -0,0 +1,353 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    bidi,
+    book::{Book, Chapter},
+    bouten,
+    chinese, cjk, dom,
+    epub::{self, Epub, ManifestItem},
+    error::{warn_or_fail, EpubToMdError},
+    fixed_layout, footnotes,
+    links::{self, Anchored},
+    markdown::{self, Converted, ReferenceKind},
+    matter, normalize,
+    options::{Chinese, Format, Normalization, Options, Order},
+    pages, picker,
+    progress::Progress,
+    toc::TocEntry,
+    vertical,
+};
+
+// 不依赖 pandoc，直接解析 EPUB 并逐个转换内容文档
+pub fn convert(epub_path: &Path, options: &Options) -> Result<Book, EpubToMdError> {
+    let mut epub = Epub::open(epub_path, options)?;
+
+    let (documents, toc) = match options.order {
+        // 按 spine 排列时目录只用于章节标题，读取失败不影响转换
+        Order::Spine => (epub.spine_documents(), epub.toc().unwrap_or_default()),
+        Order::Toc => {
+            let toc = epub.toc()?;
+            if toc.is_empty() {
+                log::warn!("{}", tr!("the EPUB has no table of contents, using spine order."));
+            }
+            (toc_order(epub.spine_documents(), &toc), toc)
+        }
+    };
+
+
+    if epub.has_fixed_layout() {
+        log::warn!("{}", tr!("fixed-layout EPUB detected, pre-paginated pages are emitted as one image per page."));
+    }
+
+    let mut loaded = Vec::new();
+    for item in documents {
+        let html_content = match epub.read_text(&item.path) {
+            Ok(html_content) => html_content,
+            // 抢救模式下跳过无法读取的章节，最后统一报告
+            Err(e) if options.recover => {
+                log::warn!("{}", e);
+                let prefix = format!("{}:", item.path);
+                if !epub.lost.iter().any(|lost| lost.starts_with(&prefix)) {
+                    epub.lost.push(format!("{} {}", prefix, e));
+                }
+                continue;
+            }
+            Err(e) => return Err(e),
+        };
+        if !options.recover {
+            check_document(&epub, &item, &html_content, options.strict)?;
+        }
+        loaded.push((item, html_content));
+    }
+
+    let read: Vec<String> = loaded.iter().map(|(item, _)| item.path.clone()).collect();
+    if options.skip_frontmatter || options.skip_backmatter {
+        let landmarks = epub.landmarks();
+        loaded = matter::skip(loaded, &landmarks, &toc, options.skip_frontmatter, options.skip_backmatter);
+    }
+    if !options.exclude_titles.is_empty() {
+        loaded = matter::exclude(loaded, &toc, &options.exclude_titles);
+        if loaded.is_empty() {
+            return Err(EpubToMdError::InputError(tr!("--exclude-title matches every chapter.")));
+        }
+    }
+    if options.interactive {
+        loaded = picker::pick(loaded, &toc)?;
+    }
+    // 被上面去掉的章节不在输出中，指向它们的链接只保留文字
+    let removed: HashSet<String> = read
+        .into_iter()
+        .filter(|path| !loaded.iter().any(|(item, _)| item.path == *path))
+        .collect();
+    if !removed.is_empty() {
+        for (item, html_content) in loaded.iter_mut() {
+            if let Some(unlinked) = links::unlink(&item.path, html_content, &removed) {
+                *html_content = unlinked;
+            }
+        }
+    }
+
+    // 竖排或从右向左翻页的书中文字本来就按阅读顺序排列，只需把竖排标点改为横排字形
+    if vertical::is_vertical(&mut epub, &loaded) {
+        log::info!("{}", tr!("Vertical writing mode detected, writing horizontal Markdown in reading order."));
+        for (_, html_content) in loaded.iter_mut() {
+            if let Some(horizontal) = vertical::horizontal(html_content) {
+                *html_content = horizontal;
+            }
+        }
+    }
+    if epub.rtl {
+        log::info!("{}", tr!("Right-to-left page progression detected, chapters are written in reading order."));
+    }
+    // 阿拉伯文、希伯来文等从右向左书写的文字保持逻辑顺序，只在方向相反的段落与行内文字处加上方向标记
+    let rtl = epub.metadata.language.as_deref().is_some_and(bidi::is_rtl_language);
+    if rtl {
+        log::info!("{}", tr!("Right-to-left language detected, adding direction marks where text changes direction."));
+    }
+    for (_, html_content) in loaded.iter_mut() {
+        if let Some(marked) = bidi::mark(html_content, rtl) {
+            *html_content = marked;
+        }
+    }
+
+    // 样式表中声明了着重号的 class，标出使用它们的元素
+    let mut stylesheets = epub.stylesheets();
+    stylesheets.extend(loaded.iter().map(|(_, html)| html.clone()));
+    let classes = bouten::emphasis_classes(&stylesheets);
+    for (_, html_content) in loaded.iter_mut() {
+        if let Some(marked) = bouten::mark(html_content, &classes) {
+            *html_content = marked;
+        }
+    }
+
+    // 页码列表可能指向任意元素，先在这些位置标出分页
+    let page_list = epub.page_list();
+    if options.page_markers.is_some() {
+        for (item, html_content) in loaded.iter_mut() {
+            if let Some(marked) = pages::mark(html_content, &item.path, &page_list) {
+                *html_content = marked;
+            }
+        }
+    }
+
+    // Unicode 规范化在简繁转换之前进行，分解的字符先组合起来
+    if options.unicode_normalize != Normalization::None {
+        for (_, html_content) in loaded.iter_mut() {
+            *html_content = normalize::normalize_html(html_content, options.unicode_normalize);
+        }
+    }
+
+    // 简繁转换、中文标点与中英文间距只改动文字节点，在生成锚点之前进行，标题与指向它的链接保持一致
+    if options.chinese != Chinese::None || cjk::enabled(options) {
+        for (_, html_content) in loaded.iter_mut() {
+            *html_content = chinese::convert_html(html_content, options.chinese);
+            if cjk::enabled(options) {
+                *html_content = cjk::convert_html(html_content, options);
+            }
+        }
+    }
+
+    // 脚注和书内链接可能跨文档引用，需要先看过全书再改写
+    let sources: Vec<(&str, &str)> = loaded
+        .iter()
+        .filter(|(item, _)| !epub.is_fixed_layout(item))
+        .map(|(item, html)| (item.path.as_str(), html.as_str()))
+        .collect();
+    let footnoted = footnotes::rewrite(&sources, options.citations);
+    // 其他输出格式有各自的链接写法，不需要插入锚点
+    let anchored = match options.format {
+        Format::Markdown | Format::Mdbook => {
+            let footnoted: Vec<(&str, &str)> =
+                sources.iter().zip(&footnoted).map(|((path, _), html)| (*path, html.as_str())).collect();
+            links::anchor(&footnoted)
+        }
+        _ => footnoted
+            .into_iter()
+            .map(|html| Anchored {
+                html,
+                targets: Vec::new(),
+            })
+            .collect(),
+    };
+    let mut rewritten: HashMap<String, Anchored> =
+        sources.iter().map(|(path, _)| path.to_string()).zip(anchored).collect();
+
+    let mut chapters = Vec::new();
+    let mut page_number = 0;
+    let mut progress = Progress::chapters(loaded.len());
+    for (item, html_content) in loaded {
+        let converted = if epub.is_fixed_layout(&item) {
+            page_number += 1;
+            Converted {
+                markdown: fixed_layout::gallery_page(&html_content, page_number, options),
+                references: Vec::new(),
+            }
+        } else {
+            let html = rewritten.get(&item.path).map_or(&html_content, |anchored| &anchored.html);
+            markdown::convert_document(html, &item.path, options)
+        };
+        progress.inc();
+        if converted.markdown.trim().is_empty() {
+            log::debug!("{}", tr!("Skipping {}, no text after conversion", item.path));
+        } else {
+            log::debug!("{}", tr!("Converted {} ({} lines)", item.path, converted.markdown.lines().count()));
+            let headings = headings(&html_content);
+            let title = TocEntry::flatten(&toc)
+                .into_iter()
+                .find(|entry| entry.path == item.path && !entry.title.is_empty())
+                .map(|entry| entry.title.clone())
+                .or_else(|| headings.first().map(|(_, text)| text.clone()));
+            let page_anchors = pages::page_anchors(&html_content, &item.path, &page_list);
+            let link_targets = rewritten.remove(&item.path).map(|anchored| anchored.targets).unwrap_or_default();
+            chapters.push(Chapter {
+                source: item.path,
+                id: Some(item.id),
+                title,
+                language: document_language(&html_content).or_else(|| epub.metadata.language.clone()),
+                markdown: converted.markdown,
+                references: converted.references,
+                anchors: headings
+                    .into_iter()
+                    .filter_map(|(id, text)| id.map(|id| (id, text)))
+                    .chain(page_anchors)
+                    .collect(),
+                link_targets,
+            });
+        }
+    }
+
+    progress.finish();
+
+    if options.recover && !epub.lost.is_empty() {
+        chapters.push(Chapter {
+            source: "recovery-report".to_string(),
+            id: None,
+            title: Some("Recovery report".to_string()),
+            language: None,
+            markdown: recovery_report(&epub.lost),
+            references: Vec::new(),
+            anchors: Vec::new(),
+            link_targets: Vec::new(),
+        });
+    }
+
+    // 只有需要复制或提取资源时才读取图片
+    let mut resources = BTreeMap::new();
+    if options.reads_images() {
+        let images = chapters
+            .iter()
+            .flat_map(|chapter| &chapter.references)
+            .filter(|reference| reference.kind == ReferenceKind::Image);
+        for reference in images {
+            if resources.contains_key(&reference.path) || !epub.contains(&reference.path) {
+                continue;
+            }
+            match epub.read_bytes(&reference.path) {
+                Ok(bytes) => {
+                    resources.insert(reference.path.clone(), bytes);
+                }
+                Err(e) => warn_or_fail(options.strict, e)?,
+            }
+        }
+    }
+
+    Ok(Book {
+        metadata: epub.metadata.clone(),
+        chapters,
+        toc,
+        resources,
+        images: Default::default(),
+    })
+}
+
+// 文档中所有 h1-h6 的 id 与文字
+fn headings(html: &str) -> Vec<(Option<String>, String)> {
+    let document = dom::parse(html);
+    dom::descendants(&document.document)
+        .iter()
+        .filter(|node| matches!(dom::tag_name(node).as_deref(), Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6")))
+        .map(|node| (dom::attr(node, "id"), dom::text_content(node)))
+        .filter(|(_, text)| !text.is_empty())
+        .collect()
+}
+
+// <html> 或 <body> 上的 xml:lang 或 lang
+fn document_language(html: &str) -> Option<String> {
+    let document = dom::parse(html);
+    dom::descendants(&document.document)
+        .iter()
+        .filter(|node| matches!(dom::tag_name(node).as_deref(), Some("html" | "body")))
+        .filter_map(|node| dom::attr(node, "lang"))
+        .map(|lang| lang.trim().to_string())
+        .rfind(|lang| !lang.is_empty())
+}
+
+fn recovery_report(lost: &[String]) -> String {
+    let mut report = String::from("---\n\n## Recovery report\n\nThe following content could not be recovered:\n");
+    for item in lost {
+        report.push_str(&format!("\n- {}", item));
+    }
+    report
+}
+
+// 检查 XHTML 是否为合法 XML，以及文档内的链接与资源是否存在于压缩包中
+fn check_document(epub: &Epub, item: &ManifestItem, html: &str, strict: bool) -> Result<(), EpubToMdError> {
+    if item.media_type == "application/xhtml+xml" {
+        if let Err(e) = epub::parse_xml(html, &item.path) {
+            warn_or_fail(strict, e)?;
+        }
+    }
+
+    let base_dir = epub::parent_dir(&item.path);
+    let document = dom::parse(html);
+    for node in dom::descendants(&document.document) {
+        let target = match dom::tag_name(&node).as_deref() {
+            Some("a") | Some("link") => dom::attr(&node, "href"),
+            Some("img") | Some("image") | Some("source") | Some("audio") | Some("video") => {
+                dom::attr(&node, "src").or_else(|| dom::attr(&node, "href"))
+            }
+            _ => None,
+        };
+        // 跳过外部链接、页内锚点和 data: URI
+        let Some(target) = target.filter(|t| !t.is_empty() && !t.starts_with('#') && !t.contains(':')) else {
+            continue;
+        };
+        let path = epub::resolve_href(base_dir, &target);
+        if !epub.contains(&path) {
+            warn_or_fail(
+                strict,
+                EpubToMdError::EpubError(tr!("Broken link in '{}': {}", item.path, target)),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// 按目录中首次出现的顺序排列文档；目录未引用的文档紧跟在其 spine 前一项之后
+fn toc_order(spine_documents: Vec<ManifestItem>, toc: &[TocEntry]) -> Vec<ManifestItem> {
+    let mut seen = HashSet::new();
+    let mut ordered: Vec<ManifestItem> = TocEntry::flatten(toc)
+        .into_iter()
+        .filter(|entry| seen.insert(entry.path.as_str()))
+        .filter_map(|entry| spine_documents.iter().find(|item| item.path == entry.path))
+        .cloned()
+        .collect();
+
+    for (i, item) in spine_documents.iter().enumerate() {
+        if ordered.iter().any(|ordered_item| ordered_item.path == item.path) {
+            continue;
+        }
+        let position = match i.checked_sub(1) {
+            Some(prev) => ordered
+                .iter()
+                .position(|ordered_item| ordered_item.path == spine_documents[prev].path)
+                .map_or(0, |p| p + 1),
+            None => 0,
+        };
+        ordered.insert(position, item.clone());
+    }
+    ordered
+}
@@ -0,0 +1,94 @@
+use markup5ever_rcdom::{Handle, NodeData};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{book::Book, dom, options::Normalization, toc::TocEntry};
+
+// 图片说明与提示文字同样输出到 Markdown 中
+const TEXT_ATTRIBUTES: &[&str] = &["alt", "title"];
+
+// 纯 ASCII 文字在各种形式下都不变
+pub fn normalize(text: &str, form: Normalization) -> String {
+    match form {
+        Normalization::None => text.to_string(),
+        _ if text.is_ascii() => text.to_string(),
+        Normalization::Nfc => text.nfc().collect(),
+        Normalization::Nfkc => text.nfkc().collect(),
+    }
+}
+
+fn normalize_children(node: &Handle, form: Normalization) {
+    for child in node.children.borrow().iter() {
+        match &child.data {
+            NodeData::Text { contents } => {
+                let normalized = normalize(&contents.borrow(), form);
+                *contents.borrow_mut() = normalized.as_str().into();
+            }
+            NodeData::Element { .. } => {
+                for name in TEXT_ATTRIBUTES {
+                    if let Some(value) = dom::attr(child, name) {
+                        dom::set_attr(child, name, &normalize(&value, form));
+                    }
+                }
+                normalize_children(child, form);
+            }
+            _ => {}
+        }
+    }
+}
+
+// 按 --unicode-normalize 规范化正文文字，链接地址与图片路径不变，仍与书中的文件名对应
+pub fn normalize_html(html: &str, form: Normalization) -> String {
+    if form == Normalization::None {
+        return html.to_string();
+    }
+    let document = dom::parse(html);
+    normalize_children(&document.document, form);
+    dom::inner_html(&document.document)
+}
+
+fn normalize_toc(entries: &mut [TocEntry], form: Normalization) {
+    for entry in entries {
+        entry.title = normalize(&entry.title, form);
+        normalize_toc(&mut entry.children, form);
+    }
+}
+
+// 书名、作者等元数据以及目录与章节标题，正文已在转换前按文档规范化
+pub fn normalize_book(book: &mut Book, form: Normalization) {
+    if form == Normalization::None {
+        return;
+    }
+    let metadata = &mut book.metadata;
+    for value in metadata.title.iter_mut().chain(&mut metadata.publisher).chain(&mut metadata.description) {
+        *value = normalize(value, form);
+    }
+    for value in metadata.authors.iter_mut().chain(&mut metadata.subjects) {
+        *value = normalize(value, form);
+    }
+    for chapter in &mut book.chapters {
+        if let Some(title) = chapter.title.as_mut() {
+            *title = normalize(title, form);
+        }
+    }
+    normalize_toc(&mut book.toc, form);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_and_folds_compatibility_characters() {
+        assert_eq!(normalize("e\u{302}\u{301}", Normalization::Nfc), "ế");
+        assert_eq!(normalize("\u{1100}\u{1161}\u{11A8}", Normalization::Nfc), "각");
+        assert_eq!(normalize("Ａ１ﬁ²ｶﾞ", Normalization::Nfc), "Ａ１ﬁ²ｶﾞ");
+        assert_eq!(normalize("Ａ１ﬁ²ｶﾞ", Normalization::Nfkc), "A1fi2ガ");
+        assert_eq!(normalize("e\u{301}", Normalization::None), "e\u{301}");
+    }
+
+    #[test]
+    fn html_links_keep_their_form() {
+        let html = normalize_html(r#"<p><a href="e&#x301;.xhtml" title="e&#x301;">e&#x301;</a></p>"#, Normalization::Nfc);
+        assert!(html.contains("href=\"e\u{301}.xhtml\" title=\"é\">é</a>"), "{}", html);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    book::{Book, Chapter},
+    error::EpubToMdError,
+    frontmatter::Frontmatter,
+    links,
+    markdown::{self, Reference, ReferenceKind},
+    naming,
+    options::Options,
+    output::{self, write_text},
+};
+
+const ATTACHMENTS_DIR: &str = "attachments";
+
+// 写出 Obsidian 笔记库：每章一篇带 YAML frontmatter 的笔记，书内链接改为 [[wikilink]]，图片复制到 attachments/
+pub fn write_vault(out_dir: &Path, book: &Book, options: &Options) -> Result<(), EpubToMdError> {
+    let file_names = naming::chapter_file_names(
+        &book.chapters,
+        options.name_template.as_ref(),
+        book.metadata.title.as_deref(),
+        &[],
+    )?;
+    let notes: Vec<&str> = file_names
+        .iter()
+        .map(|name| name.strip_suffix(".md").unwrap_or(name))
+        .collect();
+    let attachments = attachment_names(book);
+
+    let mut written = HashSet::new();
+    for (resource, name) in &attachments {
+        if !written.insert(name) {
+            continue;
+        }
+        let path = out_dir.join(ATTACHMENTS_DIR).join(name);
+        output::write_resource(&path, &book.resources[*resource], |e| {
+            EpubToMdError::FileIOError(tr!("Failed to write attachment '{}': {}", path.display(), e))
+        })?;
+    }
+
+    let tags: Vec<String> = book.metadata.subjects.iter().map(|subject| tag(subject)).collect();
+    for (i, (chapter, file_name)) in book.chapters.iter().zip(&file_names).enumerate() {
+        let body = markdown::render_references(&chapter.markdown, &chapter.references, &|reference, text| {
+            render(reference, text, i, book, &notes, &attachments)
+        });
+        let (body, _) = links::restyle(&body, options.link_style, 1);
+        let mut frontmatter = Frontmatter::default();
+        frontmatter
+            .string("title", Some(chapter.title.as_deref().unwrap_or(notes[i])))
+            .list("author", &book.metadata.authors)
+            .list("tags", &tags)
+            .string("lang", chapter.language.as_deref());
+
+        let path = out_dir.join(file_name);
+        output::create_parent_dir(&path)?;
+        write_text(&path, &frontmatter.prepend(options.frontmatter_format(), &body), options)?;
+    }
+    Ok(())
+}
+
+// 按文件名为每个资源分配附件名，重名时追加 -2、-3 等后缀；内容相同的资源共用首次出现时的附件
+fn attachment_names(book: &Book) -> HashMap<&str, String> {
+    let mut used = HashSet::new();
+    let mut by_content: HashMap<&[u8], String> = HashMap::new();
+    let mut names = HashMap::new();
+    for (resource, bytes) in book.resources_in_order() {
+        let file_name = resource.rsplit('/').next().unwrap_or(resource);
+        let name = by_content
+            .entry(bytes)
+            .or_insert_with(|| naming::unique_name(&mut used, file_name));
+        names.insert(resource, name.clone());
+    }
+    names
+}
+
+fn render(
+    reference: &Reference,
+    text: &str,
+    current: usize,
+    book: &Book,
+    notes: &[&str],
+    attachments: &HashMap<&str, String>,
+) -> String {
+    let attachment = attachments.get(reference.path.as_str());
+    match reference.kind {
+        ReferenceKind::Image => match attachment {
+            Some(name) => {
+                let alt = clean_link_text(text);
+                // 纯数字会被 Obsidian 当作图片宽度
+                if alt.is_empty() || alt.chars().all(|c| c.is_ascii_digit()) {
+                    format!("![[{}/{}]]", ATTACHMENTS_DIR, name)
+                } else {
+                    format!("![[{}/{}|{}]]", ATTACHMENTS_DIR, name, alt)
+                }
+            }
+            None => reference.to_markdown(text),
+        },
+        ReferenceKind::Link => {
+            let Some(target) = book.chapter_index(reference) else {
+                return match attachment {
+                    Some(name) => format!("[{}]({}/{})", text, ATTACHMENTS_DIR, name.replace(' ', "%20")),
+                    None => reference.to_markdown(text),
+                };
+            };
+            let note = if target == current { "" } else { notes[target] };
+            let link = match heading_for(&book.chapters[target], reference) {
+                Some(heading) => format!("{}#{}", note, heading),
+                None if note.is_empty() => return text.to_string(),
+                None => note.to_string(),
+            };
+            // 链接文字中有方括号或竖线（例如嵌套图片）时无法作为 wikilink 别名
+            if text.contains(['[', ']', '|']) {
+                format!("[{}]({})", text, link.replace(' ', "%20").replace('#', ".md#"))
+            } else if text.is_empty() || text == link {
+                format!("[[{}]]", link)
+            } else {
+                format!("[[{}|{}]]", link, text)
+            }
+        }
+    }
+}
+
+// 片段 id 指向标题时链接到该标题，Obsidian 会把标题中的 # | ^ : 等字符视为空格
+fn heading_for(chapter: &Chapter, reference: &Reference) -> Option<String> {
+    let heading = chapter.anchor_heading(reference.fragment.as_deref()?)?;
+    let heading = heading.replace(['#', '|', '^', ':', '[', ']'], " ");
+    Some(heading.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn clean_link_text(text: &str) -> String {
+    text.replace(['[', ']', '|'], "").trim().to_string()
+}
+
+// Obsidian 标签不能包含空格和大部分标点
+fn tag(subject: &str) -> String {
+    subject
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '-' || c == '/'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
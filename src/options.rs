@@ -0,0 +1,1132 @@
+use std::{env, path::PathBuf, str::FromStr};
+
+use regex::Regex;
+
+use crate::{
+    error::EpubToMdError,
+    naming::{NameTemplate, OutputTemplate},
+    pandoc::PandocVersion,
+};
+
+// 解析带单位的大小，例如 500KB、64MiB、1G；不带单位时按字节计
+pub fn parse_size(s: &str) -> Result<u64, EpubToMdError> {
+    let invalid = || EpubToMdError::ArgumentError(tr!("Invalid size '{}'.", s));
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        _ => return Err(invalid()),
+    };
+    number.checked_mul(multiplier).ok_or_else(invalid)
+}
+
+// 转换后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    // pandoc 直接输出 Markdown
+    Pandoc,
+    // 内置 EPUB 解析器，不依赖 pandoc
+    Native,
+    // pandoc 输出 HTML，再由 html2md 转换
+    Html2md,
+}
+
+impl FromStr for Engine {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pandoc" => Ok(Engine::Pandoc),
+            "native" => Ok(Engine::Native),
+            "html2md" => Ok(Engine::Html2md),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown engine '{}', expected one of: pandoc, native, html2md.",
+                s
+            ))),
+        }
+    }
+}
+
+// pandoc 后端输出的 Markdown 方言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MdFlavor {
+    #[default]
+    Gfm,
+    CommonmarkX,
+    MarkdownStrict,
+}
+
+impl MdFlavor {
+    // 对应的 pandoc `-t` 输出格式名
+    pub fn pandoc_format(self) -> &'static str {
+        match self {
+            MdFlavor::Gfm => "gfm",
+            MdFlavor::CommonmarkX => "commonmark_x",
+            MdFlavor::MarkdownStrict => "markdown_strict",
+        }
+    }
+}
+
+impl FromStr for MdFlavor {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gfm" => Ok(MdFlavor::Gfm),
+            "commonmark_x" => Ok(MdFlavor::CommonmarkX),
+            "markdown_strict" => Ok(MdFlavor::MarkdownStrict),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown markdown flavor '{}', expected one of: gfm, commonmark_x, markdown_strict.",
+                s
+            ))),
+        }
+    }
+}
+
+// 输出章节的排列顺序
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Order {
+    #[default]
+    Spine,
+    Toc,
+}
+
+impl FromStr for Order {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spine" => Ok(Order::Spine),
+            "toc" => Ok(Order::Toc),
+            _ => Err(EpubToMdError::ArgumentError(tr!("Unknown order '{}', expected spine or toc.", s))),
+        }
+    }
+}
+
+// 输出拆分方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Split {
+    // 合并为单个文件
+    #[default]
+    None,
+    // 每个内容文档写成一个文件
+    Chapter,
+    // 在不高于该级别的标题处切分，与内容文档的划分无关
+    Heading(usize),
+}
+
+impl FromStr for Split {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Split::None),
+            "chapter" => Ok(Split::Chapter),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Ok(Split::Heading(s[1..].parse().unwrap_or(1))),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown split mode '{}', expected none, chapter or h1-h6.",
+                s
+            ))),
+        }
+    }
+}
+
+// 输出布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    // 普通 Markdown 文件
+    #[default]
+    Markdown,
+    // mdBook 项目：book.toml 与 src/SUMMARY.md
+    Mdbook,
+    // Obsidian 笔记库：每章一篇笔记，书内链接改为 [[wikilink]]，图片放在 attachments/
+    Obsidian,
+    // Hugo 内容目录：_index.md 加每章一个页面包，图片作为页面资源
+    Hugo,
+    // Jekyll 页面目录：index.md 加每章一个 <name>/index.md
+    Jekyll,
+    // Zola 章节：_index.md 加每章一个带同目录资源的页面，TOML frontmatter
+    Zola,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Markdown => "markdown",
+            Format::Mdbook => "mdbook",
+            Format::Obsidian => "obsidian",
+            Format::Hugo => "hugo",
+            Format::Jekyll => "jekyll",
+            Format::Zola => "zola",
+        }
+    }
+
+    // 是否需要把书中的图片复制到输出目录
+    pub fn copies_resources(self) -> bool {
+        matches!(self, Format::Obsidian | Format::Hugo | Format::Jekyll | Format::Zola)
+    }
+
+    // 目标工具能识别的 frontmatter 格式：mdBook 不识别 frontmatter，Obsidian 与 Jekyll 只认 YAML，Zola 使用 TOML
+    pub fn supports_frontmatter(self, frontmatter: FrontmatterFormat) -> bool {
+        match self {
+            Format::Markdown | Format::Hugo => true,
+            Format::Mdbook => frontmatter == FrontmatterFormat::None,
+            Format::Obsidian | Format::Jekyll => matches!(frontmatter, FrontmatterFormat::Yaml | FrontmatterFormat::None),
+            Format::Zola => matches!(frontmatter, FrontmatterFormat::Toml | FrontmatterFormat::None),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(Format::Markdown),
+            "mdbook" => Ok(Format::Mdbook),
+            "obsidian" => Ok(Format::Obsidian),
+            "hugo" => Ok(Format::Hugo),
+            "jekyll" => Ok(Format::Jekyll),
+            "zola" => Ok(Format::Zola),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown format '{}', expected one of: markdown, mdbook, obsidian, hugo, jekyll, zola.",
+                s
+            ))),
+        }
+    }
+}
+
+// 单文件输出时章节之间的分隔
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Separator {
+    // 只留空行
+    #[default]
+    None,
+    // Markdown 分隔线 ---
+    Rule,
+    // <!-- pagebreak --> 注释，便于后续按页拆分或打印
+    PageBreak,
+    Custom(String),
+}
+
+impl Separator {
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Separator::None => None,
+            Separator::Rule => Some("---"),
+            Separator::PageBreak => Some("<!-- pagebreak -->"),
+            Separator::Custom(text) => Some(text),
+        }
+    }
+}
+
+impl FromStr for Separator {
+    type Err = EpubToMdError;
+
+    // 其他值都作为自定义分隔文字，其中的 \n 表示换行
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Separator::None),
+            "hr" | "---" => Ok(Separator::Rule),
+            "pagebreak" => Ok(Separator::PageBreak),
+            _ => Ok(Separator::Custom(s.replace("\\n", "\n"))),
+        }
+    }
+}
+
+// 章节文件开头元数据块的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrontmatterFormat {
+    // --- 包围的 YAML
+    Yaml,
+    // +++ 包围的 TOML
+    Toml,
+    // 文件开头的 JSON 对象（Hugo 支持）
+    Json,
+    None,
+}
+
+impl FrontmatterFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            FrontmatterFormat::Yaml => "yaml",
+            FrontmatterFormat::Toml => "toml",
+            FrontmatterFormat::Json => "json",
+            FrontmatterFormat::None => "none",
+        }
+    }
+}
+
+impl FromStr for FrontmatterFormat {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yaml" | "yml" => Ok(FrontmatterFormat::Yaml),
+            "toml" => Ok(FrontmatterFormat::Toml),
+            "json" => Ok(FrontmatterFormat::Json),
+            "none" => Ok(FrontmatterFormat::None),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown frontmatter format '{}', expected one of: yaml, toml, json, none.",
+                s
+            ))),
+        }
+    }
+}
+
+// 写出 Markdown 时使用的换行符
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Eol {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl FromStr for Eol {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(Eol::Lf),
+            "crlf" => Ok(Eol::Crlf),
+            _ => Err(EpubToMdError::ArgumentError(tr!("Unknown line ending '{}', expected lf or crlf.", s))),
+        }
+    }
+}
+
+// 链接的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LinkStyle {
+    // [文字](地址)
+    #[default]
+    Inline,
+    // [文字][n]，地址作为编号的定义放在章节末尾
+    Reference,
+}
+
+impl FromStr for LinkStyle {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "inline" => Ok(LinkStyle::Inline),
+            "reference" => Ok(LinkStyle::Reference),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown link style '{}', expected inline or reference.",
+                s
+            ))),
+        }
+    }
+}
+
+// 段落的换行方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    // 每个段落一行
+    None,
+    // 在给定列数之前换行
+    Columns(usize),
+}
+
+impl FromStr for Wrap {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Wrap::None),
+            _ => s.parse().ok().filter(|&columns| columns > 0).map(Wrap::Columns).ok_or_else(|| {
+                EpubToMdError::ArgumentError(tr!("Invalid wrap '{}', expected none or a column number.", s))
+            }),
+        }
+    }
+}
+
+// 书籍元数据附带文件的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetadataFormat {
+    Json,
+    Yaml,
+}
+
+impl MetadataFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            MetadataFormat::Json => "json",
+            MetadataFormat::Yaml => "yaml",
+        }
+    }
+}
+
+impl FromStr for MetadataFormat {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(MetadataFormat::Json),
+            "yaml" | "yml" => Ok(MetadataFormat::Yaml),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown metadata format '{}', expected one of: json, yaml.",
+                s
+            ))),
+        }
+    }
+}
+
+// 提取图片时重新编码的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    // 无损 WebP
+    Webp,
+    Jpeg,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Webp => "webp",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Png => "png",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "webp" => Ok(ImageFormat::Webp),
+            "jpeg" | "jpg" => Ok(ImageFormat::Jpeg),
+            "png" => Ok(ImageFormat::Png),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown image format '{}', expected one of: webp, jpeg, png.",
+                s
+            ))),
+        }
+    }
+}
+
+// <figure> 中 <figcaption> 的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptionStyle {
+    // 图片下方单独一行斜体文字
+    #[default]
+    Italic,
+    // 图片下方单独一行普通文字
+    Plain,
+    // 只作为图片的替代文字，许多渲染器会把单独成段图片的替代文字显示为图注
+    Alt,
+}
+
+impl FromStr for CaptionStyle {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "italic" => Ok(CaptionStyle::Italic),
+            "plain" => Ok(CaptionStyle::Plain),
+            "alt" => Ok(CaptionStyle::Alt),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown caption style '{}', expected one of: italic, plain, alt.",
+                s
+            ))),
+        }
+    }
+}
+
+// 引号、破折号与省略号的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Typography {
+    // 保持转换结果不变
+    #[default]
+    Keep,
+    // 改为弯引号、— – 与 …
+    Smart,
+    // 改为 ASCII 的直引号、--- -- 与 ...
+    Straight,
+}
+
+impl FromStr for Typography {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Typography::Keep),
+            "smart" => Ok(Typography::Smart),
+            "straight" => Ok(Typography::Straight),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown typography '{}', expected one of: smart, straight, keep.",
+                s
+            ))),
+        }
+    }
+}
+
+// --unicode-normalize 的规范化形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Normalization {
+    #[default]
+    None,
+    Nfc,
+    // 同时把全角字母、连字、上标数字等兼容字符改为普通字符
+    Nfkc,
+}
+
+impl FromStr for Normalization {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Normalization::None),
+            "nfc" => Ok(Normalization::Nfc),
+            "nfkc" => Ok(Normalization::Nfkc),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown Unicode normalization '{}', expected one of: nfc, nfkc, none.",
+                s
+            ))),
+        }
+    }
+}
+
+// 带着重号（日文的傍点）的文字写成的样式
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Bouten {
+    #[default]
+    Bold,
+    Emphasis,
+    // 只保留文字
+    Plain,
+    // 含有 {text} 的模板，例如 =={text}== 或 <span class="sesame">{text}</span>
+    Template(String),
+}
+
+impl FromStr for Bouten {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bold" => Ok(Bouten::Bold),
+            "emphasis" => Ok(Bouten::Emphasis),
+            "plain" => Ok(Bouten::Plain),
+            _ if s.contains("{text}") => Ok(Bouten::Template(s.to_string())),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown bouten style '{}', expected one of: bold, emphasis, plain, or a template containing {{text}}.",
+                s
+            ))),
+        }
+    }
+}
+
+// 中文标点的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Punctuation {
+    #[default]
+    Keep,
+    // 中文之后的半角标点改为全角
+    Full,
+    // 全角标点改为半角
+    Half,
+}
+
+impl FromStr for Punctuation {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Punctuation::Keep),
+            "full" => Ok(Punctuation::Full),
+            "half" => Ok(Punctuation::Half),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown punctuation style '{}', expected one of: full, half, keep.",
+                s
+            ))),
+        }
+    }
+}
+
+// 中文简繁转换的方向
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Chinese {
+    #[default]
+    None,
+    // 简体转繁体
+    S2t,
+    // 繁体转简体
+    T2s,
+}
+
+impl FromStr for Chinese {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Chinese::None),
+            "s2t" => Ok(Chinese::S2t),
+            "t2s" => Ok(Chinese::T2s),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown Chinese conversion '{}', expected one of: s2t, t2s, none.",
+                s
+            ))),
+        }
+    }
+}
+
+// 内置 HTML 转换输出的 Markdown 方言，决定表格、脚注、公式、删除线与锚点的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+    // 不支持表格、脚注与删除线，改用 HTML
+    Commonmark,
+    Gfm,
+    // 锚点写成 []{#id}
+    Pandoc,
+    // 公式写成 \\(...\\)，删除线用 HTML
+    Multimarkdown,
+    Obsidian,
+}
+
+impl Flavor {
+    pub fn pipe_tables(self) -> bool {
+        self != Flavor::Commonmark
+    }
+
+    pub fn footnotes(self) -> bool {
+        self != Flavor::Commonmark
+    }
+
+    pub fn strikethrough(self) -> bool {
+        matches!(self, Flavor::Gfm | Flavor::Pandoc | Flavor::Obsidian)
+    }
+
+    // 行内公式或公式块；CommonMark 本身没有公式，写成 GitHub 与 GitLab 都支持的 $`...`$ 和 ```math 代码块
+    pub fn math(self, latex: &str, display: bool) -> String {
+        match (self, display) {
+            (Flavor::Commonmark, false) => format!("$`{}`$", latex),
+            (Flavor::Commonmark, true) => format!("```math\n{}\n```", latex),
+            (Flavor::Multimarkdown, false) => format!("\\\\({}\\\\)", latex),
+            (Flavor::Multimarkdown, true) => format!("\\\\[\n{}\n\\\\]", latex),
+            (_, false) => format!("${}$", latex),
+            (_, true) => format!("$$\n{}\n$$", latex),
+        }
+    }
+
+    // 书内链接目标处的空锚点
+    pub fn anchor(self, id: &str) -> String {
+        match self {
+            Flavor::Pandoc => format!("[]{{#{}}}", id),
+            _ => format!("<a id=\"{}\"></a>", id),
+        }
+    }
+}
+
+impl FromStr for Flavor {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "commonmark" => Ok(Flavor::Commonmark),
+            "gfm" => Ok(Flavor::Gfm),
+            "pandoc" => Ok(Flavor::Pandoc),
+            "multimarkdown" => Ok(Flavor::Multimarkdown),
+            "obsidian" => Ok(Flavor::Obsidian),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown Markdown flavor '{}', expected one of: commonmark, gfm, pandoc, multimarkdown, obsidian.",
+                s
+            ))),
+        }
+    }
+}
+
+// 参考文献引用（epub:type="biblioref"）的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Citations {
+    // 保留参考文献一节，引用链接指向其中的条目
+    #[default]
+    Link,
+    // 被引用的条目改为脚注，未被引用的留在参考文献一节中
+    Footnote,
+}
+
+impl FromStr for Citations {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "link" => Ok(Citations::Link),
+            "footnote" => Ok(Citations::Footnote),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown citation style '{}', expected one of: link, footnote.",
+                s
+            ))),
+        }
+    }
+}
+
+// <u>、<ins> 与带下划线样式的文字的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Underline {
+    // 行内 HTML <u>
+    #[default]
+    Html,
+    // *强调*
+    Emphasis,
+    // 普通文字
+    Plain,
+}
+
+impl FromStr for Underline {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(Underline::Html),
+            "emphasis" => Ok(Underline::Emphasis),
+            "plain" => Ok(Underline::Plain),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown underline style '{}', expected one of: html, emphasis, plain.",
+                s
+            ))),
+        }
+    }
+}
+
+// <sup> / <sub> 的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SupSubStyle {
+    // 行内 HTML
+    #[default]
+    Html,
+    // Pandoc 与 markdown-it 插件的 ^上标^ / ~下标~
+    Caret,
+    // Unicode 上下标字符，没有对应字符时退回 HTML
+    Unicode,
+}
+
+impl FromStr for SupSubStyle {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(SupSubStyle::Html),
+            "caret" => Ok(SupSubStyle::Caret),
+            "unicode" => Ok(SupSubStyle::Unicode),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown superscript style '{}', expected one of: html, caret, unicode.",
+                s
+            ))),
+        }
+    }
+}
+
+// 硬换行的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineBreak {
+    // 行尾两个空格
+    #[default]
+    Spaces,
+    // 行尾反斜杠，不会被编辑器去掉行尾空白时误删
+    Backslash,
+}
+
+impl FromStr for LineBreak {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "spaces" => Ok(LineBreak::Spaces),
+            "backslash" => Ok(LineBreak::Backslash),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown line break style '{}', expected one of: spaces, backslash.",
+                s
+            ))),
+        }
+    }
+}
+
+// <dl> 定义列表的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionListStyle {
+    // Pandoc、PHP Markdown Extra、kramdown 与 Goldmark 支持的 术语 / :   释义
+    Colon,
+    // 加粗的术语作为列表项，任何 Markdown 渲染器都能显示
+    Bold,
+}
+
+impl FromStr for DefinitionListStyle {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "colon" => Ok(DefinitionListStyle::Colon),
+            "bold" => Ok(DefinitionListStyle::Bold),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown definition list style '{}', expected one of: colon, bold.",
+                s
+            ))),
+        }
+    }
+}
+
+// <ruby> 注音的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RubyStyle {
+    // 漢字（かんじ）
+    #[default]
+    Paren,
+    // 原样保留 <ruby> 元素
+    Html,
+    // 只保留被注字
+    Base,
+}
+
+impl FromStr for RubyStyle {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "paren" => Ok(RubyStyle::Paren),
+            "html" => Ok(RubyStyle::Html),
+            "base" => Ok(RubyStyle::Base),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown ruby style '{}', expected one of: paren, html, base.",
+                s
+            ))),
+        }
+    }
+}
+
+// 提示框（epub:type="note"/"tip"/"warning" 等）的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AdmonitionStyle {
+    // GitHub、Obsidian 与 mdBook 支持的 > [!NOTE]
+    #[default]
+    Github,
+    // Pandoc、VitePress 与 Docusaurus 的 ::: note
+    Colon,
+    // mdbook-admonish 的 ```admonish note 代码块
+    Admonish,
+    // 只转换其中的文字
+    None,
+}
+
+impl FromStr for AdmonitionStyle {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "github" => Ok(AdmonitionStyle::Github),
+            "colon" => Ok(AdmonitionStyle::Colon),
+            "admonish" => Ok(AdmonitionStyle::Admonish),
+            "none" => Ok(AdmonitionStyle::None),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown admonition style '{}', expected one of: github, colon, admonish, none.",
+                s
+            ))),
+        }
+    }
+}
+
+// Markdown 无法表达的元素（<aside>、<details>、自定义元素等）的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownHtml {
+    // 原样保留为 HTML
+    Keep,
+    // 连同内容一起删除
+    Strip,
+    // 放进 HTML 注释，便于之后检查
+    Comment,
+}
+
+impl FromStr for UnknownHtml {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(UnknownHtml::Keep),
+            "strip" => Ok(UnknownHtml::Strip),
+            "comment" => Ok(UnknownHtml::Comment),
+            _ => Err(EpubToMdError::ArgumentError(tr!(
+                "Unknown HTML policy '{}', expected one of: keep, strip, comment.",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    // 未指定时，安装了 pandoc 就使用 html2md 后端，否则使用内置解析器
+    pub engine: Option<Engine>,
+    // 仅对 pandoc 后端有效；指定后未给出 --engine 时默认使用 pandoc 后端
+    pub md_flavor: Option<MdFlavor>,
+    // 指定 pandoc 可执行文件，优先级高于 EPUB2MD_PANDOC 环境变量
+    pub pandoc_path: Option<PathBuf>,
+    // 追加到 pandoc 命令末尾的额外参数
+    pub pandoc_args: Vec<String>,
+    // 按顺序传给 pandoc 的 Lua 过滤器
+    pub lua_filters: Vec<PathBuf>,
+    // 要求的最低 pandoc 版本，未指定时为 2.0
+    pub min_pandoc_version: Option<PandocVersion>,
+    pub order: Order,
+    // 尽力从损坏的压缩包中抢救内容
+    pub recover: bool,
+    // 单个条目与全部条目解压后的大小上限
+    pub max_entry_size: Option<u64>,
+    pub max_total_size: Option<u64>,
+    // container.xml 中要转换的 rootfile（序号或路径）
+    pub rootfile: Option<String>,
+    // 遇到不规范的 OPF/XHTML、缺失的 manifest 条目或失效链接时中止，而不是警告后继续
+    pub strict: bool,
+    // 按章节拆分时输出路径为目录
+    pub split: Split,
+    // 拆分输出时的文件名模板
+    pub name_template: Option<NameTemplate>,
+    // 未指定输出路径时由书的元数据生成路径，例如 "{author}/{title}.md"
+    pub output_template: Option<OutputTemplate>,
+    pub format: Format,
+    pub separator: Separator,
+    // 章节不以标题开头时插入目录中的章节标题
+    pub chapter_headings: bool,
+    // 单个输出文件的大小上限，超过时在段落边界处拆分
+    pub max_file_size: Option<u64>,
+    // 把生成的文件打包成 .zip / .tar / .tar.gz，而不是写到输出路径
+    pub archive: Option<PathBuf>,
+    // 拆分输出时索引页的文件名，默认为 index.md
+    pub index_name: Option<String>,
+    // 保证多次运行、不同机器上的输出逐字节一致：打包时不记录时间戳与属主，临时文件使用固定名称
+    pub deterministic: bool,
+    // 在输出旁边写出 book.json / book.yaml，包含完整的书籍元数据和章节列表
+    pub metadata_file: Option<MetadataFormat>,
+    // 未指定时按输出格式选择：拆分的 Markdown、Obsidian、Hugo、Jekyll 用 YAML，Zola 用 TOML，其余不写
+    pub frontmatter: Option<FrontmatterFormat>,
+    pub eol: Eol,
+    // 在 Markdown 文件开头写入 UTF-8 BOM，部分 Windows 旧工具需要它识别编码
+    pub bom: bool,
+    // 覆盖已存在的输出
+    pub force: bool,
+    // 输出比输入 EPUB 新时跳过转换，输出过期时覆盖
+    pub update: bool,
+    // 把引用的图片提取到 Markdown 旁边的该目录中，并改写图片链接
+    pub extract_images: Option<PathBuf>,
+    // 不超过该大小的图片以 data: URI 内嵌到 Markdown 中
+    pub embed_images: Option<u64>,
+    // 提取或内嵌前把位图重新编码为该格式
+    pub image_format: Option<ImageFormat>,
+    // 宽度超过该值的位图按比例缩小
+    pub max_image_width: Option<u32>,
+    pub caption_style: CaptionStyle,
+    // 每章最高一级的标题变为 H1，下级标题不跳级；合并为单个文件时书名为唯一的 H1
+    pub normalize_headings: bool,
+    // 所有标题级别加上该值，正数降低级别
+    pub heading_offset: i32,
+    pub typography: Typography,
+    // 写出前不整理 Markdown，保留转换器的原始输出
+    pub no_format: bool,
+    // 未指定时保持转换器的换行
+    pub wrap: Option<Wrap>,
+    pub link_style: LinkStyle,
+    // 未指定时只转换其中的文字
+    pub unknown_html: Option<UnknownHtml>,
+    pub admonitions: AdmonitionStyle,
+    pub ruby: RubyStyle,
+    // 未指定时由 --format 决定
+    pub definition_lists: Option<DefinitionListStyle>,
+    pub line_breaks: LineBreak,
+    pub sup_sub: SupSubStyle,
+    pub underline: Underline,
+    // 在分页位置写出页码标记，{page} 替换为页码
+    pub page_markers: Option<String>,
+    pub citations: Citations,
+    // 按地标、<guide> 与标题去掉开头和结尾的版权页、献词、作者简介等样板页
+    pub skip_frontmatter: bool,
+    pub skip_backmatter: bool,
+    // 转换前在终端中勾选要转换的章节
+    pub interactive: bool,
+    // --exclude-title，去掉目录标题匹配任一模式的章节
+    pub exclude_titles: Vec<Regex>,
+    // 转换后打印每章的字数等统计，stats_file 为 --stats=<file> 写出 JSON 的位置
+    pub stats: bool,
+    pub stats_file: Option<PathBuf>,
+    // 未指定时 --format obsidian 用 obsidian，其余用 gfm
+    pub flavor: Option<Flavor>,
+    // --chinese，只转换正文文字
+    pub chinese: Chinese,
+    // --pangu
+    pub pangu: bool,
+    pub punct: Punctuation,
+    // --bouten
+    pub bouten: Bouten,
+    pub unicode_normalize: Normalization,
+}
+
+impl Options {
+    // 只能用于转换一本书的选项
+    pub fn single_book_option(&self) -> Option<&'static str> {
+        if self.interactive {
+            return Some("--interactive");
+        }
+        if self.stats_file.is_some() {
+            return Some("--stats=<file>");
+        }
+        None
+    }
+
+    // 返回第一个只有内置解析器支持的选项名，用于自动选择后端和参数校验
+    pub fn native_only_option(&self) -> Option<&'static str> {
+        if self.order != Order::Spine {
+            return Some("--order");
+        }
+        if self.recover {
+            return Some("--recover");
+        }
+        if self.max_entry_size.is_some() {
+            return Some("--max-entry-size");
+        }
+        if self.max_total_size.is_some() {
+            return Some("--max-total-size");
+        }
+        if self.rootfile.is_some() {
+            return Some("--rootfile");
+        }
+        if self.strict {
+            return Some("--strict");
+        }
+        if self.split != Split::None {
+            return Some("--split");
+        }
+        if self.format != Format::Markdown {
+            return Some("--format");
+        }
+        if self.separator != Separator::None {
+            return Some("--separator");
+        }
+        if self.chapter_headings {
+            return Some("--chapter-headings");
+        }
+        if self.metadata_file.is_some() {
+            return Some("--metadata-file");
+        }
+        if self.frontmatter.is_some_and(|frontmatter| frontmatter != FrontmatterFormat::None) {
+            return Some("--frontmatter");
+        }
+        if self.extract_images.is_some() {
+            return Some("--extract-images");
+        }
+        if self.embed_images.is_some() {
+            return Some("--embed-images");
+        }
+        if self.skip_frontmatter {
+            return Some("--skip-frontmatter");
+        }
+        if self.skip_backmatter {
+            return Some("--skip-backmatter");
+        }
+        if self.interactive {
+            return Some("--interactive");
+        }
+        if !self.exclude_titles.is_empty() {
+            return Some("--exclude-title");
+        }
+        None
+    }
+
+    // 返回第一个作用于内置 HTML 转换（native 与 html2md 后端）的选项名
+    pub fn html_conversion_option(&self) -> Option<&'static str> {
+        if self.unicode_normalize != Normalization::default() {
+            return Some("--unicode-normalize");
+        }
+        if self.bouten != Bouten::default() {
+            return Some("--bouten");
+        }
+        if self.punct != Punctuation::default() {
+            return Some("--punct");
+        }
+        if self.pangu {
+            return Some("--pangu");
+        }
+        if self.chinese != Chinese::default() {
+            return Some("--chinese");
+        }
+        if self.flavor.is_some() {
+            return Some("--flavor");
+        }
+        if self.citations != Citations::default() {
+            return Some("--citations");
+        }
+        if self.page_markers.is_some() {
+            return Some("--page-markers");
+        }
+        if self.caption_style != CaptionStyle::default() {
+            return Some("--caption-style");
+        }
+        if self.underline != Underline::default() {
+            return Some("--underline");
+        }
+        if self.sup_sub != SupSubStyle::default() {
+            return Some("--sup-sub");
+        }
+        if self.line_breaks != LineBreak::default() {
+            return Some("--line-breaks");
+        }
+        if self.definition_lists.is_some() {
+            return Some("--definition-lists");
+        }
+        if self.ruby != RubyStyle::default() {
+            return Some("--ruby");
+        }
+        if self.admonitions != AdmonitionStyle::default() {
+            return Some("--admonitions");
+        }
+        if self.unknown_html.is_some() {
+            return Some("--unknown-html");
+        }
+        None
+    }
+
+    pub fn frontmatter_format(&self) -> FrontmatterFormat {
+        self.frontmatter.unwrap_or(match self.format {
+            Format::Markdown if self.split == Split::None => FrontmatterFormat::None,
+            Format::Markdown | Format::Obsidian | Format::Hugo | Format::Jekyll => FrontmatterFormat::Yaml,
+            Format::Zola => FrontmatterFormat::Toml,
+            Format::Mdbook => FrontmatterFormat::None,
+        })
+    }
+
+    // Hugo（Goldmark）与 Jekyll（kramdown）默认支持定义列表语法，其他目标不一定支持
+    pub fn definition_list_style(&self) -> DefinitionListStyle {
+        self.definition_lists.unwrap_or(match (self.flavor(), self.format) {
+            (Flavor::Pandoc | Flavor::Multimarkdown, _) | (_, Format::Hugo | Format::Jekyll) => DefinitionListStyle::Colon,
+            _ => DefinitionListStyle::Bold,
+        })
+    }
+
+    pub fn flavor(&self) -> Flavor {
+        self.flavor.unwrap_or(match self.format {
+            Format::Obsidian => Flavor::Obsidian,
+            _ => Flavor::Gfm,
+        })
+    }
+
+    // 是否需要从 EPUB 中读出章节引用的图片
+    pub fn reads_images(&self) -> bool {
+        self.format.copies_resources() || self.extract_images.is_some() || self.embed_images.is_some()
+    }
+
+    // 输出路径是否为目录
+    pub fn writes_directory(&self) -> bool {
+        self.split != Split::None || self.format != Format::Markdown
+    }
+
+    // 依次取 --pandoc-path、EPUB2MD_PANDOC，最后在 PATH 中查找 pandoc
+    pub fn pandoc_binary(&self) -> PathBuf {
+        self.pandoc_path
+            .clone()
+            .or_else(|| env::var_os("EPUB2MD_PANDOC").filter(|p| !p.is_empty()).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("pandoc"))
+    }
+}
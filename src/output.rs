@@ -0,0 +1,505 @@
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use tempfile::NamedTempFile;
+use zip::{write::SimpleFileOptions, DateTime, ZipWriter};
+
+use crate::{
+    book::Book,
+    cleanup,
+    error::EpubToMdError,
+    frontmatter::Frontmatter,
+    links,
+    markdown::{Reference, ReferenceKind},
+    naming,
+    options::{Eol, Options},
+    report,
+    toc::TocEntry,
+    wrap,
+};
+
+// 拆分输出时索引页的默认文件名
+const DEFAULT_INDEX_NAME: &str = "index.md";
+
+// 先写入同目录下唯一命名的临时文件，再原子地重命名为目标文件，
+// 并发转换不会互相覆盖，进程中途退出也不会留下残缺的输出
+pub fn write_output(md_path: &Path, content: &str) -> Result<(), EpubToMdError> {
+    report::output(md_path);
+    if report::dry_run() {
+        return Ok(());
+    }
+    let dir = match md_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut temp_file = temp_file_for(dir, md_path)?;
+    temp_file
+        .write_all(content.as_bytes())
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to write Markdown file: {}", e)))?;
+    temp_file
+        .persist(md_path)
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to write Markdown file: {}", e.error)))?;
+
+    Ok(())
+}
+
+// 临时文件默认只有所有者可读写；新文件改为与 fs::write 相同的 0666 去掉 umask 中的位，
+// 覆盖已有文件时沿用原来的权限
+fn temp_file_for(dir: &Path, target: &Path) -> Result<NamedTempFile, EpubToMdError> {
+    let mut builder = tempfile::Builder::new();
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o666));
+    let temp_file = builder
+        .tempfile_in(dir)
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to create temporary file: {}", e)))?;
+    if let Ok(metadata) = fs::metadata(target) {
+        temp_file
+            .as_file()
+            .set_permissions(metadata.permissions())
+            .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to create temporary file: {}", e)))?;
+    }
+    Ok(temp_file)
+}
+
+// 整理 Markdown（除非指定了 --no-format）并按 --wrap 重排段落，再按 --eol 与 --bom 转换；内部统一使用 \n
+pub fn encode_text(content: &str, options: &Options) -> String {
+    let mut content = match options.no_format {
+        true => content.to_string(),
+        false => cleanup::format_markdown(content),
+    };
+    if let Some(wrap) = options.wrap {
+        content = wrap::wrap(&content, wrap);
+    }
+    let mut text = String::with_capacity(content.len() + 3);
+    if options.bom {
+        text.push('\u{FEFF}');
+    }
+    match options.eol {
+        Eol::Lf => text.push_str(&content),
+        Eol::Crlf => text.push_str(&content.replace("\r\n", "\n").replace('\n', "\r\n")),
+    }
+    text
+}
+
+// 原子地写出一个 Markdown 文件，并应用换行符与 BOM 设置
+pub fn write_text(path: &Path, content: &str, options: &Options) -> Result<(), EpubToMdError> {
+    write_output(path, &encode_text(content, options))
+}
+
+// 每个章节写成输出目录下的一个文件，文件名带序号以保持阅读顺序，另外生成链接到所有章节的索引页
+pub fn write_chapters(out_dir: &Path, book: &Book, options: &Options) -> Result<(), EpubToMdError> {
+    let index_name = options.index_name.as_deref().unwrap_or(DEFAULT_INDEX_NAME);
+    let file_names = naming::chapter_file_names(
+        &book.chapters,
+        options.name_template.as_ref(),
+        book.metadata.title.as_deref(),
+        &[index_name],
+    )?;
+    for (i, (chapter, file_name)) in book.chapters.iter().zip(&file_names).enumerate() {
+        // 每章开头附上章节与书的元数据，静态网站和笔记工具可直接读取
+        let mut frontmatter = Frontmatter::default();
+        frontmatter
+            .string("title", chapter.title.as_deref())
+            .integer("order", i as i64 + 1)
+            .string("source", Some(&chapter.source))
+            .string("book", book.metadata.title.as_deref())
+            .list("author", &book.metadata.authors)
+            .string("lang", chapter.language.as_deref());
+        let path = out_dir.join(file_name);
+        create_parent_dir(&path)?;
+        let (body, _) = links::restyle(&book.plain_markdown(i, Some(&file_names)), options.link_style, 1);
+        write_markdown(&path, &frontmatter.prepend(options.frontmatter_format(), &body), options)?;
+    }
+
+    let index_path = out_dir.join(index_name);
+    create_parent_dir(&index_path)?;
+    write_text(&index_path, &index_page(book, &file_names), options)?;
+    Ok(())
+}
+
+// 索引页：书的元数据加上与目录层级一致的章节链接列表，目录未引用的章节追加在末尾
+fn index_page(book: &Book, file_names: &[String]) -> String {
+    let metadata = &book.metadata;
+    let mut page = format!("# {}\n", metadata.title.as_deref().unwrap_or("Contents"));
+    let mut details = Vec::new();
+    if !metadata.authors.is_empty() {
+        details.push(format!("- Author: {}", metadata.authors.join(", ")));
+    }
+    if let Some(date) = &metadata.date {
+        details.push(format!("- Date: {}", date));
+    }
+    if !metadata.subjects.is_empty() {
+        details.push(format!("- Subjects: {}", metadata.subjects.join(", ")));
+    }
+    if !details.is_empty() {
+        page.push_str(&format!("\n{}\n", details.join("\n")));
+    }
+
+    let mut linked = vec![false; book.chapters.len()];
+    let mut lines = Vec::new();
+    for entry in TocEntry::flatten(&book.toc).into_iter().filter(|entry| !entry.title.is_empty()) {
+        let indent = "  ".repeat(entry.depth);
+        let reference = Reference {
+            kind: ReferenceKind::Link,
+            href: String::new(),
+            path: entry.path.clone(),
+            fragment: entry.fragment.clone(),
+            title: None,
+        };
+        match book.chapter_index(&reference) {
+            Some(i) => {
+                linked[i] = true;
+                // 只有落在标题上的片段才能换成 Markdown 渲染器生成的锚点
+                let anchor = entry
+                    .fragment
+                    .as_deref()
+                    .and_then(|fragment| book.chapters[i].anchor_heading(fragment))
+                    .map(|heading| format!("#{}", naming::heading_anchor(heading)))
+                    .unwrap_or_default();
+                lines.push(format!("{}- [{}]({}{})", indent, entry.title, link_target(&file_names[i]), anchor));
+            }
+            None => lines.push(format!("{}- {}", indent, entry.title)),
+        }
+    }
+    for (i, chapter) in book.chapters.iter().enumerate().filter(|(i, _)| !linked[*i]) {
+        let title = chapter.title.clone().unwrap_or_else(|| chapter.stem());
+        lines.push(format!("- [{}]({})", title, link_target(&file_names[i])));
+    }
+
+    page.push_str(&format!("\n{}\n", lines.join("\n")));
+    page
+}
+
+fn link_target(file_name: &str) -> String {
+    file_name.replace(' ', "%20")
+}
+
+// 写到标准输出，便于通过管道交给其他程序；下游提前关闭管道时不算错误
+pub fn write_stdout(content: &str) -> Result<(), EpubToMdError> {
+    report::output(Path::new("-"));
+    if report::dry_run() {
+        return Ok(());
+    }
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(content.as_bytes()).and_then(|_| stdout.flush()) {
+        Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+            Err(EpubToMdError::FileIOError(tr!("Failed to write to stdout: {}", e)))
+        }
+        _ => Ok(()),
+    }
+}
+
+// 超过 --max-file-size 时在段落边界处拆成 <name>-01.md、<name>-02.md 等多个文件
+pub fn write_markdown(md_path: &Path, content: &str, options: &Options) -> Result<(), EpubToMdError> {
+    let max_file_size = match options.max_file_size {
+        Some(max) if content.len() as u64 > max => max,
+        _ => return write_text(md_path, content, options),
+    };
+
+    let parts = split_at_paragraphs(content, max_file_size as usize);
+    let width = parts.len().to_string().len().max(2);
+    for (i, part) in parts.iter().enumerate() {
+        write_text(&part_path(md_path, i + 1, width), part, options)?;
+    }
+    Ok(())
+}
+
+// 拆分后第 index 个文件的路径，例如 book-01.md
+pub fn part_path(md_path: &Path, index: usize, width: usize) -> PathBuf {
+    let stem = md_path.file_stem().and_then(|stem| stem.to_str()).unwrap_or("part");
+    let extension = md_path.extension().and_then(|ext| ext.to_str()).unwrap_or("md");
+    md_path.with_file_name(format!("{}-{:0width$}.{}", stem, index, extension, width = width))
+}
+
+// 按空行分段累积，加入下一段会超过上限时另起一部分；代码块内的空行不作为分段点，
+// 单独超过上限的段落自成一部分
+fn split_at_paragraphs(content: &str, max: usize) -> Vec<String> {
+    let mut blocks: Vec<String> = Vec::new();
+    let mut in_fence = false;
+    let mut block = String::new();
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if line.trim().is_empty() && !in_fence {
+            if !block.is_empty() {
+                blocks.push(std::mem::take(&mut block));
+            }
+            continue;
+        }
+        if !block.is_empty() {
+            block.push('\n');
+        }
+        block.push_str(line);
+    }
+    if !block.is_empty() {
+        blocks.push(block);
+    }
+
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    for block in blocks {
+        if !part.is_empty() && part.len() + 2 + block.len() > max {
+            parts.push(std::mem::take(&mut part));
+        }
+        if !part.is_empty() {
+            part.push_str("\n\n");
+        }
+        part.push_str(&block);
+    }
+    if !part.is_empty() {
+        parts.push(part);
+    }
+    parts
+}
+
+// 创建输出文件所在的目录，文件名模板中可以包含子目录
+// 写出图片等资源文件；--dry-run 时只记录路径
+pub fn write_resource(
+    path: &Path,
+    bytes: &[u8],
+    error: impl FnOnce(io::Error) -> EpubToMdError,
+) -> Result<(), EpubToMdError> {
+    report::image(path);
+    if report::dry_run() {
+        return Ok(());
+    }
+    create_parent_dir(path)?;
+    fs::write(path, bytes).map_err(error)
+}
+
+pub fn create_parent_dir(path: &Path) -> Result<(), EpubToMdError> {
+    if let Some(dir) = path.parent().filter(|_| !report::dry_run()) {
+        fs::create_dir_all(dir).map_err(|e| {
+            EpubToMdError::FileIOError(tr!("Failed to create output directory '{}': {}", dir.display(), e))
+        })?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+// 按扩展名判断打包格式
+pub fn archive_kind(path: &Path) -> Result<ArchiveKind, EpubToMdError> {
+    let name = path.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Ok(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Ok(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar") {
+        Ok(ArchiveKind::Tar)
+    } else {
+        Err(EpubToMdError::ArgumentError(tr!(
+            "Unsupported archive '{}', expected a .zip, .tar or .tar.gz file.",
+            path.display()
+        )))
+    }
+}
+
+// 把目录下的所有文件按相对路径打包，条目按路径排序，同样先写临时文件再重命名
+// deterministic 为 true 时不记录修改时间、属主与权限，同样的输入总是得到逐字节相同的压缩包
+pub fn write_archive(dir: &Path, archive_path: &Path, deterministic: bool) -> Result<(), EpubToMdError> {
+    let kind = archive_kind(archive_path)?;
+    if report::dry_run() {
+        report::archived(archive_path);
+        return Ok(());
+    }
+    let archive_error = |e: &dyn std::fmt::Display| EpubToMdError::FileIOError(tr!("Failed to write archive: {}", e));
+
+    let mut files = Vec::new();
+    collect_files(dir, dir, &mut files)?;
+    files.sort();
+
+    let parent = match archive_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let temp_file = temp_file_for(parent, archive_path)?;
+    let file = temp_file.reopen().map_err(|e| archive_error(&e))?;
+
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = ZipWriter::new(file);
+            let mut file_options = SimpleFileOptions::default();
+            if deterministic {
+                file_options = file_options.last_modified_time(DateTime::default()).unix_permissions(0o644);
+            }
+            for relative in &files {
+                zip.start_file(archive_name(relative), file_options)
+                    .map_err(|e| archive_error(&e))?;
+                let bytes = fs::read(dir.join(relative)).map_err(|e| archive_error(&e))?;
+                zip.write_all(&bytes).map_err(|e| archive_error(&e))?;
+            }
+            zip.finish().map_err(|e| archive_error(&e))?;
+        }
+        ArchiveKind::Tar => {
+            append_tar(tar::Builder::new(file), dir, &files, deterministic).map_err(|e| archive_error(&e))?;
+        }
+        ArchiveKind::TarGz => {
+            // GzEncoder 默认不写入文件名与时间戳
+            append_tar(tar::Builder::new(GzEncoder::new(file, Compression::default())), dir, &files, deterministic)
+                .map_err(|e| archive_error(&e))?
+                .finish()
+                .map_err(|e| archive_error(&e))?;
+        }
+    }
+
+    temp_file
+        .persist(archive_path)
+        .map_err(|e| archive_error(&e.error))?;
+    report::archived(archive_path);
+    Ok(())
+}
+
+fn append_tar<W: Write>(
+    mut builder: tar::Builder<W>,
+    dir: &Path,
+    files: &[PathBuf],
+    deterministic: bool,
+) -> io::Result<W> {
+    for relative in files {
+        let mut file = File::open(dir.join(relative))?;
+        if deterministic {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(file.metadata()?.len());
+            header.set_mode(0o644);
+            header.set_mtime(source_date_epoch());
+            header.set_entry_type(tar::EntryType::Regular);
+            builder.append_data(&mut header, archive_name(relative), &mut file)?;
+        } else {
+            builder.append_file(archive_name(relative), &mut file)?;
+        }
+    }
+    builder.into_inner()
+}
+
+// 遵循 reproducible-builds 的约定，设置了 SOURCE_DATE_EPOCH 时用它作为修改时间，否则为 0
+pub fn source_date_epoch() -> u64 {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+// 压缩包内统一使用 / 分隔的相对路径
+fn archive_name(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), EpubToMdError> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to read directory '{}': {}", dir.display(), e)))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to read directory '{}': {}", dir.display(), e)))?
+            .path();
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_replaces_the_target_without_leaving_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("book.md");
+        fs::write(&path, "old").unwrap();
+        #[cfg(unix)]
+        fs::set_permissions(&path, std::os::unix::fs::PermissionsExt::from_mode(0o640)).unwrap();
+        write_output(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        }
+    }
+
+    #[test]
+    fn text_gets_the_chosen_line_endings_and_bom() {
+        let options = Options {
+            no_format: true,
+            eol: Eol::Crlf,
+            bom: true,
+            ..Options::default()
+        };
+        assert_eq!(encode_text("a\nb\r\n", &options), "\u{FEFF}a\r\nb\r\n");
+        let options = Options {
+            no_format: true,
+            ..Options::default()
+        };
+        assert_eq!(encode_text("a\nb\n", &options), "a\nb\n");
+    }
+
+    #[test]
+    fn large_output_splits_at_paragraphs_outside_code() {
+        let content = "one\n\ntwo\n\n```\nx\n\ny\n```\n\nthree";
+        assert_eq!(split_at_paragraphs(content, 8), ["one\n\ntwo", "```\nx\n\ny\n```", "three"]);
+        assert_eq!(split_at_paragraphs(content, 100), [content]);
+        assert_eq!(part_path(Path::new("out/book.md"), 3, 2), Path::new("out/book-03.md"));
+    }
+
+    // 写出 index.md 与 chapters/01.md 两个文件的输出目录
+    fn output_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("chapters")).unwrap();
+        fs::write(dir.path().join("index.md"), "index").unwrap();
+        fs::write(dir.path().join("chapters/01.md"), "one").unwrap();
+        dir
+    }
+
+    #[test]
+    fn archives_hold_the_output_files_by_relative_path() {
+        let dir = output_dir();
+        let target = tempfile::tempdir().unwrap();
+        let zip_path = target.path().join("book.zip");
+        write_archive(dir.path(), &zip_path, false).unwrap();
+        let zip = zip::ZipArchive::new(File::open(&zip_path).unwrap()).unwrap();
+        let names: Vec<String> = zip.file_names().map(|name| name.unwrap().into_owned()).collect();
+        assert_eq!(names, ["chapters/01.md", "index.md"]);
+        let tar_path = target.path().join("book.tar");
+        write_archive(dir.path(), &tar_path, false).unwrap();
+        let mut tar = tar::Archive::new(File::open(&tar_path).unwrap());
+        let names: Vec<String> =
+            tar.entries().unwrap().map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned()).collect();
+        assert_eq!(names, ["chapters/01.md", "index.md"]);
+        assert!(matches!(archive_kind(Path::new("book.rar")), Err(EpubToMdError::ArgumentError(_))));
+    }
+
+    #[test]
+    fn deterministic_archives_ignore_modification_times() {
+        let dir = output_dir();
+        let target = tempfile::tempdir().unwrap();
+        for name in ["book.zip", "book.tar.gz"] {
+            let first = target.path().join(format!("first-{}", name));
+            write_archive(dir.path(), &first, true).unwrap();
+            let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+            File::options().write(true).open(dir.path().join("index.md")).unwrap().set_modified(modified).unwrap();
+            let second = target.path().join(format!("second-{}", name));
+            write_archive(dir.path(), &second, true).unwrap();
+            assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::{HashMap, HashSet};
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+use markup5ever_rcdom::NodeData;
+
+use crate::{dom, markdown::default_handler, toc::TocEntry};
+
+// 页码列表中指向普通元素的位置，在其前面插入该元素表示分页
+pub const PAGE_TAG: &str = "epub2md-page";
+pub const DEFAULT_TEMPLATE: &str = "<!-- page {page} -->";
+// 表格单元格与标题中不能换行，分页标记只能留在行内
+const INLINE_ANCESTORS: &[&str] = &["td", "th", "h1", "h2", "h3", "h4", "h5", "h6"];
+
+fn is_pagebreak(node: &Handle) -> bool {
+    dom::tag_name(node).as_deref() == Some(PAGE_TAG)
+        || dom::attr(node, "type").is_some_and(|types| types.split_whitespace().any(|t| t == "pagebreak"))
+        || dom::attr(node, "role").is_some_and(|role| role.split_whitespace().any(|r| r == "doc-pagebreak"))
+}
+
+// 页码取自 title、aria-label 或元素中的文字，去掉 "Page" 等前缀
+fn page_number(node: &Handle) -> Option<String> {
+    let label = dom::attr(node, "title")
+        .or_else(|| dom::attr(node, "aria-label"))
+        .or_else(|| dom::attr(node, "page"))
+        .unwrap_or_else(|| dom::text_content(node));
+    label_number(&label)
+}
+
+fn label_number(label: &str) -> Option<String> {
+    let label = label.trim();
+    let number = match label.get(..4) {
+        Some(prefix) if prefix.eq_ignore_ascii_case("page") => label[4..].trim_start_matches(['.', ':', ' ']),
+        _ => label,
+    };
+    Some(number.trim().to_string()).filter(|number| !number.is_empty() && number.chars().count() <= 16)
+}
+
+// 按 EPUB 的页码列表标出文档中的分页位置：已是分页元素的补上页码，其他元素前插入 PAGE_TAG；
+// 没有改动时返回 None
+pub fn mark(html: &str, path: &str, page_list: &[TocEntry]) -> Option<String> {
+    let pages: HashMap<&str, &str> = page_list
+        .iter()
+        .filter(|entry| entry.path == path && !entry.title.is_empty())
+        .filter_map(|entry| entry.fragment.as_deref().map(|fragment| (fragment, entry.title.as_str())))
+        .collect();
+    if pages.is_empty() {
+        return None;
+    }
+    let document = dom::parse(html);
+    let mut changed = false;
+    for node in dom::descendants(&document.document) {
+        let Some(page) = dom::attr(&node, "id").and_then(|id| pages.get(id.as_str()).copied()) else {
+            continue;
+        };
+        if is_pagebreak(&node) {
+            if dom::attr(&node, "title").is_none() {
+                dom::set_attr(&node, "title", page);
+                changed = true;
+            }
+        } else {
+            dom::insert_before(&node, dom::element(PAGE_TAG, &[("page", page)]));
+            changed = true;
+        }
+    }
+    changed.then(|| dom::inner_html(&document.document))
+}
+
+// 分页位置的 id 与其所在小节的标题：(id, 标题文字)。不插入锚点的输出格式据此把索引等处指向页码的
+// 链接改为指向该小节的标题；位于第一个标题之前的分页位置不在其中，链接指向章节开头
+pub fn page_anchors(html: &str, path: &str, page_list: &[TocEntry]) -> Vec<(String, String)> {
+    let pages: HashSet<&str> = page_list
+        .iter()
+        .filter(|entry| entry.path == path)
+        .filter_map(|entry| entry.fragment.as_deref())
+        .collect();
+    let document = dom::parse(html);
+    let mut heading: Option<String> = None;
+    let mut anchors = Vec::new();
+    for node in dom::descendants(&document.document) {
+        if matches!(dom::tag_name(&node).as_deref(), Some("h1" | "h2" | "h3" | "h4" | "h5" | "h6")) {
+            heading = Some(dom::text_content(&node)).filter(|text| !text.is_empty()).or(heading);
+            continue;
+        }
+        let Some(id) = dom::attr(&node, "id") else {
+            continue;
+        };
+        if let Some(heading) = heading.as_ref().filter(|_| is_pagebreak(&node) || pages.contains(id.as_str())) {
+            anchors.push((id, heading.clone()));
+        }
+    }
+    anchors
+}
+
+// 分页元素输出为按模板生成的页码标记，例如 <!-- page 12 -->，其余元素交给原来的处理器
+struct PageHandler {
+    template: String,
+    marker: bool,
+    fallback: Box<dyn TagHandler>,
+}
+
+impl TagHandler for PageHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        let page = is_pagebreak(tag).then(|| page_number(tag)).flatten();
+        self.marker = page.is_some();
+        let Some(page) = page else {
+            self.fallback.handle(tag, printer);
+            return;
+        };
+        // 其余位置的标记总是单独成段，段落中间的分页也把段落分开
+        let mut block = true;
+        let mut ancestor = dom::parent(tag);
+        while let Some(node) = ancestor {
+            if dom::tag_name(&node).is_some_and(|name| INLINE_ANCESTORS.contains(&name.as_str())) {
+                block = false;
+                break;
+            }
+            ancestor = dom::parent(&node);
+        }
+        let marker = self.template.replace("{page}", &page);
+        if block {
+            printer.insert_newline();
+            printer.insert_newline();
+            printer.append_str(&marker);
+            printer.insert_newline();
+            printer.insert_newline();
+        } else {
+            printer.append_str(&marker);
+        }
+        // 元素中只有页码文字时不再输出；HTML 解析器会把自闭合的 <span/> 当作开始标签，
+        // 其中的后续内容不是页码，照常转换
+        let only_text = tag.children.borrow().iter().all(|child| !matches!(child.data, NodeData::Element { .. }));
+        let text = dom::text_content(tag);
+        if only_text && (text.trim().is_empty() || label_number(&text).as_deref() == Some(page.as_str())) {
+            tag.children.borrow_mut().clear();
+        }
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        if !self.marker {
+            self.fallback.after_handle(printer);
+        }
+    }
+
+    fn skip_descendants(&self) -> bool {
+        !self.marker && self.fallback.skip_descendants()
+    }
+}
+
+struct PageHandlerFactory {
+    template: String,
+    tag: &'static str,
+    fallback: Option<Box<dyn TagHandlerFactory>>,
+}
+
+impl TagHandlerFactory for PageHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        let fallback = match &self.fallback {
+            Some(factory) => factory.instantiate(),
+            None => default_handler(self.tag),
+        };
+        Box::new(PageHandler {
+            template: self.template.clone(),
+            marker: false,
+            fallback,
+        })
+    }
+}
+
+// 需在其他处理器之后注册，不是分页的 <span> 等仍交给已注册的处理器
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, template: &str) {
+    for tag in [PAGE_TAG, "span", "div", "hr"] {
+        let fallback = handlers.remove(tag);
+        handlers.insert(
+            tag.to_string(),
+            Box::new(PageHandlerFactory {
+                template: template.to_string(),
+                tag,
+                fallback,
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(fragment: &str, title: &str) -> TocEntry {
+        TocEntry {
+            title: title.to_string(),
+            path: "OEBPS/ch1.xhtml".to_string(),
+            fragment: Some(fragment.to_string()),
+            depth: 0,
+            children: Vec::new(),
+        }
+    }
+
+    fn convert(html: &str) -> String {
+        let mut handlers = HashMap::new();
+        register(&mut handlers, DEFAULT_TEMPLATE);
+        html2md::parse_html_custom(html, &handlers)
+    }
+
+    #[test]
+    fn page_list_entries_are_marked_in_the_document() {
+        let list = [page("p3", "3"), page("p4", "4")];
+        let html = r#"<p id="p3">a</p><span epub:type="pagebreak" id="p4"></span><p>b</p>"#;
+        let marked = mark(html, "OEBPS/ch1.xhtml", &list).unwrap();
+        assert!(marked.contains(r#"<epub2md-page page="3"></epub2md-page><p id="p3">"#), "{}", marked);
+        assert!(marked.contains(r#"<span epub:type="pagebreak" id="p4" title="4"></span>"#), "{}", marked);
+        assert_eq!(mark("<p>x</p>", "OEBPS/ch2.xhtml", &list), None);
+    }
+
+    #[test]
+    fn pagebreaks_become_markers() {
+        let html = r#"<p>before<span epub:type="pagebreak" title="Page 12">12</span>after</p>"#;
+        assert_eq!(convert(html), "before\n\n<!-- page 12 -->\n\nafter");
+        let html = r#"<h2>Title<span role="doc-pagebreak" aria-label="5"></span> end</h2>"#;
+        assert_eq!(convert(html), "Title<!-- page 5 --> end\n----------");
+    }
+
+    #[test]
+    fn anchors_point_to_the_enclosing_section() {
+        let list = [page("p3", "3")];
+        let html = concat!(
+            r#"<span epub:type="pagebreak" id="p1"></span><h2>Intro</h2>"#,
+            r#"<p id="p3">a</p><span epub:type="pagebreak" id="p9"></span>"#,
+        );
+        let anchors = page_anchors(html, "OEBPS/ch1.xhtml", &list);
+        assert_eq!(anchors, [("p3".to_string(), "Intro".to_string()), ("p9".to_string(), "Intro".to_string())]);
+    }
+}
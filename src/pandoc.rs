@@ -0,0 +1,150 @@
+use std::{ffi::OsString, fmt, path::Path, process::Command, str::FromStr};
+
+use crate::{
+    error::EpubToMdError,
+    options::{MdFlavor, Options, Wrap},
+    output,
+};
+
+// 未通过 --min-pandoc-version 指定时要求的最低版本
+const DEFAULT_MIN_VERSION: PandocVersion = PandocVersion([2, 0, 0, 0]);
+// gfm 写出器在 2.0 之前名为 markdown_github
+const GFM_WRITER_VERSION: PandocVersion = PandocVersion([2, 0, 0, 0]);
+const COMMONMARK_X_VERSION: PandocVersion = PandocVersion([2, 11, 0, 0]);
+const LUA_FILTER_VERSION: PandocVersion = PandocVersion([2, 0, 0, 0]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PandocVersion([u32; 4]);
+
+impl FromStr for PandocVersion {
+    type Err = EpubToMdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || EpubToMdError::ArgumentError(tr!("Invalid Pandoc version '{}'.", s));
+        let mut parts = [0; 4];
+        for (i, part) in s.trim().split('.').enumerate() {
+            let slot = parts.get_mut(i).ok_or_else(invalid)?;
+            *slot = part.parse().map_err(|_| invalid())?;
+        }
+        Ok(PandocVersion(parts))
+    }
+}
+
+impl fmt::Display for PandocVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 去掉末尾多余的 0，但至少保留 major.minor
+        let len = self.0.iter().rposition(|&n| n != 0).map_or(0, |i| i + 1).max(2);
+        let parts: Vec<String> = self.0[..len].iter().map(|n| n.to_string()).collect();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+// 检查 pandoc 是否安装，并返回其版本号
+pub fn check_pandoc(options: &Options) -> Result<PandocVersion, EpubToMdError> {
+    let pandoc = options.pandoc_binary();
+    let output = match Command::new(&pandoc).arg("--version").output() {
+        Ok(output) => output,
+        Err(_) if pandoc.as_os_str() != "pandoc" => {
+            return Err(EpubToMdError::PandocCheckError(tr!(
+                "Pandoc binary '{}' could not be executed.",
+                pandoc.display()
+            )))
+        }
+        Err(_) => {
+            return Err(EpubToMdError::PandocCheckError(tr!(
+                "Pandoc is not installed or not in PATH. Please ensure pandoc is installed and accessible."
+            )))
+        }
+    };
+    if !output.status.success() {
+        return Err(EpubToMdError::PandocCheckError(tr!("Pandoc command failed to execute.")));
+    }
+
+    // 第一行形如 "pandoc 3.1.2"
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().find(|token| token.starts_with(|c: char| c.is_ascii_digit())))
+        .and_then(|token| token.parse::<PandocVersion>().ok())
+        .ok_or_else(|| EpubToMdError::PandocCheckError(tr!("Failed to detect the Pandoc version.")))?;
+
+    let min_version = options.min_pandoc_version.unwrap_or(DEFAULT_MIN_VERSION);
+    if version < min_version {
+        return Err(EpubToMdError::PandocCheckError(tr!(
+            "Pandoc {} is older than the required minimum {}. Please upgrade pandoc or lower --min-pandoc-version.",
+            version, min_version
+        )));
+    }
+    if !options.lua_filters.is_empty() && version < LUA_FILTER_VERSION {
+        return Err(EpubToMdError::PandocCheckError(tr!(
+            "Lua filters require Pandoc {} or newer, found {}.",
+            LUA_FILTER_VERSION, version
+        )));
+    }
+
+    Ok(version)
+}
+
+// 执行 pandoc 命令将 EPUB 转换为 HTML，直接从标准输出读取结果
+pub fn epub_to_html(options: &Options, epub_path: &Path) -> Result<String, EpubToMdError> {
+    run_pandoc(options, epub_path, "html", &[])
+}
+
+// 由 pandoc 直接输出指定方言的 Markdown，跳过 html2md
+pub fn epub_to_markdown(options: &Options, version: PandocVersion, epub_path: &Path) -> Result<String, EpubToMdError> {
+    let to_format = match options.md_flavor.unwrap_or_default() {
+        MdFlavor::Gfm if version < GFM_WRITER_VERSION => "markdown_github",
+        MdFlavor::CommonmarkX if version < COMMONMARK_X_VERSION => {
+            return Err(EpubToMdError::PandocError(tr!(
+                "The commonmark_x flavor requires Pandoc {} or newer, found {}.",
+                COMMONMARK_X_VERSION, version
+            )));
+        }
+        flavor => flavor.pandoc_format(),
+    };
+    // 由 pandoc 换行，之后的重排不会再改变已经符合要求的段落
+    let wrap_args = match options.wrap {
+        Some(Wrap::None) => vec!["--wrap=none".to_string()],
+        Some(Wrap::Columns(columns)) => vec!["--wrap=auto".to_string(), format!("--columns={}", columns)],
+        None => Vec::new(),
+    };
+    run_pandoc(options, epub_path, to_format, &wrap_args)
+}
+
+fn run_pandoc(options: &Options, epub_path: &Path, to_format: &str, extra_args: &[String]) -> Result<String, EpubToMdError> {
+    let mut command = Command::new(options.pandoc_binary());
+    // pandoc 会把 SOURCE_DATE_EPOCH 作为当前时间，避免生成的内容里出现转换时刻
+    if options.deterministic {
+        command.env("SOURCE_DATE_EPOCH", output::source_date_epoch().to_string());
+    }
+    command
+        .arg(epub_path)
+        .arg("-t")
+        .arg(to_format)
+        .arg("-o")
+        .arg("-")
+        .args(options.lua_filters.iter().map(|filter| {
+            let mut arg = OsString::from("--lua-filter=");
+            arg.push(filter);
+            arg
+        }))
+        .args(extra_args)
+        .args(&options.pandoc_args);
+    let command_line: Vec<String> = std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    log::debug!("{}", tr!("Running {}", command_line.join(" ")));
+    let pandoc_output = command
+        .output()
+        .map_err(|e| EpubToMdError::PandocError(tr!("Failed to execute pandoc: {}", e)))?;
+
+    if !pandoc_output.status.success() {
+        let error_message = String::from_utf8_lossy(&pandoc_output.stderr);
+        return Err(EpubToMdError::PandocError(tr!("pandoc command failed: {}", error_message)));
+    }
+
+    String::from_utf8(pandoc_output.stdout)
+        .map_err(|e| EpubToMdError::PandocError(tr!("pandoc produced invalid UTF-8: {}", e)))
+}
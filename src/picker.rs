@@ -0,0 +1,52 @@
+use std::io::{self, IsTerminal};
+
+use dialoguer::MultiSelect;
+
+use crate::{epub::ManifestItem, error::EpubToMdError, toc::TocEntry};
+
+// 列表中每个文档的缩进层级与标题：目录中第一个指向它的条目，没有时用文件路径
+fn labels(loaded: &[(ManifestItem, String)], toc: &[TocEntry]) -> Vec<(usize, String)> {
+    let entries = TocEntry::flatten(toc);
+    loaded
+        .iter()
+        .map(|(item, _)| match entries.iter().find(|entry| entry.path == item.path) {
+            Some(entry) => (entry.depth, entry.title.clone()),
+            None => (0, item.path.clone()),
+        })
+        .collect()
+}
+
+// 在终端中列出各章供勾选，返回选中的文档；列表写到标准错误，按键从终端读取
+pub fn pick(loaded: Vec<(ManifestItem, String)>, toc: &[TocEntry]) -> Result<Vec<(ManifestItem, String)>, EpubToMdError> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err(EpubToMdError::ArgumentError(tr!("--interactive needs a terminal.")));
+    }
+    let items: Vec<String> =
+        labels(&loaded, toc).into_iter().map(|(depth, title)| format!("{}{}", "  ".repeat(depth), title)).collect();
+    let hint = tr!("Space: toggle, a: all or none, Enter: convert, q: quit");
+    let mut prompt = hint.clone();
+    let mut defaults = vec![true; items.len()];
+    loop {
+        let selection = MultiSelect::new()
+            .with_prompt(&prompt)
+            .items(&items)
+            .defaults(&defaults)
+            .interact_opt()
+            .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to read from the terminal: {}", e)))?;
+        match selection {
+            None => return Err(EpubToMdError::InputError(tr!("No chapters were selected."))),
+            // 一章都没选时重新列出，而不是转换出空文件
+            Some(selected) if selected.is_empty() => {
+                prompt = format!("{} {}", tr!("Select at least one chapter."), hint);
+                defaults.fill(false);
+            }
+            Some(selected) => {
+                return Ok(loaded
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(index, document)| selected.contains(&index).then_some(document))
+                    .collect())
+            }
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::logging;
+
+const BAR_WIDTH: usize = 30;
+// 两次重绘之间的最短间隔
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+// 转换单本书时显示逐章的进度；批量转换时由逐本的进度条代替
+static CHAPTERS: AtomicBool = AtomicBool::new(true);
+// 标准错误上是否留有未换行的进度条
+static DRAWN: Mutex<bool> = Mutex::new(false);
+
+// 标准输出与标准错误都是终端时才显示进度，重定向到文件或管道时不输出控制字符；-q 时不显示
+pub fn available() -> bool {
+    io::stdout().is_terminal() && io::stderr().is_terminal() && logging::shown(log::Level::Info)
+}
+
+pub fn show_chapters(show: bool) {
+    CHAPTERS.store(show, Ordering::Relaxed);
+}
+
+// 打印其他消息前先擦掉进度条，下次更新时再画出
+pub fn clear() {
+    let mut drawn = DRAWN.lock().unwrap_or_else(|e| e.into_inner());
+    if *drawn {
+        eprint!("\r\x1b[K");
+        *drawn = false;
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds {
+        0..=59 => format!("{:.1}s", duration.as_secs_f64()),
+        60..=3599 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        _ => format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+pub struct Progress {
+    label: String,
+    // 计数的单位，例如 chapters、books
+    unit: String,
+    total: usize,
+    done: usize,
+    start: Instant,
+    drawn_at: Option<Instant>,
+    enabled: bool,
+}
+
+impl Progress {
+    pub fn new(label: String, unit: String, total: usize) -> Self {
+        Progress {
+            label,
+            unit,
+            total,
+            done: 0,
+            start: Instant::now(),
+            drawn_at: None,
+            enabled: available() && total > 1,
+        }
+    }
+
+    // 单本书的逐章进度，批量转换时不显示
+    pub fn chapters(total: usize) -> Self {
+        let mut progress = Progress::new(tr!("Converting"), tr!("chapters"), total);
+        progress.enabled &= CHAPTERS.load(Ordering::Relaxed);
+        progress
+    }
+
+    pub fn inc(&mut self) {
+        self.done += 1;
+        if self.enabled && self.drawn_at.is_none_or(|at| at.elapsed() >= REDRAW_INTERVAL || self.done == self.total) {
+            self.draw();
+        }
+    }
+
+    fn draw(&mut self) {
+        let filled = BAR_WIDTH * self.done / self.total.max(1);
+        let elapsed = self.start.elapsed();
+        let eta = match self.done {
+            0 => String::new(),
+            done => {
+                let remaining = elapsed.mul_f64((self.total - done.min(self.total)) as f64 / done as f64);
+                tr!(", ETA {}", format_duration(remaining))
+            }
+        };
+        let line = format!(
+            "{} [{}{}] {}/{} {}{}",
+            self.label,
+            "=".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            self.done,
+            self.total,
+            self.unit,
+            eta
+        );
+        let mut drawn = DRAWN.lock().unwrap_or_else(|e| e.into_inner());
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[K{}", line);
+        let _ = stderr.flush();
+        *drawn = true;
+        self.drawn_at = Some(Instant::now());
+    }
+
+    // 擦掉进度条并打印用时
+    pub fn finish(self) {
+        if !self.enabled || self.drawn_at.is_none() {
+            return;
+        }
+        clear();
+        eprintln!("{}", tr!("Finished {} {} in {}", self.done, self.unit, format_duration(self.start.elapsed())));
+    }
+}
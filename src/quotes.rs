@@ -0,0 +1,136 @@
+use std::{collections::HashMap, rc::Rc};
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+use markup5ever_rcdom::NodeData;
+
+use crate::{dom, markdown::default_handler};
+
+// class 中含有这些词的段落是引文出处
+const ATTRIBUTION_WORDS: &[&str] = &["attribution", "author", "source", "cite", "citation", "signature", "byline"];
+// 出处开头常见的破折号
+const DASHES: &[&str] = &["—", "―", "–", "--", "-"];
+
+// 同一父元素中后面第一个元素或非空白文字
+fn next_sibling(node: &Handle) -> Option<Handle> {
+    let parent = dom::parent(node)?;
+    let children = parent.children.borrow();
+    children
+        .iter()
+        .skip_while(|child| !Rc::ptr_eq(child, node))
+        .skip(1)
+        .find(|child| match &child.data {
+            NodeData::Element { .. } => true,
+            NodeData::Text { contents } => !contents.borrow().trim().is_empty(),
+            _ => false,
+        })
+        .cloned()
+}
+
+fn has_attribution_class(node: &Handle) -> bool {
+    dom::attr(node, "class").is_some_and(|class| {
+        let class = class.to_ascii_lowercase();
+        class.split_whitespace().any(|token| ATTRIBUTION_WORDS.iter().any(|word| token.contains(word)))
+    })
+}
+
+fn starts_with_dash(node: &Handle) -> bool {
+    let text = dom::text_content(node);
+    text.starts_with(['—', '―']) || text.starts_with("--")
+}
+
+// 引文块内最后的 <cite>、<footer> 或出处段落，或者紧跟在引文块后面的出处段落
+fn attribution(blockquote: &Handle) -> Option<Handle> {
+    let last = blockquote
+        .children
+        .borrow()
+        .iter()
+        .rev()
+        .find(|child| match &child.data {
+            NodeData::Element { .. } => true,
+            NodeData::Text { contents } => !contents.borrow().trim().is_empty(),
+            _ => false,
+        })
+        .cloned();
+    let inside = last.filter(|last| {
+        // 只有一个段落时它就是引文本身
+        let blocks = blockquote.children.borrow().iter().filter(|child| dom::tag_name(child).is_some()).count();
+        match dom::tag_name(last).as_deref() {
+            Some("cite" | "footer") => true,
+            Some("p" | "div") => blocks > 1 && (has_attribution_class(last) || starts_with_dash(last)),
+            _ => false,
+        }
+    });
+    inside.or_else(|| {
+        next_sibling(blockquote).filter(|next| match dom::tag_name(next).as_deref() {
+            Some("cite") => true,
+            Some("p" | "div") => has_attribution_class(next) || starts_with_dash(next),
+            _ => false,
+        })
+    })
+}
+
+// 出处写成引文块最后单独一行的“— 出处”，避免与引文混在一起
+fn move_attribution(blockquote: &Handle) {
+    let Some(source) = attribution(blockquote) else {
+        return;
+    };
+    let text = dom::text_content(&source);
+    let name = DASHES
+        .iter()
+        .find_map(|dash| text.strip_prefix(dash))
+        .unwrap_or(&text)
+        .trim();
+    if name.is_empty() {
+        return;
+    }
+    // 引文块外的段落属于正在遍历的父元素，不能移除，只清空其内容
+    for child in source.children.take() {
+        child.parent.set(None);
+    }
+    let inside = dom::parent(&source).is_some_and(|parent| Rc::ptr_eq(&parent, blockquote));
+    if inside {
+        dom::detach(&source);
+    }
+    let line = dom::element("p", &[]);
+    dom::append(&line, dom::text(&format!("— {}", name)));
+    dom::append(blockquote, line);
+}
+
+struct QuoteHandler {
+    fallback: Box<dyn TagHandler>,
+}
+
+impl TagHandler for QuoteHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        move_attribution(tag);
+        self.fallback.handle(tag, printer);
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        self.fallback.after_handle(printer);
+    }
+
+    fn skip_descendants(&self) -> bool {
+        self.fallback.skip_descendants()
+    }
+}
+
+struct QuoteHandlerFactory {
+    fallback: Option<Box<dyn TagHandlerFactory>>,
+}
+
+impl TagHandlerFactory for QuoteHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        let fallback = match &self.fallback {
+            Some(factory) => factory.instantiate(),
+            None => default_handler("blockquote"),
+        };
+        Box::new(QuoteHandler { fallback })
+    }
+}
+
+// 需在其他处理器之后注册，引文块的其余处理仍交给已注册的处理器
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>) {
+    let fallback = handlers.remove("blockquote");
+    handlers.insert("blockquote".to_string(), Box::new(QuoteHandlerFactory { fallback }));
+}
@@ -0,0 +1,103 @@
+use std::{cell::RefCell, path::Path, time::Duration};
+
+use crate::{error::EpubToMdError, sidecar::Value, stats::Stats};
+
+// --json 与 --dry-run 时记录一本书转换的结果；每本书在一个线程中转换，记录按线程分开，并行转换时互不混杂
+#[derive(Default)]
+pub struct Report {
+    // 只记录要写出的文件，不实际写出
+    pub dry_run: bool,
+    // 结果以 JSON 打印，--stats 的统计写入其中而不另外打印
+    pub json: bool,
+    pub outputs: Vec<String>,
+    pub chapters: usize,
+    pub images: usize,
+    pub warnings: Vec<String>,
+    pub stats: Option<Stats>,
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<Report>> = const { RefCell::new(None) };
+}
+
+fn with(record: impl FnOnce(&mut Report)) {
+    CURRENT.with(|current| {
+        if let Some(report) = current.borrow_mut().as_mut() {
+            record(report);
+        }
+    });
+}
+
+// 开始记录当前线程上的转换，未开始时下面的记录函数什么都不做
+pub fn start(dry_run: bool, json: bool) {
+    CURRENT.with(|current| {
+        *current.borrow_mut() = Some(Report {
+            dry_run,
+            json,
+            ..Report::default()
+        })
+    });
+}
+
+pub fn dry_run() -> bool {
+    CURRENT.with(|current| current.borrow().as_ref().is_some_and(|report| report.dry_run))
+}
+
+pub fn json() -> bool {
+    CURRENT.with(|current| current.borrow().as_ref().is_some_and(|report| report.json))
+}
+
+pub fn finish() -> Report {
+    CURRENT.with(|current| current.borrow_mut().take()).unwrap_or_default()
+}
+
+pub fn output(path: &Path) {
+    with(|report| report.outputs.push(path.to_string_lossy().into_owned()));
+}
+
+pub fn image(path: &Path) {
+    with(|report| {
+        report.images += 1;
+        report.outputs.push(path.to_string_lossy().into_owned());
+    });
+}
+
+pub fn chapters(count: usize) {
+    with(|report| report.chapters = count);
+}
+
+pub fn stats(stats: Stats) {
+    with(|report| report.stats = Some(stats));
+}
+
+pub fn warning(message: &str) {
+    with(|report| report.warnings.push(message.to_string()));
+}
+
+// 打包输出时先写到临时目录，写出的只有压缩包本身
+pub fn archived(path: &Path) {
+    with(|report| report.outputs = vec![path.to_string_lossy().into_owned()]);
+}
+
+// status 为 converted、up_to_date 或 failed
+pub fn to_value(input: &str, result: &Result<bool, EpubToMdError>, report: &Report, duration: Duration) -> Value {
+    let (status, error) = match result {
+        Ok(true) => ("converted", Value::Null),
+        Ok(false) => ("up_to_date", Value::Null),
+        Err(e) => ("failed", Value::String(e.to_string())),
+    };
+    let mut fields = vec![
+        ("input", Value::String(input.to_string())),
+        ("status", Value::String(status.to_string())),
+        ("outputs", Value::strings(&report.outputs)),
+        ("chapters", Value::Integer(report.chapters as i64)),
+        ("images", Value::Integer(report.images as i64)),
+        ("warnings", Value::strings(&report.warnings)),
+        ("duration_ms", Value::Integer(duration.as_millis() as i64)),
+        ("error", error),
+    ];
+    if let Some(stats) = &report.stats {
+        fields.push(("stats", stats.to_value()));
+    }
+    Value::Map(fields)
+}
@@ -0,0 +1,85 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::EpubToMdError;
+
+// 未指定 --resume=<file> 时，状态文件写在输出目录中
+pub const DEFAULT_NAME: &str = ".epub2md-resume";
+
+// 已转换完成的书：每行是内容的 SHA-256 与输入路径，用制表符分隔；
+// 每完成一本就追加一行，中途中断时已完成的记录不会丢失
+pub struct State {
+    path: PathBuf,
+    done: HashSet<(String, String)>,
+    file: Option<File>,
+}
+
+impl State {
+    pub fn load(path: &Path) -> Result<State, EpubToMdError> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(EpubToMdError::FileIOError(tr!("Failed to read {}: {}", path.display(), e))),
+        };
+        let done = text
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(hash, input)| (hash.to_string(), input.to_string()))
+            .collect();
+        Ok(State {
+            path: path.to_path_buf(),
+            done,
+            file: None,
+        })
+    }
+
+    pub fn is_done(&self, input: &str, hash: &str) -> bool {
+        self.done.contains(&(hash.to_string(), input.to_string()))
+    }
+
+    pub fn record(&mut self, input: &str, hash: &str) -> Result<(), EpubToMdError> {
+        let error = |e: std::io::Error| EpubToMdError::FileIOError(tr!("Failed to write {}: {}", self.path.display(), e));
+        if self.file.is_none() {
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&self.path).map_err(error)?);
+        }
+        if let Some(file) = &mut self.file {
+            writeln!(file, "{}\t{}", hash, input).and_then(|()| file.flush()).map_err(error)?;
+        }
+        self.done.insert((hash.to_string(), input.to_string()));
+        Ok(())
+    }
+}
+
+fn hash_dir(hasher: &mut Sha256, root: &Path, dir: &Path) -> std::io::Result<()> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?.map(|entry| entry.map(|entry| entry.path())).collect::<Result<_, _>>()?;
+    entries.sort();
+    for path in entries {
+        if path.is_dir() {
+            hash_dir(hasher, root, &path)?;
+        } else {
+            let relative = path.strip_prefix(root).unwrap_or(&path);
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(fs::read(&path)?);
+        }
+    }
+    Ok(())
+}
+
+// EPUB 文件内容的 SHA-256；解压后的目录按路径顺序对每个文件的路径与内容求值
+pub fn content_hash(input: &str) -> Result<String, EpubToMdError> {
+    let path = Path::new(input);
+    let mut hasher = Sha256::new();
+    let result = match path.is_dir() {
+        true => hash_dir(&mut hasher, path, path),
+        false => fs::read(path).map(|bytes| hasher.update(bytes)),
+    };
+    result.map_err(|e| EpubToMdError::FileIOError(tr!("Failed to read {}: {}", input, e)))?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+
+use crate::{dom, markdown::SkipHandlerFactory, options::RubyStyle};
+
+// <ruby> 按 --ruby 的写法：paren 时注音放在被注字后的括号中，html 时整个元素原样保留，base 时只保留被注字
+struct RubyHandler(RubyStyle);
+
+impl TagHandler for RubyHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        if self.0 == RubyStyle::Html {
+            printer.append_str(&dom::outer_html(tag));
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+
+    fn skip_descendants(&self) -> bool {
+        self.0 == RubyStyle::Html
+    }
+}
+
+// 注音 <rt>；不支持 ruby 的阅读器显示的括号 <rp> 直接丢弃，括号由这里加上
+struct AnnotationHandler {
+    style: RubyStyle,
+    start_pos: usize,
+}
+
+impl TagHandler for AnnotationHandler {
+    fn handle(&mut self, _tag: &Handle, printer: &mut StructuredPrinter) {
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        let annotation = printer.data.split_off(self.start_pos);
+        let annotation = annotation.split_whitespace().collect::<Vec<_>>().join(" ");
+        if self.style == RubyStyle::Paren && !annotation.is_empty() {
+            printer.append_str(&format!("（{}）", annotation));
+        }
+    }
+
+    fn skip_descendants(&self) -> bool {
+        self.style == RubyStyle::Base
+    }
+}
+
+struct RubyHandlerFactory(RubyStyle);
+
+impl TagHandlerFactory for RubyHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(RubyHandler(self.0))
+    }
+}
+
+struct AnnotationHandlerFactory(RubyStyle);
+
+impl TagHandlerFactory for AnnotationHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(AnnotationHandler {
+            style: self.0,
+            start_pos: 0,
+        })
+    }
+}
+
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, style: RubyStyle) {
+    handlers.insert("ruby".to_string(), Box::new(RubyHandlerFactory(style)));
+    handlers.insert("rt".to_string(), Box::new(AnnotationHandlerFactory(style)));
+    handlers.insert("rp".to_string(), Box::new(SkipHandlerFactory));
+}
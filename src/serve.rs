@@ -0,0 +1,219 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use percent_encoding::percent_decode_str;
+
+use crate::{archive::safe_relative_path, error::EpubToMdError};
+
+// 目录请求时优先返回的文件
+const INDEX_FILES: &[&str] = &["index.md", "README.md", "SUMMARY.md"];
+// 客户端迟迟不发送请求时断开连接
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+// 请求行与请求头的总长度上限
+const MAX_HEADER_SIZE: u64 = 16 * 1024;
+
+fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+    match extension.to_ascii_lowercase().as_str() {
+        "md" | "markdown" | "txt" | "adoc" | "org" | "rst" => "text/plain; charset=utf-8",
+        "html" | "htm" | "xhtml" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "json" => "application/json",
+        "yaml" | "yml" | "toml" => "text/plain; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "avif" => "image/avif",
+        _ => "application/octet-stream",
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// 没有索引文件的目录列出其中的文件
+fn listing(dir: &Path, request_path: &str) -> Vec<u8> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if entry.path().is_dir() {
+                name + "/"
+            } else {
+                name
+            }
+        })
+        .collect();
+    names.sort();
+    let base = if request_path.ends_with('/') { request_path.to_string() } else { format!("{}/", request_path) };
+    let items: String = names
+        .iter()
+        .map(|name| format!("<li><a href=\"{}{}\">{}</a></li>\n", escape_html(&base), escape_html(name), escape_html(name)))
+        .collect();
+    format!(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head>\n<body><h1>{0}</h1>\n<ul>\n{1}</ul></body></html>\n",
+        escape_html(request_path),
+        items
+    )
+    .into_bytes()
+}
+
+// 请求路径对应的文件；路径经过与解压相同的校验，不会读到目录之外
+fn resolve(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let relative = request_path.trim_matches('/');
+    let path = if relative.is_empty() { root.to_path_buf() } else { root.join(safe_relative_path(relative).ok()?) };
+    path.exists().then_some(path)
+}
+
+fn respond(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8], head: bool) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    if !head {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+fn handle(mut stream: TcpStream, root: &Path) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone()?.take(MAX_HEADER_SIZE));
+    reader.read_line(&mut request_line)?;
+    // 读完请求头，客户端才会等待响应；请求头在空行之前结束或超过上限时不再处理
+    let mut header = String::new();
+    let complete = loop {
+        header.clear();
+        match reader.read_line(&mut header)? {
+            0 => break false,
+            _ if header.trim_end_matches(['\r', '\n']).is_empty() => break true,
+            _ => {}
+        }
+    };
+    if !complete {
+        return match reader.get_ref().limit() {
+            0 => respond(
+                &mut stream,
+                "431 Request Header Fields Too Large",
+                "text/plain; charset=utf-8",
+                b"Request Header Fields Too Large\n",
+                false,
+            ),
+            _ => respond(&mut stream, "400 Bad Request", "text/plain; charset=utf-8", b"Bad Request\n", false),
+        };
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or("/");
+    let head = method == "HEAD";
+    if method != "GET" && !head {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain; charset=utf-8", b"Method Not Allowed\n", false);
+    }
+    let request_path = percent_decode_str(target.split(['?', '#']).next().unwrap_or("/")).decode_utf8_lossy();
+    let Some(path) = resolve(root, &request_path) else {
+        return respond(&mut stream, "404 Not Found", "text/plain; charset=utf-8", b"Not Found\n", head);
+    };
+    let file = if path.is_dir() {
+        INDEX_FILES.iter().map(|name| path.join(name)).find(|index| index.is_file())
+    } else {
+        Some(path.clone())
+    };
+    match file {
+        Some(file) => match fs::read(&file) {
+            Ok(body) => respond(&mut stream, "200 OK", content_type(&file), &body, head),
+            Err(_) => respond(&mut stream, "404 Not Found", "text/plain; charset=utf-8", b"Not Found\n", head),
+        },
+        None => respond(&mut stream, "200 OK", "text/html; charset=utf-8", &listing(&path, &request_path), head),
+    }
+}
+
+// 每个连接在单独的线程中处理，一个不发送请求的客户端不会挡住其他请求
+fn accept(listener: TcpListener, root: &Path) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let root = root.to_path_buf();
+                thread::spawn(move || {
+                    if let Err(e) = handle(stream, &root) {
+                        log::warn!("{}", tr!("request failed: {}", e));
+                    }
+                });
+            }
+            Err(e) => log::warn!("{}", tr!("request failed: {}", e)),
+        }
+    }
+}
+
+// 在 127.0.0.1 上提供转换结果，直到进程被中断
+pub fn serve(root: &Path, port: u16) -> Result<(), EpubToMdError> {
+    let listener = TcpListener::bind(("127.0.0.1", port))
+        .map_err(|e| EpubToMdError::FileIOError(tr!("Failed to listen on port {}: {}", port, e)))?;
+    let port = listener.local_addr().map_or(port, |address| address.port());
+    log::info!("{}", tr!("Serving the Markdown at http://127.0.0.1:{}/ (press Ctrl+C to stop)", port));
+    accept(listener, root);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Shutdown;
+
+    use super::*;
+
+    fn start(root: &Path) -> u16 {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let root = root.to_path_buf();
+        thread::spawn(move || accept(listener, &root));
+        port
+    }
+
+    fn request(port: u16, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        stream.write_all(request).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn idle_client_does_not_block_others() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.md"), "# Book\n").unwrap();
+        let port = start(dir.path());
+        // 只连接不发送请求
+        let _idle = TcpStream::connect(("127.0.0.1", port)).unwrap();
+        let response = request(port, b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+        assert!(response.ends_with("# Book\n"));
+    }
+
+    #[test]
+    fn headers_are_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let port = start(dir.path());
+        // 正好读到上限仍没有结束的请求头
+        let mut oversized = b"GET / HTTP/1.1\r\nX-Padding: ".to_vec();
+        oversized.resize(MAX_HEADER_SIZE as usize, b'a');
+        let response = request(port, &oversized);
+        assert!(response.starts_with("HTTP/1.1 431 "), "{}", response);
+        assert!(request(port, b"GET / HTTP/1.1\r\nHost: localhost\r\n").starts_with("HTTP/1.1 400 "));
+    }
+}
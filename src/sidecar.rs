@@ -0,0 +1,171 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    book::Book,
+    epub::Metadata,
+    error::EpubToMdError,
+    frontmatter::quote,
+    options::MetadataFormat,
+    output::{self, write_output},
+};
+
+const SIDECAR_STEM: &str = "book";
+
+pub enum Value {
+    Null,
+    Bool(bool),
+    String(String),
+    Integer(i64),
+    List(Vec<Value>),
+    Map(Vec<(&'static str, Value)>),
+}
+
+impl Value {
+    fn optional(text: Option<&str>) -> Value {
+        text.map_or(Value::Null, |text| Value::String(text.to_string()))
+    }
+
+    pub fn strings(items: &[String]) -> Value {
+        Value::List(items.iter().map(|item| Value::String(item.clone())).collect())
+    }
+}
+
+// 附带文件的路径：目录输出时写在目录内，单文件输出时与 Markdown 文件同名
+pub fn sidecar_path(md_path: &Path, writes_directory: bool, format: MetadataFormat) -> PathBuf {
+    if writes_directory {
+        md_path.join(format!("{}.{}", SIDECAR_STEM, format.extension()))
+    } else {
+        md_path.with_extension(format.extension())
+    }
+}
+
+// 书籍元数据文件与 --metadata-only 共用的字段
+pub fn metadata_fields(metadata: &Metadata) -> Vec<(&'static str, Value)> {
+    vec![
+        ("title", Value::optional(metadata.title.as_deref())),
+        ("creators", Value::strings(&metadata.authors)),
+        ("language", Value::optional(metadata.language.as_deref())),
+        ("identifiers", Value::strings(&metadata.identifiers)),
+        ("publisher", Value::optional(metadata.publisher.as_deref())),
+        ("date", Value::optional(metadata.date.as_deref())),
+        ("description", Value::optional(metadata.description.as_deref())),
+        ("subjects", Value::strings(&metadata.subjects)),
+        ("cover", Value::optional(metadata.cover.as_deref())),
+    ]
+}
+
+// 写出书籍元数据与章节列表，供索引工具直接读取而无需再次打开 EPUB
+pub fn write_sidecar(path: &Path, book: &Book, format: MetadataFormat) -> Result<(), EpubToMdError> {
+    let chapters = book
+        .chapters
+        .iter()
+        .enumerate()
+        .map(|(i, chapter)| {
+            Value::Map(vec![
+                ("order", Value::Integer(i as i64 + 1)),
+                ("title", Value::optional(chapter.title.as_deref())),
+                ("id", Value::optional(chapter.id.as_deref())),
+                ("source", Value::String(chapter.source.clone())),
+                ("lang", Value::optional(chapter.language.as_deref())),
+            ])
+        })
+        .collect();
+    let mut fields = metadata_fields(&book.metadata);
+    fields.push(("chapters", Value::List(chapters)));
+    let value = Value::Map(fields);
+
+    let text = match format {
+        MetadataFormat::Json => to_json(&value),
+        MetadataFormat::Yaml => {
+            let mut text = String::new();
+            write_yaml(&mut text, &value, 0);
+            text
+        }
+    };
+    output::create_parent_dir(path)?;
+    write_output(path, &text)
+}
+
+// 两格缩进的 JSON，末尾带换行
+pub fn to_json(value: &Value) -> String {
+    let mut text = String::new();
+    write_json(&mut text, value, 0);
+    text.push('\n');
+    text
+}
+
+fn write_json(out: &mut String, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth + 1);
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(&b.to_string()),
+        Value::String(text) => out.push_str(&quote(text)),
+        Value::Integer(n) => out.push_str(&n.to_string()),
+        Value::List(items) if items.is_empty() => out.push_str("[]"),
+        Value::List(items) => {
+            out.push_str("[\n");
+            for (i, item) in items.iter().enumerate() {
+                out.push_str(&indent);
+                write_json(out, item, depth + 1);
+                out.push_str(if i + 1 < items.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&format!("{}]", "  ".repeat(depth)));
+        }
+        Value::Map(entries) => {
+            out.push_str("{\n");
+            for (i, (key, item)) in entries.iter().enumerate() {
+                out.push_str(&format!("{}{}: ", indent, quote(key)));
+                write_json(out, item, depth + 1);
+                out.push_str(if i + 1 < entries.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&format!("{}}}", "  ".repeat(depth)));
+        }
+    }
+}
+
+// 块状 YAML；列表中的映射以 "- " 开头，后续键与第一个键对齐
+fn write_yaml(out: &mut String, value: &Value, depth: usize) {
+    let indent = "  ".repeat(depth);
+    match value {
+        Value::Map(entries) => {
+            for (key, item) in entries {
+                out.push_str(&format!("{}{}:", indent, key));
+                match item {
+                    Value::List(items) if !items.is_empty() => {
+                        out.push('\n');
+                        write_yaml(out, item, depth);
+                    }
+                    Value::Map(_) => {
+                        out.push('\n');
+                        write_yaml(out, item, depth + 1);
+                    }
+                    _ => out.push_str(&format!(" {}\n", yaml_scalar(item))),
+                }
+            }
+        }
+        Value::List(items) => {
+            for item in items {
+                match item {
+                    Value::Map(_) => {
+                        let mut nested = String::new();
+                        write_yaml(&mut nested, item, depth + 1);
+                        out.push_str(&format!("{}- {}", indent, &nested[indent.len() + 2..]));
+                    }
+                    _ => out.push_str(&format!("{}- {}\n", indent, yaml_scalar(item))),
+                }
+            }
+        }
+        _ => out.push_str(&format!("{}{}\n", indent, yaml_scalar(value))),
+    }
+}
+
+fn yaml_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(b) => b.to_string(),
+        Value::String(text) => quote(text),
+        Value::Integer(n) => n.to_string(),
+        Value::List(_) => "[]".to_string(),
+        Value::Map(_) => "{}".to_string(),
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    book::Book,
+    error::EpubToMdError,
+    frontmatter::Frontmatter,
+    links,
+    markdown::{self, Reference, ReferenceKind},
+    naming::{self, heading_anchor, slugify},
+    options::{Format, Options},
+    output::{self, write_text},
+};
+
+// 写出静态网站的内容目录：书的索引页加每章一个页面包（<name>/index.md），
+// 章节引用的图片复制到页面包内，Hugo 称之为页面资源，Zola 称之为同目录资源，Jekyll 按静态文件复制
+pub fn write_site(
+    out_dir: &Path,
+    book: &Book,
+    options: &Options,
+) -> Result<(), EpubToMdError> {
+    let format = options.format;
+    let frontmatter_format = options.frontmatter_format();
+    let file_names = naming::chapter_file_names(
+        &book.chapters,
+        options.name_template.as_ref(),
+        book.metadata.title.as_deref(),
+        &[],
+    )?;
+    let bundles: Vec<String> = file_names
+        .iter()
+        .map(|name| {
+            let name = name.strip_suffix(".md").unwrap_or(name);
+            match format {
+                Format::Hugo => name.to_string(),
+                // Jekyll 与 Zola 直接用目录名作为永久链接，逐段转成 slug
+                _ => name.split('/').map(slugify).collect::<Vec<_>>().join("/"),
+            }
+        })
+        .collect();
+    let bundles: Vec<&str> = bundles.iter().map(String::as_str).collect();
+    let date = book.metadata.date.as_deref().and_then(normalize_date);
+
+    let mut section = Frontmatter::default();
+    section.string("title", book.metadata.title.as_deref());
+    let (index_name, section_text) = match format {
+        Format::Jekyll => {
+            section
+                .string("date", date.as_deref())
+                .list("authors", &book.metadata.authors)
+                .list("tags", &book.metadata.subjects);
+            // Jekyll 没有章节列表页，索引页中直接列出各章链接
+            let contents: Vec<String> = book
+                .chapters
+                .iter()
+                .zip(&bundles)
+                .map(|(chapter, bundle)| format!("- [{}]({}/)", chapter.title.as_deref().unwrap_or(bundle), bundle))
+                .collect();
+            ("index.md", section.prepend(frontmatter_format, &format!("{}\n", contents.join("\n"))))
+        }
+        Format::Zola => {
+            section.string("sort_by", Some("weight"));
+            ("_index.md", section.prepend(frontmatter_format, ""))
+        }
+        _ => {
+            section
+                .string("date", date.as_deref())
+                .list("authors", &book.metadata.authors)
+                .list("tags", &book.metadata.subjects);
+            ("_index.md", section.prepend(frontmatter_format, ""))
+        }
+    };
+    let index_path = out_dir.join(index_name);
+    output::create_parent_dir(&index_path)?;
+    write_text(&index_path, &section_text, options)?;
+
+    for (i, (chapter, bundle)) in book.chapters.iter().zip(&bundles).enumerate() {
+        let bundle_dir = out_dir.join(bundle);
+        let resources = bundle_resources(book, &chapter.references);
+        for (resource, name) in &resources {
+            let path = bundle_dir.join(name);
+            output::write_resource(&path, &book.resources[*resource], |e| {
+                EpubToMdError::FileIOError(tr!("Failed to write resource '{}': {}", path.display(), e))
+            })?;
+        }
+
+        let body = markdown::render_references(&chapter.markdown, &chapter.references, &|reference, text| {
+            render(reference, text, i, book, &bundles, &resources, format)
+        });
+        let (body, _) = links::restyle(&body, options.link_style, 1);
+
+        let mut frontmatter = Frontmatter::default();
+        frontmatter.string("title", Some(chapter.title.as_deref().unwrap_or(bundle)));
+        match format {
+            Format::Jekyll => frontmatter
+                .integer("nav_order", i as i64 + 1)
+                .string("date", date.as_deref())
+                .list("tags", &book.metadata.subjects)
+                .string("lang", chapter.language.as_deref()),
+            // Zola 不接受未知的顶层字段，自定义字段放在 extra 中
+            Format::Zola => frontmatter
+                .integer("weight", i as i64 + 1)
+                .string("date", date.as_deref())
+                .list("taxonomies.tags", &book.metadata.subjects)
+                .string("extra.lang", chapter.language.as_deref()),
+            _ => frontmatter
+                .integer("weight", i as i64 + 1)
+                .string("date", date.as_deref())
+                .list("tags", &book.metadata.subjects)
+                .string("lang", chapter.language.as_deref()),
+        };
+
+        let path = bundle_dir.join("index.md");
+        output::create_parent_dir(&path)?;
+        write_text(&path, &frontmatter.prepend(frontmatter_format, &body), options)?;
+    }
+    Ok(())
+}
+
+// 章节引用的资源在页面包内的文件名
+fn bundle_resources<'a>(book: &'a Book, references: &[Reference]) -> HashMap<&'a str, String> {
+    let mut used = HashSet::from(["index.md".to_string()]);
+    let mut names = HashMap::new();
+    for reference in references {
+        let Some((resource, _)) = book.resources.get_key_value(&reference.path) else {
+            continue;
+        };
+        if !names.contains_key(resource.as_str()) {
+            let file_name = resource.rsplit('/').next().unwrap_or(resource);
+            names.insert(resource.as_str(), naming::unique_name(&mut used, file_name));
+        }
+    }
+    names
+}
+
+fn render(
+    reference: &Reference,
+    text: &str,
+    current: usize,
+    book: &Book,
+    bundles: &[&str],
+    resources: &HashMap<&str, String>,
+    format: Format,
+) -> String {
+    if let Some(name) = resources.get(reference.path.as_str()) {
+        let target = name.replace(' ', "%20");
+        return match reference.kind {
+            ReferenceKind::Image => format!("![{}]({})", text, target),
+            ReferenceKind::Link => format!("[{}]({})", text, target),
+        };
+    }
+    let target = book.chapter_index(reference);
+    match (reference.kind, target) {
+        (ReferenceKind::Link, Some(target)) => {
+            let chapter = &book.chapters[target];
+            // 三者都按标题文字生成锚点，落在标题上的 id 改为对应的锚点，其余保持原样
+            let anchor = match reference.fragment.as_deref() {
+                Some(fragment) => match chapter.anchor_heading(fragment) {
+                    // Zola 用 slug 生成锚点，Hugo 与 Jekyll 采用 GitHub 风格
+                    Some(heading) if format == Format::Zola => format!("#{}", slugify(heading)),
+                    Some(heading) => format!("#{}", heading_anchor(heading)),
+                    None => format!("#{}", fragment),
+                },
+                None => String::new(),
+            };
+            if target == current && !anchor.is_empty() {
+                return format!("[{}]({})", text, anchor);
+            }
+            // 页面包之间用相对链接互相引用，层级由当前 bundle 名决定
+            let depth = bundles[current].matches('/').count() + 1;
+            format!("[{}]({}{}/{})", text, "../".repeat(depth), bundles[target], anchor)
+        }
+        _ => reference.to_markdown(text),
+    }
+}
+
+// 静态网站生成器只接受完整日期，dc:date 可能只有年份或年月
+fn normalize_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    let parts: Vec<&str> = date.split('-').collect();
+    if !parts[0].chars().all(|c| c.is_ascii_digit()) || parts[0].len() != 4 {
+        return None;
+    }
+    match parts.len() {
+        1 => Some(format!("{}-01-01", date)),
+        2 => Some(format!("{}-01", date)),
+        _ => Some(date.to_string()),
+    }
+}
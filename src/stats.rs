@@ -0,0 +1,218 @@
+use std::{cell::Cell, path::Path};
+
+use crate::{
+    book::Book,
+    cjk,
+    error::EpubToMdError,
+    markdown::{self, ReferenceKind},
+    output,
+    sidecar::{self, Value},
+};
+
+// 估算阅读时间：英文等每分钟约读 230 个词，中日韩文字每分钟约读 400 字
+const WORDS_PER_MINUTE: f64 = 230.0;
+const CJK_PER_MINUTE: f64 = 400.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct Counts {
+    // 中日韩文字每个字算一个词
+    pub words: usize,
+    // 去掉 Markdown 标记后的非空白字符
+    pub characters: usize,
+    pub images: usize,
+    pub footnotes: usize,
+    cjk: usize,
+}
+
+impl Counts {
+    fn add(&mut self, other: &Counts) {
+        self.words += other.words;
+        self.characters += other.characters;
+        self.images += other.images;
+        self.footnotes += other.footnotes;
+        self.cjk += other.cjk;
+    }
+
+    // 向上取整到分钟，有文字时至少为一分钟
+    pub fn reading_minutes(&self) -> usize {
+        let minutes = (self.words - self.cjk) as f64 / WORDS_PER_MINUTE + self.cjk as f64 / CJK_PER_MINUTE;
+        minutes.ceil() as usize
+    }
+
+    fn to_value(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("words", Value::Integer(self.words as i64)),
+            ("characters", Value::Integer(self.characters as i64)),
+            ("images", Value::Integer(self.images as i64)),
+            ("footnotes", Value::Integer(self.footnotes as i64)),
+            ("reading_minutes", Value::Integer(self.reading_minutes() as i64)),
+        ]
+    }
+}
+
+// 每章与整本书的统计，章节按输出顺序排列
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    pub chapters: Vec<(String, Counts)>,
+    pub total: Counts,
+}
+
+// 去掉 HTML 标签、链接地址、脚注标号与强调、标题等标记，只留下读者看到的文字
+fn plain_text(markdown: &str) -> String {
+    let mut plain = String::with_capacity(markdown.len());
+    for line in markdown.lines() {
+        // 分隔线、Setext 标题的下划线与表格的分隔行
+        if line.chars().all(|c| matches!(c, '-' | '=' | ':' | '|' | '*' | '_' | ' ')) {
+            continue;
+        }
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '<' if chars.peek().is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '/' | '!')) => {
+                    chars.by_ref().find(|&c| c == '>');
+                }
+                ']' if chars.peek() == Some(&'(') => {
+                    let mut depth = 0;
+                    for c in chars.by_ref() {
+                        match c {
+                            '(' => depth += 1,
+                            ')' if depth == 1 => break,
+                            ')' => depth -= 1,
+                            _ => {}
+                        }
+                    }
+                }
+                '[' if chars.peek() == Some(&'^') => {
+                    chars.by_ref().find(|&c| c == ']');
+                    if chars.peek() == Some(&':') {
+                        chars.next();
+                    }
+                }
+                '#' | '*' | '_' | '`' | '~' | '>' | '|' | '[' | ']' => plain.push(' '),
+                '!' if chars.peek() == Some(&'[') => {}
+                c => plain.push(c),
+            }
+        }
+        plain.push('\n');
+    }
+    plain
+}
+
+fn count(markdown: &str, references: &[markdown::Reference]) -> Counts {
+    // 书内的图片是占位标记，数出后不计入文字
+    let images = Cell::new(0);
+    let text = markdown::render_references(markdown, references, &|reference, text| match reference.kind {
+        ReferenceKind::Image => {
+            images.set(images.get() + 1);
+            String::new()
+        }
+        ReferenceKind::Link => text.to_string(),
+    });
+    let external_images = text.matches("![").count();
+    let footnotes = text
+        .lines()
+        .filter(|line| (line.starts_with("[^") && line.contains("]:")) || line.starts_with("<a id=\"fn-") || line.starts_with("[]{#fn-"))
+        .count();
+    let plain = plain_text(&text);
+    let mut counts = Counts {
+        images: images.get() + external_images,
+        footnotes,
+        characters: plain.chars().filter(|c| !c.is_whitespace()).count(),
+        ..Counts::default()
+    };
+    let mut in_word = false;
+    for c in plain.chars() {
+        if cjk::is_cjk(c) {
+            counts.words += 1;
+            counts.cjk += 1;
+            in_word = false;
+        } else if c.is_alphanumeric() || (in_word && matches!(c, '\'' | '’' | '-')) {
+            if !in_word {
+                counts.words += 1;
+            }
+            in_word = true;
+        } else {
+            in_word = false;
+        }
+    }
+    counts
+}
+
+pub fn collect(book: &Book) -> Stats {
+    let mut stats = Stats::default();
+    for chapter in &book.chapters {
+        let counts = count(&chapter.markdown, &chapter.references);
+        stats.total.add(&counts);
+        stats.chapters.push((chapter.title.clone().unwrap_or_else(|| chapter.stem()), counts));
+    }
+    stats
+}
+
+impl Stats {
+    pub fn to_value(&self) -> Value {
+        let chapters = self
+            .chapters
+            .iter()
+            .map(|(title, counts)| {
+                let mut fields = vec![("title", Value::String(title.clone()))];
+                fields.extend(counts.to_value());
+                Value::Map(fields)
+            })
+            .collect();
+        let mut fields = vec![("chapters", Value::Integer(self.chapters.len() as i64))];
+        fields.extend(self.total.to_value());
+        fields.push(("per_chapter", Value::List(chapters)));
+        Value::Map(fields)
+    }
+
+    // 每章一行，最后一行为全书合计
+    pub fn table(&self) -> String {
+        let header = [
+            tr!("Chapter"),
+            tr!("Words"),
+            tr!("Characters"),
+            tr!("Images"),
+            tr!("Footnotes"),
+            tr!("Reading time"),
+        ];
+        let row = |title: String, counts: &Counts| {
+            [
+                title,
+                counts.words.to_string(),
+                counts.characters.to_string(),
+                counts.images.to_string(),
+                counts.footnotes.to_string(),
+                tr!("{} min", counts.reading_minutes()),
+            ]
+        };
+        let columns = header.len();
+        let mut rows = vec![header];
+        for (index, (title, counts)) in self.chapters.iter().enumerate() {
+            rows.push(row(format!("{}. {}", index + 1, title), counts));
+        }
+        rows.push(row(tr!("Total ({} chapters)", self.chapters.len()), &self.total));
+        // 按显示宽度对齐，中日韩文字占两列
+        let width = |text: &str| text.chars().map(|c| if cjk::is_cjk(c) || matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF00}'..='\u{FFEF}') { 2 } else { 1 }).sum::<usize>();
+        let widths: Vec<usize> = (0..columns).map(|column| rows.iter().map(|row| width(&row[column])).max().unwrap_or(0)).collect();
+        let mut table = String::new();
+        for row in &rows {
+            let cells: Vec<String> = row
+                .iter()
+                .enumerate()
+                .map(|(column, cell)| {
+                    let padding = " ".repeat(widths[column] - width(cell));
+                    // 标题左对齐，数字右对齐
+                    if column == 0 { format!("{}{}", cell, padding) } else { format!("{}{}", padding, cell) }
+                })
+                .collect();
+            table.push_str(cells.join("  ").trim_end());
+            table.push('\n');
+        }
+        table
+    }
+}
+
+// --stats=<file> 时以 JSON 写出统计
+pub fn write(path: &Path, stats: &Stats) -> Result<(), EpubToMdError> {
+    output::write_output(path, &sidecar::to_json(&stats.to_value()))
+}
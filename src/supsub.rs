@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+
+use crate::options::SupSubStyle;
+
+const SUPERSCRIPTS: &[(char, char)] = &[
+    ('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'), ('5', '⁵'), ('6', '⁶'), ('7', '⁷'), ('8', '⁸'),
+    ('9', '⁹'), ('+', '⁺'), ('-', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'), ('a', 'ᵃ'), ('b', 'ᵇ'), ('c', 'ᶜ'),
+    ('d', 'ᵈ'), ('e', 'ᵉ'), ('f', 'ᶠ'), ('g', 'ᵍ'), ('h', 'ʰ'), ('i', 'ⁱ'), ('j', 'ʲ'), ('k', 'ᵏ'), ('l', 'ˡ'),
+    ('m', 'ᵐ'), ('n', 'ⁿ'), ('o', 'ᵒ'), ('p', 'ᵖ'), ('r', 'ʳ'), ('s', 'ˢ'), ('t', 'ᵗ'), ('u', 'ᵘ'), ('v', 'ᵛ'),
+    ('w', 'ʷ'), ('x', 'ˣ'), ('y', 'ʸ'), ('z', 'ᶻ'),
+];
+const SUBSCRIPTS: &[(char, char)] = &[
+    ('0', '₀'), ('1', '₁'), ('2', '₂'), ('3', '₃'), ('4', '₄'), ('5', '₅'), ('6', '₆'), ('7', '₇'), ('8', '₈'),
+    ('9', '₉'), ('+', '₊'), ('-', '₋'), ('=', '₌'), ('(', '₍'), (')', '₎'), ('a', 'ₐ'), ('e', 'ₑ'), ('h', 'ₕ'),
+    ('i', 'ᵢ'), ('j', 'ⱼ'), ('k', 'ₖ'), ('l', 'ₗ'), ('m', 'ₘ'), ('n', 'ₙ'), ('o', 'ₒ'), ('p', 'ₚ'), ('r', 'ᵣ'),
+    ('s', 'ₛ'), ('t', 'ₜ'), ('u', 'ᵤ'), ('v', 'ᵥ'), ('x', 'ₓ'),
+];
+
+// <sup> / <sub> 按 --sup-sub 写成 HTML、Pandoc 的 ^上标^ / ~下标~ 或 Unicode 上下标字符
+struct SupSubHandler {
+    style: SupSubStyle,
+    tag: &'static str,
+    start_pos: usize,
+}
+
+impl TagHandler for SupSubHandler {
+    fn handle(&mut self, _tag: &Handle, printer: &mut StructuredPrinter) {
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        let content = printer.data.split_off(self.start_pos);
+        let text = content.trim();
+        // 上标中只有脚注引用时，[^n] 本身就会显示为上标
+        if text.is_empty() || (text.starts_with("[^") && text.ends_with(']') && !text[1..].contains('[')) {
+            printer.append_str(text);
+            return;
+        }
+        let (delimiter, table) = match self.tag {
+            "sup" => ("^", SUPERSCRIPTS),
+            _ => ("~", SUBSCRIPTS),
+        };
+        let unicode: Option<String> = text
+            .chars()
+            .map(|c| table.iter().find(|(from, _)| *from == c).map(|(_, to)| *to))
+            .collect();
+        let converted = match (self.style, unicode) {
+            (SupSubStyle::Unicode, Some(unicode)) => unicode,
+            // 空格需要转义
+            (SupSubStyle::Caret, _) => format!("{}{}{}", delimiter, text.replace(' ', "\\ "), delimiter),
+            // 没有对应字符时退回 HTML
+            _ => format!("<{}>{}</{}>", self.tag, text, self.tag),
+        };
+        printer.append_str(&converted);
+    }
+}
+
+struct SupSubHandlerFactory {
+    style: SupSubStyle,
+    tag: &'static str,
+}
+
+impl TagHandlerFactory for SupSubHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(SupSubHandler {
+            style: self.style,
+            tag: self.tag,
+            start_pos: 0,
+        })
+    }
+}
+
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, style: SupSubStyle) {
+    for tag in ["sup", "sub"] {
+        handlers.insert(tag.to_string(), Box::new(SupSubHandlerFactory { style, tag }));
+    }
+}
@@ -0,0 +1,336 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use html2md::{common::get_tag_attr, Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+
+use crate::dom;
+
+// 管道表格无法表达的内容：合并单元格、嵌套表格和单元格中的列表、代码块
+const FALLBACK_TAGS: &[&str] = &["table", "ul", "ol", "pre", "dl"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alignment {
+    Default,
+    Left,
+    Center,
+    Right,
+}
+
+impl Alignment {
+    // 取自 align 属性或行内样式中的 text-align
+    fn of(tag: &Handle) -> Alignment {
+        let style = get_tag_attr(tag, "style").and_then(|style| {
+            style.split(';').find_map(|declaration| {
+                let (property, value) = declaration.split_once(':')?;
+                (property.trim().eq_ignore_ascii_case("text-align")).then(|| value.trim().to_string())
+            })
+        });
+        match get_tag_attr(tag, "align").or(style).map(|align| align.to_ascii_lowercase()).as_deref() {
+            Some("left" | "start") => Alignment::Left,
+            Some("center") => Alignment::Center,
+            Some("right" | "end") => Alignment::Right,
+            _ => Alignment::Default,
+        }
+    }
+
+    fn delimiter(self) -> &'static str {
+        match self {
+            Alignment::Default => "---",
+            Alignment::Left => ":---",
+            Alignment::Center => ":---:",
+            Alignment::Right => "---:",
+        }
+    }
+}
+
+struct Cell {
+    text: String,
+    header: bool,
+    alignment: Alignment,
+}
+
+struct Row {
+    // 位于 <thead> 中
+    head: bool,
+    cells: Vec<Cell>,
+}
+
+impl Row {
+    fn is_header(&self) -> bool {
+        self.head || (!self.cells.is_empty() && self.cells.iter().all(|cell| cell.header))
+    }
+}
+
+#[derive(Default)]
+struct Table {
+    caption: Option<String>,
+    rows: Vec<Row>,
+}
+
+impl Table {
+    fn to_markdown(&self) -> String {
+        let columns = self.rows.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return String::new();
+        }
+        // 没有表头时输出空表头，不把第一行数据提升为表头
+        let (header, body) = match self.rows.split_first() {
+            Some((first, rest)) if first.is_header() => (Some(first), rest),
+            _ => (None, self.rows.as_slice()),
+        };
+        let alignments: Vec<Alignment> = (0..columns)
+            .map(|column| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.cells.get(column))
+                    .map(|cell| cell.alignment)
+                    .find(|alignment| *alignment != Alignment::Default)
+                    .unwrap_or(Alignment::Default)
+            })
+            .collect();
+
+        let line = |cells: Vec<&str>| format!("| {} |\n", cells.join(" | "));
+        let row_line = |row: Option<&Row>| {
+            line((0..columns)
+                .map(|column| row.and_then(|row| row.cells.get(column)).map_or("", |cell| cell.text.as_str()))
+                .collect())
+        };
+        let mut markdown = String::new();
+        if let Some(caption) = self.caption.as_deref().filter(|caption| !caption.is_empty()) {
+            markdown.push_str(caption);
+            markdown.push_str("\n\n");
+        }
+        markdown.push_str(&row_line(header));
+        markdown.push_str(&line(alignments.iter().map(|alignment| alignment.delimiter()).collect()));
+        for row in body {
+            markdown.push_str(&row_line(Some(row)));
+        }
+        markdown
+    }
+}
+
+// 正在转换的表格；嵌套表格整体退回 HTML，栈中通常只有一张表
+type Tables = Rc<RefCell<Vec<Table>>>;
+
+// 单元格内容合并为一行，段落之间用 <br> 分隔，并转义竖线
+fn cell_text(markdown: &str) -> String {
+    markdown
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("<br>")
+        .replace('|', "\\|")
+}
+
+fn needs_fallback(table: &Handle) -> bool {
+    dom::descendants(table).iter().any(|node| match dom::tag_name(node).as_deref() {
+        Some("td" | "th") => ["rowspan", "colspan"].iter().any(|name| {
+            dom::attr(node, name)
+                .and_then(|span| span.trim().parse::<usize>().ok())
+                .is_some_and(|span| span > 1)
+        }),
+        Some(tag) => FALLBACK_TAGS.contains(&tag),
+        None => false,
+    })
+}
+
+// 简单表格转换为 GFM 管道表格，复杂表格和不支持管道表格的方言原样保留 HTML
+struct TableHandler {
+    tables: Tables,
+    start_pos: usize,
+    pipe_tables: bool,
+    fallback: bool,
+}
+
+impl TagHandler for TableHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        self.fallback = !self.pipe_tables || needs_fallback(tag);
+        if self.fallback {
+            printer.insert_newline();
+            printer.insert_newline();
+            printer.append_str(&dom::outer_html(tag));
+            printer.insert_newline();
+            printer.insert_newline();
+        } else {
+            self.tables.borrow_mut().push(Table::default());
+            self.start_pos = printer.data.len();
+        }
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        if self.fallback {
+            return;
+        }
+        let Some(table) = self.tables.borrow_mut().pop() else {
+            return;
+        };
+        // 丢弃行与行之间的空白等残留文字
+        printer.data.truncate(self.start_pos);
+        let markdown = table.to_markdown();
+        if !markdown.is_empty() {
+            printer.insert_newline();
+            printer.insert_newline();
+            printer.append_str(&markdown);
+            printer.insert_newline();
+        }
+    }
+
+    fn skip_descendants(&self) -> bool {
+        self.fallback
+    }
+}
+
+struct RowHandler(Tables);
+
+impl TagHandler for RowHandler {
+    fn handle(&mut self, tag: &Handle, _printer: &mut StructuredPrinter) {
+        let head = dom::parent(tag).is_some_and(|parent| dom::tag_name(&parent).as_deref() == Some("thead"));
+        if let Some(table) = self.0.borrow_mut().last_mut() {
+            table.rows.push(Row {
+                head,
+                cells: Vec::new(),
+            });
+        }
+    }
+
+    fn after_handle(&mut self, _printer: &mut StructuredPrinter) {}
+}
+
+struct CellHandler {
+    tables: Tables,
+    start_pos: usize,
+    header: bool,
+    alignment: Alignment,
+}
+
+impl TagHandler for CellHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        self.start_pos = printer.data.len();
+        self.header = dom::tag_name(tag).as_deref() == Some("th");
+        self.alignment = Alignment::of(tag);
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        let mut tables = self.tables.borrow_mut();
+        // 不在表格中的单元格保持 html2md 的默认输出
+        let Some(row) = tables.last_mut().and_then(|table| table.rows.last_mut()) else {
+            return;
+        };
+        let text = printer.data.split_off(self.start_pos);
+        row.cells.push(Cell {
+            text: cell_text(&text),
+            header: self.header,
+            alignment: self.alignment,
+        });
+    }
+}
+
+// 表格标题输出为表格上方的一段文字
+struct CaptionHandler {
+    tables: Tables,
+    start_pos: usize,
+}
+
+impl TagHandler for CaptionHandler {
+    fn handle(&mut self, _tag: &Handle, printer: &mut StructuredPrinter) {
+        self.start_pos = printer.data.len();
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        if let Some(table) = self.tables.borrow_mut().last_mut() {
+            let text = printer.data.split_off(self.start_pos);
+            table.caption = Some(cell_text(&text).replace("<br>", " "));
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Part {
+    Table,
+    Row,
+    Cell,
+    Caption,
+}
+
+struct TableHandlerFactory {
+    part: Part,
+    tables: Tables,
+    pipe_tables: bool,
+}
+
+impl TagHandlerFactory for TableHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        let tables = self.tables.clone();
+        match self.part {
+            Part::Table => Box::new(TableHandler {
+                tables,
+                start_pos: 0,
+                pipe_tables: self.pipe_tables,
+                fallback: false,
+            }),
+            Part::Row => Box::new(RowHandler(tables)),
+            Part::Cell => Box::new(CellHandler {
+                tables,
+                start_pos: 0,
+                header: false,
+                alignment: Alignment::Default,
+            }),
+            Part::Caption => Box::new(CaptionHandler { tables, start_pos: 0 }),
+        }
+    }
+}
+
+// 注册表格相关元素的处理器，替换 html2md 自带的表格转换
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, pipe_tables: bool) {
+    let tables = Tables::default();
+    for (tag, part) in [
+        ("table", Part::Table),
+        ("tr", Part::Row),
+        ("td", Part::Cell),
+        ("th", Part::Cell),
+        ("caption", Part::Caption),
+    ] {
+        handlers.insert(
+            tag.to_string(),
+            Box::new(TableHandlerFactory {
+                part,
+                tables: tables.clone(),
+                pipe_tables,
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(html: &str, pipe_tables: bool) -> String {
+        let mut handlers = HashMap::new();
+        register(&mut handlers, pipe_tables);
+        html2md::parse_html_custom(html, &handlers)
+    }
+
+    #[test]
+    fn simple_tables_become_pipe_tables() {
+        let html = concat!(
+            r#"<table><caption>Prices</caption><thead><tr><th>Name</th><th align="right">Cost</th></tr></thead>"#,
+            r#"<tbody><tr><td>a|b</td><td style="text-align: center">1</td></tr></tbody></table>"#,
+        );
+        assert_eq!(convert(html, true), "Prices\n\n| Name | Cost |\n| --- | ---: |\n| a\\|b | 1 |");
+    }
+
+    #[test]
+    fn rows_without_a_header_get_an_empty_one() {
+        let html = "<table><tr><td>one</td><td>two</td></tr><tr><td><p>three</p><p>four</p></td></tr></table>";
+        assert_eq!(convert(html, true), "|  |  |\n| --- | --- |\n| one | two |\n| three<br>four |  |");
+    }
+
+    #[test]
+    fn complex_tables_stay_html() {
+        let html = r#"<table><tbody><tr><td colspan="2">wide</td></tr></tbody></table>"#;
+        assert_eq!(convert(html, true), html);
+        let html = "<table><tbody><tr><td>one</td></tr></tbody></table>";
+        assert_eq!(convert(html, false), html);
+    }
+}
@@ -0,0 +1,263 @@
+use roxmltree::Node;
+
+use crate::{
+    epub::{parent_dir, parse_xml, resolve_href},
+    error::EpubToMdError,
+    sidecar::Value,
+};
+
+const OPS_NAMESPACE: &str = "http://www.idpf.org/2007/ops";
+
+#[derive(Debug, Clone)]
+pub struct TocEntry {
+    pub title: String,
+    // 压缩包内的完整路径，不含片段
+    pub path: String,
+    pub fragment: Option<String>,
+    // 顶层条目为 0
+    pub depth: usize,
+    pub children: Vec<TocEntry>,
+}
+
+impl TocEntry {
+    fn new(title: &str, href: Option<&str>, base_dir: &str, depth: usize) -> Self {
+        let href = href.unwrap_or_default();
+        TocEntry {
+            title: normalize_title(title),
+            path: if href.is_empty() { String::new() } else { resolve_href(base_dir, href) },
+            fragment: href.split_once('#').map(|(_, fragment)| fragment.to_string()),
+            depth,
+            children: Vec::new(),
+        }
+    }
+
+    // 按先序遍历展开整棵目录树
+    pub fn flatten(entries: &[TocEntry]) -> Vec<&TocEntry> {
+        let mut result = Vec::new();
+        for entry in entries {
+            result.push(entry);
+            result.extend(TocEntry::flatten(&entry.children));
+        }
+        result
+    }
+}
+
+// 以缩进列表的形式打印目录树
+pub fn print_tree(entries: &[TocEntry]) {
+    for entry in TocEntry::flatten(entries) {
+        let target = match &entry.fragment {
+            Some(fragment) => format!("{}#{}", entry.path, fragment),
+            None => entry.path.clone(),
+        };
+        println!("{}- {} ({})", "  ".repeat(entry.depth), entry.title, target);
+    }
+}
+
+// inspect --json 中的目录树，子条目嵌套在 children 中
+pub fn tree_value(entries: &[TocEntry]) -> Value {
+    Value::List(
+        entries
+            .iter()
+            .map(|entry| {
+                Value::Map(vec![
+                    ("title", Value::String(entry.title.clone())),
+                    ("path", Value::String(entry.path.clone())),
+                    ("fragment", entry.fragment.clone().map_or(Value::Null, Value::String)),
+                    ("children", tree_value(&entry.children)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn normalize_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// 解析 EPUB 3 导航文档中 epub:type="toc" 的 <nav>
+pub fn parse_nav(text: &str, nav_path: &str) -> Result<Vec<TocEntry>, EpubToMdError> {
+    let doc = parse_xml(text, nav_path)?;
+    let navs: Vec<Node> = doc.descendants().filter(|n| n.has_tag_name("nav")).collect();
+    let toc_nav = navs
+        .iter()
+        .find(|nav| nav.attribute((OPS_NAMESPACE, "type")).is_some_and(|t| t.split_whitespace().any(|t| t == "toc")))
+        .or_else(|| navs.first());
+
+    let Some(list) = toc_nav.and_then(|nav| nav.children().find(|n| n.has_tag_name("ol") || n.has_tag_name("ul"))) else {
+        return Ok(Vec::new());
+    };
+    Ok(parse_nav_list(list, parent_dir(nav_path), 0))
+}
+
+// 解析导航文档中 epub:type="page-list" 的 <nav>，条目标题为页码
+pub fn parse_page_list(text: &str, nav_path: &str) -> Result<Vec<TocEntry>, EpubToMdError> {
+    let doc = parse_xml(text, nav_path)?;
+    let page_list = doc.descendants().find(|n| {
+        n.has_tag_name("nav")
+            && n.attribute((OPS_NAMESPACE, "type")).is_some_and(|t| t.split_whitespace().any(|t| t == "page-list"))
+    });
+    let Some(list) = page_list.and_then(|nav| nav.children().find(|n| n.has_tag_name("ol") || n.has_tag_name("ul"))) else {
+        return Ok(Vec::new());
+    };
+    Ok(parse_nav_list(list, parent_dir(nav_path), 0))
+}
+
+// 解析导航文档中 epub:type="landmarks" 的 <nav>，返回 (文档路径, epub:type)
+pub fn parse_landmarks(text: &str, nav_path: &str) -> Result<Vec<(String, String)>, EpubToMdError> {
+    let doc = parse_xml(text, nav_path)?;
+    let Some(landmarks) = doc.descendants().find(|n| {
+        n.has_tag_name("nav")
+            && n.attribute((OPS_NAMESPACE, "type")).is_some_and(|t| t.split_whitespace().any(|t| t == "landmarks"))
+    }) else {
+        return Ok(Vec::new());
+    };
+    let base_dir = parent_dir(nav_path);
+    Ok(landmarks
+        .descendants()
+        .filter(|n| n.has_tag_name("a"))
+        .filter_map(|a| {
+            let href = a.attribute("href")?.split('#').next()?;
+            let kind = a.attribute((OPS_NAMESPACE, "type"))?;
+            Some((resolve_href(base_dir, href), kind.to_ascii_lowercase()))
+        })
+        .collect())
+}
+
+fn parse_nav_list(list: Node, base_dir: &str, depth: usize) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    for li in list.children().filter(|n| n.has_tag_name("li")) {
+        let label = li.children().find(|n| n.has_tag_name("a") || n.has_tag_name("span"));
+        let title = label.map(text_content).unwrap_or_default();
+        let mut entry = TocEntry::new(&title, label.and_then(|n| n.attribute("href")), base_dir, depth);
+        if let Some(sublist) = li.children().find(|n| n.has_tag_name("ol") || n.has_tag_name("ul")) {
+            entry.children = parse_nav_list(sublist, base_dir, depth + 1);
+        }
+        entries.push(entry);
+    }
+    entries
+}
+
+// 解析 EPUB 2 的 NCX navMap
+pub fn parse_ncx(text: &str, ncx_path: &str) -> Result<Vec<TocEntry>, EpubToMdError> {
+    let doc = parse_xml(text, ncx_path)?;
+    let Some(nav_map) = doc.descendants().find(|n| n.has_tag_name("navMap")) else {
+        return Ok(Vec::new());
+    };
+    Ok(parse_nav_points(nav_map, parent_dir(ncx_path), 0))
+}
+
+// 解析 EPUB 2 NCX 中的 pageList
+pub fn parse_ncx_page_list(text: &str, ncx_path: &str) -> Result<Vec<TocEntry>, EpubToMdError> {
+    let doc = parse_xml(text, ncx_path)?;
+    let Some(page_list) = doc.descendants().find(|n| n.has_tag_name("pageList")) else {
+        return Ok(Vec::new());
+    };
+    let base_dir = parent_dir(ncx_path);
+    Ok(page_list
+        .children()
+        .filter(|n| n.has_tag_name("pageTarget"))
+        .map(|target| {
+            let label = target.attribute("value").map(str::to_string).unwrap_or_else(|| {
+                target.children().find(|n| n.has_tag_name("navLabel")).map(text_content).unwrap_or_default()
+            });
+            let src = target.children().find(|n| n.has_tag_name("content")).and_then(|n| n.attribute("src"));
+            TocEntry::new(&label, src, base_dir, 0)
+        })
+        .collect())
+}
+
+fn parse_nav_points(parent: Node, base_dir: &str, depth: usize) -> Vec<TocEntry> {
+    let mut entries = Vec::new();
+    for point in parent.children().filter(|n| n.has_tag_name("navPoint")) {
+        let title = point
+            .children()
+            .find(|n| n.has_tag_name("navLabel"))
+            .map(text_content)
+            .unwrap_or_default();
+        let src = point
+            .children()
+            .find(|n| n.has_tag_name("content"))
+            .and_then(|n| n.attribute("src"));
+        let mut entry = TocEntry::new(&title, src, base_dir, depth);
+        entry.children = parse_nav_points(point, base_dir, depth + 1);
+        entries.push(entry);
+    }
+    entries
+}
+
+fn text_content(node: Node) -> String {
+    node.descendants().filter(|n| n.is_text()).filter_map(|n| n.text()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAV: &str = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><body>
+        <nav epub:type="landmarks">
+            <ol><li><a epub:type="bodymatter" href="../text/ch1.xhtml#start">Start</a></li></ol>
+        </nav>
+        <nav epub:type="toc"><ol>
+            <li><a href="../text/ch1.xhtml">Chapter
+                One</a><ol><li><a href="../text/ch1.xhtml#s1">Section</a></li></ol></li>
+            <li><span>Part</span></li>
+        </ol></nav>
+        <nav epub:type="page-list"><ol><li><a href="../text/ch1.xhtml#p1">1</a></li></ol></nav>
+    </body></html>"#;
+
+    const NCX: &str = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/"><navMap>
+        <navPoint><navLabel><text>One</text></navLabel><content src="ch1.xhtml"/>
+            <navPoint><navLabel><text>Two</text></navLabel><content src="ch1.xhtml#b"/></navPoint>
+        </navPoint>
+    </navMap><pageList><pageTarget value="7"><content src="ch1.xhtml#p7"/></pageTarget></pageList></ncx>"#;
+
+    // (深度, 标题, 路径#片段)
+    fn summary(entries: &[TocEntry]) -> Vec<(usize, &str, String)> {
+        TocEntry::flatten(entries)
+            .into_iter()
+            .map(|entry| {
+                let target = match &entry.fragment {
+                    Some(fragment) => format!("{}#{}", entry.path, fragment),
+                    None => entry.path.clone(),
+                };
+                (entry.depth, entry.title.as_str(), target)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nav_document_lists_resolve_against_its_directory() {
+        let toc = parse_nav(NAV, "OEBPS/nav/nav.xhtml").unwrap();
+        assert_eq!(
+            summary(&toc),
+            [
+                (0, "Chapter One", "OEBPS/text/ch1.xhtml".to_string()),
+                (1, "Section", "OEBPS/text/ch1.xhtml#s1".to_string()),
+                (0, "Part", String::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ncx_nav_points_nest() {
+        let toc = parse_ncx(NCX, "OEBPS/toc.ncx").unwrap();
+        assert_eq!(
+            summary(&toc),
+            [(0, "One", "OEBPS/ch1.xhtml".to_string()), (1, "Two", "OEBPS/ch1.xhtml#b".to_string())]
+        );
+    }
+
+    #[test]
+    fn page_lists_from_nav_and_ncx() {
+        let pages = parse_page_list(NAV, "OEBPS/nav/nav.xhtml").unwrap();
+        assert_eq!(summary(&pages), [(0, "1", "OEBPS/text/ch1.xhtml#p1".to_string())]);
+        let pages = parse_ncx_page_list(NCX, "OEBPS/toc.ncx").unwrap();
+        assert_eq!(summary(&pages), [(0, "7", "OEBPS/ch1.xhtml#p7".to_string())]);
+    }
+
+    #[test]
+    fn landmarks_drop_the_fragment() {
+        let landmarks = parse_landmarks(NAV, "OEBPS/nav/nav.xhtml").unwrap();
+        assert_eq!(landmarks, [("OEBPS/text/ch1.xhtml".to_string(), "bodymatter".to_string())]);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::options::Typography;
+
+// 出现在文字或这些标点之后的引号是右引号，其他情况下后面紧跟文字的引号是左引号
+const CLOSING_CONTEXT: &str = ".,;:!?)]}%”’…";
+
+// 按 --typography 统一 Markdown 正文中的引号、破折号与省略号。代码块、行内代码、数学公式、
+// HTML 标签、链接地址以及分隔线等只由符号组成的行保持不变
+pub fn apply(markdown: &str, typography: Typography) -> String {
+    if typography == Typography::Keep {
+        return markdown.to_string();
+    }
+    let mut in_fence = false;
+    let mut in_math = false;
+    markdown
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim();
+            if trimmed.starts_with("```") {
+                in_fence = !in_fence;
+                return line.to_string();
+            }
+            if trimmed == "$$" && !in_fence {
+                in_math = !in_math;
+                return line.to_string();
+            }
+            let rule = trimmed.chars().all(|c| "-=*_|: ".contains(c));
+            if in_fence || in_math || rule {
+                return line.to_string();
+            }
+            convert_line(line, typography)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// 逐段转换一行，跳过不应改动的部分
+fn convert_line(line: &str, typography: Typography) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut output = String::with_capacity(line.len());
+    let mut text = String::new();
+    let mut prev = None;
+    let mut i = 0;
+    while i < chars.len() {
+        let Some(end) = protected_end(&chars, i) else {
+            text.push(chars[i]);
+            i += 1;
+            continue;
+        };
+        flush(&mut output, &mut text, prev, typography);
+        output.extend(&chars[i..end]);
+        prev = chars.get(end - 1).copied();
+        i = end;
+    }
+    flush(&mut output, &mut text, prev, typography);
+    output
+}
+
+fn flush(output: &mut String, text: &mut String, prev: Option<char>, typography: Typography) {
+    let converted = match typography {
+        Typography::Smart => smarten(text, prev),
+        Typography::Straight => straighten(text),
+        Typography::Keep => text.clone(),
+    };
+    output.push_str(&converted);
+    text.clear();
+}
+
+// 从 start 开始的转义字符、行内代码、HTML 标签、行内公式或链接地址的结束位置
+pub fn protected_end(chars: &[char], start: usize) -> Option<usize> {
+    let find = |from: usize, target: char| chars[from..].iter().position(|&c| c == target).map(|p| from + p);
+    match chars[start] {
+        '\\' if start + 1 < chars.len() => Some(start + 2),
+        '`' => {
+            let run = chars[start..].iter().take_while(|&&c| c == '`').count();
+            let mut i = start + run;
+            while i < chars.len() {
+                let closing = chars[i..].iter().take_while(|&&c| c == '`').count();
+                if closing == run {
+                    return Some(i + run);
+                }
+                i += closing.max(1);
+            }
+            None
+        }
+        '<' if chars.get(start + 1).is_some_and(|&c| c.is_ascii_alphabetic() || c == '/' || c == '!') => {
+            find(start + 1, '>').map(|end| end + 1)
+        }
+        // 与 Pandoc 相同的判断：开头的 $ 后与结尾的 $ 前不是空白，结尾的 $ 后不是数字
+        '$' if chars.get(start + 1).is_some_and(|c| !c.is_whitespace()) => {
+            let mut i = start + 1;
+            while let Some(end) = find(i, '$') {
+                let closes = !chars[end - 1].is_whitespace() && !chars.get(end + 1).is_some_and(char::is_ascii_digit);
+                if closes && end > start + 1 {
+                    return Some(end + 1);
+                }
+                i = end + 1;
+            }
+            None
+        }
+        ']' if chars.get(start + 1) == Some(&'(') => find(start + 2, ')').map(|end| end + 1),
+        _ => None,
+    }
+}
+
+fn smarten(text: &str, mut prev: Option<char>) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut output = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let rest = &chars[i..];
+        let (replacement, length) = if rest.starts_with(&['.', '.', '.']) {
+            ('…', 3)
+        } else if rest.starts_with(&['-', '-', '-']) {
+            ('—', 3)
+        } else if rest.starts_with(&['-', '-']) {
+            ('–', 2)
+        } else {
+            let opening = prev.is_none_or(|p| !p.is_alphanumeric() && !CLOSING_CONTEXT.contains(p))
+                && rest.get(1).is_some_and(|next| !next.is_whitespace());
+            match (rest[0], opening) {
+                ('"', true) => ('“', 1),
+                ('"', false) => ('”', 1),
+                ('\'', true) => ('‘', 1),
+                // 省字号与右单引号相同
+                ('\'', false) => ('’', 1),
+                (c, _) => (c, 1),
+            }
+        };
+        output.push(replacement);
+        prev = Some(replacement);
+        i += length;
+    }
+    output
+}
+
+fn straighten(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '“' | '”' | '„' | '‟' | '″' => output.push('"'),
+            '‘' | '’' | '‚' | '‛' | '′' => output.push('\''),
+            '—' => output.push_str("---"),
+            '–' => output.push_str("--"),
+            '…' => output.push_str("..."),
+            _ => output.push(c),
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn smartens_quotes_dashes_and_ellipses() {
+        assert_eq!(
+            apply(r#""Hello," she said -- 'it's done'..."#, Typography::Smart),
+            "“Hello,” she said – ‘it’s done’…"
+        );
+        assert_eq!(apply("Wait --- what?", Typography::Smart), "Wait — what?");
+    }
+
+    #[test]
+    fn straightens_to_ascii() {
+        assert_eq!(apply("“Smart” — ‘quotes’ – and…", Typography::Straight), r#""Smart" --- 'quotes' -- and..."#);
+    }
+
+    #[test]
+    fn code_links_and_blocks_are_protected() {
+        let line = r#"Keep `"code"` and [a "link"](http://x/"a") and <span title="x">y</span>"#;
+        assert_eq!(apply(line, Typography::Smart), r#"Keep `"code"` and [a “link”](http://x/"a") and <span title="x">y</span>"#);
+        let blocks = "```\n\"fenced\"\n```\n---\n$$\n\"math\"\n$$";
+        assert_eq!(apply(blocks, Typography::Smart), blocks);
+        assert_eq!(apply(r#""as is""#, Typography::Keep), r#""as is""#);
+    }
+}
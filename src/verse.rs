@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use html2md::{Handle, StructuredPrinter, TagHandler, TagHandlerFactory};
+use markup5ever_rcdom::NodeData;
+
+use crate::{dom, markdown::default_handler, options::LineBreak};
+
+// 可能是诗歌、歌词或地址的块级元素
+const VERSE_TAGS: &[&str] = &["p", "div", "section", "blockquote", "address"];
+// class 或 epub:type（z3998:verse 等）中含有这些词的元素按行排版
+const VERSE_WORDS: &[&str] = &["verse", "poem", "poetry", "lyric", "stanza"];
+// 诗节中的行；<span> 只在 class 含 line 时算作一行
+const LINE_TAGS: &[&str] = &["p", "div"];
+
+fn is_verse(tag: &Handle) -> bool {
+    if dom::tag_name(tag).as_deref() == Some("address") {
+        return true;
+    }
+    ["class", "type"]
+        .iter()
+        .filter_map(|name| dom::attr(tag, name))
+        .flat_map(|value| value.split_whitespace().map(str::to_ascii_lowercase).collect::<Vec<_>>())
+        .any(|token| token == "song" || VERSE_WORDS.iter().any(|word| token.contains(word)))
+}
+
+fn element_children(node: &Handle) -> Vec<Handle> {
+    node.children
+        .borrow()
+        .iter()
+        .filter(|child| matches!(child.data, NodeData::Element { .. }))
+        .cloned()
+        .collect()
+}
+
+// 把诗节中的每一行改为行内元素并在行末插入 <br>，源码中文字内的换行同样改为 <br>；
+// 自身又包含多行的子元素是一个诗节，诗节之间仍然空一行
+fn break_lines(block: &Handle) {
+    for node in dom::descendants(block) {
+        let NodeData::Text { ref contents } = node.data else {
+            continue;
+        };
+        let text = contents.borrow().to_string();
+        let lines: Vec<&str> = text.split('\n').map(str::trim).filter(|line| !line.is_empty()).collect();
+        if lines.len() < 2 {
+            continue;
+        }
+        for (i, line) in lines.iter().enumerate() {
+            // 与前后行内元素之间的空格
+            let before = if i == 0 && text.starts_with([' ', '\t']) { " " } else { "" };
+            let after = if i == lines.len() - 1 && text.ends_with([' ', '\t']) { " " } else { "" };
+            if i > 0 {
+                dom::insert_before(&node, dom::element("br", &[]));
+            }
+            dom::insert_before(&node, dom::text(&format!("{}{}{}", before, line, after)));
+        }
+        dom::detach(&node);
+    }
+
+    let children = element_children(block);
+    let lines: Vec<&Handle> = children
+        .iter()
+        .filter(|child| match dom::tag_name(child).as_deref() {
+            Some("span") => dom::attr(child, "class").is_some_and(|class| class.to_ascii_lowercase().contains("line")),
+            Some(tag) => LINE_TAGS.contains(&tag),
+            None => false,
+        })
+        .collect();
+    for (i, line) in lines.iter().enumerate() {
+        let stanza = element_children(line)
+            .iter()
+            .any(|child| dom::tag_name(child).is_some_and(|tag| matches!(tag.as_str(), "p" | "div")));
+        if stanza {
+            break_lines(line);
+            continue;
+        }
+        let inline = dom::element("span", &[]);
+        for child in line.children.take() {
+            dom::append(&inline, child);
+        }
+        dom::replace(line, inline.clone());
+        let next_is_line = lines.get(i + 1).is_some_and(|next| {
+            !element_children(next)
+                .iter()
+                .any(|child| dom::tag_name(child).is_some_and(|tag| matches!(tag.as_str(), "p" | "div")))
+        });
+        if next_is_line {
+            dom::insert_before(lines[i + 1], dom::element("br", &[]));
+        }
+    }
+}
+
+// 诗歌类元素先调整结构再交给原来的处理器
+struct VerseHandler {
+    fallback: Box<dyn TagHandler>,
+}
+
+impl TagHandler for VerseHandler {
+    fn handle(&mut self, tag: &Handle, printer: &mut StructuredPrinter) {
+        if is_verse(tag) {
+            break_lines(tag);
+        }
+        self.fallback.handle(tag, printer);
+    }
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        self.fallback.after_handle(printer);
+    }
+
+    fn skip_descendants(&self) -> bool {
+        self.fallback.skip_descendants()
+    }
+}
+
+struct VerseHandlerFactory {
+    tag: &'static str,
+    fallback: Option<Box<dyn TagHandlerFactory>>,
+}
+
+impl TagHandlerFactory for VerseHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        let fallback = match &self.fallback {
+            Some(factory) => factory.instantiate(),
+            // <address> 单独成段
+            None if self.tag == "address" => default_handler("div"),
+            None => default_handler(self.tag),
+        };
+        Box::new(VerseHandler { fallback })
+    }
+}
+
+// <br> 按 --line-breaks 写成行尾两个空格或反斜杠
+struct BreakHandler(LineBreak);
+
+impl TagHandler for BreakHandler {
+    fn handle(&mut self, _tag: &Handle, _printer: &mut StructuredPrinter) {}
+
+    fn after_handle(&mut self, printer: &mut StructuredPrinter) {
+        match self.0 {
+            LineBreak::Spaces => printer.append_str("  \n"),
+            LineBreak::Backslash => printer.append_str("\\\n"),
+        }
+    }
+}
+
+struct BreakHandlerFactory(LineBreak);
+
+impl TagHandlerFactory for BreakHandlerFactory {
+    fn instantiate(&self) -> Box<dyn TagHandler> {
+        Box::new(BreakHandler(self.0))
+    }
+}
+
+// 需在其他处理器之后注册，不是诗歌的元素仍交给已注册的处理器
+pub fn register(handlers: &mut HashMap<String, Box<dyn TagHandlerFactory>>, line_break: LineBreak) {
+    handlers.insert("br".to_string(), Box::new(BreakHandlerFactory(line_break)));
+    for tag in VERSE_TAGS {
+        let fallback = handlers.remove(*tag);
+        handlers.insert(tag.to_string(), Box::new(VerseHandlerFactory { tag, fallback }));
+    }
+}
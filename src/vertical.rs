@@ -0,0 +1,48 @@
+use markup5ever_rcdom::NodeData;
+
+use crate::{
+    dom,
+    epub::{Epub, ManifestItem},
+};
+
+// 竖排专用的标点字形与对应的横排标点
+const VERTICAL_FORMS: &[(char, char)] = &[
+    ('︐', '，'), ('︑', '、'), ('︒', '。'), ('︓', '：'), ('︔', '；'), ('︕', '！'), ('︖', '？'), ('︗', '〖'),
+    ('︘', '〗'), ('︙', '…'), ('︰', '‥'), ('︱', '—'), ('︲', '–'), ('︳', '_'), ('︴', '_'), ('︵', '（'),
+    ('︶', '）'), ('︷', '｛'), ('︸', '｝'), ('︹', '〔'), ('︺', '〕'), ('︻', '【'), ('︼', '】'), ('︽', '《'),
+    ('︾', '》'), ('︿', '〈'), ('﹀', '〉'), ('﹁', '「'), ('﹂', '」'), ('﹃', '『'), ('﹄', '』'), ('﹇', '［'),
+    ('﹈', '］'),
+];
+
+// writing-mode: vertical-rl 等竖排声明，含 -epub- 与 -webkit- 前缀以及旧的 tb-rl 写法
+fn declares_vertical(css: &str) -> bool {
+    let css: String = css.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_lowercase();
+    css.contains("writing-mode:vertical") || css.contains("writing-mode:tb")
+}
+
+// 样式表、文档内的 <style> 或 style 属性是否把正文设为竖排
+pub fn is_vertical(epub: &mut Epub, documents: &[(ManifestItem, String)]) -> bool {
+    if documents.iter().any(|(_, html)| declares_vertical(html)) {
+        return true;
+    }
+    epub.stylesheets().iter().any(|css| declares_vertical(css))
+}
+
+// 竖排标点改为横排字形，例如 ︵︶ 改为（），﹁﹂ 改为「」；文档中的文字本来就按阅读顺序排列，无需调整
+pub fn horizontal(html: &str) -> Option<String> {
+    if !html.chars().any(|c| VERTICAL_FORMS.iter().any(|(vertical, _)| *vertical == c)) {
+        return None;
+    }
+    let document = dom::parse(html);
+    for node in dom::descendants(&document.document) {
+        if let NodeData::Text { contents } = &node.data {
+            let text: String = contents
+                .borrow()
+                .chars()
+                .map(|c| VERTICAL_FORMS.iter().find(|(vertical, _)| *vertical == c).map_or(c, |(_, horizontal)| *horizontal))
+                .collect();
+            *contents.borrow_mut() = text.as_str().into();
+        }
+    }
+    Some(dom::inner_html(&document.document))
+}
@@ -0,0 +1,114 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::{Duration, SystemTime},
+};
+
+use notify_debouncer_mini::{new_debouncer, notify::{self, RecursiveMode}, DebouncedEventKind};
+
+use crate::{error::EpubToMdError, glob};
+
+// 文件在这段时间内没有新的变动才转换，避免读到还没复制完的文件
+const QUIET_PERIOD: Duration = Duration::from_secs(1);
+
+// 文件的大小与修改时间；解压后的 EPUB 目录取 container.xml 的
+fn stamp(path: &Path) -> Option<(u64, SystemTime)> {
+    let path = if path.is_dir() { path.join("META-INF").join("container.xml") } else { path.to_path_buf() };
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()?))
+}
+
+fn is_epub_file(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("epub"))
+}
+
+fn is_exploded(path: &Path) -> bool {
+    path.join("META-INF").join("container.xml").is_file()
+}
+
+// 变动的路径所属的书：EPUB 文件本身、它所在的解压后的 EPUB 目录，或者新出现的目录中的所有书；
+// 与 glob::find_epubs 一样跳过隐藏的文件与目录
+fn affected_books(root: &Path, path: &Path, books: &mut BTreeSet<PathBuf>) {
+    let Ok(relative) = path.strip_prefix(root) else {
+        return;
+    };
+    if relative.components().any(|part| part.as_os_str().to_string_lossy().starts_with('.')) {
+        return;
+    }
+    if let Some(dir) = path.ancestors().take_while(|dir| dir.starts_with(root)).find(|dir| is_exploded(dir)) {
+        books.insert(dir.to_path_buf());
+    } else if path.is_dir() {
+        books.extend(glob::find_epubs(path).unwrap_or_default().into_iter().map(PathBuf::from));
+    } else if is_epub_file(path) && path.is_file() {
+        books.insert(path.to_path_buf());
+    }
+}
+
+// 先转换目录树中已有的书，再监视文件系统的变动，新增或修改后稳定下来的书交给 convert，直到进程被中断
+pub fn watch(root: &Path, mut convert: impl FnMut(&str)) -> Result<(), EpubToMdError> {
+    let books = glob::find_epubs(root)?;
+    // 监视绝对路径，事件中的路径转换前换回用户给出的写法
+    let absolute = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+    let (sender, events) = mpsc::channel();
+    let watch_error = |e: notify::Error| EpubToMdError::FileIOError(tr!("Failed to watch {}: {}", root.display(), e));
+    let mut debouncer = new_debouncer(QUIET_PERIOD, sender).map_err(watch_error)?;
+    debouncer.watcher().watch(&absolute, RecursiveMode::Recursive).map_err(watch_error)?;
+
+    // 上一次转换时的状态；读取文件同样会产生事件，状态没变的书不再转换
+    let mut converted = HashMap::new();
+    for path in books {
+        let book = Path::new(&path).strip_prefix(root).map_or(PathBuf::from(&path), |relative| absolute.join(relative));
+        converted.insert(book, stamp(Path::new(&path)));
+        convert(&path);
+    }
+    for result in events {
+        let mut books = BTreeSet::new();
+        // AnyContinuous 表示文件仍在变动，等它稳定后的 Any 事件
+        for event in result.map_err(watch_error)?.into_iter().filter(|event| event.kind == DebouncedEventKind::Any) {
+            // 删除的书重新出现时再次转换
+            if !event.path.exists() {
+                converted.retain(|book, _| !book.starts_with(&event.path));
+            }
+            affected_books(&absolute, &event.path, &mut books);
+        }
+        for book in books {
+            let current = stamp(&book);
+            if converted.get(&book) == Some(&current) {
+                continue;
+            }
+            converted.insert(book.clone(), current);
+            let path = book.strip_prefix(&absolute).map_or(book.clone(), |relative| root.join(relative));
+            convert(&path.to_string_lossy());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_map_to_books() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for file in ["a.epub", "notes.txt", "new/b.EPUB", "exploded/META-INF/container.xml", "exploded/OEBPS/ch1.xhtml", ".hidden/c.epub"] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        let affected = |path: &str| {
+            let mut books = BTreeSet::new();
+            affected_books(root, &root.join(path), &mut books);
+            books.into_iter().map(|book| book.strip_prefix(root).unwrap().to_string_lossy().into_owned()).collect::<Vec<_>>()
+        };
+        assert_eq!(affected("a.epub"), ["a.epub"]);
+        assert_eq!(affected("notes.txt"), Vec::<String>::new());
+        assert_eq!(affected("new"), ["new/b.EPUB"]);
+        assert_eq!(affected("exploded/OEBPS/ch1.xhtml"), ["exploded"]);
+        assert_eq!(affected(".hidden/c.epub"), Vec::<String>::new());
+        assert_eq!(affected("deleted.epub"), Vec::<String>::new());
+    }
+}
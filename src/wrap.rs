@@ -0,0 +1,320 @@
+use crate::{cjk, cleanup, headings, options::Wrap};
+
+// 禁则：不能出现在行首的收尾标点与不能出现在行尾的开头标点
+const NO_LINE_START: &str = "，。、；：！？）］｝〕〉》」』】〗〙〛・ー…‥〜～％”’ぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ";
+const NO_LINE_END: &str = "（［｛〔〈《「『【〖〘〚“‘";
+
+// 正在合并的段落：首行前缀（列表标记、引用符号等）、后续行前缀，以及按硬换行分开的各段文字
+struct Paragraph {
+    first_prefix: String,
+    prefix: String,
+    segments: Vec<(String, &'static str)>,
+}
+
+// 按 --wrap 重排段落：none 时每段一行，N 时在第 N 列之前的空白处换行。
+// 列表项、引用和脚注定义保持各自的缩进，标题、表格、HTML、代码块与公式块不变
+pub fn wrap(content: &str, wrap: Wrap) -> String {
+    let (frontmatter, body) = cleanup::split_frontmatter(content);
+    let lines: Vec<&str> = body.lines().collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut paragraph: Option<Paragraph> = None;
+    let mut in_fence = false;
+    let mut in_math = false;
+    // 上一个块是列表项或脚注定义，缩进的行是其后续内容而不是代码块
+    let mut in_item = false;
+    let mut previous_blank = true;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let fence = trimmed.starts_with("```");
+        let math = trimmed == "$$" && !in_fence;
+        if in_fence || in_math || fence || math {
+            flush(&mut output, &mut paragraph, wrap);
+            in_fence ^= fence;
+            in_math ^= math;
+            output.push(line.to_string());
+            previous_blank = false;
+            continue;
+        }
+        if trimmed.is_empty() {
+            flush(&mut output, &mut paragraph, wrap);
+            output.push(String::new());
+            previous_blank = true;
+            continue;
+        }
+        let indented = line.starts_with("    ") || line.starts_with('\t');
+        let preformatted = (indented && previous_blank && !in_item)
+            || headings::heading_level(line, lines.get(i + 1).copied()).is_some()
+            || trimmed.starts_with(['|', '<', '#'])
+            || trimmed.chars().all(|c| "-=*_ ".contains(c))
+            || is_link_definition(trimmed)
+            || trimmed.starts_with(":::")
+            || is_definition_term(&lines[i + 1..]);
+        previous_blank = false;
+        if preformatted {
+            flush(&mut output, &mut paragraph, wrap);
+            output.push(line.to_string());
+            // setext 标题的下划线随标题原样输出
+            continue;
+        }
+
+        let (first_prefix, prefix, text) = match block_prefix(line) {
+            Some(block) => block,
+            None => match &paragraph {
+                // 段落的后续行，包括列表项中的懒惰续行
+                Some(_) => (String::new(), String::new(), line.trim_start()),
+                None => {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+                    (indent.to_string(), indent.to_string(), line.trim_start())
+                }
+            },
+        };
+        let is_item = !first_prefix.trim().is_empty() && first_prefix.trim() != ">";
+        let continues = paragraph.as_ref().is_some_and(|current| {
+            (!is_item && first_prefix.is_empty())
+                || (first_prefix.trim() == ">" && current.prefix == first_prefix && !starts_block(text))
+        });
+        if !continues {
+            flush(&mut output, &mut paragraph, wrap);
+            if !line.starts_with(char::is_whitespace) {
+                in_item = is_item;
+            }
+            // 引用中的标题、列表等不重排
+            if first_prefix.trim() == ">" && starts_block(text) {
+                output.push(line.to_string());
+                continue;
+            }
+            paragraph = Some(Paragraph {
+                first_prefix,
+                prefix,
+                segments: Vec::new(),
+            });
+        }
+        let Some(current) = paragraph.as_mut() else {
+            continue;
+        };
+        // 行尾两个空格或反斜杠表示硬换行
+        let (text, hard_break) = match (line.ends_with("  "), text.trim_end().ends_with('\\')) {
+            (true, _) => (text.trim_end(), "  "),
+            (false, true) => (text.trim_end().strip_suffix('\\').unwrap_or(text), "\\"),
+            _ => (text.trim_end(), ""),
+        };
+        match current.segments.last_mut() {
+            Some((segment, end)) if end.is_empty() => {
+                // 中日韩文字之间的换行不是空格
+                let cjk = segment.chars().last().is_some_and(is_wide) && text.chars().next().is_some_and(is_wide);
+                if !cjk {
+                    segment.push(' ');
+                }
+                segment.push_str(text);
+                *end = hard_break;
+            }
+            _ => current.segments.push((text.to_string(), hard_break)),
+        }
+    }
+    flush(&mut output, &mut paragraph, wrap);
+
+    let mut wrapped = frontmatter.to_string();
+    wrapped.push_str(&output.join("\n"));
+    if body.ends_with('\n') {
+        wrapped.push('\n');
+    }
+    wrapped
+}
+
+// 列表项、脚注定义或引用开头的前缀：(首行前缀, 后续行前缀, 正文)
+fn block_prefix(line: &str) -> Option<(String, String, &str)> {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    // 定义列表的释义以 : 开头
+    let marker = if rest.starts_with(['-', '+', '*', ':']) {
+        1
+    } else if rest.starts_with("[^") {
+        rest.find("]:").map_or(0, |end| end + 2)
+    } else if let Some(quoted) = rest.strip_prefix('>') {
+        let quote = rest.len() - quoted.strip_prefix(' ').unwrap_or(quoted).len();
+        let prefix = line[..indent + quote].to_string();
+        return Some((prefix.clone(), prefix, &rest[quote..]));
+    } else {
+        let digits = rest.chars().take_while(char::is_ascii_digit).count();
+        match rest[digits..].chars().next() {
+            Some('.' | ')') if (1..=9).contains(&digits) => digits + 1,
+            _ => 0,
+        }
+    };
+    if marker == 0 || !rest[marker..].starts_with(' ') {
+        return None;
+    }
+    let text = rest[marker..].trim_start();
+    let first_prefix = &line[..line.len() - text.len()];
+    // 脚注定义的后续行缩进四个空格
+    let width = if rest.starts_with("[^") { indent + 4 } else { first_prefix.chars().count() };
+    Some((first_prefix.to_string(), " ".repeat(width), text))
+}
+
+// 引用中以标题、列表、表格、提示框标记等开头的行
+fn starts_block(text: &str) -> bool {
+    text.starts_with(['#', '|', '<', '>']) || text.starts_with("[!") || block_prefix(text).is_some() || text.starts_with("```")
+}
+
+// 中日韩文字与全角标点，占两列
+fn is_wide(c: char) -> bool {
+    cjk::is_cjk(c) || matches!(c, '\u{3000}'..='\u{303F}' | '\u{FE30}'..='\u{FE4F}' | '\u{FF01}'..='\u{FF60}' | '\u{FFE0}'..='\u{FFE6}')
+}
+
+fn width(text: &str) -> usize {
+    text.chars().map(|c| if is_wide(c) { 2 } else { 1 }).sum()
+}
+
+// 段落中的行内代码与链接地址，其中的文字不能拆开
+#[derive(Default)]
+struct Inline {
+    code: Option<usize>,
+    destination: usize,
+}
+
+impl Inline {
+    // 读入一个字符，返回其后是否仍在行内代码或链接地址中；run 为以该字符开头的连续反引号数
+    fn advance(&mut self, previous: Option<char>, c: char, run: usize) -> bool {
+        match (self.code, c) {
+            (Some(open), '`') if open == run => self.code = None,
+            (None, '`') if previous != Some('`') => self.code = Some(run),
+            (Some(_), _) => {}
+            (None, '(') if self.destination > 0 || previous == Some(']') => self.destination += 1,
+            (None, ')') if self.destination > 0 => self.destination -= 1,
+            _ => {}
+        }
+        self.code.is_some() || self.destination > 0
+    }
+}
+
+// 中日韩文字之间可以换行，但收尾标点不放在行首，开头标点不放在行尾；
+// 与英文、数字或 Markdown 标记相邻处不换行，以免拆开“**强调**”等写法
+fn pieces<'a>(word: &'a str, inline: &mut Inline) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut previous: Option<char> = None;
+    let mut protected = false;
+    for (i, c) in word.char_indices() {
+        let breakable = previous.is_some_and(|p| is_wide(p) && is_wide(c) && !NO_LINE_END.contains(p) && !NO_LINE_START.contains(c));
+        if breakable && !protected {
+            pieces.push(&word[start..i]);
+            start = i;
+        }
+        let run = word[i..].chars().take_while(|&c| c == '`').count();
+        protected = inline.advance(previous, c, run);
+        previous = Some(c);
+    }
+    pieces.push(&word[start..]);
+    pieces
+}
+
+fn flush(output: &mut Vec<String>, paragraph: &mut Option<Paragraph>, wrap: Wrap) {
+    let Some(paragraph) = paragraph.take() else {
+        return;
+    };
+    let mut first = true;
+    for (text, hard_break) in paragraph.segments {
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        let mut inline = Inline::default();
+        for word in text.split_whitespace() {
+            // 同一个词中的各段之间没有空格
+            for (i, piece) in pieces(word, &mut inline).into_iter().enumerate() {
+                let prefix = if first && lines.is_empty() { &paragraph.first_prefix } else { &paragraph.prefix };
+                if line.is_empty() {
+                    line = format!("{}{}", prefix, piece);
+                    continue;
+                }
+                let space = if i == 0 { " " } else { "" };
+                let too_long = match wrap {
+                    Wrap::None => false,
+                    Wrap::Columns(columns) => width(&line) + space.len() + width(piece) > columns,
+                };
+                // 换行后位于行首会被当作列表、标题或引用的词不能折到下一行
+                if too_long && !starts_block(piece) && !is_marker(piece) && !piece.starts_with(|c| NO_LINE_START.contains(c)) {
+                    lines.push(std::mem::take(&mut line));
+                    line = format!("{}{}", paragraph.prefix, piece);
+                } else {
+                    line.push_str(space);
+                    line.push_str(piece);
+                }
+            }
+        }
+        if line.is_empty() {
+            line = paragraph.first_prefix.clone();
+        }
+        line.push_str(hard_break);
+        lines.push(line);
+        output.extend(lines);
+        first = false;
+    }
+}
+
+// 引用式链接的定义 [n]: 地址
+fn is_link_definition(line: &str) -> bool {
+    line.strip_prefix('[')
+        .filter(|rest| !rest.starts_with('^'))
+        .and_then(|rest| rest.split_once("]:"))
+        .is_some_and(|(label, _)| !label.is_empty() && !label.contains(['[', ']']))
+}
+
+// 定义列表的术语各占一行：其后连续的非空行以释义的 : 结束
+fn is_definition_term(following: &[&str]) -> bool {
+    following
+        .iter()
+        .take_while(|line| !line.trim().is_empty())
+        .find(|line| !line.starts_with(char::is_whitespace) && line.starts_with(':'))
+        .is_some_and(|line| line.starts_with(": "))
+}
+
+// 单独作为一个词时像列表或引用标记的文字
+fn is_marker(word: &str) -> bool {
+    matches!(word, "-" | "+" | "*" | ">" | "=")
+        || word.chars().all(|c| c == '-' || c == '=')
+        || word
+            .strip_suffix(['.', ')'])
+            .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_lines_without_spaces_between_cjk() {
+        assert_eq!(wrap("中文的\n换行\nand English\nwords", Wrap::None), "中文的换行 and English words");
+    }
+
+    #[test]
+    fn wraps_cjk_by_display_width() {
+        assert_eq!(
+            wrap("这是一段很长的中文文字，用来测试按列换行。", Wrap::Columns(20)),
+            // 收尾标点不在行首，连同前一个字一起移到下一行
+            "这是一段很长的中文文\n字，用来测试按列换\n行。"
+        );
+    }
+
+    #[test]
+    fn opening_punctuation_does_not_end_a_line() {
+        let wrapped = wrap("一二三四五六七八九「十一」", Wrap::Columns(20));
+        assert_eq!(wrapped, "一二三四五六七八九\n「十一」");
+    }
+
+    #[test]
+    fn wraps_english_at_spaces() {
+        assert_eq!(
+            wrap("English words that\nshould be joined and wrapped at twenty columns.", Wrap::Columns(20)),
+            "English words that\nshould be joined and\nwrapped at twenty\ncolumns."
+        );
+    }
+
+    #[test]
+    fn keeps_prefixes_and_leaves_blocks_alone() {
+        assert_eq!(
+            wrap("- item one that is long enough to wrap\n  continued\n\n> quoted text\n> more", Wrap::Columns(20)),
+            "- item one that is\n  long enough to\n  wrap continued\n\n> quoted text more"
+        );
+        let blocks = "# Heading that is long and must not be wrapped\n\n| a | b |\n|---|---|\n\n```\ncode that is long and stays as it is\n```\n";
+        assert_eq!(wrap(blocks, Wrap::Columns(20)), blocks);
+    }
+}